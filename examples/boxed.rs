@@ -0,0 +1,39 @@
+use ode_event_solvers::dop_shared::ode_fn;
+use ode_event_solvers::problems::{HarmonicOscillator, VanDerPol};
+use ode_event_solvers::{Euler, System, Vector2};
+
+type State = Vector2<f64>;
+
+/// Selects one of the registered models by name.
+fn model(name: &str) -> Option<Box<dyn System<State>>> {
+    match name {
+        "oscillator" => Some(Box::new(HarmonicOscillator::new(1.0))),
+        "van_der_pol" => Some(Box::new(VanDerPol::new(1.0))),
+        "decay" => Some(Box::new(ode_fn(|_x, y: &State, dy: &mut State| {
+            *dy = -y;
+        }))),
+        _ => None,
+    }
+}
+
+fn main() {
+    for name in ["oscillator", "van_der_pol", "decay"] {
+        let system = model(name).expect("unknown model");
+        let mut solver = Euler::new(
+            system,
+            0.0,
+            State::new(1.0, 0.0),
+            10.0,
+            vec![0.001, 0.01, 0.1],
+        );
+        match solver.integrate() {
+            Ok(stats) => println!(
+                "{}: y(10) = {:?}, {}",
+                name,
+                solver.y_final().as_slice(),
+                stats
+            ),
+            Err(e) => println!("{}: an error occurred: {}", name, e),
+        }
+    }
+}
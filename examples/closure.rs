@@ -0,0 +1,17 @@
+use ode_event_solvers::dop_shared::ode_fn;
+use ode_event_solvers::{Euler, Vector1};
+
+fn main() {
+    // y' = -y
+    let system = ode_fn(|_x, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = -y[0]);
+
+    let mut solver = Euler::new(system, 0.0, Vector1::new(1.0), 1.0, vec![0.001, 0.01, 0.1]);
+    match solver.integrate() {
+        Ok(stats) => {
+            println!("{}", stats);
+            let y_end = solver.y_out().last().unwrap()[0];
+            println!("y(1) = {} (exact: {})", y_end, (-1.0f64).exp());
+        }
+        Err(e) => println!("An error occurred: {}", e),
+    }
+}
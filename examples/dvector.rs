@@ -0,0 +1,62 @@
+use ode_event_solvers::{DVector, Dopri5, Euler, System};
+
+type State = DVector<f64>;
+
+/// Heat equation `u_t = u_xx` on `[0, 1]` with zero boundary values, discretized by finite
+/// differences on a number of interior points only known at runtime.
+struct Heat {
+    dx: f64,
+}
+
+impl System<State> for Heat {
+    fn ode(&self, _x: f64, y: &State, dy: &mut State) {
+        let n = y.len();
+        for i in 0..n {
+            let left = if i > 0 { y[i - 1] } else { 0.0 };
+            let right = if i + 1 < n { y[i + 1] } else { 0.0 };
+            dy[i] = (left - 2.0 * y[i] + right) / (self.dx * self.dx);
+        }
+    }
+}
+
+fn main() {
+    // Number of components, e.g. parsed from a configuration
+    let n = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(50);
+    let dx = 1.0 / (n + 1) as f64;
+    // Fundamental mode, decaying as exp(-lambda t)
+    let y0 = State::from_fn(n, |i, _| (std::f64::consts::PI * (i + 1) as f64 * dx).sin());
+    let lambda = (2.0 * (std::f64::consts::PI * dx / 2.0).sin() / dx).powi(2);
+    let exact = y0[0] * (-lambda * 0.1_f64).exp();
+
+    let h = 0.4 * dx * dx;
+    let mut euler = Euler::new(
+        Heat { dx },
+        0.0,
+        y0.clone(),
+        0.1,
+        vec![h, (10.0 * h).min(0.01), 0.01],
+    );
+    match euler.integrate() {
+        Ok(stats) => println!(
+            "euler: y_0(0.1) = {} (exact: {}), {}",
+            euler.y_final()[0],
+            exact,
+            stats
+        ),
+        Err(e) => println!("euler: an error occurred: {}", e),
+    }
+
+    let mut dopri5 = Dopri5::new(Heat { dx }, 0.0, y0, 0.1, None, 1.0e-8, 1.0e-10);
+    match dopri5.integrate() {
+        Ok(stats) => println!(
+            "dopri5: y_0(0.1) = {} (exact: {}), {}",
+            dopri5.y_final()[0],
+            exact,
+            stats
+        ),
+        Err(e) => println!("dopri5: an error occurred: {}", e),
+    }
+}
@@ -0,0 +1,523 @@
+//! Adams-Bashforth-Moulton predictor-corrector method of order 2 to 5 with fixed step size.
+
+use crate::dop_shared::{
+    check_eval, find_segment, reject_events, step_count, validate_step_size, ControlFlag,
+    HistoryPoint, IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Output, Stats,
+    System, STEP_TOL,
+};
+
+use std::collections::VecDeque;
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+// Adams-Bashforth coefficients of order 2 to 5, applied to the past derivatives from newest to oldest.
+const AB: [&[f64]; 4] = [
+    &[3.0 / 2.0, -1.0 / 2.0],
+    &[23.0 / 12.0, -16.0 / 12.0, 5.0 / 12.0],
+    &[55.0 / 24.0, -59.0 / 24.0, 37.0 / 24.0, -9.0 / 24.0],
+    &[
+        1901.0 / 720.0,
+        -2774.0 / 720.0,
+        2616.0 / 720.0,
+        -1274.0 / 720.0,
+        251.0 / 720.0,
+    ],
+];
+// Adams-Moulton coefficients of order 2 to 5, applied to the predicted derivative followed by the
+// past derivatives from newest to oldest.
+const AM: [&[f64]; 4] = [
+    &[1.0 / 2.0, 1.0 / 2.0],
+    &[5.0 / 12.0, 8.0 / 12.0, -1.0 / 12.0],
+    &[9.0 / 24.0, 19.0 / 24.0, -5.0 / 24.0, 1.0 / 24.0],
+    &[
+        251.0 / 720.0,
+        646.0 / 720.0,
+        -264.0 / 720.0,
+        106.0 / 720.0,
+        -19.0 / 720.0,
+    ],
+];
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// # Startup
+///
+/// A method of order `k` needs the derivatives at the `k` previous points. The first `k - 1`
+/// steps are therefore taken with the classical fourth order Runge-Kutta method, whose accuracy is
+/// sufficient not to degrade the order of the multistep method. The last step, if it has to be
+/// shortened to end exactly at `x_end`, is also taken with the Runge-Kutta method since the
+/// Adams coefficients assume equally spaced points.
+///
+/// Each multistep step then costs two evaluations of `System::ode`: one at the predicted state
+/// (Adams-Bashforth) and one at the corrected state (Adams-Moulton), the latter being stored for
+/// the following steps.
+///
+/// # Warm start
+///
+/// The startup steps are skipped if the solver is seeded with
+/// [`warm_start`](Self::warm_start), e.g. with the [`history`](Self::history) of a previous run,
+/// in which case it continues at full order from the newest point of the history.
+pub struct AdamsBashforthMoulton<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    order: usize,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    warm_start: Vec<HistoryPoint<V>>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> AdamsBashforthMoulton<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    /// * `order`       - Order of the method, between 2 and 5
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64, order: usize) -> Self {
+        AdamsBashforthMoulton {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            order,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            warm_start: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Seeds the solver with a precomputed history of `(x, y, dy)` triples, sorted from the
+    /// oldest to the newest point and spaced by the step size in the direction of integration,
+    /// `dy` being the derivative at `(x, y)`. The integration starts from the newest point,
+    /// replacing the initial values given to [`new`](Self::new), and only the last `order`
+    /// derivatives are used. A solver seeded with the history of another one at some point
+    /// produces the same trajectory as the latter from that point on.
+    pub fn warm_start(&mut self, history: Vec<HistoryPoint<OVector<T, D>>>) {
+        if let Some((x, y, _)) = history.last() {
+            self.x = *x;
+            self.y = y.clone();
+        }
+        self.warm_start = history;
+    }
+
+    /// Returns the last `order` points of the integration as `(x, y, dy)` triples sorted from
+    /// the oldest to the newest, which may be passed to [`warm_start`](Self::warm_start).
+    pub fn history(&self) -> Vec<HistoryPoint<OVector<T, D>>> {
+        let start = self.output.x.len().saturating_sub(self.order);
+        (start..self.output.x.len())
+            .map(|i| {
+                (
+                    self.output.x[i],
+                    self.output.y[i].clone(),
+                    self.dy_out[i].clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        if !(2..=5).contains(&self.order) {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "order must be between 2 and 5".to_string(),
+            });
+        }
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+
+        // Past derivatives, from newest to oldest
+        let mut history = VecDeque::with_capacity(self.order + 1);
+        let warm_start = std::mem::take(&mut self.warm_start);
+        if warm_start.is_empty() {
+            let mut dy = self.y.clone();
+            self.f.ode(self.x, &self.y, &mut dy);
+            self.stats.num_eval += 1;
+            check_eval(&self.f)?;
+            history.push_front(dy);
+        } else {
+            let uniform = warm_start.windows(2).all(|w| {
+                (w[1].0 - w[0].0 - direction * self.step_size).abs() <= STEP_TOL * self.step_size
+            });
+            if !uniform {
+                return Err(IntegrationError::InvalidStepConfig {
+                    reason: "the warm start history must be spaced by the step size".to_string(),
+                });
+            }
+            for (_, _, dy) in warm_start.into_iter().rev().take(self.order) {
+                history.push_back(dy);
+            }
+        }
+        self.dy_out.push(history[0].clone());
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let uniform = (h.abs() - self.step_size).abs() <= STEP_TOL * self.step_size;
+            let y_new = if history.len() < self.order || !uniform {
+                self.rk4_step(h, &history[0])
+            } else {
+                self.pece_step(h, &history)
+            };
+            check_eval(&self.f)?;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.y = y_new;
+            self.f.project(self.x, &mut self.y);
+            let mut dy = self.y.clone();
+            self.f.ode(self.x, &self.y, &mut dy);
+            self.stats.num_eval += 1;
+            check_eval(&self.f)?;
+            self.stats.accepted_steps += 1;
+            self.dy_out.push(dy.clone());
+            history.push_front(dy);
+            history.truncate(self.order);
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Predicts the new state with the Adams-Bashforth formula and corrects it once with the
+    /// Adams-Moulton formula. The derivative at the corrected state is evaluated by the caller.
+    fn pece_step(&mut self, h: f64, history: &VecDeque<OVector<T, D>>) -> OVector<T, D> {
+        let ab = AB[self.order - 2];
+        let am = AM[self.order - 2];
+
+        let mut increment = history[0].clone() * ab[0];
+        for (dy, w) in history.iter().zip(ab.iter()).skip(1) {
+            increment += dy.clone() * *w;
+        }
+        let y_pred = &self.y + increment * h;
+        let mut dy_pred = y_pred.clone();
+        self.f.ode(self.x + h, &y_pred, &mut dy_pred);
+        self.stats.num_eval += 1;
+
+        let mut increment = dy_pred * am[0];
+        for (dy, w) in history.iter().zip(am[1..].iter()) {
+            increment += dy.clone() * *w;
+        }
+        &self.y + increment * h
+    }
+
+    /// Performs one step of the classical Runge-Kutta method, given the derivative `k0` at the
+    /// start of the step.
+    fn rk4_step(&mut self, h: f64, k0: &OVector<T, D>) -> OVector<T, D> {
+        let (rows, cols) = self.y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); 3];
+
+        self.f.ode(
+            self.x + 0.5 * h,
+            &(&self.y + k0.clone() * (0.5 * h)),
+            &mut k[0],
+        );
+        self.f.ode(
+            self.x + 0.5 * h,
+            &(&self.y + k[0].clone() * (0.5 * h)),
+            &mut k[1],
+        );
+        self.f
+            .ode(self.x + h, &(&self.y + k[1].clone() * h), &mut k[2]);
+        self.stats.num_eval += 3;
+
+        &self.y
+            + (k0.clone() * (1.0 / 6.0)
+                + k[0].clone() * (1.0 / 3.0)
+                + k[1].clone() * (1.0 / 3.0)
+                + k[2].clone() * (1.0 / 6.0))
+                * h
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` within the step from `x_i` to `x_{i+1}` as the Adams
+    /// formulas do, i.e. `y_i` plus the integral from `x_i` to `x` of the polynomial interpolating
+    /// the derivatives stored at `order` consecutive points: `x_{i+1}` and the previous ones, or
+    /// the following ones over the first steps. The interpolation thus has the order of the
+    /// method. It is corrected by a linear term, of the size of the local error, so as to match
+    /// the stored state at `x_{i+1}`. Returns `None` if `x` lies outside of the integration
+    /// interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        let first = (i + 2)
+            .saturating_sub(self.order)
+            .min(self.output.x.len().saturating_sub(self.order));
+        let nodes = &self.output.x[first..(first + self.order).min(self.output.x.len())];
+        let (x0, x1) = (self.output.x[i], self.output.x[i + 1]);
+        let w = integrated_lagrange_weights(nodes, x0, x);
+        let w1 = integrated_lagrange_weights(nodes, x0, x1);
+        let theta = (x - x0) / (x1 - x0);
+        let mut y = &self.output.y[i] + (&self.output.y[i + 1] - &self.output.y[i]) * theta;
+        for (j, (w_j, w1_j)) in w.iter().zip(w1.iter()).enumerate() {
+            y += self.dy_out.get(first + j)?.clone() * (w_j - theta * w1_j);
+        }
+        Some(y)
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+/// Integrals from `a` to `b` of the Lagrange basis polynomials of the `nodes`, at most five, by
+/// Gauss-Legendre quadrature with three points, which is exact up to degree five.
+fn integrated_lagrange_weights(nodes: &[f64], a: f64, b: f64) -> Vec<f64> {
+    const GAUSS: [(f64, f64); 3] = [
+        (-0.774_596_669_241_483_4, 5.0 / 9.0),
+        (0.0, 8.0 / 9.0),
+        (0.774_596_669_241_483_4, 5.0 / 9.0),
+    ];
+    let (mid, half) = (0.5 * (a + b), 0.5 * (b - a));
+    (0..nodes.len())
+        .map(|j| {
+            GAUSS
+                .iter()
+                .map(|(s, w)| {
+                    let t = mid + half * s;
+                    let basis = nodes
+                        .iter()
+                        .enumerate()
+                        .filter(|(m, _)| *m != j)
+                        .fold(1.0, |acc, (_, x_m)| acc * (t - x_m) / (nodes[j] - x_m));
+                    w * half * basis
+                })
+                .sum()
+        })
+        .collect()
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for AdamsBashforthMoulton<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        AdamsBashforthMoulton::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        AdamsBashforthMoulton::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        AdamsBashforthMoulton::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        AdamsBashforthMoulton::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        AdamsBashforthMoulton::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for AdamsBashforthMoulton<V, F> {
+    fn order(&self) -> u32 {
+        self.order as u32
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::ode_fn;
+    use crate::Vector2;
+
+    /// Error at `x = 5` of the harmonic oscillator integrated with step size `h`.
+    fn error(order: usize, h: f64) -> f64 {
+        let oscillator = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+            dy[0] = y[1];
+            dy[1] = -y[0];
+        });
+        let mut solver =
+            AdamsBashforthMoulton::new(oscillator, 0.0, Vector2::new(1.0, 0.0), 5.0, h, order);
+        solver.integrate().unwrap();
+        (solver.y_final() - Vector2::new(5.0f64.cos(), -5.0f64.sin())).norm()
+    }
+
+    #[test]
+    fn converges_with_the_order_of_the_method() {
+        for order in 2..=5 {
+            let ratio = error(order, 0.02) / error(order, 0.01);
+            let observed = ratio.log2();
+            assert!(
+                (observed - order as f64).abs() < 0.3,
+                "order {}: {}",
+                order,
+                observed
+            );
+        }
+    }
+
+    /// Largest error of the dense output of the harmonic oscillator between the steps of size `h`.
+    fn dense_error(order: usize, h: f64) -> f64 {
+        let oscillator = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+            dy[0] = y[1];
+            dy[1] = -y[0];
+        });
+        let mut solver =
+            AdamsBashforthMoulton::new(oscillator, 0.0, Vector2::new(1.0, 0.0), 5.0, h, order);
+        solver.integrate().unwrap();
+        (0..17)
+            .map(|k| 0.013 + 0.29 * k as f64)
+            .map(|x| (solver.at(x).unwrap() - Vector2::new(x.cos(), -x.sin())).norm())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn dense_output_has_the_order_of_the_method() {
+        for order in 2..=5 {
+            let observed = (dense_error(order, 0.02) / dense_error(order, 0.01)).log2();
+            assert!(
+                (observed - order as f64).abs() < 0.3,
+                "order {}: {}",
+                order,
+                observed
+            );
+        }
+    }
+
+    #[test]
+    fn warm_start_continues_the_trajectory_of_a_previous_run() {
+        let oscillator = || {
+            ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                dy[0] = y[1];
+                dy[1] = -y[0];
+            })
+        };
+        let y0 = Vector2::new(1.0, 0.0);
+        let mut continuous = AdamsBashforthMoulton::new(oscillator(), 0.0, y0, 5.0, 0.125, 4);
+        continuous.integrate().unwrap();
+        let mut first = AdamsBashforthMoulton::new(oscillator(), 0.0, y0, 2.0, 0.125, 4);
+        first.integrate().unwrap();
+        let mut warm = AdamsBashforthMoulton::new(oscillator(), 0.0, y0, 5.0, 0.125, 4);
+        warm.warm_start(first.history());
+        let stats = warm.integrate().unwrap();
+        // The bootstrap of the first steps is skipped
+        assert_eq!(stats.num_eval(), 2 * stats.accepted_steps());
+        let n = first.x_out().len() - 1;
+        assert_eq!(warm.x_out()[..], continuous.x_out()[n..]);
+        assert_eq!(warm.y_out()[..], continuous.y_out()[n..]);
+    }
+}
@@ -0,0 +1,310 @@
+//! Adjoint sensitivity analysis with the classical fourth order Runge-Kutta method.
+
+use crate::dop_shared::{
+    check_eval, reject_events, step_count, validate_step_size, ControlFlag, IntegrationError,
+    IntegrationResult, OdeSolver, Stats, System,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The gradient of a loss `L(y(x_end))` with respect to the `System::num_params` parameters of
+/// the system is obtained in one backward pass, whatever the number of parameters, unlike the
+/// forward [`Sensitivity`](crate::Sensitivity). The forward integration stores the state at every
+/// step as checkpoints. [`gradient`](Self::gradient) then integrates the adjoint equations
+///
+/// `lambda' = -J^T lambda`, `mu' = -(df/dp)^T lambda`
+///
+/// backward from `lambda(x_end) = dL/dy`, `mu(x_end) = 0` to `lambda(x) = dL/dy(x)` and
+/// `mu(x) = dL/dp`. The products `J^T lambda` are given by `System::vjp` and the derivatives
+/// `df/dp_j` by `System::df_dp`. The state in the middle of each step is recomputed from the
+/// checkpoint at its start.
+pub struct AdjointSensitivity<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    y_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> AdjointSensitivity<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        AdjointSensitivity {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method, storing the checkpoints of the backward pass.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let y_new = self.rk4_step(self.x, &self.y, h);
+            check_eval(&self.f)?;
+            self.y = y_new;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.stats.num_eval += 4;
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Integrates the adjoint equations backward over the trajectory computed by
+    /// [`integrate`](Self::integrate), `dl_dy` being the gradient of the loss with respect to the
+    /// final state. Returns the gradients of the loss with respect to the initial state and to
+    /// the parameters of the system. The evaluations of the backward pass are added to the
+    /// statistics returned by [`stats`](Self::stats): each step recomputes the state in the middle
+    /// of the step with four evaluations of `System::ode` and evaluates the adjoint equations four
+    /// times, each evaluation counting once in `Stats::num_eval` and in `Stats::num_jac_eval` for
+    /// the product `J^T lambda`.
+    pub fn gradient(
+        &mut self,
+        dl_dy: OVector<T, D>,
+    ) -> Result<(OVector<T, D>, Vec<f64>), IntegrationError> {
+        if self.x_out.is_empty() {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "the forward integration must be run before computing the gradient"
+                    .to_string(),
+            });
+        }
+        let mut lambda = dl_dy;
+        let mut mu = vec![0.0; self.f.num_params()];
+        for i in (0..self.x_out.len() - 1).rev() {
+            let (x0, x1) = (self.x_out[i], self.x_out[i + 1]);
+            let h = x0 - x1;
+            let y0 = self.y_out[i].clone();
+            let y1 = self.y_out[i + 1].clone();
+            let y_mid = self.rk4_step(x0, &y0, -0.5 * h);
+            let x_mid = x1 + 0.5 * h;
+            self.stats.num_eval += 4;
+
+            let (a0, m0) = self.adjoint_derivatives(x1, &y1, &lambda);
+            let (a1, m1) =
+                self.adjoint_derivatives(x_mid, &y_mid, &(&lambda + a0.clone() * (0.5 * h)));
+            let (a2, m2) =
+                self.adjoint_derivatives(x_mid, &y_mid, &(&lambda + a1.clone() * (0.5 * h)));
+            let (a3, m3) = self.adjoint_derivatives(x0, &y0, &(&lambda + a2.clone() * h));
+
+            lambda += (a0 + a1 * 2.0 + a2 * 2.0 + a3) * (h / 6.0);
+            for (j, mu_j) in mu.iter_mut().enumerate() {
+                *mu_j += (m0[j] + 2.0 * m1[j] + 2.0 * m2[j] + m3[j]) * h / 6.0;
+            }
+        }
+        Ok((lambda, mu))
+    }
+
+    /// Evaluates the derivatives `-J^T lambda` and `-(df/dp)^T lambda` of the adjoint variables.
+    fn adjoint_derivatives(
+        &mut self,
+        x: f64,
+        y: &OVector<T, D>,
+        lambda: &OVector<T, D>,
+    ) -> (OVector<T, D>, Vec<f64>) {
+        let (rows, cols) = y.shape_generic();
+        let mut vjp = OVector::zeros_generic(rows, cols);
+        self.f.vjp(x, y, lambda, &mut vjp);
+        self.stats.num_eval += 1;
+        self.stats.num_jac_eval += 1;
+        let mut df_dp = vec![OVector::zeros_generic(rows, cols); self.f.num_params()];
+        self.f.df_dp(x, y, &mut df_dp);
+        let dmu = df_dp
+            .iter()
+            .map(|df_dp_j| {
+                -f64::from(
+                    df_dp_j
+                        .iter()
+                        .zip(lambda.iter())
+                        .fold(T::zero(), |acc, (d, l)| acc + *d * *l),
+                )
+            })
+            .collect();
+        (-vjp, dmu)
+    }
+
+    /// Performs one step of size `h` of the classical Runge-Kutta method from (`x`, `y`).
+    fn rk4_step(&self, x: f64, y: &OVector<T, D>, h: f64) -> OVector<T, D> {
+        let (rows, cols) = y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); 4];
+        self.f.ode(x, y, &mut k[0]);
+        self.f
+            .ode(x + 0.5 * h, &(y + k[0].clone() * (0.5 * h)), &mut k[1]);
+        self.f
+            .ode(x + 0.5 * h, &(y + k[1].clone() * (0.5 * h)), &mut k[2]);
+        self.f.ode(x + h, &(y + k[2].clone() * h), &mut k[3]);
+        y + (k[0].clone() * (1.0 / 6.0)
+            + k[1].clone() * (1.0 / 3.0)
+            + k[2].clone() * (1.0 / 3.0)
+            + k[3].clone() * (1.0 / 6.0))
+            * h
+    }
+
+    /// Statistics of the forward integration and of the backward passes of
+    /// [`gradient`](Self::gradient) performed since.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        &self.y_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        IntegrationResult {
+            x: self.x_out,
+            y: self.y_out,
+            stats: self.stats,
+        }
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for AdjointSensitivity<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        AdjointSensitivity::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        AdjointSensitivity::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        AdjointSensitivity::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        AdjointSensitivity::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        AdjointSensitivity::y_final(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector1;
+
+    /// Exponential decay `y' = -p y`.
+    struct Decay {
+        p: f64,
+    }
+
+    impl System<Vector1<f64>> for Decay {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = -self.p * y[0];
+        }
+
+        fn num_params(&self) -> usize {
+            1
+        }
+
+        fn df_dp(&self, _x: f64, y: &Vector1<f64>, out: &mut [Vector1<f64>]) {
+            out[0][0] = -y[0];
+        }
+    }
+
+    #[test]
+    fn gradient_of_the_final_state() {
+        let (p, x_end, y0) = (0.5, 2.0, 3.0);
+        let mut solver = AdjointSensitivity::new(Decay { p }, 0.0, Vector1::new(y0), x_end, 0.01);
+        let stats = solver.integrate().unwrap();
+        let (dl_dy0, dl_dp) = solver.gradient(Vector1::new(1.0)).unwrap();
+        let decay = (-p * x_end).exp();
+        assert!((dl_dy0[0] - decay).abs() < 1e-9);
+        assert!((dl_dp[0] + x_end * y0 * decay).abs() < 1e-8);
+
+        let n = stats.accepted_steps();
+        assert_eq!(n, 200);
+        assert_eq!(stats.num_eval(), 4 * n);
+        // The backward pass recomputes the midpoint states and evaluates the adjoint equations
+        assert_eq!(solver.stats().num_eval(), 12 * n);
+        assert_eq!(solver.stats().num_jac_eval(), 4 * n);
+    }
+}
@@ -0,0 +1,364 @@
+//! Automatic switching between an explicit and an implicit method depending on the stiffness of
+//! the problem.
+//!
+//! The integration starts with the Dormand-Prince method [`Dopri5`], whose last two stages give an
+//! estimate of `|h * lambda|` for the dominant eigenvalue `lambda` of the Jacobian. When the step
+//! size is repeatedly limited by the stability of the method rather than by the accuracy, the
+//! problem is considered stiff and the integration continues with the [`Bdf`] method. The
+//! explicit method is used again once the step size times the norm of the Jacobian has remained
+//! within the stability domain of the explicit method for several steps.
+
+use crate::bdf::Bdf;
+use crate::dop_shared::{
+    reject_events, ControlFlag, EvalError, IntegrationError, IntegrationResult, MethodInfo,
+    OdeSolver, State, Stats, System, Tolerance,
+};
+#[cfg(feature = "logging")]
+use crate::dop_shared::{LogRecord, Logger};
+use crate::dopri5::Dopri5;
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OVector, RealField};
+use simba::scalar::SubsetOf;
+use std::cell::Cell;
+#[cfg(feature = "logging")]
+use std::rc::Rc;
+
+/// Method used by the [`Auto`] solver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// Explicit Runge-Kutta method of order 5(4) ([`Dopri5`]).
+    Explicit,
+    /// Backward differentiation formulas ([`Bdf`]).
+    Implicit,
+}
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Mass matrices are not supported since the explicit method ignores them.
+pub struct Auto<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    method: Method,
+    switches: Vec<(f64, Method)>,
+    x_out: Vec<f64>,
+    y_out: Vec<V>,
+    stats: Stats,
+    #[cfg(feature = "logging")]
+    logger: Option<Rc<Logger>>,
+}
+
+impl<T, D, F> Auto<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        Auto {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            rtol: rtol.into(),
+            atol: atol.into(),
+            method: Method::Explicit,
+            switches: Vec::new(),
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            stats: Stats::new(),
+            #[cfg(feature = "logging")]
+            logger: None,
+        }
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration from the explicit and the
+    /// implicit solvers, as described for [`Dopri5::logger`] and [`Bdf::logger`]. Requires the
+    /// `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.logger = Some(Rc::new(logger));
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        loop {
+            let first = self.x_out.is_empty();
+            let f = Borrowed {
+                f: &mut self.f,
+                skip_observer: Cell::new(!first),
+            };
+            let (result, status, h, switch) = match self.method {
+                Method::Explicit => {
+                    let mut solver = Dopri5::new(
+                        f,
+                        self.x,
+                        self.y.clone(),
+                        self.x_end,
+                        self.h,
+                        self.rtol.clone(),
+                        self.atol.clone(),
+                    );
+                    solver.detect_switch = true;
+                    #[cfg(feature = "logging")]
+                    if let Some(logger) = self.logger.clone() {
+                        solver.logger(move |record| logger(record));
+                    }
+                    let status = solver.integrate();
+                    let h = solver.last_step_size();
+                    let switch = solver.switch;
+                    (solver.into_result(), status, h, switch)
+                }
+                Method::Implicit => {
+                    let mut solver = Bdf::new(
+                        f,
+                        self.x,
+                        self.y.clone(),
+                        self.x_end,
+                        self.h,
+                        self.rtol.clone(),
+                        self.atol.clone(),
+                    );
+                    solver.detect_switch = true;
+                    #[cfg(feature = "logging")]
+                    if let Some(logger) = self.logger.clone() {
+                        solver.logger(move |record| logger(record));
+                    }
+                    let status = solver.integrate();
+                    let h = solver.last_step_size();
+                    let switch = solver.switch;
+                    (solver.into_result(), status, h, switch)
+                }
+            };
+            self.stats.merge(&result.stats);
+            // The initial point of a segment is the final point of the previous one
+            let skip = if first { 0 } else { 1 };
+            self.x_out.extend(result.x.into_iter().skip(skip));
+            self.y_out.extend(result.y.into_iter().skip(skip));
+            if let Some(x) = self.x_out.last() {
+                self.x = *x;
+                self.y = self.y_out[self.y_out.len() - 1].clone();
+            }
+            status?;
+            if h != 0.0 {
+                self.h = h;
+            }
+            // The segment otherwise reached x_end or was stopped by `solout`
+            if !switch || self.x == self.x_end {
+                break;
+            }
+            self.method = match self.method {
+                Method::Explicit => Method::Implicit,
+                Method::Implicit => Method::Explicit,
+            };
+            self.switches.push((self.x, self.method));
+            self.stats.num_switches += 1;
+        }
+        Ok(self.stats)
+    }
+
+    /// Returns the values of the independent variable at which the method was switched, together
+    /// with the method used from there on. The integration starts with [`Method::Explicit`].
+    pub fn switches(&self) -> &[(f64, Method)] {
+        &self.switches
+    }
+
+    /// Returns the method currently in use, i.e. the one which completed the integration.
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        &self.y_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        IntegrationResult {
+            x: self.x_out,
+            y: self.y_out,
+            stats: self.stats,
+        }
+    }
+}
+
+impl<T, D, F> OdeSolver<OVector<T, D>> for Auto<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Auto::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Auto::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Auto::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Auto::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Auto::y_final(self)
+    }
+}
+
+/// Properties of the method in use, which changes when the solver switches.
+impl<V, F: System<V>> MethodInfo for Auto<V, F> {
+    fn order(&self) -> u32 {
+        5
+    }
+
+    fn is_implicit(&self) -> bool {
+        self.method == Method::Implicit
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        self.method == Method::Explicit
+    }
+}
+
+/// System borrowed by the solver of one segment of the integration. The observer is not called
+/// again at the initial point of the segments following the first one, and receives the
+/// statistics of the current segment.
+struct Borrowed<'a, F> {
+    f: &'a mut F,
+    skip_observer: Cell<bool>,
+}
+
+impl<V, F> System<V> for Borrowed<'_, F>
+where
+    F: System<V>,
+{
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        self.f.ode(x, y, dy);
+    }
+
+    fn take_eval_error(&self) -> Option<(f64, EvalError)> {
+        self.f.take_eval_error()
+    }
+
+    fn jacobian(&self, x: f64, y: &V, out: &mut V::Matrix)
+    where
+        V: State,
+    {
+        self.f.jacobian(x, y, out);
+    }
+
+    fn vjp(&self, x: f64, y: &V, lambda: &V, out: &mut V)
+    where
+        V: State,
+    {
+        self.f.vjp(x, y, lambda, out);
+    }
+
+    fn jacobian_bandwidth(&self) -> Option<(usize, usize)> {
+        self.f.jacobian_bandwidth()
+    }
+
+    fn jacobian_sparsity(&self) -> Option<Vec<Vec<usize>>> {
+        self.f.jacobian_sparsity()
+    }
+
+    fn project(&self, x: f64, y: &mut V) {
+        self.f.project(x, y);
+    }
+
+    fn observer_full(&self, x: f64, y: &V, h: f64, stats: &Stats) {
+        if !self.skip_observer.replace(false) {
+            self.f.observer_full(x, y, h, stats);
+        }
+    }
+
+    fn solout(&mut self, x: f64, y: &V) -> ControlFlag {
+        self.f.solout(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::VanDerPol;
+
+    #[test]
+    fn switches_to_bdf_on_the_stiff_van_der_pol_oscillator() {
+        let problem = VanDerPol::new(1000.0);
+        let y0 = problem.initial_state();
+        let mut solver = Auto::new(problem, 0.0, y0, 20.0, None, 1e-6, 1e-6);
+        let stats = solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 20.0);
+        assert_eq!(solver.switches()[0].1, Method::Implicit);
+        assert_eq!(stats.num_switches() as usize, solver.switches().len());
+
+        // The explicit method alone is limited by its stability over the whole interval
+        let mut explicit = Dopri5::new(problem, 0.0, y0, 20.0, None, 1e-6, 1e-6);
+        let explicit = explicit.integrate().unwrap();
+        assert!(stats.accepted_steps() < explicit.accepted_steps() / 10);
+    }
+}
@@ -0,0 +1,690 @@
+//! Backward differentiation formulas of order 1 to 5 with adaptive step size and order control.
+//!
+//! The implementation follows the quasi-constant step size formulation of Shampine and Reichelt
+//! (The MATLAB ODE Suite, 1997): the solution is represented by its backward differences, which
+//! are rescaled whenever the step size changes.
+
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, find_segment, initial_step_size, limit_step, record_diagnostic, reject_events,
+    ControlFlag, Factorization, IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Output,
+    SolverLog, Stats, StepDiagnostic, System, Tolerance,
+};
+use crate::dopri5::STIFF_RATIO;
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OMatrix, OVector, RealField};
+use simba::scalar::SubsetOf;
+
+const MAX_ORDER: usize = 5;
+const NEWTON_MAX_ITER: u32 = 4;
+
+/// Structure containing the parameters for the numerical integration.
+pub struct Bdf<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    h_accepted: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    safety: f64,
+    fac_min: f64,
+    fac_max: f64,
+    output: Output<V>,
+    dense: Vec<(f64, Vec<V>)>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    stats: Stats,
+    log: SolverLog,
+    pub(crate) detect_switch: bool,
+    pub(crate) switch: bool,
+}
+
+impl<T, D, F> Bdf<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        Bdf {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            h_accepted: 0.0,
+            rtol: rtol.into(),
+            atol: atol.into(),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            safety: 0.9,
+            fac_min: 0.2,
+            fac_max: 10.0,
+            output: Output::new(),
+            dense: Vec::new(),
+            diagnostics: None,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+            detect_switch: false,
+            switch: false,
+        }
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets the safety factor applied to the step size proposed from the error estimate (default
+    /// 0.9), further reduced when the Newton iteration needs several iterations. A smaller factor
+    /// takes more but safer steps, and thus rejects fewer steps.
+    pub fn safety_factor(&mut self, safety: f64) {
+        self.safety = safety;
+    }
+
+    /// Sets the largest ratio between the next and the current step size (default 10).
+    pub fn max_increase_factor(&mut self, fac_max: f64) {
+        self.fac_max = fac_max;
+    }
+
+    /// Sets the smallest ratio between the next and the current step size after a rejected step
+    /// (default 0.2).
+    pub fn max_decrease_factor(&mut self, fac_min: f64) {
+        self.fac_min = fac_min;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger), together with a warning for every nearly singular
+    /// iteration matrix. Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+
+        let (rows, cols) = self.y.shape_generic();
+        let mut dy = OVector::zeros_generic(rows, cols);
+        self.f.ode(self.x, &self.y, &mut dy);
+        self.stats.num_eval += 1;
+        let mut mass = OMatrix::identity_generic(rows, rows);
+        self.f.mass_matrix(&mut mass);
+        if mass != OMatrix::identity_generic(rows, rows) {
+            dy = match mass.clone().lu().solve(&dy) {
+                Some(dy) => dy,
+                None => {
+                    return Err(IntegrationError::InvalidStepConfig {
+                        reason: "the mass matrix is singular".to_string(),
+                    })
+                }
+            };
+        }
+        if self.h == 0.0 {
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = OVector::zeros_generic(rows, cols);
+                    self.f.ode(x, y, &mut dy);
+                    mass.clone().lu().solve(&dy).unwrap_or(dy)
+                },
+                self.x,
+                &self.y,
+                &dy,
+                self.x_end,
+                2,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.stats.num_eval += 1;
+        }
+
+        // Backward differences of the solution, d[0] being the solution itself
+        let mut d = vec![OVector::zeros_generic(rows, cols); MAX_ORDER + 3];
+        d[0] = self.y.clone();
+        d[1] = dy * self.h;
+        let mut order = 1;
+        let mut n_equal_steps = 0;
+
+        let mut jac = OMatrix::zeros_generic(rows, rows);
+        self.f.jacobian(self.x, &self.y, &mut jac);
+        self.stats.num_jac_eval += 1;
+        let mut lu: Option<Factorization<T, D>> = None;
+        let rtol = self.rtol.min();
+        let newton_tol = (10.0 * f64::EPSILON / rtol).max(0.03_f64.min(rtol.sqrt()));
+
+        let mut n_step = 0;
+        let mut n_non_stiff = 0;
+        'outer: while (self.x_end - self.x) * posneg > 0.0 {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            n_step += 1;
+
+            let mut current_jac = false;
+            loop {
+                if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                    return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+                }
+                // Do not step past the end point
+                let mut last = false;
+                if (self.x + self.h - self.x_end) * posneg >= 0.0 {
+                    let factor = (self.x_end - self.x) / self.h;
+                    change_differences(&mut d, order, factor);
+                    self.h = self.x_end - self.x;
+                    n_equal_steps = 0;
+                    lu = None;
+                    last = true;
+                }
+                let x_new = if last { self.x_end } else { self.x + self.h };
+
+                let y_pred = d[1..=order].iter().fold(d[0].clone(), |acc, d_j| acc + d_j);
+                let scale = self.scale(&y_pred);
+                let psi = d[1..=order]
+                    .iter()
+                    .enumerate()
+                    .fold(OVector::zeros_generic(rows, cols), |acc, (j, d_j)| {
+                        acc + d_j.clone() * gamma(j + 1)
+                    })
+                    * (1.0 / gamma(order));
+                let c = self.h / gamma(order);
+                let c_t: T = nalgebra::convert(c);
+                let bandwidth = self.f.jacobian_bandwidth();
+                let (x, h, log) = (self.x, self.h, &self.log);
+                let lu_ref = lu.get_or_insert_with(|| {
+                    let lu = Factorization::new(mass.clone() - jac.clone() * c_t, bandwidth);
+                    log.factorization(x, h, &lu);
+                    lu
+                });
+
+                let solution =
+                    self.solve_newton(x_new, &y_pred, &psi, c, lu_ref, &mass, &scale, newton_tol);
+                check_eval(&self.f)?;
+                let (n_iter, correction) = match solution {
+                    Some(solution) => solution,
+                    None => {
+                        if !current_jac {
+                            // Retry with an up to date Jacobian
+                            self.f.jacobian(x_new, &y_pred, &mut jac);
+                            self.stats.num_jac_eval += 1;
+                            current_jac = true;
+                        } else {
+                            if 0.05 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                                return Err(IntegrationError::NewtonDidNotConverge { x: self.x });
+                            }
+                            record_diagnostic(
+                                &mut self.diagnostics,
+                                self.x,
+                                self.h,
+                                f64::INFINITY,
+                                false,
+                            );
+                            self.log.rejected(self.x, self.h, f64::INFINITY);
+                            let factor = self.limit_factor(0.5)?;
+                            self.h *= factor;
+                            change_differences(&mut d, order, factor);
+                            n_equal_steps = 0;
+                            self.stats.rejected_steps += 1;
+                        }
+                        lu = None;
+                        continue;
+                    }
+                };
+
+                let safety = self.safety * (2 * NEWTON_MAX_ITER + 1) as f64
+                    / (2 * NEWTON_MAX_ITER + n_iter) as f64;
+                let y_new = y_pred + &correction;
+                let scale = self.scale(&y_new);
+                let err = error_constant(order) * weighted_norm(&correction, &scale);
+                record_diagnostic(&mut self.diagnostics, self.x, self.h, err, err <= 1.0);
+                if err > 1.0 {
+                    self.log.rejected(self.x, self.h, err);
+                    let factor = self
+                        .fac_min
+                        .max(safety * err.powf(-1.0 / (order as f64 + 1.0)));
+                    let factor = self.limit_factor(factor)?;
+                    self.h *= factor;
+                    change_differences(&mut d, order, factor);
+                    n_equal_steps = 0;
+                    lu = None;
+                    self.stats.rejected_steps += 1;
+                    continue;
+                }
+
+                self.log.accepted(self.x, self.h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(self.h);
+                n_equal_steps += 1;
+                self.x = x_new;
+                self.y = y_new;
+                self.h_accepted = self.h;
+                // Detection of the end of stiffness, used by the `Auto` solver: the step size times
+                // the norm of the Jacobian lies within the stability domain of the explicit method
+                if self.detect_switch {
+                    let rho = jac
+                        .row_iter()
+                        .map(|row| row.iter().fold(0.0, |acc, j| acc + f64::from(*j).abs()))
+                        .fold(0.0, f64::max);
+                    if self.h.abs() * rho < STIFF_RATIO {
+                        n_non_stiff += 1;
+                    } else {
+                        n_non_stiff = 0;
+                    }
+                    self.switch = n_non_stiff == 15;
+                }
+
+                // Update the backward differences
+                d[order + 2] = &correction - &d[order + 1];
+                d[order + 1] = correction;
+                for i in (0..=order).rev() {
+                    d[i] = &d[i] + &d[i + 1];
+                }
+                // The projected state enters all the backward differences with a unit coefficient
+                self.f.project(self.x, &mut self.y);
+                if self.y != d[0] {
+                    let shift = &self.y - &d[0];
+                    for d_i in d[..=order].iter_mut() {
+                        *d_i += &shift;
+                    }
+                }
+                self.dense.push((self.h, d[..=order].to_vec()));
+                self.output.push(self.x, self.y.clone());
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop || self.switch {
+                    break 'outer;
+                }
+
+                // Select the order and the step size once the current ones have been used long enough
+                if n_equal_steps > order && self.x != self.x_end {
+                    let err_m = if order > 1 {
+                        error_constant(order - 1) * weighted_norm(&d[order], &scale)
+                    } else {
+                        f64::INFINITY
+                    };
+                    let err_p = if order < MAX_ORDER {
+                        error_constant(order + 1) * weighted_norm(&d[order + 2], &scale)
+                    } else {
+                        f64::INFINITY
+                    };
+                    let factors = [err_m, err, err_p]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, e)| e.powf(-1.0 / (order + i) as f64))
+                        .collect::<Vec<_>>();
+                    let (delta, max_factor) = factors.iter().enumerate().fold(
+                        (0, f64::NEG_INFINITY),
+                        |best, (i, fac)| if *fac > best.1 { (i, *fac) } else { best },
+                    );
+                    order = order + delta - 1;
+                    let factor = self.limit_factor(self.fac_max.min(safety * max_factor))?;
+                    self.h *= factor;
+                    change_differences(&mut d, order, factor);
+                    n_equal_steps = 0;
+                    lu = None;
+                }
+                break;
+            }
+        }
+        check_eval(&self.f)?;
+        Ok(self.stats)
+    }
+
+    /// Solves the nonlinear system of one step with a simplified Newton iteration, starting from
+    /// the predicted solution. Returns the number of iterations and the correction of the
+    /// solution with respect to the prediction, or `None` if the iteration does not converge.
+    #[allow(clippy::too_many_arguments)]
+    fn solve_newton(
+        &mut self,
+        x_new: f64,
+        y_pred: &OVector<T, D>,
+        psi: &OVector<T, D>,
+        c: f64,
+        lu: &Factorization<T, D>,
+        mass: &OMatrix<T, D, D>,
+        scale: &[f64],
+        tol: f64,
+    ) -> Option<(u32, OVector<T, D>)> {
+        let (rows, cols) = y_pred.shape_generic();
+        let mut y = y_pred.clone();
+        let mut correction = OVector::zeros_generic(rows, cols);
+        let mut dy = OVector::zeros_generic(rows, cols);
+        let mut norm_old: Option<f64> = None;
+        for k in 0..NEWTON_MAX_ITER {
+            self.f.ode(x_new, &y, &mut dy);
+            self.stats.num_eval += 1;
+            self.stats.num_newton_iter += 1;
+            if !dy.iter().all(|dy_i| f64::from(*dy_i).is_finite()) {
+                return None;
+            }
+            let delta = lu.solve(&(dy.clone() * c - mass * (psi + &correction)))?;
+            let norm = weighted_norm(&delta, scale);
+            let rate = norm_old.map(|norm_old| norm / norm_old);
+            if let Some(rate) = rate {
+                let remaining = (NEWTON_MAX_ITER - k) as i32;
+                if rate >= 1.0 || rate.powi(remaining) / (1.0 - rate) * norm > tol {
+                    return None;
+                }
+            }
+            y += &delta;
+            correction += &delta;
+            if norm == 0.0 || rate.is_some_and(|rate| rate / (1.0 - rate) * norm < tol) {
+                return Some((k + 1, correction));
+            }
+            norm_old = Some(norm);
+        }
+        None
+    }
+
+    /// Limits the factor by which the step size is multiplied so that the new step size lies
+    /// within the bounds set with [`min_step`](Self::min_step) and [`max_step`](Self::max_step).
+    fn limit_factor(&self, factor: f64) -> Result<f64, IntegrationError> {
+        let h = limit_step(self.h * factor, self.min_step, self.max_step, self.x)?;
+        self.log.clamped(self.x, self.h * factor, h, self.max_step);
+        Ok(h / self.h)
+    }
+
+    /// Computes the error weights `atol + rtol * |y_i|`.
+    fn scale(&self, y: &OVector<T, D>) -> Vec<f64> {
+        y.iter()
+            .enumerate()
+            .map(|(i, y_i)| self.atol.get(i) + self.rtol.get(i) * f64::from(*y_i).abs())
+            .collect()
+    }
+
+    /// Getter for the last accepted step size.
+    pub fn last_step_size(&self) -> f64 {
+        self.h_accepted
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Evaluates the interpolating polynomial of the method at `x`. Returns `None` if `x` lies
+    /// outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        let (h, d) = &self.dense[i];
+        let x_new = self.output.x[i + 1];
+        let mut p = 1.0;
+        let mut y = d[0].clone();
+        for (j, d_j) in d.iter().enumerate().skip(1) {
+            let shift = x_new - h * (j - 1) as f64;
+            p *= (x - shift) / (h * j as f64);
+            y += d_j.clone() * p;
+        }
+        Some(y)
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D, F> OdeSolver<OVector<T, D>> for Bdf<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Bdf::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Bdf::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Bdf::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Bdf::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Bdf::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for Bdf<V, F> {
+    fn order(&self) -> u32 {
+        5
+    }
+
+    fn is_implicit(&self) -> bool {
+        true
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+/// Returns `sum_{j=1}^{k} 1 / j`, the leading coefficient of the formula of order `k`.
+fn gamma(k: usize) -> f64 {
+    (1..=k).map(|j| 1.0 / j as f64).sum()
+}
+
+/// Returns the error constant of the formula of order `k`.
+fn error_constant(k: usize) -> f64 {
+    1.0 / (k as f64 + 1.0)
+}
+
+/// Computes the weighted root mean square norm of `v`.
+fn weighted_norm<T, D: Dim>(v: &OVector<T, D>, scale: &[f64]) -> f64
+where
+    f64: From<T>,
+    T: Copy + RealField,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let sum: f64 = v
+        .iter()
+        .zip(scale.iter())
+        .map(|(v_i, sc)| (f64::from(*v_i) / sc).powi(2))
+        .sum();
+    (sum / v.len() as f64).sqrt()
+}
+
+/// Rescales the backward differences of order up to `order` when the step size is multiplied by `factor`.
+fn change_differences<T, D: Dim>(d: &mut [OVector<T, D>], order: usize, factor: f64)
+where
+    T: Copy + RealField,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let r = difference_matrix(order, factor);
+    let u = difference_matrix(order, 1.0);
+    let (rows, cols) = d[0].shape_generic();
+    let rescaled = (0..=order)
+        .map(|i| {
+            (0..=order).fold(OVector::zeros_generic(rows, cols), |acc, j| {
+                let ru: f64 = (0..=order).map(|k| r[j][k] * u[k][i]).sum();
+                acc + d[j].clone() * ru
+            })
+        })
+        .collect::<Vec<_>>();
+    for (d_i, rescaled_i) in d.iter_mut().zip(rescaled) {
+        *d_i = rescaled_i;
+    }
+}
+
+/// Computes the matrix relating the backward differences for the step sizes `h` and `factor * h`.
+fn difference_matrix(order: usize, factor: f64) -> Vec<Vec<f64>> {
+    let mut m = vec![vec![0.0; order + 1]; order + 1];
+    m[0] = vec![1.0; order + 1];
+    for i in 1..=order {
+        let prev = m[i - 1].clone();
+        for (j, m_ij) in m[i].iter_mut().enumerate().skip(1) {
+            *m_ij = prev[j] * (i as f64 - 1.0 - factor * j as f64) / i as f64;
+        }
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::VanDerPol;
+    use crate::Vector2;
+
+    /// Integrates the stiff Van der Pol oscillator with `mu = 100` over two periods.
+    fn van_der_pol(configure: impl Fn(&mut Bdf<Vector2<f64>, VanDerPol>)) -> (Stats, Vec<f64>) {
+        let problem = VanDerPol::new(100.0);
+        let (x0, x_end) = problem.t_span();
+        let mut solver = Bdf::new(
+            problem,
+            x0,
+            problem.initial_state(),
+            x_end,
+            None,
+            1e-6,
+            1e-6,
+        );
+        solver.record_diagnostics(true);
+        configure(&mut solver);
+        let stats = solver.integrate().unwrap();
+        let steps = solver.diagnostics().iter().map(|d| d.h).collect();
+        (stats, steps)
+    }
+
+    #[test]
+    fn conservative_safety_factor_rejects_fewer_steps() {
+        let (default, _) = van_der_pol(|_| {});
+        let (conservative, _) = van_der_pol(|solver| solver.safety_factor(0.5));
+        assert!(2 * conservative.rejected_steps() < default.rejected_steps());
+        assert!(conservative.accepted_steps() > default.accepted_steps());
+    }
+
+    #[test]
+    fn step_size_growth_is_bounded() {
+        let (_, steps) = van_der_pol(|solver| solver.max_increase_factor(1.5));
+        assert!(steps.windows(2).all(|w| w[1] <= 1.5 * w[0] * (1.0 + 1e-12)));
+        let (_, steps) = van_der_pol(|_| {});
+        assert!(steps.windows(2).any(|w| w[1] > 1.5 * w[0]));
+    }
+}
@@ -0,0 +1,250 @@
+//! Work-precision comparison of the solvers on a problem, to choose a solver by measuring the
+//! work each of them needs to reach a given accuracy.
+//!
+//! The adaptive solvers are run at each of the tolerances, used as both the relative and the
+//! absolute tolerance, and the fixed step solvers at each of the step sizes. Each run records the
+//! number of evaluations of the right-hand side, the largest error over the components of the
+//! final state against a reference solution and the elapsed wall-clock time.
+//!
+//! ```
+//! # use ode_event_solvers::{problems::HarmonicOscillator, WorkPrecision};
+//! let problem = HarmonicOscillator::default();
+//! let (x, x_end) = problem.t_span();
+//! let reference = problem.exact_solution(x_end);
+//! let mut bench = WorkPrecision::new(x, problem.initial_state(), x_end, reference);
+//! bench.tolerances(vec![1.0e-4, 1.0e-6, 1.0e-8]);
+//! bench.step_sizes(vec![1.0e-2, 1.0e-3]);
+//! let points = bench.run(|| problem);
+//! # assert!(!points.is_empty());
+//! ```
+
+use crate::dop_shared::{OdeSolver, System};
+use crate::{
+    Bdf, BogackiShampine23, BulirschStoer, CashKarp45, CrankNicolson, Dop853, Dopri5, Euler, Heun,
+    ImplicitEuler, Midpoint, Ralston2, Rosenbrock4, RungeKutta4, SspRk3,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OVector};
+use std::time::{Duration, Instant};
+
+// Tolerance and largest number of iterations of the Newton iteration of the implicit fixed step
+// solvers
+const NEWTON_TOL: f64 = 1.0e-10;
+const MAX_NEWTON_ITER: u32 = 20;
+
+/// Solvers compared by [`WorkPrecision`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Method {
+    Dopri5,
+    Dop853,
+    BogackiShampine23,
+    CashKarp45,
+    BulirschStoer,
+    Rosenbrock4,
+    Bdf,
+    Euler,
+    Heun,
+    Midpoint,
+    Ralston2,
+    RungeKutta4,
+    SspRk3,
+    ImplicitEuler,
+    CrankNicolson,
+}
+
+impl Method {
+    /// All the solvers, adaptive ones first.
+    pub const ALL: [Method; 15] = [
+        Method::Dopri5,
+        Method::Dop853,
+        Method::BogackiShampine23,
+        Method::CashKarp45,
+        Method::BulirschStoer,
+        Method::Rosenbrock4,
+        Method::Bdf,
+        Method::Euler,
+        Method::Heun,
+        Method::Midpoint,
+        Method::Ralston2,
+        Method::RungeKutta4,
+        Method::SspRk3,
+        Method::ImplicitEuler,
+        Method::CrankNicolson,
+    ];
+
+    /// Returns true if the solver adapts its step size to tolerances, false if it takes a fixed
+    /// step size.
+    pub fn is_adaptive(&self) -> bool {
+        matches!(
+            self,
+            Method::Dopri5
+                | Method::Dop853
+                | Method::BogackiShampine23
+                | Method::CashKarp45
+                | Method::BulirschStoer
+                | Method::Rosenbrock4
+                | Method::Bdf
+        )
+    }
+}
+
+/// Work needed by a solver to reach an accuracy, measured by [`WorkPrecision::run`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkPrecisionPoint {
+    /// Solver of the run.
+    pub method: Method,
+    /// Tolerance of an adaptive solver or step size of a fixed step solver.
+    pub setting: f64,
+    /// Number of evaluations of the right-hand side.
+    pub num_eval: u32,
+    /// Largest error over the components of the final state.
+    pub error: f64,
+    /// Wall-clock time of the integration.
+    pub elapsed: Duration,
+}
+
+/// Work-precision comparison of the solvers on the problem of initial state `y` at `x`,
+/// integrated up to `x_end`, whose solution there is `reference`, either exact or computed at a
+/// tight tolerance.
+pub struct WorkPrecision<D: Dim>
+where
+    DefaultAllocator: Allocator<f64, D>,
+{
+    x: f64,
+    y: OVector<f64, D>,
+    x_end: f64,
+    reference: OVector<f64, D>,
+    methods: Vec<Method>,
+    tolerances: Vec<f64>,
+    step_sizes: Vec<f64>,
+}
+
+impl<D> WorkPrecision<D>
+where
+    D: Dim + DimMin<D, Output = D>,
+    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Default initializer for the structure, comparing all the solvers at the tolerances
+    /// `1e-3`, `1e-5`, `1e-7` and `1e-9` and at the step sizes of 1/100 and 1/1000 of the
+    /// interval of integration.
+    pub fn new(x: f64, y: OVector<f64, D>, x_end: f64, reference: OVector<f64, D>) -> Self {
+        let span = (x_end - x).abs();
+        WorkPrecision {
+            x,
+            y,
+            x_end,
+            reference,
+            methods: Method::ALL.to_vec(),
+            tolerances: vec![1.0e-3, 1.0e-5, 1.0e-7, 1.0e-9],
+            step_sizes: vec![1.0e-2 * span, 1.0e-3 * span],
+        }
+    }
+
+    /// Sets the solvers to compare.
+    pub fn methods(&mut self, methods: Vec<Method>) {
+        self.methods = methods;
+    }
+
+    /// Sets the tolerances at which the adaptive solvers are run.
+    pub fn tolerances(&mut self, tolerances: Vec<f64>) {
+        self.tolerances = tolerances;
+    }
+
+    /// Sets the step sizes at which the fixed step solvers are run.
+    pub fn step_sizes(&mut self, step_sizes: Vec<f64>) {
+        self.step_sizes = step_sizes;
+    }
+
+    /// Runs the solvers on the systems built by `make`, one for each run, and returns the
+    /// measured points in the order of the solvers and of the settings. The runs which fail, or
+    /// which stop before `x_end`, are left out.
+    pub fn run<F>(&self, make: impl Fn() -> F) -> Vec<WorkPrecisionPoint>
+    where
+        F: System<OVector<f64, D>>,
+    {
+        let mut points = Vec::new();
+        for &method in &self.methods {
+            let settings = if method.is_adaptive() {
+                &self.tolerances
+            } else {
+                &self.step_sizes
+            };
+            for &setting in settings {
+                if let Some((num_eval, error, elapsed)) = self.measure(method, setting, make()) {
+                    points.push(WorkPrecisionPoint {
+                        method,
+                        setting,
+                        num_eval,
+                        error,
+                        elapsed,
+                    });
+                }
+            }
+        }
+        points
+    }
+
+    /// Integrates the system `f` with `method` at the tolerance or step size `setting`.
+    fn measure<F>(&self, method: Method, setting: f64, f: F) -> Option<(u32, f64, Duration)>
+    where
+        F: System<OVector<f64, D>>,
+    {
+        let (x, y, x_end) = (self.x, self.y.clone(), self.x_end);
+        match method {
+            Method::Dopri5 => self.time(Dopri5::new(f, x, y, x_end, None, setting, setting)),
+            Method::Dop853 => self.time(Dop853::new(f, x, y, x_end, None, setting, setting)),
+            Method::BogackiShampine23 => self.time(BogackiShampine23::new(
+                f, x, y, x_end, None, setting, setting,
+            )),
+            Method::CashKarp45 => {
+                self.time(CashKarp45::new(f, x, y, x_end, None, setting, setting))
+            }
+            Method::BulirschStoer => {
+                self.time(BulirschStoer::new(f, x, y, x_end, None, setting, setting))
+            }
+            Method::Rosenbrock4 => {
+                self.time(Rosenbrock4::new(f, x, y, x_end, None, setting, setting))
+            }
+            Method::Bdf => self.time(Bdf::new(f, x, y, x_end, None, setting, setting)),
+            Method::Euler => self.time(Euler::new(f, x, y, x_end, vec![setting; 3])),
+            Method::Heun => self.time(Heun::new(f, x, y, x_end, setting)),
+            Method::Midpoint => self.time(Midpoint::new(f, x, y, x_end, setting)),
+            Method::Ralston2 => self.time(Ralston2::new(f, x, y, x_end, setting)),
+            Method::RungeKutta4 => self.time(RungeKutta4::new(f, x, y, x_end, setting)),
+            Method::SspRk3 => self.time(SspRk3::new(f, x, y, x_end, setting)),
+            Method::ImplicitEuler => self.time(ImplicitEuler::new(
+                f,
+                x,
+                y,
+                x_end,
+                setting,
+                NEWTON_TOL,
+                MAX_NEWTON_ITER,
+            )),
+            Method::CrankNicolson => self.time(CrankNicolson::new(
+                f,
+                x,
+                y,
+                x_end,
+                setting,
+                NEWTON_TOL,
+                MAX_NEWTON_ITER,
+            )),
+        }
+    }
+
+    /// Runs `solver`, returning the number of evaluations, the error of the final state and the
+    /// elapsed time, or `None` if the integration does not reach `x_end`.
+    fn time(&self, mut solver: impl OdeSolver<OVector<f64, D>>) -> Option<(u32, f64, Duration)> {
+        let start = Instant::now();
+        let stats = solver.integrate().ok()?;
+        let elapsed = start.elapsed();
+        if solver.x_final() != self.x_end {
+            return None;
+        }
+        let error = (solver.y_final() - &self.reference).amax();
+        Some((stats.num_eval(), error, elapsed))
+    }
+}
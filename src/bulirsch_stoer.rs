@@ -0,0 +1,619 @@
+//! Extrapolation method of Gragg, Bulirsch and Stoer with adaptive order and step size control.
+//!
+//! The implementation follows the order and step size control of Hairer's ODEX code (Hairer,
+//! Norsett and Wanner, Solving Ordinary Differential Equations I, Section II.9).
+
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, error_scale, find_segment, hermite_interpolation, initial_step_size, limit_step,
+    record_diagnostic, reject_events, weighted_rms_norm, ControlFlag, ErrorNorm, IntegrationError,
+    IntegrationResult, MethodInfo, OdeSolver, Output, SolverLog, Stats, StepDiagnostic, System,
+    Tolerance,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Maximum number of rows of the extrapolation table.
+const MAX_ROWS: usize = 9;
+const SAFETY1: f64 = 0.65;
+const SAFETY2: f64 = 0.94;
+const FAC1: f64 = 0.02;
+const FAC2: f64 = 4.0;
+
+/// Number of substeps of the modified midpoint rule in the row `j` of the extrapolation table.
+fn substeps(j: usize) -> usize {
+    2 * j
+}
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Each step of size `H` is computed with Gragg's modified midpoint rule using `n_j = 2j`
+/// substeps, `j = 1, 2, ...`, and the results are extrapolated to a vanishing substep size with
+/// the Aitken-Neville algorithm, the row `j` of the table being of order `2j`. Both the number of
+/// rows and the step size are adapted to minimise the number of evaluations of `System::ode` per
+/// unit step while meeting the tolerances. The method is very efficient at tight tolerances on
+/// smooth non-stiff problems. The solution between the steps is interpolated with cubic Hermite
+/// polynomials, which are much less accurate than the steps when these are large, with errors of
+/// order `1e-4` or more even at `rtol = 1e-10`. The steps therefore end exactly at the values set with
+/// [`t_eval`](Self::t_eval), at the cost of more steps when these are closely spaced.
+pub struct BulirschStoer<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    h_accepted: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    norm: Box<ErrorNorm<V>>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    rows: usize,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    stats: Stats,
+    log: SolverLog,
+}
+
+/// Outcome of a step of the extrapolation method.
+struct StepOutcome<V> {
+    /// Extrapolated solution, `None` if the step is rejected.
+    y_new: Option<V>,
+    /// Proposed size of the next step.
+    h_new: f64,
+    /// Proposed number of rows of the extrapolation table for the next step.
+    rows_new: usize,
+    /// Norm of the last error estimate of the table.
+    err: f64,
+}
+
+impl<T, D: Dim, F> BulirschStoer<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        BulirschStoer {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            h_accepted: 0.0,
+            rtol: rtol.into(),
+            atol: atol.into(),
+            norm: Box::new(weighted_rms_norm),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            rows: 0,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            diagnostics: None,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the norm of the local error estimate, see [`Dopri5::error_norm`](crate::Dopri5::error_norm).
+    pub fn error_norm(&mut self, norm: impl Fn(&OVector<T, D>, &OVector<T, D>) -> f64 + 'static) {
+        self.norm = Box::new(norm);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger). Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). Unlike the other solvers, the steps are
+    /// shortened to end exactly at these values, so that the solution is as accurate there as at
+    /// the steps. The values must be sorted in the direction of integration and lie within
+    /// `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+        // Initial number of rows from the tolerance, as in ODEX
+        let rtol = self.rtol.min().max(1.0e-40);
+        self.rows = ((-rtol.log10() * 0.6 + 1.5) as usize).clamp(3, MAX_ROWS - 1);
+
+        let mut dy = self.y.clone();
+        self.f.ode(self.x, &self.y, &mut dy);
+        self.stats.num_eval += 1;
+        self.dy_out.push(dy.clone());
+        if self.h == 0.0 {
+            let (rows, cols) = self.y.shape_generic();
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = OVector::zeros_generic(rows, cols);
+                    self.f.ode(x, y, &mut dy);
+                    dy
+                },
+                self.x,
+                &self.y,
+                &dy,
+                self.x_end,
+                2 * self.rows as u32 - 1,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.stats.num_eval += 1;
+        }
+
+        let mut last = false;
+        let mut n_step = 0;
+        let mut rejected = false;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point, nor past the next output value
+            let x_stop = self.next_stop(posneg);
+            let stop = (self.x + 1.01 * self.h - x_stop) * posneg > 0.0;
+            if stop {
+                self.h = x_stop - self.x;
+                last = x_stop == self.x_end;
+            }
+            n_step += 1;
+
+            let outcome = self.step(&dy);
+            check_eval(&self.f)?;
+            let accepted = outcome.y_new.is_some();
+            record_diagnostic(&mut self.diagnostics, self.x, self.h, outcome.err, accepted);
+            let mut h_new = outcome.h_new;
+            match outcome.y_new {
+                Some(y_new) => {
+                    // Do not increase the order nor the step size right after a rejection
+                    if rejected {
+                        h_new = h_new.abs().min(self.h.abs()) * posneg;
+                        self.rows = outcome.rows_new.min(self.rows);
+                    } else {
+                        self.rows = outcome.rows_new;
+                    }
+                    rejected = false;
+                    self.log.accepted(self.x, self.h, outcome.err);
+                    self.stats.accepted_steps += 1;
+                    self.stats.record_step(self.h);
+                    self.x = if stop { x_stop } else { self.x + self.h };
+                    self.y = y_new;
+                    self.f.project(self.x, &mut self.y);
+                    self.h_accepted = self.h;
+                    self.f.ode(self.x, &self.y, &mut dy);
+                    self.stats.num_eval += 1;
+                    self.dy_out.push(dy.clone());
+                    self.output.push(self.x, self.y.clone());
+                    // Call Observer
+                    self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                    if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                        break;
+                    }
+                }
+                None => {
+                    rejected = true;
+                    self.rows = outcome.rows_new;
+                    self.log.rejected(self.x, self.h, outcome.err);
+                    self.stats.rejected_steps += 1;
+                    last = false;
+                }
+            }
+            if !last {
+                self.h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, self.h, self.max_step);
+            }
+        }
+        check_eval(&self.f)?;
+        Ok(self.stats)
+    }
+
+    /// Next value of the independent variable at which a step must end, i.e. the first value set
+    /// with [`t_eval`](Self::t_eval) beyond the current point or else `x_end`.
+    fn next_stop(&self, posneg: f64) -> f64 {
+        let t_eval = self.output.t_eval().unwrap_or(&[]);
+        let i = t_eval.partition_point(|t| (t - self.x) * posneg <= 0.0);
+        t_eval.get(i).map_or(self.x_end, |t| *t)
+    }
+
+    /// Computes one step of size `h` from the current point by filling the extrapolation table
+    /// row by row, `dy` being the derivative at the current point. Convergence is tested from the
+    /// row `rows - 1` on, up to the row `rows + 1`.
+    fn step(&mut self, dy: &OVector<T, D>) -> StepOutcome<OVector<T, D>> {
+        let h = self.h;
+        let k = self.rows;
+        let mut h_opt = [0.0; MAX_ROWS + 1];
+        let mut work = [0.0; MAX_ROWS + 1];
+        let mut cost = 1.0;
+        let mut table: Vec<OVector<T, D>> = Vec::with_capacity(MAX_ROWS);
+        let mut last_err = f64::INFINITY;
+
+        for j in 1..=(k + 1).min(MAX_ROWS) {
+            // Row j of the table, extrapolated from the previous row
+            let mut row = Vec::with_capacity(j);
+            row.push(self.modified_midpoint(dy, substeps(j)));
+            cost += substeps(j) as f64;
+            for l in 1..j {
+                let ratio = (substeps(j) as f64 / substeps(j - l) as f64).powi(2);
+                let diff = &row[l - 1] - &table[l - 1];
+                row.push(&row[l - 1] + diff * (1.0 / (ratio - 1.0)));
+            }
+            table = row;
+            if j == 1 {
+                continue;
+            }
+
+            let scale = error_scale(&self.y, &table[j - 1], &self.rtol, &self.atol);
+            let err = (self.norm)(&(&table[j - 1] - &table[j - 2]), &scale);
+            last_err = err;
+            let expo = 1.0 / (2 * j - 1) as f64;
+            let fac_min = FAC1.powf(expo);
+            let fac = if err == 0.0 {
+                1.0 / fac_min
+            } else {
+                (SAFETY2 * (SAFETY1 / err).powf(expo)).clamp(fac_min / FAC2, 1.0 / fac_min)
+            };
+            h_opt[j] = h * fac;
+            work[j] = cost / h_opt[j].abs();
+
+            if j + 1 >= k {
+                if err <= 1.0 {
+                    let (rows_new, h_new) = self.next_order(j, &h_opt, &work);
+                    return StepOutcome {
+                        y_new: Some(table.pop().unwrap_or_else(|| self.y.clone())),
+                        h_new,
+                        rows_new,
+                        err,
+                    };
+                }
+                // Reject as soon as convergence within the row k + 1 is not to be expected
+                let n1 = substeps(1) as f64;
+                let hopeless = if j + 1 == k {
+                    err > (substeps(k) as f64 * substeps(k + 1) as f64 / (n1 * n1)).powi(2)
+                } else if j == k {
+                    err > (substeps(k + 1) as f64 / n1).powi(2)
+                } else {
+                    true
+                };
+                if hopeless {
+                    let rows_new = if j >= 3 && work[j - 1] < 0.8 * work[j] {
+                        j - 1
+                    } else {
+                        j
+                    }
+                    .clamp(2, MAX_ROWS - 1);
+                    return StepOutcome {
+                        y_new: None,
+                        h_new: h_opt[rows_new.min(j)],
+                        rows_new,
+                        err,
+                    };
+                }
+            }
+        }
+        StepOutcome {
+            y_new: None,
+            h_new: h_opt[k.min(MAX_ROWS)],
+            rows_new: k,
+            err: last_err,
+        }
+    }
+
+    /// Chooses the number of rows and the size of the next step minimising the work per unit
+    /// step, after convergence in the row `j`.
+    fn next_order(&self, j: usize, h_opt: &[f64], work: &[f64]) -> (usize, f64) {
+        if j >= 3 && work[j - 1] < 0.8 * work[j] {
+            (j - 1, h_opt[j - 1])
+        } else if j + 1 < MAX_ROWS && (j == 2 || work[j] < 0.9 * work[j - 1]) {
+            // The step size of the next row is estimated from the cost of its row
+            let cost_j = 1.0 + (1..=j).map(|i| substeps(i) as f64).sum::<f64>();
+            let cost_next = cost_j + substeps(j + 1) as f64;
+            (j + 1, h_opt[j] * cost_next / cost_j)
+        } else {
+            (j.min(MAX_ROWS - 1), h_opt[j])
+        }
+    }
+
+    /// Gragg's modified midpoint rule with `n` substeps over the current step, followed by the
+    /// smoothing step of Bader and Deuflhard.
+    fn modified_midpoint(&mut self, dy: &OVector<T, D>, n: usize) -> OVector<T, D> {
+        let h = self.h / n as f64;
+        let mut z_prev = self.y.clone();
+        let mut z = &self.y + dy.clone() * h;
+        let mut dz = dy.clone();
+        for m in 1..n {
+            self.f.ode(self.x + m as f64 * h, &z, &mut dz);
+            let z_next = &z_prev + dz.clone() * (2.0 * h);
+            z_prev = z;
+            z = z_next;
+        }
+        self.f.ode(self.x + self.h, &z, &mut dz);
+        self.stats.num_eval += n as u32;
+        (z + z_prev + dz * h) * 0.5
+    }
+
+    /// Getter for the last accepted step size.
+    pub fn last_step_size(&self) -> f64 {
+        self.h_accepted
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    /// The interpolant is only of third order, its error between large steps exceeds the
+    /// tolerances by orders of magnitude; set the values needed with [`t_eval`](Self::t_eval) to
+    /// obtain them at full accuracy.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for BulirschStoer<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        BulirschStoer::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        BulirschStoer::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        BulirschStoer::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        BulirschStoer::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        BulirschStoer::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for BulirschStoer<V, F> {
+    fn order(&self) -> u32 {
+        2 * MAX_ROWS as u32
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::ode_fn;
+    use crate::{Vector1, Vector2};
+
+    #[test]
+    fn chooses_an_initial_step_accepted_at_the_first_attempt() {
+        for tol in [1e-3, 1e-6, 1e-9, 1e-12] {
+            let van_der_pol = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                dy[0] = y[1];
+                dy[1] = 5.0 * (1.0 - y[0] * y[0]) * y[1] - y[0];
+            });
+            let mut solver = BulirschStoer::new(
+                van_der_pol,
+                0.0,
+                Vector2::new(2.0, 0.0),
+                10.0,
+                None,
+                tol,
+                tol,
+            );
+            solver.record_diagnostics(true);
+            solver.integrate().unwrap();
+            assert!(solver.diagnostics()[0].accepted, "{}", tol);
+        }
+    }
+
+    #[test]
+    fn ends_the_steps_at_the_output_values() {
+        let oscillator = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+            dy[0] = y[1];
+            dy[1] = -y[0];
+        });
+        let t_eval: Vec<f64> = (0..=27).map(|i| 0.37 * i as f64).collect();
+        let mut solver = BulirschStoer::new(
+            oscillator,
+            0.0,
+            Vector2::new(1.0, 0.0),
+            10.0,
+            None,
+            1e-10,
+            1e-10,
+        );
+        solver.t_eval(t_eval.clone());
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_out(), &t_eval);
+        for (x, y) in solver.x_out().iter().zip(solver.y_out()) {
+            assert!((y[0] - x.cos()).abs() < 1e-8, "{}", x);
+        }
+    }
+
+    #[test]
+    fn extrapolation_converges_on_exponential_growth() {
+        let growth = |_, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = y[0];
+        let mut errors = Vec::new();
+        for tol in [1e-4, 1e-7, 1e-10, 1e-13] {
+            let f = ode_fn(growth);
+            let mut solver = BulirschStoer::new(f, 0.0, Vector1::new(1.0), 1.0, None, tol, tol);
+            solver.integrate().unwrap();
+            let error = (solver.y_final()[0] - std::f64::consts::E).abs();
+            assert!(error < 10.0 * tol, "{}: {}", tol, error);
+            errors.push(error);
+        }
+        assert!(errors.windows(2).all(|w| w[1] < w[0]), "{:?}", errors);
+    }
+}
@@ -0,0 +1,244 @@
+//! Explicit Runge-Kutta method of order 5(4) with adaptive step size control (Cash-Karp).
+
+use crate::controller::StepController;
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Stats, System, Tolerance,
+};
+use crate::tableau::{ButcherTableau, ExplicitRungeKutta};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The solver runs the generic [`ExplicitRungeKutta`] driver with the
+/// [`cash_karp45`](ButcherTableau::cash_karp45) tableau. The fifth order solution is propagated
+/// and the local error is estimated from the embedded fourth order one. Each step costs six
+/// evaluations of `System::ode`, the last stage not being reused by the next step as in
+/// [`Dopri5`](crate::Dopri5).
+pub struct CashKarp45<V, F>
+where
+    F: System<V>,
+{
+    solver: ExplicitRungeKutta<V, F>,
+}
+
+impl<T, D: Dim, F> CashKarp45<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        let mut solver = ExplicitRungeKutta::new(f, x, y, x_end, h, ButcherTableau::cash_karp45());
+        solver.tolerances(rtol, atol);
+        CashKarp45 { solver }
+    }
+
+    /// Sets the step size controller, a [`PiController`](crate::PiController) by default.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.solver.step_controller(controller);
+    }
+
+    /// Sets the norm of the local error estimate, see [`ExplicitRungeKutta::error_norm`].
+    pub fn error_norm(&mut self, norm: impl Fn(&OVector<T, D>, &OVector<T, D>) -> f64 + 'static) {
+        self.solver.error_norm(norm);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.solver.min_step(min_step);
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.solver.max_step(max_step);
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.solver.max_steps(max_steps);
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, see
+    /// [`ExplicitRungeKutta::logger`]. Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.solver.logger(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.solver.t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        self.solver.integrate()
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.solver.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.solver.y_out()
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.solver.x_final()
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        self.solver.y_final()
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        self.solver.at(x)
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.solver.into_result()
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for CashKarp45<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        CashKarp45::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        CashKarp45::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        CashKarp45::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        CashKarp45::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        CashKarp45::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for CashKarp45<V, F> {
+    fn order(&self) -> u32 {
+        5
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::HarmonicOscillator;
+
+    #[test]
+    fn converges_on_the_harmonic_oscillator() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let exact = problem.exact_solution(x_end);
+        let mut errors = Vec::new();
+        let mut steps = Vec::new();
+        for tol in [1e-5, 1e-7, 1e-9, 1e-11] {
+            let mut solver =
+                CashKarp45::new(problem, x0, problem.initial_state(), x_end, 0.01, tol, tol);
+            let stats = solver.integrate().unwrap();
+            assert_eq!(solver.x_final(), x_end);
+            // Six evaluations per attempted step and one at the final state for dense output
+            assert_eq!(
+                stats.num_eval(),
+                6 * (stats.accepted_steps() + stats.rejected_steps()) + 1
+            );
+            errors.push((solver.y_final() - exact).norm());
+            steps.push(stats.accepted_steps() as f64);
+        }
+        // The global error is proportional to the tolerance
+        assert!(errors.windows(2).all(|w| w[0] / w[1] > 50.0));
+        // An error per step of order h^5 gives a number of steps growing like tol^(-1/5)
+        for w in steps.windows(2) {
+            let ratio = w[1] / w[0];
+            assert!(ratio > 2.0 && ratio < 3.5, "{}", ratio);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Step size controllers of the adaptive solvers.
+//!
+//! The controllers scale the step size proposed from the error estimate by a safety factor
+//! `safety` below 1 and bound the ratio between the next and the current step size within
+//! `[fac_min, fac_max]`. These parameters are public fields, e.g.
+//! `solver.step_controller(IController { safety: 0.8, ..IController::default() })`. A smaller
+//! safety factor or a smaller `fac_max` reduces the number of rejected steps, at the cost of more
+//! accepted steps.
+
+/// Trait implemented by the step size control laws of the adaptive solvers.
+pub trait StepController {
+    /// Decides from the norm `err` of the local error estimate of a step of size `h` whether the
+    /// step is accepted, and proposes the size of the next step. `order` is the order of the
+    /// error estimate, i.e. the lowest of the orders of the propagated and embedded solutions.
+    /// `err <= 1` means that the step satisfies the tolerances.
+    fn next_step(&mut self, err: f64, h: f64, order: u32) -> (bool, f64);
+}
+
+/// Proportional-integral controller of Gustafsson, as used by Hairer's codes.
+#[derive(Clone, Debug)]
+pub struct PiController {
+    /// Exponent of the integral term, with respect to the error of the previous accepted step.
+    pub beta: f64,
+    /// Safety factor applied to the proposed step size.
+    pub safety: f64,
+    /// Smallest ratio between the next and the current step size.
+    pub fac_min: f64,
+    /// Largest ratio between the next and the current step size.
+    pub fac_max: f64,
+    fac_old: f64,
+}
+
+impl PiController {
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `beta`        - Exponent of the integral term
+    /// * `safety`      - Safety factor applied to the proposed step size
+    /// * `fac_min`     - Smallest ratio between the next and the current step size
+    /// * `fac_max`     - Largest ratio between the next and the current step size
+    ///
+    pub fn new(beta: f64, safety: f64, fac_min: f64, fac_max: f64) -> Self {
+        PiController {
+            beta,
+            safety,
+            fac_min,
+            fac_max,
+            fac_old: 1.0e-4,
+        }
+    }
+}
+
+impl Default for PiController {
+    fn default() -> Self {
+        PiController::new(0.04, 0.9, 0.2, 10.0)
+    }
+}
+
+impl StepController for PiController {
+    fn next_step(&mut self, err: f64, h: f64, order: u32) -> (bool, f64) {
+        let alpha = 1.0 / (order as f64 + 1.0) - 0.75 * self.beta;
+        let fac11 = err.powf(alpha);
+        if err <= 1.0 {
+            let fac = (fac11 / self.fac_old.powf(self.beta) / self.safety)
+                .clamp(1.0 / self.fac_max, 1.0 / self.fac_min);
+            self.fac_old = err.max(1.0e-4);
+            (true, h / fac)
+        } else {
+            (false, h / (1.0 / self.fac_min).min(fac11 / self.safety))
+        }
+    }
+}
+
+/// Elementary (integral) controller, scaling the step size by `safety * err^(-1 / (order + 1))`.
+#[derive(Clone, Debug)]
+pub struct IController {
+    /// Safety factor applied to the proposed step size.
+    pub safety: f64,
+    /// Smallest ratio between the next and the current step size.
+    pub fac_min: f64,
+    /// Largest ratio between the next and the current step size.
+    pub fac_max: f64,
+}
+
+impl IController {
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `safety`      - Safety factor applied to the proposed step size
+    /// * `fac_min`     - Smallest ratio between the next and the current step size
+    /// * `fac_max`     - Largest ratio between the next and the current step size
+    ///
+    pub fn new(safety: f64, fac_min: f64, fac_max: f64) -> Self {
+        IController {
+            safety,
+            fac_min,
+            fac_max,
+        }
+    }
+}
+
+impl Default for IController {
+    fn default() -> Self {
+        IController::new(0.9, 0.2, 10.0)
+    }
+}
+
+impl StepController for IController {
+    fn next_step(&mut self, err: f64, h: f64, order: u32) -> (bool, f64) {
+        let fac = if err == 0.0 {
+            self.fac_max
+        } else {
+            (self.safety * err.powf(-1.0 / (order as f64 + 1.0))).clamp(self.fac_min, self.fac_max)
+        };
+        if err <= 1.0 {
+            (true, h * fac)
+        } else {
+            // Never increase the step size after a rejection
+            (false, h * fac.min(1.0))
+        }
+    }
+}
+
+/// Predictive controller of Gustafsson, as used by Hairer's RADAU5 code. The step size proposed
+/// by the elementary controller is reduced whenever the ratio of the errors of the last two
+/// accepted steps, extrapolated to the next step, predicts an error growth. This reduces the
+/// number of rejected steps on mildly stiff problems, whereas the [`PiController`] is better
+/// suited to explicit methods whose step size is limited by their stability domain.
+#[derive(Clone, Debug)]
+pub struct GustafssonController {
+    /// Safety factor applied to the proposed step size.
+    pub safety: f64,
+    /// Smallest ratio between the next and the current step size.
+    pub fac_min: f64,
+    /// Largest ratio between the next and the current step size.
+    pub fac_max: f64,
+    accepted_old: Option<(f64, f64)>,
+}
+
+impl GustafssonController {
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `safety`      - Safety factor applied to the proposed step size
+    /// * `fac_min`     - Smallest ratio between the next and the current step size
+    /// * `fac_max`     - Largest ratio between the next and the current step size
+    ///
+    pub fn new(safety: f64, fac_min: f64, fac_max: f64) -> Self {
+        GustafssonController {
+            safety,
+            fac_min,
+            fac_max,
+            accepted_old: None,
+        }
+    }
+}
+
+impl Default for GustafssonController {
+    fn default() -> Self {
+        GustafssonController::new(0.9, 0.2, 8.0)
+    }
+}
+
+impl StepController for GustafssonController {
+    fn next_step(&mut self, err: f64, h: f64, order: u32) -> (bool, f64) {
+        let expo = 1.0 / (order as f64 + 1.0);
+        let mut quot = (err.powf(expo) / self.safety).clamp(1.0 / self.fac_max, 1.0 / self.fac_min);
+        if err <= 1.0 {
+            // Predict the error of the next step from the errors of the last two accepted steps
+            if let Some((h_old, err_old)) = self.accepted_old {
+                let quot_gus = (h_old / h * (err * err / err_old).powf(expo) / self.safety)
+                    .clamp(1.0 / self.fac_max, 1.0 / self.fac_min);
+                quot = quot.max(quot_gus);
+            }
+            self.accepted_old = Some((h, err.max(1.0e-2)));
+            (true, h / quot)
+        } else {
+            // Never increase the step size after a rejection
+            (false, h / quot.max(1.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::{ode_fn, Stats};
+    use crate::{Rosenbrock4, Vector2};
+
+    /// Integrates the Van der Pol oscillator with `mu = 10` over one period with the controller.
+    fn van_der_pol(controller: impl StepController + 'static, tol: f64) -> Stats {
+        let f = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+            dy[0] = y[1];
+            dy[1] = 10.0 * (1.0 - y[0] * y[0]) * y[1] - y[0];
+        });
+        let mut solver = Rosenbrock4::new(f, 0.0, Vector2::new(2.0, 0.0), 20.0, None, tol, tol);
+        solver.step_controller(controller);
+        solver.integrate().unwrap()
+    }
+
+    #[test]
+    fn predictive_controller_rejects_fewer_steps_on_a_stiff_problem() {
+        for tol in [1e-3, 1e-5] {
+            let elementary = van_der_pol(IController::default(), tol);
+            let predictive = van_der_pol(GustafssonController::default(), tol);
+            assert!(
+                2 * predictive.rejected_steps() < elementary.rejected_steps(),
+                "{} {}",
+                predictive.rejected_steps(),
+                elementary.rejected_steps()
+            );
+            // The rejections are not traded for many more accepted steps
+            assert!(10 * predictive.accepted_steps() < 11 * elementary.accepted_steps());
+        }
+    }
+}
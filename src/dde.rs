@@ -0,0 +1,253 @@
+//! Classical fourth order Runge-Kutta method with fixed step size for delay differential
+//! equations.
+
+use crate::dop_shared::{
+    find_segment, hermite_interpolation, step_count, ControlFlag, DelaySystem, IntegrationError,
+    IntegrationResult, Stats,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The delayed states are evaluated with `DelaySystem::history` before the initial value of the
+/// independent variable and with the cubic Hermite interpolant of the computed solution after it
+/// (method of steps). The step size must not exceed the delay, so that the delayed states are
+/// always known, and the integration proceeds forward in time.
+pub struct DelayRungeKutta4<V, F>
+where
+    F: DelaySystem<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    y_out: Vec<V>,
+    dy_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> DelayRungeKutta4<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: DelaySystem<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the DelaySystem<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        DelayRungeKutta4 {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            dy_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let invalid = |reason: &str| {
+            Err(IntegrationError::InvalidStepConfig {
+                reason: reason.to_string(),
+            })
+        };
+        if self.x_end < self.x {
+            return invalid("backward integration is not supported");
+        }
+        if !(self.step_size > 0.0 && self.step_size <= self.f.delay()) {
+            return invalid("step size must be strictly positive and not exceed the delay");
+        }
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.y);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let num_steps = step_count(self.x_end - self.x, self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                self.step_size
+            };
+            let (x_new, y_new) = self.step(h);
+            self.x = if last { self.x_end } else { x_new };
+            self.y = y_new;
+            self.stats.num_eval += 4;
+            self.stats.accepted_steps += 1;
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.y);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        // Derivative at the final state, used for dense output
+        let dy = self.derivative(self.x, &self.y);
+        self.stats.num_eval += 1;
+        self.dy_out.push(dy);
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the classical Runge-Kutta method. The derivative at the start of the
+    /// step is stored first, so that the delayed states within the current step can be
+    /// interpolated when the step size equals the delay.
+    fn step(&mut self, h: f64) -> (f64, OVector<T, D>) {
+        let k0 = self.derivative(self.x, &self.y);
+        self.dy_out.push(k0.clone());
+        let k1 = self.derivative(self.x + 0.5 * h, &(&self.y + k0.clone() * (0.5 * h)));
+        let k2 = self.derivative(self.x + 0.5 * h, &(&self.y + k1.clone() * (0.5 * h)));
+        let k3 = self.derivative(self.x + h, &(&self.y + k2.clone() * h));
+
+        let x_new = self.x + h;
+        let y_new = &self.y
+            + (k0 * (1.0 / 6.0) + k1 * (1.0 / 3.0) + k2 * (1.0 / 3.0) + k3 * (1.0 / 6.0)) * h;
+        (x_new, y_new)
+    }
+
+    /// Evaluates the right-hand side at (`x`, `y`) together with the delayed state.
+    fn derivative(&self, x: f64, y: &OVector<T, D>) -> OVector<T, D> {
+        let y_delayed = self.delayed(x - self.f.delay());
+        let (rows, cols) = y.shape_generic();
+        let mut dy = OVector::zeros_generic(rows, cols);
+        self.f.ode(x, y, &y_delayed, &mut dy);
+        dy
+    }
+
+    /// State at `x`, given by the history before the initial value of the independent variable
+    /// and by the dense output after it.
+    fn delayed(&self, x: f64) -> OVector<T, D> {
+        if x < self.x_out[0] {
+            return self.f.history(x);
+        }
+        match self.at(x) {
+            Some(y) => y,
+            None => self.y.clone(),
+        }
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        &self.y_out
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states returned by [`y_out`](Self::y_out).
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.x_out.len() == 1 && self.x_out[0] == x {
+            return Some(self.y_out[0].clone());
+        }
+        let i = find_segment(&self.x_out, x)?;
+        Some(hermite_interpolation(
+            (self.x_out[i], &self.y_out[i], self.dy_out.get(i)?),
+            (
+                self.x_out[i + 1],
+                &self.y_out[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        IntegrationResult {
+            x: self.x_out,
+            y: self.y_out,
+            stats: self.stats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector1;
+
+    /// Hutchinson's delayed logistic equation `y' = r y(x) (1 - y(x - 1))`.
+    struct Hutchinson {
+        r: f64,
+    }
+
+    impl DelaySystem<Vector1<f64>> for Hutchinson {
+        fn delay(&self) -> f64 {
+            1.0
+        }
+
+        fn ode(&self, _x: f64, y: &Vector1<f64>, y_delayed: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = self.r * y[0] * (1.0 - y_delayed[0]);
+        }
+
+        fn history(&self, _x: f64) -> Vector1<f64> {
+            Vector1::new(0.5)
+        }
+    }
+
+    /// Range of the solution over the last quarter of `[0, 200]`.
+    fn late_amplitude(r: f64) -> f64 {
+        let mut solver =
+            DelayRungeKutta4::new(Hutchinson { r }, 0.0, Vector1::new(0.5), 200.0, 0.01);
+        solver.integrate().unwrap();
+        let late = solver
+            .x_out()
+            .iter()
+            .zip(solver.y_out())
+            .filter(|(x, _)| **x >= 150.0)
+            .map(|(_, y)| y[0]);
+        let (min, max) = late.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| {
+            (lo.min(y), hi.max(y))
+        });
+        max - min
+    }
+
+    #[test]
+    fn oscillates_beyond_the_hopf_threshold() {
+        // The equilibrium y = 1 is stable for r tau < pi / 2 and a limit cycle appears beyond
+        assert!(late_amplitude(1.2) < 1e-3);
+        assert!(late_amplitude(1.9) > 0.5);
+    }
+}
@@ -0,0 +1,896 @@
+//! Explicit Runge-Kutta method of order 8(5,3) with adaptive step size control and dense output
+//! of order 7 (Dormand-Prince).
+
+use crate::controller::{PiController, StepController};
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, dense_polynomial, error_scale, event_values, find_segment, initial_step_size,
+    limit_step, locate_dense_event, order_reduced, ramp_initial_step, ramp_step, record_diagnostic,
+    refine_output, ContinuousSolution, ControlFlag, EventAction, IntegrationError,
+    IntegrationResult, MethodInfo, OdeSolver, Output, SolverLog, Stats, StepDiagnostic, System,
+    Tolerance,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+// Nodes of the Dormand-Prince 8(5,3) tableau. Stages 12 and 13 are evaluated at the end of the
+// step, stage 13 being the first stage of the next step, stages 14 to 16 are only used by the
+// dense output.
+const C: [f64; 16] = [
+    0.0,
+    0.05260015195876773,
+    0.0789002279381516,
+    0.1183503419072274,
+    0.2816496580927726,
+    0.3333333333333333,
+    0.25,
+    0.3076923076923077,
+    0.6512820512820513,
+    0.6,
+    0.8571428571428571,
+    1.0,
+    1.0,
+    0.1,
+    0.2,
+    0.7777777777777778,
+];
+// Stage matrix, the row of stage 13 holding the weights of the eighth order solution.
+const A: [&[f64]; 16] = [
+    &[],
+    &[0.05260015195876773],
+    &[0.0197250569845379, 0.0591751709536137],
+    &[0.02958758547680685, 0.0, 0.08876275643042054],
+    &[
+        0.2413651341592667,
+        0.0,
+        -0.8845494793282861,
+        0.924834003261792,
+    ],
+    &[
+        0.037037037037037035,
+        0.0,
+        0.0,
+        0.17082860872947386,
+        0.12546768756682242,
+    ],
+    &[
+        0.037109375,
+        0.0,
+        0.0,
+        0.17025221101954405,
+        0.06021653898045596,
+        -0.017578125,
+    ],
+    &[
+        0.03709200011850479,
+        0.0,
+        0.0,
+        0.17038392571223998,
+        0.10726203044637328,
+        -0.015319437748624402,
+        0.008273789163814023,
+    ],
+    &[
+        0.6241109587160757,
+        0.0,
+        0.0,
+        -3.3608926294469414,
+        -0.868219346841726,
+        27.59209969944671,
+        20.154067550477894,
+        -43.48988418106996,
+    ],
+    &[
+        0.47766253643826434,
+        0.0,
+        0.0,
+        -2.4881146199716677,
+        -0.590290826836843,
+        21.230051448181193,
+        15.279233632882423,
+        -33.28821096898486,
+        -0.020331201708508627,
+    ],
+    &[
+        -0.9371424300859873,
+        0.0,
+        0.0,
+        5.186372428844064,
+        1.0914373489967295,
+        -8.149787010746927,
+        -18.52006565999696,
+        22.739487099350505,
+        2.4936055526796523,
+        -3.0467644718982196,
+    ],
+    &[
+        2.273310147516538,
+        0.0,
+        0.0,
+        -10.53449546673725,
+        -2.0008720582248625,
+        -17.9589318631188,
+        27.94888452941996,
+        -2.8589982771350235,
+        -8.87285693353063,
+        12.360567175794303,
+        0.6433927460157636,
+    ],
+    &[
+        0.054293734116568765,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        4.450312892752409,
+        1.8915178993145003,
+        -5.801203960010585,
+        0.3111643669578199,
+        -0.1521609496625161,
+        0.20136540080403034,
+        0.04471061572777259,
+    ],
+    &[
+        0.056167502283047954,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.25350021021662483,
+        -0.2462390374708025,
+        -0.12419142326381637,
+        0.15329179827876568,
+        0.00820105229563469,
+        0.007567897660545699,
+        -0.008298,
+    ],
+    &[
+        0.03183464816350214,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.028300909672366776,
+        0.053541988307438566,
+        -0.05492374857139099,
+        0.0,
+        0.0,
+        -0.00010834732869724932,
+        0.0003825710908356584,
+        -0.00034046500868740456,
+        0.1413124436746325,
+    ],
+    &[
+        -0.42889630158379194,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -4.697621415361164,
+        7.683421196062599,
+        4.06898981839711,
+        0.3567271874552811,
+        0.0,
+        0.0,
+        0.0,
+        -0.0013990241651590145,
+        2.9475147891527724,
+        -9.15095847217987,
+    ],
+];
+// Coefficients of the fifth and third order error estimates.
+const E: [f64; 12] = [
+    0.01312004499419488,
+    0.0,
+    0.0,
+    0.0,
+    0.0,
+    -1.2251564463762044,
+    -0.4957589496572502,
+    1.6643771824549864,
+    -0.35032884874997366,
+    0.3341791187130175,
+    0.08192320648511571,
+    -0.022355307863886294,
+];
+const BHH: [f64; 3] = [0.2440944881889764, 0.7338466882816118, 0.022058823529411766];
+// Coefficients of the dense output of order 7.
+const D: [[f64; 16]; 4] = [
+    [
+        -8.428938276109013,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.5667149535193777,
+        -3.0689499459498917,
+        2.38466765651207,
+        2.117034582445028,
+        -0.871391583777973,
+        2.2404374302607883,
+        0.6315787787694688,
+        -0.08899033645133331,
+        18.148505520854727,
+        -9.194632392478356,
+        -4.436036387594894,
+    ],
+    [
+        10.427508642579134,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        242.28349177525817,
+        165.20045171727028,
+        -374.5467547226902,
+        -22.113666853125306,
+        7.733432668472264,
+        -30.674084731089398,
+        -9.332130526430229,
+        15.697238121770845,
+        -31.139403219565178,
+        -9.35292435884448,
+        35.81684148639408,
+    ],
+    [
+        19.985053242002433,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -387.0373087493518,
+        -189.17813819516758,
+        527.8081592054236,
+        -11.57390253995963,
+        6.8812326946963,
+        -1.0006050966910838,
+        0.7777137798053443,
+        -2.778205752353508,
+        -60.19669523126412,
+        84.32040550667716,
+        11.99229113618279,
+    ],
+    [
+        -25.69393346270375,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -154.18974869023643,
+        -231.5293791760455,
+        357.6391179106141,
+        93.40532418362432,
+        -37.45832313645163,
+        104.0996495089623,
+        29.8402934266605,
+        -43.53345659001114,
+        96.32455395918828,
+        -39.17726167561544,
+        -149.72683625798564,
+    ],
+];
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The eighth order solution is propagated and the local error is estimated from a combination
+/// of embedded fifth and third order solutions, which keeps the step size control reliable at
+/// tolerances as tight as 1e-13. Each step costs twelve evaluations of `System::ode`, the last
+/// stage being reused by the next step, and each accepted step three more evaluations for the
+/// dense output used by [`at`](Self::at) and [`t_eval`](Self::t_eval).
+pub struct Dop853<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    h_accepted: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    controller: Box<dyn StepController>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    output: Output<V>,
+    dense_samples: usize,
+    output_tol: Option<(f64, f64)>,
+    store_derivatives: bool,
+    dy_out: Vec<V>,
+    dense: Vec<[V; 8]>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    startup_ramp: usize,
+    stats: Stats,
+    log: SolverLog,
+}
+
+impl<T, D: Dim, F> Dop853<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        Dop853 {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            h_accepted: 0.0,
+            rtol: rtol.into(),
+            atol: atol.into(),
+            controller: Box::new(PiController::new(0.0, 0.9, 0.333, 6.0)),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            output: Output::new(),
+            output_tol: None,
+            dense_samples: 0,
+            store_derivatives: false,
+            dy_out: Vec::new(),
+            dense: Vec::new(),
+            diagnostics: None,
+            startup_ramp: 0,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the step size controller. Defaults to a [`PiController`] without proportional part
+    /// and with the step size factors of Hairer's DOP853 code.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.controller = Box::new(controller);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger). Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the number of accepted steps at the start of the integration during which the step
+    /// size may grow by at most a factor of 2 from one step to the next (default 0), easing into
+    /// the adaptive regime on a problem with a sharp initial layer. An initial step size selected
+    /// automatically is first divided by `2^steps`, the ramp growing it back.
+    pub fn startup_ramp(&mut self, steps: usize) {
+        self.startup_ramp = steps;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). `Stats::order_reduced` then flags an observed order well
+    /// below the nominal one. Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Sets the bound on the change of the solution between consecutive points returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out), `rtol * max(|y_a|, |y_b|) + atol` for
+    /// each component, e.g. to plot a solution made of long steps. The steps are subdivided with
+    /// the dense output where the solution changes fast, so that the linear interpolation of the
+    /// output stays within the bound of the solution, see
+    /// [`ContinuousSolution::refine`](crate::ContinuousSolution::refine). The internal stepping
+    /// is unaffected and [`t_eval`](Self::t_eval) takes precedence.
+    pub fn output_tolerance(&mut self, rtol: f64, atol: f64) {
+        self.output_tol = Some((rtol, atol));
+    }
+
+    /// Sets the number of points evenly spaced inside each accepted step at which the observer is
+    /// called in addition to the end of the step, the solution being evaluated there with the
+    /// dense output (default 0). The step sizes are unaffected.
+    pub fn dense_samples(&mut self, n: usize) {
+        self.dense_samples = n;
+    }
+
+    /// Sets whether the derivatives `f(x, y)` at the stored states are returned by
+    /// [`dy_out`](Self::dy_out) (default false). They are the first stage of the next step, so
+    /// that storing them costs no evaluation of the system.
+    pub fn store_derivatives(&mut self, store_derivatives: bool) {
+        self.store_derivatives = store_derivatives;
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        self.sample_t_eval();
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        if let Some((rtol, atol)) = self.output_tol {
+            if !(rtol >= 0.0 && atol >= 0.0 && rtol + atol > 0.0) {
+                return Err(IntegrationError::InvalidTolerance {
+                    reason: "the output tolerances must be non-negative and not both zero"
+                        .to_string(),
+                });
+            }
+        }
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+
+        let (rows, cols) = self.y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); 16];
+        self.f.ode(self.x, &self.y, &mut k[0]);
+        self.stats.num_eval += 1;
+        if self.store_derivatives {
+            self.dy_out.push(k[0].clone());
+        }
+        if self.h == 0.0 {
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = OVector::zeros_generic(rows, cols);
+                    self.f.ode(x, y, &mut dy);
+                    dy
+                },
+                self.x,
+                &self.y,
+                &k[0],
+                self.x_end,
+                8,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.h = ramp_initial_step(self.h, self.startup_ramp);
+            self.stats.num_eval += 1;
+        }
+
+        let mut last = false;
+        let mut n_step = 0;
+        // Event indicators at the current state, and event at which the step being retried ends
+        let mut g = event_values(&self.f, self.x, &self.y);
+        let mut pending_event = None;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point
+            if pending_event.is_none() && (self.x + 1.01 * self.h - self.x_end) * posneg > 0.0 {
+                self.h = self.x_end - self.x;
+                last = true;
+            }
+            n_step += 1;
+
+            let y_new = self.step(&mut k);
+            self.stats.num_eval += 11;
+            check_eval(&self.f)?;
+
+            let err = self.error(&k, &y_new);
+            let (accept, h_new) = self.controller.next_step(err, self.h, 7);
+            let h_new = ramp_step(h_new, self.h, self.stats.accepted_steps, self.startup_ramp);
+            record_diagnostic(&mut self.diagnostics, self.x, self.h, err, accept);
+            if accept {
+                self.f.ode(self.x + self.h, &y_new, &mut k[12]);
+                let dense = self.dense_coefficients(&mut k, &y_new);
+                self.stats.num_eval += 4;
+                check_eval(&self.f)?;
+                let mut event = None;
+                if !g.is_empty() {
+                    let h = self.h;
+                    let g_new = event_values(&self.f, self.x + h, &y_new);
+                    event = match pending_event.take() {
+                        Some(idx) => Some(idx),
+                        None => match locate_dense_event(&self.f, self.x, h, &g, &g_new, &dense) {
+                            // Take the step again, ending just past the crossing
+                            Some((idx, dx)) if dx != h => {
+                                pending_event = Some(idx);
+                                self.h = dx;
+                                last = false;
+                                continue;
+                            }
+                            located => located.map(|(idx, _)| idx),
+                        },
+                    };
+                    g = g_new;
+                }
+                self.log.accepted(self.x, self.h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(self.h);
+                self.dense.push(dense);
+                // Call Observer at the interior points of the step
+                if let Some(r) = self.dense.last() {
+                    for i in 1..=self.dense_samples {
+                        let theta = i as f64 / (self.dense_samples + 1) as f64;
+                        let y = dense_polynomial(r, theta);
+                        self.f
+                            .observer_full(self.x + theta * self.h, &y, self.h, &self.stats);
+                    }
+                }
+                // First same as last
+                k[0] = k[12].clone();
+                self.x = if last { self.x_end } else { self.x + self.h };
+                self.y = y_new.clone();
+                self.f.project(self.x, &mut self.y);
+                let action = match event {
+                    Some(idx) => {
+                        let action = self.f.on_event(idx, self.x, &mut self.y);
+                        // The state lies just past the crossing, so that the indicator is
+                        // considered to be at zero and cannot fire again until it has left it
+                        g = event_values(&self.f, self.x, &self.y);
+                        g[idx] = 0.0;
+                        if action == EventAction::Terminate {
+                            self.stats.terminal_event = Some(idx);
+                        }
+                        action
+                    }
+                    None => EventAction::Continue,
+                };
+                // Evaluate the first stage again if the projection or the event moved the state
+                if self.y != y_new {
+                    self.f.ode(self.x, &self.y, &mut k[0]);
+                    self.stats.num_eval += 1;
+                }
+                self.h_accepted = self.h;
+                self.output.push(self.x, self.y.clone());
+                if self.store_derivatives {
+                    self.dy_out.push(k[0].clone());
+                }
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop
+                    || action == EventAction::Terminate
+                {
+                    break;
+                }
+            } else {
+                self.log.rejected(self.x, self.h, err);
+                self.stats.rejected_steps += 1;
+                pending_event = None;
+                last = false;
+            }
+            if !last {
+                self.h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, self.h, self.max_step);
+            }
+        }
+        check_eval(&self.f)?;
+        self.stats.order_reduced = order_reduced(&self.diagnostics, 7);
+        Ok(self.stats)
+    }
+
+    /// Computes the stages 2 to 12 of one step and returns the eighth order solution.
+    fn step(&self, k: &mut [OVector<T, D>]) -> OVector<T, D> {
+        for s in 1..12 {
+            let y_tmp = &self.y + combination(A[s], k) * self.h;
+            self.f.ode(self.x + C[s] * self.h, &y_tmp, &mut k[s]);
+        }
+        &self.y + combination(A[12], k) * self.h
+    }
+
+    /// Computes the norm of the local error estimate, in which the fifth order estimate is
+    /// corrected by the third order one so that it does not underestimate the error of the eighth
+    /// order solution for large step sizes (Hairer, Norsett and Wanner, Solving Ordinary
+    /// Differential Equations I, Section II.10).
+    fn error(&self, k: &[OVector<T, D>], y_new: &OVector<T, D>) -> f64 {
+        let scale = error_scale(&self.y, y_new, &self.rtol, &self.atol);
+        let err5 = combination(&E, k);
+        let err3 = combination(A[12], k)
+            - k[0].clone() * BHH[0]
+            - k[8].clone() * BHH[1]
+            - k[11].clone() * BHH[2];
+        let sum_sq = |err: &OVector<T, D>| {
+            err.iter().zip(scale.iter()).fold(0.0, |acc, (e, sc)| {
+                acc + (f64::from(*e) / f64::from(*sc)).powi(2)
+            })
+        };
+        let err5 = sum_sq(&err5);
+        let err3 = sum_sq(&err3);
+        let mut den = err5 + 0.01 * err3;
+        if den <= 0.0 {
+            den = 1.0;
+        }
+        self.h.abs() * err5 / (den * self.y.len() as f64).sqrt()
+    }
+
+    /// Computes the coefficients of the continuous extension of order 7 over the current step,
+    /// evaluating the stages 14 to 16.
+    fn dense_coefficients(
+        &self,
+        k: &mut [OVector<T, D>],
+        y_new: &OVector<T, D>,
+    ) -> [OVector<T, D>; 8] {
+        let h = self.h;
+        for s in 13..16 {
+            let y_tmp = &self.y + combination(A[s], k) * h;
+            self.f.ode(self.x + C[s] * h, &y_tmp, &mut k[s]);
+        }
+        let y_diff = y_new - &self.y;
+        let bspl = k[0].clone() * h - &y_diff;
+        [
+            self.y.clone(),
+            y_diff.clone(),
+            bspl.clone(),
+            y_diff - k[12].clone() * h - bspl,
+            combination(&D[0], k) * h,
+            combination(&D[1], k) * h,
+            combination(&D[2], k) * h,
+            combination(&D[3], k) * h,
+        ]
+    }
+
+    /// Getter for the last accepted step size.
+    pub fn last_step_size(&self) -> f64 {
+        self.h_accepted
+    }
+
+    /// Evaluates the solution at the output values set with [`t_eval`](Self::t_eval), or at the
+    /// points refined with the bound set with [`output_tolerance`](Self::output_tolerance).
+    fn sample_t_eval(&mut self) {
+        let samples = match self.output_tol {
+            Some((rtol, atol)) if self.output.t_eval().is_none() => {
+                let eval = |i: usize, x: f64| {
+                    if x == self.output.x[i] {
+                        return self.output.y[i].clone();
+                    }
+                    let h = self.output.x[i + 1] - self.output.x[i];
+                    dense_polynomial(&self.dense[i], (x - self.output.x[i]) / h)
+                };
+                Some(refine_output(&self.output.x, eval, rtol, atol))
+            }
+            _ => self.output.sample(|t| self.at(t)),
+        };
+        self.output.set_samples(samples);
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when neither [`t_eval`](Self::t_eval) nor
+    /// [`output_tolerance`](Self::output_tolerance) is set. Empty unless
+    /// enabled with [`store_derivatives`](Self::store_derivatives).
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Evaluates the continuous extension of the method at `x`. Returns `None` if `x` lies outside
+    /// of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        let h = self.output.x[i + 1] - self.output.x[i];
+        if h == 0.0 {
+            return Some(self.output.y[i].clone());
+        }
+        Some(dense_polynomial(&self.dense[i], (x - self.output.x[i]) / h))
+    }
+
+    /// Returns the continuous extension of the solution over the accepted steps, which can be
+    /// evaluated without the solver.
+    pub fn continuous_solution(&self) -> ContinuousSolution<OVector<T, D>> {
+        let coefficients = if self.dense.is_empty() {
+            self.output
+                .y
+                .iter()
+                .take(1)
+                .map(|y| vec![y.clone()])
+                .collect()
+        } else {
+            self.dense.iter().map(|r| r.to_vec()).collect()
+        };
+        ContinuousSolution::new(self.output.x.clone(), coefficients)
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+/// Computes the linear combination of the stages `k` with the coefficients `coeffs`, skipping the
+/// vanishing ones.
+fn combination<T, D: Dim>(coeffs: &[f64], k: &[OVector<T, D>]) -> OVector<T, D>
+where
+    T: Scalar + ClosedAdd + Zero,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let (rows, cols) = k[0].shape_generic();
+    coeffs
+        .iter()
+        .zip(k)
+        .filter(|(a, _)| **a != 0.0)
+        .fold(OVector::zeros_generic(rows, cols), |acc, (a, k)| {
+            acc + k.clone() * *a
+        })
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for Dop853<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Dop853::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Dop853::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Dop853::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Dop853::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Dop853::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for Dop853<V, F> {
+    fn order(&self) -> u32 {
+        8
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector4;
+
+    /// Reduced mass of the moon in the restricted three body problem of the earth and the moon.
+    const MU: f64 = 0.012277471;
+    /// Period of the Arenstorf orbit.
+    const PERIOD: f64 = 17.065216560157963;
+
+    /// Restricted three body problem in the rotating frame, with the state `(x, y, x', y')`.
+    struct Arenstorf;
+
+    impl System<Vector4<f64>> for Arenstorf {
+        fn ode(&self, _t: f64, y: &Vector4<f64>, dy: &mut Vector4<f64>) {
+            let d1 = ((y[0] + MU).powi(2) + y[1] * y[1]).powf(1.5);
+            let d2 = ((y[0] - 1.0 + MU).powi(2) + y[1] * y[1]).powf(1.5);
+            dy[0] = y[2];
+            dy[1] = y[3];
+            dy[2] = y[0] + 2.0 * y[3] - (1.0 - MU) * (y[0] + MU) / d1 - MU * (y[0] - 1.0 + MU) / d2;
+            dy[3] = y[1] - 2.0 * y[2] - (1.0 - MU) * y[1] / d1 - MU * y[1] / d2;
+        }
+    }
+
+    #[test]
+    fn closes_the_arenstorf_orbit() {
+        let y0 = Vector4::new(0.994, 0.0, 0.0, -2.0015851063790825);
+        let mut solver = Dop853::new(Arenstorf, 0.0, y0, PERIOD, None, 1e-13, 1e-13);
+        let stats = solver.integrate().unwrap();
+        let error = (solver.y_final() - y0).norm();
+        assert!(error < 1e-8, "{}", error);
+        assert!(stats.accepted_steps() < 500, "{}", stats.accepted_steps());
+    }
+}
@@ -1,68 +1,4062 @@
-//! Shared traits and structures for dopri5 and dop853.
+//! Traits, error and statistics types, output buffer and numerical helpers shared by all the
+//! solvers.
 
+use nalgebra::{
+    allocator::Allocator, linalg::LU, Const, DVector, DefaultAllocator, Dim, DimAdd, DimMin,
+    DimSum, OMatrix, OVector, RealField, Scalar, Vector1,
+};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedSub, SubsetOf, SupersetOf};
+use std::cell::RefCell;
 use std::fmt;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::rc::Rc;
 use thiserror::Error;
 
 /// Trait needed to be implemented by the user.
 pub trait System<V> {
     /// System of ordinary differential equations.
     fn ode(&self, x: f64, y: &V, dy: &mut V);
-    /// Events (optional)
+    /// Events (optional), applied by the `Euler` solver at the start of each event step. The
+    /// other solvers ignore it.
     fn event(&mut self, _x: f64, _y: &V, _dy: &mut V) {}
+    /// Number of event indicator functions computed by `event_fn` (optional). The zero crossings
+    /// of the indicators are located by the `Euler`, `Dopri5` and `Dop853` solvers, the latter two
+    /// on their dense output, and the other solvers return `IntegrationError::InvalidStepConfig`
+    /// for a system with events.
+    fn num_events(&self) -> usize {
+        0
+    }
+    /// Event indicator functions (optional). An event is triggered when one of the values changes
+    /// sign.
+    fn event_fn(&self, _x: f64, _y: &V, _out: &mut [f64]) {}
+    /// Direction of the sign changes of the event indicator `idx` which trigger the event
+    /// (optional). Defaults to both directions.
+    fn event_direction(&self, _idx: usize) -> EventDirection {
+        EventDirection::Both
+    }
+    /// Called when the zero crossing of event indicator `idx` has been located (optional). The
+    /// state may be modified in place, and so may the parameters stored in the system, e.g. to
+    /// change a transmission rate once an intervention threshold is reached: the integration
+    /// continues from the crossing and all the subsequent calls to `ode` and `event_fn` see the
+    /// new values. Returning `EventAction::Terminate` stops the integration at the crossing,
+    /// which is then the last output point, and records `idx` in `Stats::terminal_event`.
+    fn on_event(&mut self, _idx: usize, _x: f64, _y: &mut V) -> EventAction {
+        EventAction::Continue
+    }
+    /// Called by the `Euler` solver at each of the values of the independent variable set with
+    /// `Euler::scheduled_events`, on which its steps land, to apply a discrete change to the state
+    /// (optional), e.g. a dose given at a known time. The integration continues from the modified
+    /// state.
+    fn scheduled_event(&mut self, _x: f64, _y: &mut V) {}
+    /// Called by the `Euler` solver at the end of each observer step, before the observer and the
+    /// storage of the state, to modify the state in place (optional), e.g. to clamp
+    /// concentrations to nonnegative values. The event step starting the next observer step then
+    /// applies `event` to the clamped state, whose result is not clamped before the following
+    /// observer step. Does nothing by default.
+    fn clamp(&self, _x: f64, _y: &mut V) {}
+    /// Diffusion coefficients of a stochastic differential equation `dy = f(x, y) dx + g(x, y) dW`
+    /// with diagonal noise, used by the `EulerMaruyama` solver (optional). Component `i` of `out`
+    /// multiplies the increment of the `i`-th independent Wiener process. `out` is zero on entry.
+    fn diffusion(&self, _x: f64, _y: &V, _out: &mut V) {}
+    /// Selects the mode of a hybrid system after the event `idx` has been handled by `on_event`
+    /// (optional). Defaults to keeping the current mode `mode`.
+    fn next_mode(&mut self, _idx: usize, _x: f64, _y: &V, mode: usize) -> usize {
+        mode
+    }
+    /// Right-hand side of a hybrid system in the mode `mode` (optional), allowing the dynamics to
+    /// switch at events. Defaults to `ode`, ignoring the mode.
+    fn mode_ode(&self, _mode: usize, x: f64, y: &V, dy: &mut V) {
+        self.ode(x, y, dy);
+    }
+    /// Fallible variant of `mode_ode` (optional), called by the `Euler` solver, which stops with
+    /// `IntegrationError::EvalFailed` when it returns an error. Defaults to `mode_ode`, which
+    /// never fails. Implemented for the right-hand sides of a [`FallibleSystem`] wrapped with
+    /// [`fallible`].
+    fn try_mode_ode(&self, mode: usize, x: f64, y: &V, dy: &mut V) -> Result<(), EvalError> {
+        self.mode_ode(mode, x, y, dy);
+        Ok(())
+    }
+    /// Takes the first failure of an evaluation of `ode` since the last call, together with the
+    /// value of the independent variable at which it occurred (optional). The solvers other than
+    /// `Euler` call it after every step, which they discard to stop with
+    /// `IntegrationError::EvalFailed` if an evaluation failed. Defaults to `None`. Implemented for
+    /// a [`FallibleSystem`] wrapped with [`fallible`], whose `ode` records the error.
+    fn take_eval_error(&self) -> Option<(f64, EvalError)> {
+        None
+    }
+    /// Right-hand side evaluated at once for several states `ys` sharing the independent variable
+    /// `x`, writing the derivative of `ys[i]` to `out[i]` (optional), called by
+    /// [`solve_ensemble_batched`] at every stage. Overriding it is purely a performance path for
+    /// models which vectorize the evaluation, e.g. with SIMD or on a GPU. Defaults to calling
+    /// `ode` for each state.
+    fn ode_batch(&self, x: f64, ys: &[V], out: &mut [V]) {
+        for (y, dy) in ys.iter().zip(out.iter_mut()) {
+            self.ode(x, y, dy);
+        }
+    }
     /// Observer (optional)
     fn observer(&self, _x: f64, _y: &V) {}
-    /// Stop function called at every successful integration step. The integration is stopped when this function returns true.
-    fn solout(&mut self, _x: f64, _y: &V, _dy: &V) -> bool {
-        false
+    /// Observer also receiving the size `h` of the step that led to `x`, 0 at the initial point,
+    /// and the statistics of the integration so far (optional), e.g. to follow how an adaptive
+    /// solver varies its step size. Called by the solvers in place of `observer`, to which it
+    /// forwards by default.
+    fn observer_full(&self, x: f64, y: &V, _h: f64, _stats: &Stats) {
+        self.observer(x, y);
+    }
+    /// Called periodically with the fraction of the integration interval completed so far, from
+    /// 0 to 1 in both directions of integration (optional).
+    fn progress(&mut self, _fraction: f64) {}
+    /// Stop function called at every successful integration step (optional). The integration is
+    /// stopped, keeping the trajectory computed so far, when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: &V) -> ControlFlag {
+        ControlFlag::Continue
+    }
+    /// Jacobian of the system with respect to the dependent variables (optional).
+    /// Defaults to a forward finite difference approximation.
+    fn jacobian(&self, x: f64, y: &V, out: &mut V::Matrix)
+    where
+        V: State,
+    {
+        V::finite_diff_jacobian(self, x, y, out);
+    }
+    /// Constant mass matrix `M` of a system written as `M y' = f(x, y)` (optional), used by the
+    /// implicit solvers. `out` is the identity on entry, which is kept by default. Only
+    /// non-singular mass matrices are supported, singular ones (differential-algebraic
+    /// equations) being out of scope.
+    fn mass_matrix(&self, _out: &mut V::Matrix)
+    where
+        V: State,
+    {
+    }
+    /// Product `J^T lambda` of the transposed Jacobian with `lambda` (optional), used by the
+    /// adjoint sensitivity analysis. Defaults to forming the Jacobian with `jacobian`, which may
+    /// be avoided by computing the product directly.
+    fn vjp(&self, x: f64, y: &V, lambda: &V, out: &mut V)
+    where
+        V: State,
+    {
+        V::jacobian_transpose_product(self, x, y, lambda, out);
+    }
+    /// Lower and upper bandwidths `(ml, mu)` of the Jacobian (optional), i.e. `df_i / dy_j`
+    /// vanishes unless `i - ml <= j <= i + mu`. The `ImplicitEuler`, `CrankNicolson` and `Bdf`
+    /// solvers then factorize the banded iteration matrix, the mass matrix being assumed to share
+    /// the band, and the default Jacobian perturbs the components in `ml + mu + 1` groups.
+    /// Defaults to a dense Jacobian.
+    fn jacobian_bandwidth(&self) -> Option<(usize, usize)> {
+        None
+    }
+    /// Sparsity pattern of the Jacobian (optional), given for each of the columns `j` as the
+    /// indices `i` of the rows where `df_i / dy_j` may be nonzero. The default Jacobian then perturbs
+    /// together the components whose columns share no row, grouped by
+    /// [`color_columns`], which takes precedence over `jacobian_bandwidth`. Defaults to a dense
+    /// Jacobian.
+    fn jacobian_sparsity(&self) -> Option<Vec<Vec<usize>>> {
+        None
     }
+    /// Number of parameters of the system for sensitivity analysis (optional).
+    fn num_params(&self) -> usize {
+        0
+    }
+    /// Derivatives of the right-hand side with respect to the parameters (optional), one vector
+    /// per parameter in `out`, used for sensitivity analysis. The vectors are zero on entry.
+    fn df_dp(&self, _x: f64, _y: &V, _out: &mut [V]) {}
+    /// Invariant of the system such as the energy, recorded at every output point to monitor how
+    /// well it is conserved (optional).
+    fn invariant(&self, _x: f64, _y: &V) -> f64 {
+        0.0
+    }
+    /// Integrand `L(x, y)` of the running integral accumulated by the `Euler` solver with the
+    /// trapezoidal rule when enabled with `Euler::running_quadrature` (optional), e.g. the cost
+    /// of a trajectory in optimal control.
+    fn quad(&self, _x: f64, _y: &V) -> f64 {
+        0.0
+    }
+    /// Projects the state back onto the manifold of the constraints of the system, such as a
+    /// unit norm or a conserved energy, after every accepted step (optional). The drift of the
+    /// constraints accumulated by the solver is then removed at each step instead of growing
+    /// over long integrations. The solvers reusing the derivative at the end of a step evaluate it
+    /// again when the projection moved the state. Not called by the sensitivity analyses, whose
+    /// variational equations follow the unprojected flow. Does nothing by default.
+    fn project(&self, _x: f64, _y: &mut V) {}
 }
 
-/// Enumeration of the types of the integration output.
-#[derive(PartialEq, Eq)]
-pub enum OutputType {
-    Dense,
-    Sparse,
+/// Forwards all the methods to the boxed system, so that a solver may be built from a trait
+/// object `Box<dyn System<V>>`, e.g. to select the model at runtime by name.
+impl<V, S: System<V> + ?Sized> System<V> for Box<S> {
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        (**self).ode(x, y, dy);
+    }
+
+    fn event(&mut self, x: f64, y: &V, dy: &mut V) {
+        (**self).event(x, y, dy);
+    }
+
+    fn num_events(&self) -> usize {
+        (**self).num_events()
+    }
+
+    fn event_fn(&self, x: f64, y: &V, out: &mut [f64]) {
+        (**self).event_fn(x, y, out);
+    }
+
+    fn event_direction(&self, idx: usize) -> EventDirection {
+        (**self).event_direction(idx)
+    }
+
+    fn on_event(&mut self, idx: usize, x: f64, y: &mut V) -> EventAction {
+        (**self).on_event(idx, x, y)
+    }
+
+    fn scheduled_event(&mut self, x: f64, y: &mut V) {
+        (**self).scheduled_event(x, y);
+    }
+
+    fn clamp(&self, x: f64, y: &mut V) {
+        (**self).clamp(x, y);
+    }
+
+    fn diffusion(&self, x: f64, y: &V, out: &mut V) {
+        (**self).diffusion(x, y, out);
+    }
+
+    fn next_mode(&mut self, idx: usize, x: f64, y: &V, mode: usize) -> usize {
+        (**self).next_mode(idx, x, y, mode)
+    }
+
+    fn mode_ode(&self, mode: usize, x: f64, y: &V, dy: &mut V) {
+        (**self).mode_ode(mode, x, y, dy);
+    }
+
+    fn try_mode_ode(&self, mode: usize, x: f64, y: &V, dy: &mut V) -> Result<(), EvalError> {
+        (**self).try_mode_ode(mode, x, y, dy)
+    }
+
+    fn take_eval_error(&self) -> Option<(f64, EvalError)> {
+        (**self).take_eval_error()
+    }
+
+    fn ode_batch(&self, x: f64, ys: &[V], out: &mut [V]) {
+        (**self).ode_batch(x, ys, out);
+    }
+
+    fn observer(&self, x: f64, y: &V) {
+        (**self).observer(x, y);
+    }
+
+    fn observer_full(&self, x: f64, y: &V, h: f64, stats: &Stats) {
+        (**self).observer_full(x, y, h, stats);
+    }
+
+    fn progress(&mut self, fraction: f64) {
+        (**self).progress(fraction);
+    }
+
+    fn solout(&mut self, x: f64, y: &V) -> ControlFlag {
+        (**self).solout(x, y)
+    }
+
+    fn jacobian(&self, x: f64, y: &V, out: &mut V::Matrix)
+    where
+        V: State,
+    {
+        (**self).jacobian(x, y, out);
+    }
+
+    fn mass_matrix(&self, out: &mut V::Matrix)
+    where
+        V: State,
+    {
+        (**self).mass_matrix(out);
+    }
+
+    fn vjp(&self, x: f64, y: &V, lambda: &V, out: &mut V)
+    where
+        V: State,
+    {
+        (**self).vjp(x, y, lambda, out);
+    }
+
+    fn jacobian_bandwidth(&self) -> Option<(usize, usize)> {
+        (**self).jacobian_bandwidth()
+    }
+
+    fn jacobian_sparsity(&self) -> Option<Vec<Vec<usize>>> {
+        (**self).jacobian_sparsity()
+    }
+
+    fn num_params(&self) -> usize {
+        (**self).num_params()
+    }
+
+    fn df_dp(&self, x: f64, y: &V, out: &mut [V]) {
+        (**self).df_dp(x, y, out);
+    }
+
+    fn invariant(&self, x: f64, y: &V) -> f64 {
+        (**self).invariant(x, y)
+    }
+
+    fn quad(&self, x: f64, y: &V) -> f64 {
+        (**self).quad(x, y)
+    }
+
+    fn project(&self, x: f64, y: &mut V) {
+        (**self).project(x, y);
+    }
 }
 
-/// Enumeration of the errors that may arise during integration.
-#[derive(Debug, Error)]
-pub enum IntegrationError {
-    #[error("Stopped at x = {x}. Need more than {n_step} steps.")]
-    MaxNumStepReached { x: f64, n_step: u32 },
-    #[error("Stopped at x = {x}. Step size underflow.")]
-    StepSizeUnderflow { x: f64 },
-    #[error("The problem seems to become stiff at x = {x}.")]
-    StiffnessDetected { x: f64 },
+/// Adapter implementing `System` for a closure computing the right-hand side of the ODE.
+/// Created with [`ode_fn`], and `Clone` or `Copy` whenever the closure is.
+#[derive(Clone, Copy)]
+pub struct OdeFn<C> {
+    f: C,
 }
 
-/// Contains some statistics of the integration.
-#[derive(Clone, Copy, Debug)]
-pub struct Stats {
-    pub num_eval: u32,
-    pub accepted_steps: u32,
-    pub rejected_steps: u32,
+impl<V, C> System<V> for OdeFn<C>
+where
+    C: Fn(f64, &V, &mut V),
+{
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        (self.f)(x, y, dy)
+    }
 }
 
-impl Stats {
-    pub(crate) fn new() -> Stats {
-        Stats {
-            num_eval: 0,
-            accepted_steps: 0,
-            rejected_steps: 0,
+/// Wraps a closure `|x, y, dy| { ... }` into a type implementing `System`.
+pub fn ode_fn<V, C>(f: C) -> OdeFn<C>
+where
+    C: Fn(f64, &V, &mut V),
+{
+    OdeFn { f }
+}
+
+/// Trait for one-dimensional systems `y' = f(x, y)` with a scalar state, implemented by the
+/// closures `|x, y| ...` returning `f(x, y)`. Such a system is integrated by any solver once
+/// wrapped with [`lift_1d`], or [`ode_fn_1d`] for a closure, into a `System<Vector1<f64>>`, and
+/// the trajectory is read back as scalars with [`IntegrationResult::y_scalar`].
+pub trait System1d {
+    /// Right-hand side of the ODE.
+    fn ode(&self, x: f64, y: f64) -> f64;
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _y: f64) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: f64) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+impl<C> System1d for C
+where
+    C: Fn(f64, f64) -> f64,
+{
+    fn ode(&self, x: f64, y: f64) -> f64 {
+        self(x, y)
+    }
+}
+
+/// Adapter implementing `System<Vector1<f64>>` for a [`System1d`]. Created with [`lift_1d`].
+pub struct Lifted1d<F> {
+    f: F,
+}
+
+impl<F: System1d> System<Vector1<f64>> for Lifted1d<F> {
+    fn ode(&self, x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+        dy[0] = self.f.ode(x, y[0]);
+    }
+
+    fn observer(&self, x: f64, y: &Vector1<f64>) {
+        self.f.observer(x, y[0]);
+    }
+
+    fn solout(&mut self, x: f64, y: &Vector1<f64>) -> ControlFlag {
+        self.f.solout(x, y[0])
+    }
+}
+
+/// Wraps a one-dimensional system into a type implementing `System<Vector1<f64>>`.
+pub fn lift_1d<F: System1d>(f: F) -> Lifted1d<F> {
+    Lifted1d { f }
+}
+
+/// Wraps a closure `|x, y| ...` returning the right-hand side of a one-dimensional ODE, e.g.
+/// `|_x, y| -y`, into a type implementing `System<Vector1<f64>>`.
+pub fn ode_fn_1d<C>(f: C) -> Lifted1d<C>
+where
+    C: Fn(f64, f64) -> f64,
+{
+    Lifted1d { f }
+}
+
+/// Error returned by the right-hand side of a [`FallibleSystem`] evaluated outside of its domain,
+/// e.g. for a table lookup out of range or the square root of a negative value.
+#[derive(Clone, Debug, Error)]
+#[error("{reason}")]
+pub struct EvalError {
+    pub reason: String,
+}
+
+impl EvalError {
+    pub fn new(reason: impl Into<String>) -> Self {
+        EvalError {
+            reason: reason.into(),
         }
     }
+}
 
-    /// Prints some statistics related to the integration process.
-    #[deprecated(since = "0.2.0", note = "Use std::fmt::Display instead")]
-    pub fn print(&self) {
-        println!("{}", self);
+/// Trait for systems whose right-hand side may fail to evaluate. Such a system is integrated
+/// once wrapped with [`fallible`] into a type implementing `System`: the solvers then stop with
+/// `IntegrationError::EvalFailed` at the first error, keeping the trajectory computed so far.
+/// `Euler` stops at the failed evaluation itself, the other solvers at the end of the step
+/// during which it failed, the step being discarded.
+pub trait FallibleSystem<V> {
+    /// Right-hand side of the ODE, returning an error if it cannot be evaluated at `(x, y)`.
+    fn try_ode(&self, x: f64, y: &V, dy: &mut V) -> Result<(), EvalError>;
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _y: &V) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: &V) -> ControlFlag {
+        ControlFlag::Continue
     }
 }
 
-impl fmt::Display for Stats {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Number of function evaluations: {}", self.num_eval)?;
-        writeln!(f, "Number of accepted steps: {}", self.accepted_steps)?;
-        write!(f, "Number of rejected steps: {}", self.rejected_steps)
+/// Adapter implementing `System` for a [`FallibleSystem`]. Created with [`fallible`]. `ode`
+/// records the first error, which the solvers retrieve with `System::take_eval_error`.
+pub struct Fallible<F> {
+    f: F,
+    error: RefCell<Option<(f64, EvalError)>>,
+}
+
+impl<V, F: FallibleSystem<V>> System<V> for Fallible<F> {
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        if let Err(e) = self.f.try_ode(x, y, dy) {
+            self.error.borrow_mut().get_or_insert((x, e));
+        }
+    }
+
+    fn try_mode_ode(&self, _mode: usize, x: f64, y: &V, dy: &mut V) -> Result<(), EvalError> {
+        self.f.try_ode(x, y, dy)
+    }
+
+    fn take_eval_error(&self) -> Option<(f64, EvalError)> {
+        self.error.borrow_mut().take()
+    }
+
+    fn observer(&self, x: f64, y: &V) {
+        self.f.observer(x, y);
+    }
+
+    fn solout(&mut self, x: f64, y: &V) -> ControlFlag {
+        self.f.solout(x, y)
+    }
+}
+
+/// Wraps a system whose right-hand side may fail into a type implementing `System`.
+pub fn fallible<V, F: FallibleSystem<V>>(f: F) -> Fallible<F> {
+    Fallible {
+        f,
+        error: RefCell::new(None),
+    }
+}
+
+/// Trait for systems `y' = f(x, y, u(x))` driven by an external input `u`, such as a control
+/// signal or a measured disturbance, kept separate from the dynamics. Such a system is integrated
+/// once wrapped with [`forced`] together with a [`Control`] giving the input, so that different
+/// input profiles may be applied to the same system.
+pub trait ForcedSystem<V, U> {
+    /// Right-hand side of the ODE given the value `u` of the input at `x`.
+    fn ode_forced(&self, x: f64, y: &V, u: &U, dy: &mut V);
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _y: &V) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: &V) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Interpolation of the samples of a [`Control`] table between the sampling points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hold {
+    /// Zero-order hold, keeping the value of the last sample until the next one.
+    Zero,
+    /// Linear interpolation between consecutive samples.
+    Linear,
+}
+
+/// Input of a [`ForcedSystem`], given either by a function of the independent variable or by a
+/// table of samples. Before the first sample and after the last one, the table keeps the value of
+/// the nearest sample.
+pub enum Control<U> {
+    /// Input computed by a function of the independent variable.
+    Function(Box<dyn Fn(f64) -> U>),
+    /// Input sampled at the increasing values `x` of the independent variable.
+    Table { x: Vec<f64>, u: Vec<U>, hold: Hold },
+}
+
+impl<U> Control<U> {
+    /// Input computed by the function `f`.
+    pub fn function(f: impl Fn(f64) -> U + 'static) -> Self {
+        Control::Function(Box::new(f))
+    }
+
+    /// Input sampled with the values `u` at the increasing values `x` of the independent variable,
+    /// interpolated as given by `hold`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` and `u` are empty or of different lengths.
+    pub fn table(x: Vec<f64>, u: Vec<U>, hold: Hold) -> Self {
+        assert!(
+            !x.is_empty() && x.len() == u.len(),
+            "a control table needs as many samples as sampling points, and at least one"
+        );
+        Control::Table { x, u, hold }
+    }
+}
+
+impl<T, D: Dim> Control<OVector<T, D>>
+where
+    T: Scalar + ClosedAdd + ClosedSub + ClosedMul + SupersetOf<f64>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Value of the input at `x`.
+    pub fn value(&self, x: f64) -> OVector<T, D> {
+        match self {
+            Control::Function(f) => f(x),
+            Control::Table { x: xs, u, hold } => {
+                let n = xs.len();
+                if x <= xs[0] {
+                    return u[0].clone();
+                }
+                if x >= xs[n - 1] {
+                    return u[n - 1].clone();
+                }
+                let i = xs.partition_point(|x_i| *x_i <= x) - 1;
+                match hold {
+                    Hold::Zero => u[i].clone(),
+                    Hold::Linear => linear_interpolation(xs[i], &u[i], xs[i + 1], &u[i + 1], x),
+                }
+            }
+        }
+    }
+}
+
+/// Adapter implementing `System` for a [`ForcedSystem`] and its input. Created with [`forced`].
+pub struct Forced<F, U> {
+    f: F,
+    control: Control<U>,
+}
+
+impl<F, U> Forced<F, U> {
+    /// Replaces the input applied to the system.
+    pub fn set_control(&mut self, control: Control<U>) {
+        self.control = control;
+    }
+}
+
+impl<V, T, D: Dim, F> System<V> for Forced<F, OVector<T, D>>
+where
+    F: ForcedSystem<V, OVector<T, D>>,
+    T: Scalar + ClosedAdd + ClosedSub + ClosedMul + SupersetOf<f64>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        self.f.ode_forced(x, y, &self.control.value(x), dy);
+    }
+
+    fn observer(&self, x: f64, y: &V) {
+        self.f.observer(x, y);
+    }
+
+    fn solout(&mut self, x: f64, y: &V) -> ControlFlag {
+        self.f.solout(x, y)
+    }
+}
+
+/// Wraps a system driven by an external input, together with the input `control`, into a type
+/// implementing `System`.
+pub fn forced<V, U, F: ForcedSystem<V, U>>(f: F, control: Control<U>) -> Forced<F, U> {
+    Forced { f, control }
+}
+
+/// Trait needed to be implemented by the user for second order systems `q'' = a(x, q, q')`.
+pub trait SecondOrderSystem<V> {
+    /// Acceleration `q''` of the system.
+    fn acceleration(&self, x: f64, q: &V, qdot: &V, out: &mut V);
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _q: &V, _qdot: &V) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _q: &V, _qdot: &V) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Adapter implementing `System` on the stacked state `[q, q']` of dimension `2n` for a
+/// [`SecondOrderSystem`] of dimension `n`, so that it may be integrated by the general first
+/// order solvers, e.g. to compare them with the symplectic ones on the same model. Created with
+/// [`first_order`]. The initial state is built with [`stack_second_order`] and the positions and
+/// velocities are read back from the trajectory with [`positions`] and [`velocities`], whose
+/// dimension is given by the type of the result, e.g. `Vec<Vector3<f64>>`.
+pub struct FirstOrder<F, D> {
+    f: F,
+    dim: PhantomData<D>,
+}
+
+impl<T, D, F> System<OVector<T, DimSum<D, D>>> for FirstOrder<F, D>
+where
+    T: Scalar + Zero,
+    D: DimAdd<D>,
+    F: SecondOrderSystem<OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, DimSum<D, D>>,
+{
+    fn ode(&self, x: f64, y: &OVector<T, DimSum<D, D>>, dy: &mut OVector<T, DimSum<D, D>>) {
+        let (q, qdot) = split_second_order::<T, D>(y);
+        let d = D::from_usize(q.len());
+        let mut acceleration = OVector::zeros_generic(d, Const::<1>);
+        self.f.acceleration(x, &q, &qdot, &mut acceleration);
+        dy.rows_generic_mut(0, d).copy_from(&qdot);
+        dy.rows_generic_mut(q.len(), d).copy_from(&acceleration);
+    }
+
+    fn observer(&self, x: f64, y: &OVector<T, DimSum<D, D>>) {
+        let (q, qdot) = split_second_order::<T, D>(y);
+        self.f.observer(x, &q, &qdot);
+    }
+
+    fn solout(&mut self, x: f64, y: &OVector<T, DimSum<D, D>>) -> ControlFlag {
+        let (q, qdot) = split_second_order::<T, D>(y);
+        self.f.solout(x, &q, &qdot)
+    }
+}
+
+/// Wraps a second order system into a type implementing `System` on the stacked positions and
+/// velocities.
+pub fn first_order<T, D, F>(f: F) -> FirstOrder<F, D>
+where
+    T: Scalar,
+    D: Dim,
+    F: SecondOrderSystem<OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    FirstOrder {
+        f,
+        dim: PhantomData,
+    }
+}
+
+/// Stacks the positions `q` and the velocities `qdot` into the state `[q, q']` of a
+/// [`FirstOrder`] system.
+pub fn stack_second_order<T, D>(q: &OVector<T, D>, qdot: &OVector<T, D>) -> OVector<T, DimSum<D, D>>
+where
+    T: Scalar,
+    D: DimAdd<D>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, DimSum<D, D>>,
+{
+    let (d, _) = q.shape_generic();
+    OVector::from_iterator_generic(d.add(d), Const::<1>, q.iter().chain(qdot.iter()).cloned())
+}
+
+/// Splits the state `[q, q']` of a [`FirstOrder`] system into the positions and the velocities.
+fn split_second_order<T, D>(y: &OVector<T, DimSum<D, D>>) -> (OVector<T, D>, OVector<T, D>)
+where
+    T: Scalar,
+    D: DimAdd<D>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, DimSum<D, D>>,
+{
+    let n = y.len() / 2;
+    let d = D::from_usize(n);
+    (
+        y.rows_generic(0, d).into_owned(),
+        y.rows_generic(n, d).into_owned(),
+    )
+}
+
+/// Positions at each point of a trajectory `y_out` of a [`FirstOrder`] system.
+pub fn positions<T, D>(y_out: &[OVector<T, DimSum<D, D>>]) -> Vec<OVector<T, D>>
+where
+    T: Scalar,
+    D: DimAdd<D>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, DimSum<D, D>>,
+{
+    y_out
+        .iter()
+        .map(|y| split_second_order::<T, D>(y).0)
+        .collect()
+}
+
+/// Velocities at each point of a trajectory `y_out` of a [`FirstOrder`] system.
+pub fn velocities<T, D>(y_out: &[OVector<T, DimSum<D, D>>]) -> Vec<OVector<T, D>>
+where
+    T: Scalar,
+    D: DimAdd<D>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, DimSum<D, D>>,
+{
+    y_out
+        .iter()
+        .map(|y| split_second_order::<T, D>(y).1)
+        .collect()
+}
+
+/// Trait for systems whose state and derivative are expressed in typed quantities, such as those
+/// of the `uom` crate, so that dimensional mistakes in the right-hand side are caught at compile
+/// time. Such a system is integrated once wrapped with [`with_units`]: the solvers work on the
+/// raw values of the components in a coherent system of units, stripped from the quantities with
+/// [`strip`](Self::strip) and [`strip_rate`](Self::strip_rate) and given back their units with
+/// [`apply`](Self::apply). The independent variable is the raw time in the same system of units.
+///
+/// Here with newtypes standing in for the quantities of `uom`:
+///
+/// ```
+/// use ode_event_solvers::dop_shared::{with_units, UnitSystem};
+/// use ode_event_solvers::{Dopri5, Vector2};
+///
+/// struct Meters(f64);
+/// struct MetersPerSecond(f64);
+/// struct MetersPerSecondSquared(f64);
+///
+/// struct Fall;
+///
+/// impl UnitSystem<Vector2<f64>> for Fall {
+///     type State = (Meters, MetersPerSecond);
+///     type Rate = (MetersPerSecond, MetersPerSecondSquared);
+///
+///     fn ode_units(&self, _t: f64, (_, v): &Self::State) -> Self::Rate {
+///         (MetersPerSecond(v.0), MetersPerSecondSquared(-9.81))
+///     }
+///
+///     fn strip(&self, (z, v): &Self::State) -> Vector2<f64> {
+///         Vector2::new(z.0, v.0)
+///     }
+///
+///     fn strip_rate(&self, (v, a): &Self::Rate, dy: &mut Vector2<f64>) {
+///         dy[0] = v.0;
+///         dy[1] = a.0;
+///     }
+///
+///     fn apply(&self, y: &Vector2<f64>) -> Self::State {
+///         (Meters(y[0]), MetersPerSecond(y[1]))
+///     }
+/// }
+///
+/// let y0 = Fall.strip(&(Meters(100.0), MetersPerSecond(0.0)));
+/// let mut solver = Dopri5::new(with_units(Fall), 0.0, y0, 2.0, None, 1.0e-10, 1.0e-10);
+/// solver.integrate()?;
+/// let (z, v): (Meters, MetersPerSecond) = Fall.apply(solver.y_final());
+/// # assert!((z.0 - 80.38).abs() < 1e-9 && (v.0 + 19.62).abs() < 1e-9);
+/// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+/// ```
+pub trait UnitSystem<V> {
+    /// State in typed quantities.
+    type State;
+    /// Derivative of the state with respect to time, in the corresponding typed quantities.
+    type Rate;
+    /// Right-hand side of the ODE in typed quantities.
+    fn ode_units(&self, x: f64, y: &Self::State) -> Self::Rate;
+    /// Raw values of the components of the state `y`.
+    fn strip(&self, y: &Self::State) -> V;
+    /// Writes the raw values of the components of the derivative `rate` into `dy`.
+    fn strip_rate(&self, rate: &Self::Rate, dy: &mut V);
+    /// State in typed quantities given by the raw values `y`.
+    fn apply(&self, y: &V) -> Self::State;
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _y: &Self::State) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: &Self::State) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Adapter implementing `System` for a [`UnitSystem`]. Created with [`with_units`].
+pub struct WithUnits<F> {
+    f: F,
+}
+
+impl<F> WithUnits<F> {
+    /// Wrapped system, e.g. to give back their units to the stored states.
+    pub fn system(&self) -> &F {
+        &self.f
+    }
+}
+
+impl<V, F: UnitSystem<V>> System<V> for WithUnits<F> {
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        let rate = self.f.ode_units(x, &self.f.apply(y));
+        self.f.strip_rate(&rate, dy);
+    }
+
+    fn observer(&self, x: f64, y: &V) {
+        self.f.observer(x, &self.f.apply(y));
+    }
+
+    fn solout(&mut self, x: f64, y: &V) -> ControlFlag {
+        let y = self.f.apply(y);
+        self.f.solout(x, &y)
+    }
+}
+
+/// Wraps a system expressed in typed quantities into a type implementing `System` on the raw
+/// values of the quantities.
+pub fn with_units<V, F: UnitSystem<V>>(f: F) -> WithUnits<F> {
+    WithUnits { f }
+}
+
+/// Trait needed to be implemented by the user for delay differential equations
+/// `y'(x) = f(x, y(x), y(x - tau))` with a constant delay `tau`.
+pub trait DelaySystem<V> {
+    /// Constant delay `tau` of the system.
+    fn delay(&self) -> f64;
+    /// System of delay differential equations, `y_delayed` being the state at `x - tau`.
+    fn ode(&self, x: f64, y: &V, y_delayed: &V, dy: &mut V);
+    /// State of the system before the initial value of the independent variable.
+    fn history(&self, x: f64) -> V;
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _y: &V) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: &V) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Trait needed to be implemented by the user for separable Hamiltonian systems
+/// `H(q, p) = T(p) + V(q)`.
+pub trait HamiltonianSystem<V> {
+    /// Derivative of the positions `q' = dH/dp`.
+    fn position_derivative(&self, x: f64, p: &V, dq: &mut V);
+    /// Derivative of the momenta `p' = -dH/dq`.
+    fn momentum_derivative(&self, x: f64, q: &V, dp: &mut V);
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _q: &V, _p: &V) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _q: &V, _p: &V) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Trait needed to be implemented by the user for semilinear systems `y' = A y + g(x, y)` with a
+/// constant linear operator `A`.
+pub trait SemilinearSystem<V> {
+    /// Linear operator `A` of the system. `out` is zero on entry.
+    fn linear_operator(&self, out: &mut V::Matrix)
+    where
+        V: State;
+    /// Nonlinear remainder `g(x, y)` of the system.
+    fn nonlinear(&self, x: f64, y: &V, out: &mut V);
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _y: &V) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _y: &V) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Relative tolerance below which the remainder of a span is considered to be rounding error.
+pub(crate) const STEP_TOL: f64 = 1.0e-9;
+
+/// Tolerance on the location of the events by the adaptive solvers, relative to the step size.
+pub(crate) const EVENT_TOL: f64 = 1.0e-12;
+
+/// Number of steps of size `h` needed to cover `span`. A last step shorter than `STEP_TOL * h`
+/// is attributed to rounding error and not counted.
+pub(crate) fn step_count(span: f64, h: f64) -> usize {
+    let n = span / h;
+    if n.is_finite() && n > 0.0 {
+        (n - STEP_TOL).ceil() as usize
+    } else {
+        0
+    }
+}
+
+/// Initial step size of an adaptive method, chosen with the heuristic of Hairer, Nørsett and
+/// Wanner (routine HINIT of DOPRI5) from the norms of the derivative `f0` at the initial point
+/// `(x, y)` and of the second derivative, estimated by finite differences along an explicit euler
+/// step. `order` is the power of the step size to which the local error estimate is
+/// proportional, e.g. 5 for `Dopri5`. `derivative` is called once to evaluate the right-hand
+/// side. The step is bounded by `max_step` and takes the sign of `x_end - x`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn initial_step_size<T, D: Dim>(
+    mut derivative: impl FnMut(f64, &OVector<T, D>) -> OVector<T, D>,
+    x: f64,
+    y: &OVector<T, D>,
+    f0: &OVector<T, D>,
+    x_end: f64,
+    order: u32,
+    rtol: &Tolerance,
+    atol: &Tolerance,
+    max_step: f64,
+) -> f64
+where
+    f64: From<T>,
+    T: Scalar + Copy + SupersetOf<f64> + ClosedAdd,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let posneg = (x_end - x).signum();
+    let scale = error_scale(y, y, rtol, atol);
+    let squared_norm = |v: &dyn Fn(usize) -> f64| {
+        (0..y.len()).fold(0.0, |acc, i| acc + (v(i) / f64::from(scale[i])).powi(2))
+    };
+    let dnf = squared_norm(&|i| f64::from(f0[i]));
+    let dny = squared_norm(&|i| f64::from(y[i]));
+    let h = if dnf <= 1.0e-10 || dny <= 1.0e-10 {
+        1.0e-6
+    } else {
+        0.01 * (dny / dnf).sqrt()
+    };
+    let h = h.min(max_step) * posneg;
+    // Explicit euler step
+    let f1 = derivative(x + h, &(y + f0.clone() * h));
+    let der2 = squared_norm(&|i| f64::from(f1[i]) - f64::from(f0[i])).sqrt() / h.abs();
+    let der12 = der2.max(dnf.sqrt());
+    let h1 = if der12 <= 1.0e-15 {
+        1.0e-6_f64.max(h.abs() * 1.0e-3)
+    } else {
+        (0.01 / der12).powf(1.0 / order as f64)
+    };
+    (100.0 * h.abs()).min(h1).min(max_step) * posneg
+}
+
+/// Clamps the magnitude of the step size `h` proposed at `x` to `max_step`. Returns
+/// `IntegrationError::StepSizeTooSmall` if it lies below `min_step`.
+pub(crate) fn limit_step(
+    h: f64,
+    min_step: f64,
+    max_step: f64,
+    x: f64,
+) -> Result<f64, IntegrationError> {
+    if h.abs() < min_step {
+        return Err(IntegrationError::StepSizeTooSmall { x });
+    }
+    Ok(h.signum() * h.abs().min(max_step))
+}
+
+/// Returns whether a Newton update `delta` is within `tol` in the maximum norm. An update with
+/// NaN entries, which the maximum would ignore, has diverged and never converges.
+pub(crate) fn newton_converged<T>(delta: &[T], tol: f64) -> bool
+where
+    T: Copy,
+    f64: From<T>,
+{
+    let delta_norm = delta
+        .iter()
+        .fold(0.0, |acc: f64, d| acc.max(f64::from(*d).abs()));
+    delta_norm <= tol && !delta.iter().any(|d| f64::from(*d).is_nan())
+}
+
+/// Largest factor by which the step size may grow from one step to the next during the startup ramp.
+const RAMP_FACTOR: f64 = 2.0;
+
+/// Reduces the initial step size `h` selected automatically by the factor `2^ramp`, so that it
+/// is grown back during the `ramp` steps of the startup ramp.
+pub(crate) fn ramp_initial_step(h: f64, ramp: usize) -> f64 {
+    h / RAMP_FACTOR.powi(ramp.min(i32::MAX as usize) as i32)
+}
+
+/// Caps the growth of the step size `h_new` proposed after the step `h` while fewer than `ramp`
+/// steps have been accepted.
+pub(crate) fn ramp_step(h_new: f64, h: f64, accepted_steps: u32, ramp: usize) -> f64 {
+    if accepted_steps as usize >= ramp || h_new.abs() <= RAMP_FACTOR * h.abs() {
+        h_new
+    } else {
+        RAMP_FACTOR * h
+    }
+}
+
+/// Returns `IntegrationError::EvalFailed` if an evaluation of the right-hand side of `f` failed
+/// since the last check, see `System::take_eval_error`.
+pub(crate) fn check_eval<V>(f: &impl System<V>) -> Result<(), IntegrationError> {
+    match f.take_eval_error() {
+        Some((x, e)) => Err(IntegrationError::EvalFailed {
+            x,
+            reason: e.reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Returns `IntegrationError::InvalidStepConfig` if `f` has event indicators, which the solvers
+/// other than `Euler`, `Dopri5` and `Dop853` do not locate.
+pub(crate) fn reject_events<V>(f: &impl System<V>) -> Result<(), IntegrationError> {
+    if f.num_events() == 0 {
+        return Ok(());
+    }
+    Err(IntegrationError::InvalidStepConfig {
+        reason: "the events of the system are only located by Euler, Dopri5 and Dop853".to_string(),
+    })
+}
+
+/// Evaluates the event indicators of `f` at `(x, y)`.
+pub(crate) fn event_values<V>(f: &impl System<V>, x: f64, y: &V) -> Vec<f64> {
+    let mut g = vec![0.0; f.num_events()];
+    f.event_fn(x, y, &mut g);
+    g
+}
+
+/// Returns true if an event indicator changed sign from `g0` to `g1`.
+pub(crate) fn crossed(g0: f64, g1: f64) -> bool {
+    g0 != 0.0 && (g1 == 0.0 || g0.signum() != g1.signum())
+}
+
+/// Finds the earliest zero crossing of the event indicators of `f` over a step of size `h` from
+/// `x`, the indicators taking the values `g0` at the start and `g1` at the end of the step. Each
+/// crossing is bisected down to `tol` on the states `interpolant(dx)` at a distance `dx` into the
+/// step, e.g. given by the dense output of the solver. The crossings in a direction rejected by
+/// `System::event_direction` are ignored, and so are those for which `skip(idx, dx)` returns
+/// true. Returns the index of the event and the distance from `x` to a point just past the
+/// crossing.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn locate_event<V>(
+    f: &impl System<V>,
+    x: f64,
+    h: f64,
+    g0: &[f64],
+    g1: &[f64],
+    tol: f64,
+    interpolant: impl Fn(f64) -> V,
+    skip: impl Fn(usize, f64) -> bool,
+) -> Option<(usize, f64)> {
+    let mut first: Option<(usize, f64)> = None;
+    for (idx, (g0, g1)) in g0.iter().zip(g1).enumerate() {
+        if !crossed(*g0, *g1) || !f.event_direction(idx).accepts(*g0) {
+            continue;
+        }
+        // Bisection, keeping the crossing within [lo, hi]
+        let (mut lo, mut hi) = (0.0, h);
+        while (hi - lo).abs() > tol {
+            let mid = 0.5 * (lo + hi);
+            if crossed(*g0, event_values(f, x + mid, &interpolant(mid))[idx]) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        if skip(idx, hi) {
+            continue;
+        }
+        if first.is_none_or(|(_, dx)| hi.abs() < dx.abs()) {
+            first = Some((idx, hi));
+        }
+    }
+    first
+}
+
+/// Checks that the step size `h` of a fixed step method is finite and strictly positive. The
+/// direction of integration is given by the sign of `x_end - x`, not by the sign of `h`.
+pub(crate) fn validate_step_size(h: f64) -> Result<(), IntegrationError> {
+    if h.is_finite() && h > 0.0 {
+        Ok(())
+    } else {
+        Err(IntegrationError::InvalidStepConfig {
+            reason: format!(
+                "the step size must be finite and strictly positive, got {}",
+                h
+            ),
+        })
+    }
+}
+
+/// Checks that the output values `t_eval` are sorted in the direction of integration and lie within `[x, x_end]`.
+pub(crate) fn validate_t_eval(t_eval: &[f64], x: f64, x_end: f64) -> Result<(), IntegrationError> {
+    let direction = (x_end - x).signum();
+    let (lo, hi) = if x <= x_end { (x, x_end) } else { (x_end, x) };
+    if let Some(t) = t_eval.iter().find(|t| !(lo..=hi).contains(*t)) {
+        return Err(IntegrationError::InvalidOutputTimes {
+            reason: format!("t_eval value {} is outside of the integration interval", t),
+        });
+    }
+    if t_eval.windows(2).any(|w| (w[1] - w[0]) * direction < 0.0) {
+        return Err(IntegrationError::InvalidOutputTimes {
+            reason: "t_eval values must be sorted in the direction of integration".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Index `i` of the output segment `[x_out[i], x_out[i + 1]]` containing `x`, or `None` if `x`
+/// lies outside of the output range. Works for both increasing and decreasing `x_out`.
+pub(crate) fn find_segment(x_out: &[f64], x: f64) -> Option<usize> {
+    let n = x_out.len();
+    if n < 2 {
+        return None;
+    }
+    let forward = x_out[n - 1] >= x_out[0];
+    let (lo, hi) = if forward {
+        (x_out[0], x_out[n - 1])
+    } else {
+        (x_out[n - 1], x_out[0])
+    };
+    if !(lo..=hi).contains(&x) {
+        return None;
+    }
+    let i = x_out.partition_point(|x_i| if forward { *x_i <= x } else { *x_i >= x });
+    Some(i.saturating_sub(1).min(n - 2))
+}
+
+/// Error scale `atol + rtol * max(|y|, |y_new|)` of each component over a step from `y` to
+/// `y_new`, used by the adaptive solvers.
+pub(crate) fn error_scale<T, D: Dim>(
+    y: &OVector<T, D>,
+    y_new: &OVector<T, D>,
+    rtol: &Tolerance,
+    atol: &Tolerance,
+) -> OVector<T, D>
+where
+    f64: From<T>,
+    T: Scalar + Copy + SupersetOf<f64>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let (rows, cols) = y.shape_generic();
+    OVector::from_fn_generic(rows, cols, |i, _| {
+        let sc = atol.get(i) + rtol.get(i) * f64::from(y[i]).abs().max(f64::from(y_new[i]).abs());
+        nalgebra::convert(sc)
+    })
+}
+
+/// Weighted root mean square norm of the local error estimate `err` with the error scale
+/// `scale`, used by default by the adaptive solvers to accept or reject a step.
+pub fn weighted_rms_norm<T, D: Dim>(err: &OVector<T, D>, scale: &OVector<T, D>) -> f64
+where
+    f64: From<T>,
+    T: Scalar + Copy,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let n = err.len();
+    let sum: f64 = err
+        .iter()
+        .zip(scale.iter())
+        .map(|(e, sc)| (f64::from(*e) / f64::from(*sc)).powi(2))
+        .sum();
+    (sum / n as f64).sqrt()
+}
+
+/// Linear interpolation between (`x0`, `y0`) and (`x1`, `y1`).
+pub(crate) fn linear_interpolation<T, D: Dim>(
+    x0: f64,
+    y0: &OVector<T, D>,
+    x1: f64,
+    y1: &OVector<T, D>,
+    x: f64,
+) -> OVector<T, D>
+where
+    T: Scalar + ClosedAdd + ClosedSub + ClosedMul + SupersetOf<f64>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    if x1 == x0 {
+        return y0.clone();
+    }
+    let t: T = nalgebra::convert((x - x0) / (x1 - x0));
+    y0 + (y1 - y0) * t
+}
+
+/// Cubic Hermite interpolation between (`x0`, `y0`) and (`x1`, `y1`) with derivatives `dy0` and `dy1`.
+pub(crate) fn hermite_interpolation<T, D: Dim>(
+    (x0, y0, dy0): (f64, &OVector<T, D>, &OVector<T, D>),
+    (x1, y1, dy1): (f64, &OVector<T, D>, &OVector<T, D>),
+    x: f64,
+) -> OVector<T, D>
+where
+    T: Scalar + ClosedAdd + ClosedMul + SupersetOf<f64>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let h = x1 - x0;
+    if h == 0.0 {
+        return y0.clone();
+    }
+    let t = (x - x0) / h;
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let c = |c: f64| -> T { nalgebra::convert(c) };
+    y0.clone() * c(2.0 * t3 - 3.0 * t2 + 1.0)
+        + dy0.clone() * c((t3 - 2.0 * t2 + t) * h)
+        + y1.clone() * c(3.0 * t2 - 2.0 * t3)
+        + dy1.clone() * c((t3 - t2) * h)
+}
+
+/// Trait implemented by the state vectors supported by the solvers.
+pub trait State: Sized {
+    /// Square matrix type matching the dimension of the state.
+    type Matrix;
+    /// Approximates the Jacobian of `f` at (`x`, `y`) by forward finite differences.
+    fn finite_diff_jacobian<F: System<Self> + ?Sized>(
+        f: &F,
+        x: f64,
+        y: &Self,
+        out: &mut Self::Matrix,
+    );
+    /// Computes the product `J^T lambda` of the transposed Jacobian of `f` at (`x`, `y`), given
+    /// by `System::jacobian`, with `lambda`.
+    fn jacobian_transpose_product<F: System<Self> + ?Sized>(
+        f: &F,
+        x: f64,
+        y: &Self,
+        lambda: &Self,
+        out: &mut Self,
+    );
+}
+
+impl<T, D: Dim> State for OVector<T, D>
+where
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + Zero,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    type Matrix = OMatrix<T, D, D>;
+
+    fn finite_diff_jacobian<F: System<Self> + ?Sized>(
+        f: &F,
+        x: f64,
+        y: &Self,
+        out: &mut Self::Matrix,
+    ) {
+        if let Some(pattern) = f.jacobian_sparsity() {
+            return finite_diff_jacobian_sparse(f, x, y, out, &pattern);
+        }
+        match f.jacobian_bandwidth() {
+            Some((lower, upper)) => finite_diff_jacobian_banded(f, x, y, out, lower, upper),
+            None => finite_diff_jacobian(f, x, y, out),
+        }
+    }
+
+    fn jacobian_transpose_product<F: System<Self> + ?Sized>(
+        f: &F,
+        x: f64,
+        y: &Self,
+        lambda: &Self,
+        out: &mut Self,
+    ) {
+        let (rows, _) = y.shape_generic();
+        let mut jac = OMatrix::zeros_generic(rows, rows);
+        f.jacobian(x, y, &mut jac);
+        for j in 0..y.len() {
+            out[j] = jac
+                .column(j)
+                .iter()
+                .zip(lambda.iter())
+                .fold(T::zero(), |acc, (a, l)| acc + *a * *l);
+        }
+    }
+}
+
+/// Approximates the Jacobian of `f` at (`x`, `y`) by forward finite differences, perturbing the
+/// component `y_j` by `sqrt(eps) * max(|y_j|, 1)`. Calls `System::ode` `n + 1` times, `n` being the
+/// dimension of the system.
+pub fn finite_diff_jacobian<F, T, D: Dim>(
+    f: &F,
+    x: f64,
+    y: &OVector<T, D>,
+    out: &mut OMatrix<T, D, D>,
+) where
+    F: System<OVector<T, D>> + ?Sized,
+    T: Copy + SubsetOf<f64> + Scalar + Zero,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    finite_diff_jacobian_with_scale(f, x, y, out, f64::EPSILON.sqrt());
+}
+
+/// Same as [`finite_diff_jacobian`] with the component `y_j` perturbed by `scale * max(|y_j|, 1)`.
+pub fn finite_diff_jacobian_with_scale<F, T, D: Dim>(
+    f: &F,
+    x: f64,
+    y: &OVector<T, D>,
+    out: &mut OMatrix<T, D, D>,
+    scale: f64,
+) where
+    F: System<OVector<T, D>> + ?Sized,
+    T: Copy + SubsetOf<f64> + Scalar + Zero,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    let (rows, cols) = y.shape_generic();
+    let mut f0 = OVector::zeros_generic(rows, cols);
+    let mut f1 = OVector::zeros_generic(rows, cols);
+    f.ode(x, y, &mut f0);
+    let mut y_pert = y.clone();
+    for j in 0..y.len() {
+        let y_j: f64 = y[j].to_superset();
+        let delta = scale * y_j.abs().max(1.0);
+        y_pert[j] = T::from_superset_unchecked(&(y_j + delta));
+        f.ode(x, &y_pert, &mut f1);
+        for i in 0..y.len() {
+            let df: f64 = f1[i].to_superset() - f0[i].to_superset();
+            out[(i, j)] = T::from_superset_unchecked(&(df / delta));
+        }
+        y_pert[j] = y[j];
+    }
+}
+
+/// Same as [`finite_diff_jacobian`] for a Jacobian with lower and upper bandwidths `lower` and
+/// `upper`. The components `y_j` whose columns do not overlap, i.e. with the same `j` modulo
+/// `lower + upper + 1`, are perturbed together, so that `System::ode` is called
+/// `lower + upper + 2` times independently of the dimension of the system. The entries outside
+/// of the band are set to zero.
+pub fn finite_diff_jacobian_banded<F, T, D: Dim>(
+    f: &F,
+    x: f64,
+    y: &OVector<T, D>,
+    out: &mut OMatrix<T, D, D>,
+    lower: usize,
+    upper: usize,
+) where
+    F: System<OVector<T, D>> + ?Sized,
+    T: Copy + SubsetOf<f64> + Scalar + Zero,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    let n = y.len();
+    let (rows, cols) = y.shape_generic();
+    let mut f0 = OVector::zeros_generic(rows, cols);
+    let mut f1 = OVector::zeros_generic(rows, cols);
+    f.ode(x, y, &mut f0);
+    out.fill(T::zero());
+    let width = lower + upper + 1;
+    let delta: Vec<f64> = y
+        .iter()
+        .map(|y_j| f64::EPSILON.sqrt() * y_j.to_superset().abs().max(1.0))
+        .collect();
+    let mut y_pert = y.clone();
+    for group in 0..width.min(n) {
+        for j in (group..n).step_by(width) {
+            let y_j: f64 = y[j].to_superset();
+            y_pert[j] = T::from_superset_unchecked(&(y_j + delta[j]));
+        }
+        f.ode(x, &y_pert, &mut f1);
+        for j in (group..n).step_by(width) {
+            for i in j.saturating_sub(upper)..(j + lower + 1).min(n) {
+                let df: f64 = f1[i].to_superset() - f0[i].to_superset();
+                out[(i, j)] = T::from_superset_unchecked(&(df / delta[j]));
+            }
+            y_pert[j] = y[j];
+        }
+    }
+}
+
+/// Groups the columns of a Jacobian with the sparsity `pattern`, given as in
+/// `System::jacobian_sparsity`, so that the columns of a group share no row. The groups are
+/// built by greedy coloring of the columns in increasing order, each column taking the first
+/// group none of whose columns shares a row with it. A tridiagonal pattern gives three groups
+/// independently of its dimension.
+pub fn color_columns(pattern: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let num_rows = pattern.iter().flatten().map(|i| i + 1).max().unwrap_or(0);
+    // Columns already colored with a nonzero in each row
+    let mut row_columns = vec![Vec::new(); num_rows];
+    let mut color = vec![0; pattern.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (j, rows) in pattern.iter().enumerate() {
+        let mut forbidden = vec![false; groups.len()];
+        for i in rows {
+            for k in &row_columns[*i] {
+                forbidden[color[*k]] = true;
+            }
+        }
+        let c = forbidden.iter().position(|f| !f).unwrap_or(groups.len());
+        if c == groups.len() {
+            groups.push(Vec::new());
+        }
+        groups[c].push(j);
+        color[j] = c;
+        for i in rows {
+            row_columns[*i].push(j);
+        }
+    }
+    groups
+}
+
+/// Same as [`finite_diff_jacobian`] for a Jacobian with the sparsity `pattern`, given as in
+/// `System::jacobian_sparsity`. The components `y_j` of each group of [`color_columns`] are
+/// perturbed together, so that `System::ode` is called once per group plus once, e.g. four times
+/// for a tridiagonal Jacobian independently of the dimension of the system. The entries outside
+/// of the pattern are set to zero.
+pub fn finite_diff_jacobian_sparse<F, T, D: Dim>(
+    f: &F,
+    x: f64,
+    y: &OVector<T, D>,
+    out: &mut OMatrix<T, D, D>,
+    pattern: &[Vec<usize>],
+) where
+    F: System<OVector<T, D>> + ?Sized,
+    T: Copy + SubsetOf<f64> + Scalar + Zero,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    let (rows, cols) = y.shape_generic();
+    let mut f0 = OVector::zeros_generic(rows, cols);
+    let mut f1 = OVector::zeros_generic(rows, cols);
+    f.ode(x, y, &mut f0);
+    out.fill(T::zero());
+    let delta: Vec<f64> = y
+        .iter()
+        .map(|y_j| f64::EPSILON.sqrt() * y_j.to_superset().abs().max(1.0))
+        .collect();
+    let mut y_pert = y.clone();
+    for group in color_columns(pattern) {
+        for j in &group {
+            let y_j: f64 = y[*j].to_superset();
+            y_pert[*j] = T::from_superset_unchecked(&(y_j + delta[*j]));
+        }
+        f.ode(x, &y_pert, &mut f1);
+        for j in group {
+            for i in &pattern[j] {
+                let df: f64 = f1[*i].to_superset() - f0[*i].to_superset();
+                out[(*i, j)] = T::from_superset_unchecked(&(df / delta[j]));
+            }
+            y_pert[j] = y[j];
+        }
+    }
+}
+
+/// LU factorization with partial pivoting of a banded matrix, storing the band of the upper
+/// factor, widened by the lower bandwidth to hold the fill-in of the row interchanges, and the
+/// multipliers of the lower factor.
+pub(crate) struct BandedLu<T> {
+    n: usize,
+    lower: usize,
+    upper: usize,
+    u: Vec<T>,
+    l: Vec<T>,
+    pivots: Vec<usize>,
+    singular: bool,
+}
+
+impl<T: RealField + Copy> BandedLu<T> {
+    /// Factorizes the band of `m` with lower and upper bandwidths `lower` and `upper`, ignoring
+    /// the entries outside of the band.
+    pub(crate) fn new<D: Dim>(m: &OMatrix<T, D, D>, lower: usize, upper: usize) -> Self
+    where
+        DefaultAllocator: Allocator<T, D, D>,
+    {
+        let n = m.nrows();
+        let width = 2 * lower + upper + 1;
+        let mut lu = BandedLu {
+            n,
+            lower,
+            upper,
+            u: vec![T::zero(); n * width],
+            l: vec![T::zero(); n * lower],
+            pivots: vec![0; n],
+            singular: false,
+        };
+        for i in 0..n {
+            for j in i.saturating_sub(lower)..(i + upper + 1).min(n) {
+                *lu.u_mut(i, j) = m[(i, j)];
+            }
+        }
+        for k in 0..n {
+            let last_row = (k + lower).min(n - 1);
+            let last_col = (k + lower + upper).min(n - 1);
+            let p = (k..=last_row)
+                .max_by(|a, b| {
+                    lu.u(*a, k)
+                        .abs()
+                        .partial_cmp(&lu.u(*b, k).abs())
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap_or(k);
+            lu.pivots[k] = p;
+            if lu.u(p, k) == T::zero() {
+                lu.singular = true;
+                continue;
+            }
+            if p != k {
+                for j in k..=last_col {
+                    let tmp = lu.u(k, j);
+                    *lu.u_mut(k, j) = lu.u(p, j);
+                    *lu.u_mut(p, j) = tmp;
+                }
+            }
+            for i in k + 1..=last_row {
+                let factor = lu.u(i, k) / lu.u(k, k);
+                lu.l[k * lower + i - k - 1] = factor;
+                for j in k + 1..=last_col {
+                    let u_kj = lu.u(k, j);
+                    *lu.u_mut(i, j) -= factor * u_kj;
+                }
+            }
+        }
+        lu
+    }
+
+    fn u(&self, i: usize, j: usize) -> T {
+        self.u[i * (2 * self.lower + self.upper + 1) + j + self.lower - i]
+    }
+
+    fn u_mut(&mut self, i: usize, j: usize) -> &mut T {
+        &mut self.u[i * (2 * self.lower + self.upper + 1) + j + self.lower - i]
+    }
+
+    /// Solves the linear system with right-hand side `b`. Returns `None` if the matrix is
+    /// singular.
+    pub(crate) fn solve<D: Dim>(&self, b: &OVector<T, D>) -> Option<OVector<T, D>>
+    where
+        DefaultAllocator: Allocator<T, D>,
+    {
+        if self.singular {
+            return None;
+        }
+        let n = self.n;
+        let mut x = b.clone();
+        for k in 0..n {
+            x.swap_rows(k, self.pivots[k]);
+            for i in k + 1..(k + self.lower + 1).min(n) {
+                let x_k = x[k];
+                x[i] -= self.l[k * self.lower + i - k - 1] * x_k;
+            }
+        }
+        for i in (0..n).rev() {
+            let mut sum = x[i];
+            for j in i + 1..(i + self.lower + self.upper + 1).min(n) {
+                sum -= self.u(i, j) * x[j];
+            }
+            x[i] = sum / self.u(i, i);
+        }
+        Some(x)
+    }
+}
+
+/// Factorization of the iteration matrix of the implicit solvers, banded if the system declares
+/// the bandwidths of its Jacobian with `System::jacobian_bandwidth`.
+pub(crate) enum Factorization<T: RealField, D: Dim + DimMin<D, Output = D>>
+where
+    DefaultAllocator: Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    Dense(LU<T, D, D>),
+    Banded(BandedLu<T>),
+}
+
+impl<T, D> Factorization<T, D>
+where
+    T: RealField + Copy,
+    D: Dim + DimMin<D, Output = D>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Factorizes `m`, using a banded factorization if `bandwidth` is given.
+    pub(crate) fn new(m: OMatrix<T, D, D>, bandwidth: Option<(usize, usize)>) -> Self {
+        match bandwidth {
+            Some((lower, upper)) => Factorization::Banded(BandedLu::new(&m, lower, upper)),
+            None => Factorization::Dense(m.lu()),
+        }
+    }
+
+    /// Solves the linear system with right-hand side `b`. Returns `None` if the matrix is
+    /// singular.
+    pub(crate) fn solve(&self, b: &OVector<T, D>) -> Option<OVector<T, D>> {
+        match self {
+            Factorization::Dense(lu) => lu.solve(b),
+            Factorization::Banded(lu) => lu.solve(b),
+        }
+    }
+
+    /// Returns true if the ratio of the smallest to the largest pivot of the factorization lies
+    /// below `NEAR_SINGULAR`, a cheap sign of an ill-conditioned matrix.
+    pub(crate) fn nearly_singular(&self) -> bool {
+        let pivots: Vec<T> = match self {
+            Factorization::Dense(lu) => lu.u().diagonal().iter().map(|p| p.abs()).collect(),
+            Factorization::Banded(lu) => (0..lu.n).map(|i| lu.u(i, i).abs()).collect(),
+        };
+        let max = pivots.iter().fold(T::zero(), |m, p| m.max(*p));
+        let min = pivots.iter().fold(max, |m, p| m.min(*p));
+        min <= max * nalgebra::convert(NEAR_SINGULAR)
+    }
+}
+
+/// Ratio of the smallest to the largest pivot below which an iteration matrix is reported as
+/// nearly singular.
+const NEAR_SINGULAR: f64 = 1.0e-12;
+
+/// Common interface of the solvers, allowing them to be driven uniformly, e.g. as `Box<dyn OdeSolver<V>>`.
+pub trait OdeSolver<V> {
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out). If the final
+    /// value of the independent variable equals the initial one, the output is the initial point
+    /// alone and the system is not evaluated.
+    fn integrate(&mut self) -> Result<Stats, IntegrationError>;
+    /// Getter for the independent variable's output.
+    fn x_out(&self) -> &[f64];
+    /// Getter for the dependent variables' output.
+    fn y_out(&self) -> &[V];
+    /// Returns the current value of the independent variable.
+    fn x_final(&self) -> f64;
+    /// Returns the current value of the dependent variable(s).
+    fn y_final(&self) -> &V;
+}
+
+/// Properties of the numerical method of a solver, allowing generic code such as the stiffness
+/// switching of [`Auto`](crate::Auto) or a work-precision comparison to tell the solvers apart.
+pub trait MethodInfo {
+    /// Nominal order of the method, the largest order for the methods of varying order.
+    fn order(&self) -> u32;
+    /// Returns true if each step solves equations involving the Jacobian or the stages, false if
+    /// the method is explicit.
+    fn is_implicit(&self) -> bool;
+    /// Returns true if the stability domain of the method contains the left half-plane, so that
+    /// the step size is not limited by the stiffness of the problem.
+    fn is_a_stable(&self) -> bool;
+    /// Returns true if the derivative at the end of an accepted step is reused as the first stage
+    /// of the next step (first same as last).
+    fn is_fsal(&self) -> bool;
+}
+
+/// Returns `num` values evenly spaced from `start` to `stop`, both included, e.g. for
+/// `t_eval`. Returns `[start]` if `num` is 1 and no value if it is 0.
+pub fn linspace(start: f64, stop: f64, num: usize) -> Vec<f64> {
+    match num {
+        0 => Vec::new(),
+        1 => vec![start],
+        _ => {
+            let step = (stop - start) / (num - 1) as f64;
+            let mut values: Vec<f64> = (0..num).map(|i| start + step * i as f64).collect();
+            values[num - 1] = stop;
+            values
+        }
+    }
+}
+
+/// Returns `num` values `10^e` with the exponents `e` evenly spaced from `start` to `stop`, both
+/// included, so that the values from `10^start` to `10^stop` are dense near the smaller one.
+pub fn logspace(start: f64, stop: f64, num: usize) -> Vec<f64> {
+    linspace(start, stop, num)
+        .into_iter()
+        .map(|e| 10.0_f64.powf(e))
+        .collect()
+}
+
+/// Returns `num` values in geometric progression from `start` to `stop`, both included, i.e.
+/// with a constant ratio between successive values. `start` and `stop` must be nonzero and of
+/// the same sign, the intermediate values being NaN otherwise.
+pub fn geomspace(start: f64, stop: f64, num: usize) -> Vec<f64> {
+    let ratio = stop / start;
+    let mut values: Vec<f64> = linspace(0.0, 1.0, num)
+        .into_iter()
+        .map(|t| start * ratio.powf(t))
+        .collect();
+    if let Some(first) = values.first_mut() {
+        *first = start;
+    }
+    if num > 1 {
+        values[num - 1] = stop;
+    }
+    values
+}
+
+/// Resamples the trajectory `x_out`, `y_out` of any solver at `n` values of the independent
+/// variable evenly spaced from its first to its last point, both included, the states being
+/// interpolated linearly between the stored points. The trajectory is returned unchanged if `n`
+/// is not smaller than its number of points.
+pub fn resample<T, D: Dim>(
+    x_out: &[f64],
+    y_out: &[OVector<T, D>],
+    n: usize,
+) -> (Vec<f64>, Vec<OVector<T, D>>)
+where
+    T: Scalar + ClosedAdd + ClosedSub + ClosedMul + SupersetOf<f64>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    if n >= x_out.len() {
+        return (x_out.to_vec(), y_out.to_vec());
+    }
+    let x = linspace(x_out[0], x_out[x_out.len() - 1], n);
+    let y = x
+        .iter()
+        .map(|&x| match find_segment(x_out, x) {
+            Some(i) => linear_interpolation(x_out[i], &y_out[i], x_out[i + 1], &y_out[i + 1], x),
+            None => y_out[0].clone(),
+        })
+        .collect();
+    (x, y)
+}
+
+/// Decimates the trajectory `x_out`, `y_out` of any solver, keeping every `factor`-th point from
+/// the first one together with the last point. The trajectory is returned unchanged if `factor`
+/// is at most 1.
+pub fn decimate<V: Clone>(x_out: &[f64], y_out: &[V], factor: usize) -> (Vec<f64>, Vec<V>) {
+    if factor <= 1 {
+        return (x_out.to_vec(), y_out.to_vec());
+    }
+    let mut indices: Vec<usize> = (0..x_out.len()).step_by(factor).collect();
+    match x_out.len().checked_sub(1) {
+        Some(last) if last % factor != 0 => indices.push(last),
+        _ => {}
+    }
+    (
+        indices.iter().map(|&i| x_out[i]).collect(),
+        indices.iter().map(|&i| y_out[i].clone()).collect(),
+    )
+}
+
+/// Estimates the order of convergence of a solver against a known solution. For each step size
+/// `h` in `step_sizes`, the solver returned by `solver_factory(h)` is integrated and the maximum
+/// norm of the difference between its final state and `exact_fn(x_final)` is computed. The
+/// order is the least squares slope of `log(error)` against `log(h)`, vanishing errors being
+/// ignored, and is `NaN` when fewer than two errors are available. Returns the order together
+/// with the errors of every step size.
+///
+/// ```
+/// # use ode_event_solvers::dop_shared::{convergence_order, ode_fn};
+/// # use ode_event_solvers::{RungeKutta4, Vector1};
+/// let (order, errors) = convergence_order(
+///     |h| RungeKutta4::new(ode_fn(|_, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = -y[0]),
+///         0.0, Vector1::new(1.0), 1.0, h),
+///     |x| Vector1::new((-x).exp()),
+///     &[0.1, 0.05, 0.025],
+/// )?;
+/// # assert!((order - 4.0).abs() < 0.1);
+/// # assert_eq!(errors.len(), 3);
+/// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+/// ```
+pub fn convergence_order<S, T, D: Dim>(
+    mut solver_factory: impl FnMut(f64) -> S,
+    exact_fn: impl Fn(f64) -> OVector<T, D>,
+    step_sizes: &[f64],
+) -> Result<(f64, Vec<f64>), IntegrationError>
+where
+    S: OdeSolver<OVector<T, D>>,
+    f64: From<T>,
+    T: Scalar + Copy + ClosedSub,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let mut errors = Vec::with_capacity(step_sizes.len());
+    for h in step_sizes {
+        let mut solver = solver_factory(*h);
+        solver.integrate()?;
+        let err = solver.y_final() - exact_fn(solver.x_final());
+        errors.push(
+            err.iter()
+                .fold(0.0, |acc: f64, e| acc.max(f64::from(*e).abs())),
+        );
+    }
+
+    let points: Vec<(f64, f64)> = step_sizes
+        .iter()
+        .zip(errors.iter())
+        .filter(|(_, err)| **err > 0.0)
+        .map(|(h, err)| (h.abs().ln(), err.ln()))
+        .collect();
+    if points.len() < 2 {
+        return Ok((f64::NAN, errors));
+    }
+    let n = points.len() as f64;
+    let mean_h = points.iter().map(|(h, _)| h).sum::<f64>() / n;
+    let mean_err = points.iter().map(|(_, err)| err).sum::<f64>() / n;
+    let (cov, var) = points.iter().fold((0.0, 0.0), |(cov, var), (h, err)| {
+        (
+            cov + (h - mean_h) * (err - mean_err),
+            var + (h - mean_h).powi(2),
+        )
+    });
+    Ok((cov / var, errors))
+}
+
+/// Norm of the local error estimate used by the adaptive solvers, called with the error estimate
+/// and the error scale of each component.
+pub type ErrorNorm<V> = dyn Fn(&V, &V) -> f64;
+
+/// Predicate terminating the integration when it returns true, called with the independent and
+/// the dependent variable(s) after every step.
+pub type StopPredicate<V> = dyn FnMut(f64, &V) -> bool;
+
+/// Schedule of the step size, returning the size of the next step from the independent and the
+/// dependent variable(s) at its start.
+pub type StepSchedule<V> = dyn FnMut(f64, &V) -> f64;
+
+/// Point `(x, y, dy)` of the history of a multistep solver, `dy` being the derivative at `(x, y)`.
+pub type HistoryPoint<V> = (f64, V, V);
+
+/// Absolute or relative tolerance of the adaptive solvers, either shared by all the components of
+/// the state or given per component.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tolerance {
+    Scalar(f64),
+    PerComponent(DVector<f64>),
+}
+
+impl Tolerance {
+    /// Tolerance applied to the component `i` of the state.
+    pub(crate) fn get(&self, i: usize) -> f64 {
+        match self {
+            Tolerance::Scalar(tol) => *tol,
+            Tolerance::PerComponent(tol) => tol[i],
+        }
+    }
+
+    /// Smallest tolerance over all the components.
+    pub(crate) fn min(&self) -> f64 {
+        match self {
+            Tolerance::Scalar(tol) => *tol,
+            Tolerance::PerComponent(tol) => tol.min(),
+        }
+    }
+
+    /// Tolerance multiplied by `factor`, without going below `floor`.
+    pub(crate) fn tightened(&self, factor: f64, floor: f64) -> Tolerance {
+        match self {
+            Tolerance::Scalar(tol) => Tolerance::Scalar((tol * factor).max(floor)),
+            Tolerance::PerComponent(tol) => {
+                Tolerance::PerComponent(tol.map(|tol| (tol * factor).max(floor)))
+            }
+        }
+    }
+
+    /// Checks that a per component tolerance matches the dimension `n` of the state.
+    pub(crate) fn validate(&self, n: usize) -> Result<(), IntegrationError> {
+        match self {
+            Tolerance::PerComponent(tol) if tol.len() != n => {
+                Err(IntegrationError::InvalidTolerance {
+                    reason: format!("expected {} components, got {}", n, tol.len()),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<f64> for Tolerance {
+    fn from(tol: f64) -> Self {
+        Tolerance::Scalar(tol)
+    }
+}
+
+impl From<DVector<f64>> for Tolerance {
+    fn from(tol: DVector<f64>) -> Self {
+        Tolerance::PerComponent(tol)
+    }
+}
+
+impl From<Vec<f64>> for Tolerance {
+    fn from(tol: Vec<f64>) -> Self {
+        Tolerance::PerComponent(DVector::from_vec(tol))
+    }
+}
+
+/// Enumeration of the actions that may be requested when an event is triggered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventAction {
+    Continue,
+    Terminate,
+}
+
+/// Direction of the zero crossings of an event indicator which trigger the event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventDirection {
+    /// Crossings from negative to positive values.
+    Rising,
+    /// Crossings from positive to negative values.
+    Falling,
+    /// Crossings in either direction.
+    Both,
+}
+
+impl EventDirection {
+    /// Returns true if a crossing starting from the value `g0` has the requested direction.
+    pub(crate) fn accepts(self, g0: f64) -> bool {
+        match self {
+            EventDirection::Rising => g0 < 0.0,
+            EventDirection::Falling => g0 > 0.0,
+            EventDirection::Both => true,
+        }
+    }
+}
+
+/// Enumeration of the values returned by `solout` to control the integration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlFlag {
+    Continue,
+    Stop,
+}
+
+/// Enumeration of the types of the integration output.
+#[derive(PartialEq, Eq)]
+pub enum OutputType {
+    Dense,
+    Sparse,
+}
+
+/// Strategy solving the nonlinear equations of the steps of the implicit solvers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NonlinearSolver {
+    /// Simplified Newton iteration, evaluating and factorizing the Jacobian once per step.
+    Newton,
+    /// Functional (fixed-point) iteration, e.g. `y_new = y + h * f(x_new, y_new)` for the
+    /// backward euler method, which needs no Jacobian. It converges when the step is contractive,
+    /// i.e. when `h * L < 1` for a Lipschitz constant `L` of `f` (scaled by the coefficients of
+    /// the method), so that it suits non-stiff or mildly stiff problems only. A step whose
+    /// iteration does not converge fails with `IntegrationError::NewtonDidNotConverge`.
+    FunctionalIteration,
+}
+
+/// Enumeration of the errors that may arise during integration.
+#[derive(Debug, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntegrationError {
+    /// The number of steps exceeded the limit of an adaptive solver, set with `max_steps` and
+    /// 100000 by default.
+    #[error("Stopped at x = {x}. Need more than {n_step} steps.")]
+    MaxStepsExceeded { x: f64, n_step: u32 },
+    /// Former name of `MaxStepsExceeded`, no longer returned by the solvers.
+    #[deprecated(note = "renamed to `MaxStepsExceeded`, which the solvers return instead")]
+    #[error("Stopped at x = {x}. Need more than {n_step} steps.")]
+    MaxNumStepReached { x: f64, n_step: u32 },
+    #[error("Stopped at x = {x}. Step size underflow.")]
+    StepSizeUnderflow { x: f64 },
+    #[error("Stopped at x = {x}. Step size below the minimum step size.")]
+    StepSizeTooSmall { x: f64 },
+    #[error("The problem seems to become stiff at x = {x}.")]
+    StiffnessDetected { x: f64 },
+    #[error("Stopped at x = {x}. Newton iteration did not converge.")]
+    NewtonDidNotConverge { x: f64 },
+    #[error("Invalid step size configuration: {reason}.")]
+    InvalidStepConfig { reason: String },
+    #[error("Stopped at x = {x}. The state is not finite.")]
+    NonFinite { x: f64 },
+    #[error("Stopped at x = {x}. The state grew too fast, the step is too large for this stiff problem.")]
+    Unstable { x: f64 },
+    #[error("Invalid output times: {reason}.")]
+    InvalidOutputTimes { reason: String },
+    #[error("Invalid tolerance: {reason}.")]
+    InvalidTolerance { reason: String },
+    #[error("Stopped at x = {x}. Maximum duration exceeded.")]
+    Timeout { x: f64 },
+    #[error("Stopped at x = {x}. Evaluation of the system failed: {reason}.")]
+    EvalFailed { x: f64, reason: String },
+    #[error("The derivative has {got} components, expected {expected}.")]
+    DimensionMismatch { expected: usize, got: usize },
+    #[error("Invalid component {index} of a state of dimension {dim}.")]
+    InvalidComponent { index: usize, dim: usize },
+    #[error("Stopped at x = {x}. Writing the output failed: {reason}.")]
+    Io { x: f64, reason: String },
+    #[error("Shooting did not converge after {iterations} iterations, residual {residual}.")]
+    ShootingDidNotConverge { iterations: u32, residual: f64 },
+    #[error("Parareal did not converge after {iterations} iterations, change {change}.")]
+    PararealDidNotConverge { iterations: u32, change: f64 },
+}
+
+/// Trajectory and statistics returned by a completed integration.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntegrationResult<V> {
+    pub x: Vec<f64>,
+    pub y: Vec<V>,
+    pub stats: Stats,
+}
+
+impl<V> IntegrationResult<V> {
+    /// Number of points in the trajectory.
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Returns true if the trajectory contains no points.
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+}
+
+impl IntegrationResult<Vector1<f64>> {
+    /// Dependent variable of a one-dimensional system at each point of the trajectory.
+    pub fn y_scalar(&self) -> Vec<f64> {
+        self.y.iter().map(|y| y[0]).collect()
+    }
+}
+
+/// Output of a solver: the states stored at the accepted steps and, once sampled, the solution
+/// at the output values set with `t_eval` or at refined points.
+#[derive(Clone, Debug)]
+pub(crate) struct Output<V> {
+    /// Values of the independent variable at the stored steps.
+    pub(crate) x: Vec<f64>,
+    /// States at the stored steps.
+    pub(crate) y: Vec<V>,
+    t_eval: Option<Vec<f64>>,
+    sampled: bool,
+    x_eval: Vec<f64>,
+    y_eval: Vec<V>,
+}
+
+impl<V> Output<V> {
+    pub(crate) fn new() -> Self {
+        Self::with_steps(Vec::new(), Vec::new(), None)
+    }
+
+    /// Output made of the stored steps `x` and `y`, sampled at `t_eval` if set.
+    pub(crate) fn with_steps(x: Vec<f64>, y: Vec<V>, t_eval: Option<Vec<f64>>) -> Self {
+        Output {
+            x,
+            y,
+            t_eval,
+            sampled: false,
+            x_eval: Vec::new(),
+            y_eval: Vec::new(),
+        }
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned.
+    pub(crate) fn set_t_eval(&mut self, t_eval: Vec<f64>) {
+        self.t_eval = Some(t_eval);
+    }
+
+    /// Output values set with [`set_t_eval`](Self::set_t_eval).
+    pub(crate) fn t_eval(&self) -> Option<&[f64]> {
+        self.t_eval.as_deref()
+    }
+
+    /// Checks the output values, if set, against the integration interval `[x, x_end]`.
+    pub(crate) fn validate_t_eval(&self, x: f64, x_end: f64) -> Result<(), IntegrationError> {
+        match &self.t_eval {
+            Some(t_eval) => validate_t_eval(t_eval, x, x_end),
+            None => Ok(()),
+        }
+    }
+
+    /// Pushes the state `y` at `x` to the stored steps.
+    pub(crate) fn push(&mut self, x: f64, y: V) {
+        self.x.push(x);
+        self.y.push(y);
+    }
+
+    /// Evaluates the solution with `at` at the output values, up to the first value where it
+    /// returns `None`. Returns `None` if no output values are set. The samples are then stored
+    /// with [`set_samples`](Self::set_samples), `at` usually borrowing the solver holding the
+    /// output.
+    pub(crate) fn sample(&self, at: impl Fn(f64) -> Option<V>) -> Option<(Vec<f64>, Vec<V>)> {
+        self.t_eval
+            .as_ref()
+            .map(|t_eval| t_eval.iter().map_while(|t| at(*t).map(|y| (*t, y))).unzip())
+    }
+
+    /// Stores the samples of the solution, returned from then on instead of the stored steps.
+    pub(crate) fn set_samples(&mut self, samples: Option<(Vec<f64>, Vec<V>)>) {
+        if let Some((x_eval, y_eval)) = samples {
+            self.x_eval = x_eval;
+            self.y_eval = y_eval;
+            self.sampled = true;
+        }
+    }
+
+    /// Clears the stored steps and the samples, keeping the output values.
+    pub(crate) fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.x_eval.clear();
+        self.y_eval.clear();
+        self.sampled = false;
+    }
+
+    /// Whether the samples are returned instead of the stored steps.
+    fn interpolated(&self) -> bool {
+        self.t_eval.is_some() || self.sampled
+    }
+
+    /// Independent variable of the output: the samples if output values are set or samples have
+    /// been stored, the stored steps otherwise.
+    pub(crate) fn x_out(&self) -> &Vec<f64> {
+        if self.interpolated() {
+            &self.x_eval
+        } else {
+            &self.x
+        }
+    }
+
+    /// Dependent variables of the output, at the points of [`x_out`](Self::x_out).
+    pub(crate) fn y_out(&self) -> &Vec<V> {
+        if self.interpolated() {
+            &self.y_eval
+        } else {
+            &self.y
+        }
+    }
+
+    /// Consumes the output and returns it together with the statistics `stats`.
+    pub(crate) fn into_result(self, stats: Stats) -> IntegrationResult<V> {
+        if self.interpolated() {
+            IntegrationResult {
+                x: self.x_eval,
+                y: self.y_eval,
+                stats,
+            }
+        } else {
+            IntegrationResult {
+                x: self.x,
+                y: self.y,
+                stats,
+            }
+        }
+    }
+}
+
+/// Severity of a [`LogRecord`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogLevel {
+    /// Progress of the integration, e.g. an accepted step.
+    Debug,
+    /// Unusual but recoverable situation, e.g. a rejected or clamped step.
+    Warn,
+    /// Failure of the integration, logged before the error is returned.
+    Error,
+}
+
+/// Event of an integration passed to the logger set with the `logger` setter of the adaptive and
+/// implicit solvers, e.g. to forward it to the `log` or `tracing` crates, here collecting the
+/// warnings and errors. The setters require the `logging` feature, without which the solvers
+/// carry no logger and the records are never built.
+///
+/// ```
+/// # #[cfg(feature = "logging")] {
+/// # use ode_event_solvers::{problems::VanDerPol, Dopri5, LogLevel, LogRecord, Vector2};
+/// # use std::{cell::RefCell, rc::Rc};
+/// # let mut solver = Dopri5::new(VanDerPol::default(), 0.0, Vector2::new(2.0, 0.0), 20.0,
+/// #     None, 1e-6, 1e-6);
+/// let warnings = Rc::new(RefCell::new(Vec::new()));
+/// let sink = warnings.clone();
+/// solver.logger(move |record: &LogRecord| match record.level {
+///     LogLevel::Debug => {}
+///     LogLevel::Warn | LogLevel::Error => sink.borrow_mut().push(record.clone()),
+/// });
+/// solver.integrate()?;
+/// let warned_at: Vec<f64> = warnings.borrow().iter().map(|record| record.x).collect();
+/// # assert!(!warned_at.is_empty());
+/// # }
+/// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    /// Severity of the event.
+    pub level: LogLevel,
+    /// Value of the independent variable at which the event occurred.
+    pub x: f64,
+    /// Step size at the event.
+    pub h: f64,
+    /// Description of the event.
+    pub message: String,
+}
+
+/// Logger receiving the [`LogRecord`]s of an integration.
+pub type Logger = dyn Fn(&LogRecord);
+
+/// Logger of a solver, passing the [`LogRecord`]s to the function set with the `logger` setter of
+/// the solver. Zero-sized without the `logging` feature, in which case the records are never built.
+#[derive(Default)]
+pub(crate) struct SolverLog {
+    #[cfg(feature = "logging")]
+    logger: Option<Box<Logger>>,
+}
+
+impl SolverLog {
+    /// Sets the function receiving the records.
+    #[cfg(feature = "logging")]
+    pub(crate) fn set(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.logger = Some(Box::new(logger));
+    }
+
+    /// Returns true if a logger has been set.
+    pub(crate) fn enabled(&self) -> bool {
+        #[cfg(feature = "logging")]
+        return self.logger.is_some();
+        #[cfg(not(feature = "logging"))]
+        false
+    }
+
+    /// Passes the record built by `message` for the step `h` at `x` to the logger, if any.
+    #[cfg_attr(not(feature = "logging"), allow(unused_variables))]
+    pub(crate) fn log(&self, level: LogLevel, x: f64, h: f64, message: impl FnOnce() -> String) {
+        #[cfg(feature = "logging")]
+        if let Some(logger) = &self.logger {
+            logger(&LogRecord {
+                level,
+                x,
+                h,
+                message: message(),
+            });
+        }
+    }
+
+    /// Logs an accepted step `h` at `x` with the error estimate `err`.
+    pub(crate) fn accepted(&self, x: f64, h: f64, err: f64) {
+        self.log(LogLevel::Debug, x, h, || {
+            format!("step accepted, error {:e}", err)
+        });
+    }
+
+    /// Logs a rejected step `h` at `x` with the error estimate `err`.
+    pub(crate) fn rejected(&self, x: f64, h: f64, err: f64) {
+        self.log(LogLevel::Warn, x, h, || {
+            format!("step rejected, error {:e}", err)
+        });
+    }
+
+    /// Logs a step proposed as `h_new` at `x` and clamped to `h` by the maximum step size
+    /// `max_step`, see [`limit_step`].
+    pub(crate) fn clamped(&self, x: f64, h_new: f64, h: f64, max_step: f64) {
+        if h != h_new {
+            self.log(LogLevel::Warn, x, h_new, || {
+                format!("step clamped to the maximum step size {:e}", max_step)
+            });
+        }
+    }
+
+    /// Logs a nearly singular factorization `lu` of the iteration matrix of an implicit solver
+    /// for the step `h` at `x`, see [`Factorization::nearly_singular`].
+    pub(crate) fn factorization<T, D>(&self, x: f64, h: f64, lu: &Factorization<T, D>)
+    where
+        T: RealField + Copy,
+        D: Dim + DimMin<D, Output = D>,
+        DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+    {
+        if self.enabled() && lu.nearly_singular() {
+            self.log(LogLevel::Warn, x, h, || {
+                "nearly singular iteration matrix".to_string()
+            });
+        }
+    }
+
+    /// Logs the error of `result`, if any, for the step `h` at `x`, and returns `result`.
+    pub(crate) fn result<R>(
+        &self,
+        x: f64,
+        h: f64,
+        result: Result<R, IntegrationError>,
+    ) -> Result<R, IntegrationError> {
+        if let Err(e) = &result {
+            self.log(LogLevel::Error, x, h, || e.to_string());
+        }
+        result
+    }
+}
+
+/// Diagnostic of a step attempted by an adaptive solver, recorded once enabled with the
+/// `record_diagnostics` setter of the solver.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepDiagnostic {
+    /// Value of the independent variable at the start of the step.
+    pub x: f64,
+    /// Size of the attempted step.
+    pub h: f64,
+    /// Norm of the local error estimate, at most 1 when the step satisfies the tolerances.
+    /// Infinite if the step failed before the error could be estimated, e.g. because the Newton
+    /// iteration of an implicit solver did not converge.
+    pub err: f64,
+    /// Whether the step was accepted.
+    pub accepted: bool,
+}
+
+/// Appends the diagnostic of a step attempted at `x` to `diagnostics` if they are recorded.
+pub(crate) fn record_diagnostic(
+    diagnostics: &mut Option<Vec<StepDiagnostic>>,
+    x: f64,
+    h: f64,
+    err: f64,
+    accepted: bool,
+) {
+    if let Some(diagnostics) = diagnostics {
+        diagnostics.push(StepDiagnostic {
+            x,
+            h,
+            err,
+            accepted,
+        });
+    }
+}
+
+/// Observed order `q` of the local error estimate of an adaptive solver, whose norm behaves as
+/// `C |h|^(q + 1)` for a method of nominal order `q`, estimated from the `diagnostics` of the
+/// attempted steps. Each step retried from the same point after a rejection, where `C` is
+/// unchanged, gives the order from the ratio of the errors and of the step sizes, and the median
+/// of these estimates is returned. An order well below the nominal one reveals that the method
+/// lost accuracy, e.g. on a right-hand side which is not smooth. Returns `None` without any
+/// rejected step.
+pub fn observed_order(diagnostics: &[StepDiagnostic]) -> Option<f64> {
+    let mut orders: Vec<f64> = diagnostics
+        .windows(2)
+        .filter_map(|w| {
+            let (rejected, retried) = (&w[0], &w[1]);
+            let log_ratio = (rejected.h / retried.h).abs().ln();
+            let valid = !rejected.accepted
+                && rejected.x == retried.x
+                && rejected.err.is_finite()
+                && retried.err.is_finite()
+                && retried.err > 0.0
+                && log_ratio.abs() > 0.05;
+            valid.then(|| (rejected.err / retried.err).ln() / log_ratio - 1.0)
+        })
+        .collect();
+    if orders.is_empty() {
+        return None;
+    }
+    orders.sort_by(|a, b| a.total_cmp(b));
+    Some(orders[orders.len() / 2])
+}
+
+/// Returns true if the order observed from the recorded `diagnostics` falls below half the
+/// nominal `order` of the method.
+pub(crate) fn order_reduced(diagnostics: &Option<Vec<StepDiagnostic>>, order: u32) -> bool {
+    diagnostics
+        .as_deref()
+        .and_then(observed_order)
+        .is_some_and(|q| q < 0.5 * order as f64)
+}
+
+/// Continuous extension of a solution computed by a solver with dense output, which can be
+/// evaluated independently of the solver. Obtained from `continuous_solution` after the
+/// integration.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ContinuousSolution<V> {
+    x: Vec<f64>,
+    coefficients: Vec<Vec<V>>,
+}
+
+impl<T, D: Dim> ContinuousSolution<OVector<T, D>>
+where
+    T: Scalar + ClosedAdd + ClosedMul,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Builds the continuous extension from the accepted steps `x` and the coefficients of the
+    /// dense output over each step, see [`dense_polynomial`]. A single point `x = [x0]` is
+    /// represented by the coefficients `[[y0]]`.
+    pub(crate) fn new(x: Vec<f64>, coefficients: Vec<Vec<OVector<T, D>>>) -> Self {
+        if x.len() == 1 {
+            return ContinuousSolution {
+                x: vec![x[0], x[0]],
+                coefficients,
+            };
+        }
+        ContinuousSolution { x, coefficients }
+    }
+
+    /// Evaluates the solution at `x`. Returns `None` if `x` lies outside of the integration
+    /// interval.
+    pub fn eval(&self, x: f64) -> Option<OVector<T, D>> {
+        let i = find_segment(&self.x, x)?;
+        Some(self.eval_segment(i, x))
+    }
+
+    /// Evaluates the solution at every value of `xs`, `None` marking the values outside of the
+    /// integration interval. The values sorted in the direction of integration are located in
+    /// linear time overall, by walking the steps forward from the previous value, while the
+    /// others fall back to a binary search.
+    pub fn eval_many(&self, xs: &[f64]) -> Vec<Option<OVector<T, D>>> {
+        let n = self.x.len();
+        if n < 2 {
+            return vec![None; xs.len()];
+        }
+        let posneg = (self.x[n - 1] - self.x[0]).signum();
+        let mut i = 0;
+        xs.iter()
+            .map(|x| {
+                if (x - self.x[i]) * posneg >= 0.0 {
+                    while i + 2 < n && (x - self.x[i + 1]) * posneg > 0.0 {
+                        i += 1;
+                    }
+                    if (x - self.x[i + 1]) * posneg > 0.0 {
+                        return None;
+                    }
+                } else {
+                    i = find_segment(&self.x, *x)?;
+                }
+                Some(self.eval_segment(i, *x))
+            })
+            .collect()
+    }
+
+    /// Range `(x_first, x_last)` of the independent variable covered by the solution.
+    pub fn x_range(&self) -> (f64, f64) {
+        (
+            self.x.first().copied().unwrap_or(f64::NAN),
+            self.x.last().copied().unwrap_or(f64::NAN),
+        )
+    }
+
+    /// Samples the solution finely enough that the change of each component between consecutive
+    /// points stays below `rtol * max(|y_a|, |y_b|) + atol`, see [`refine_output`].
+    pub fn refine(&self, rtol: f64, atol: f64) -> (Vec<f64>, Vec<OVector<T, D>>)
+    where
+        f64: From<T>,
+    {
+        let n = if self.x[0] == self.x[1] {
+            1
+        } else {
+            self.x.len()
+        };
+        refine_output(&self.x[..n], |i, x| self.eval_segment(i, x), rtol, atol)
+    }
+
+    /// Evaluates the dense output over the step `i` at `x`.
+    fn eval_segment(&self, i: usize, x: f64) -> OVector<T, D> {
+        let h = self.x[i + 1] - self.x[i];
+        if h == 0.0 {
+            return self.coefficients[i][0].clone();
+        }
+        dense_polynomial(&self.coefficients[i], (x - self.x[i]) / h)
+    }
+}
+
+/// Maximum depth of the bisection of a step by [`refine_output`].
+const REFINE_MAX_DEPTH: u32 = 30;
+
+/// Samples a solution made of the steps between the points `x`, evaluated over the step `i` by
+/// `eval(i, x)`, so that the change of each component between consecutive output points stays
+/// below `rtol * max(|y_a|, |y_b|) + atol`, where `y_a` and `y_b` are the values at the points.
+/// The steps are bisected until both the change over a subinterval and the deviation of the
+/// solution at its middle from the chord satisfy the bound, the latter catching the extrema
+/// inside a subinterval, so that the linear interpolation of the output stays within the bound of
+/// the solution. The points of the steps are kept, every step being bisected at most 30 times.
+pub(crate) fn refine_output<T, D: Dim>(
+    x: &[f64],
+    eval: impl Fn(usize, f64) -> OVector<T, D>,
+    rtol: f64,
+    atol: f64,
+) -> (Vec<f64>, Vec<OVector<T, D>>)
+where
+    f64: From<T>,
+    T: Scalar,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let exceeds = |a: &OVector<T, D>, b: &OVector<T, D>, c: &OVector<T, D>| {
+        (0..a.len()).any(|j| {
+            let value = |v: &OVector<T, D>| f64::from(v[j].clone());
+            let (a, b, c) = (value(a), value(b), value(c));
+            let bound = rtol * a.abs().max(c.abs()) + atol;
+            (c - a).abs() > bound || (b - 0.5 * (a + c)).abs() > bound
+        })
+    };
+    let (mut x_out, mut y_out) = (Vec::new(), Vec::new());
+    let Some(&x0) = x.first() else {
+        return (x_out, y_out);
+    };
+    x_out.push(x0);
+    y_out.push(eval(0, x0));
+    for i in 0..x.len().saturating_sub(1) {
+        // Subintervals still to be processed, the leftmost one on top
+        let mut pending = vec![(x[i + 1], eval(i, x[i + 1]), 0)];
+        while let Some((x_b, y_b, depth)) = pending.pop() {
+            let x_a = x_out[x_out.len() - 1];
+            let y_a = &y_out[y_out.len() - 1];
+            let x_mid = 0.5 * (x_a + x_b);
+            let y_mid = eval(i, x_mid);
+            if depth < REFINE_MAX_DEPTH && exceeds(y_a, &y_mid, &y_b) {
+                pending.push((x_b, y_b, depth + 1));
+                pending.push((x_mid, y_mid, depth + 1));
+            } else {
+                x_out.push(x_b);
+                y_out.push(y_b);
+            }
+        }
+    }
+    (x_out, y_out)
+}
+
+/// Evaluates the dense output `r0 + theta (r1 + (1 - theta) (r2 + theta (r3 + ...)))` of the
+/// Dormand-Prince solvers over a step at the relative position `theta`.
+pub(crate) fn dense_polynomial<T, D: Dim>(r: &[OVector<T, D>], theta: f64) -> OVector<T, D>
+where
+    T: Scalar + ClosedAdd + ClosedMul,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let theta1 = 1.0 - theta;
+    let last = r.len() - 1;
+    (0..last).rev().fold(r[last].clone(), |p, j| {
+        &r[j] + p * if j % 2 == 0 { theta } else { theta1 }
+    })
+}
+
+/// Distance into a step, relative to the step size, at which the Dormand-Prince solvers evaluate
+/// the event indicators left at zero by an event at the start of the step.
+const EVENT_OFFSET: f64 = 1.0e-6;
+
+/// Finds the earliest zero crossing of the event indicators of `f` over a step of size `h` from
+/// `x` of the Dormand-Prince solvers, see [`locate_event`], bisecting on the dense output with
+/// coefficients `r`. The indicators left at zero by an event at `x` take their values a short
+/// distance into the step, so that the event may fire again within the step, e.g. when a bouncing
+/// ball lands again, but not at its start.
+pub(crate) fn locate_dense_event<T, D: Dim>(
+    f: &impl System<OVector<T, D>>,
+    x: f64,
+    h: f64,
+    g0: &[f64],
+    g1: &[f64],
+    r: &[OVector<T, D>],
+) -> Option<(usize, f64)>
+where
+    T: Scalar + ClosedAdd + ClosedMul,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let mut g0 = g0.to_vec();
+    if g0.contains(&0.0) {
+        let dx = EVENT_OFFSET * h;
+        let g_offset = event_values(f, x + dx, &dense_polynomial(r, EVENT_OFFSET));
+        for (g0_i, g_i) in g0.iter_mut().zip(g_offset) {
+            if *g0_i == 0.0 {
+                *g0_i = g_i;
+            }
+        }
+    }
+    locate_event(
+        f,
+        x,
+        h,
+        &g0,
+        g1,
+        EVENT_TOL * h.abs(),
+        |dx| dense_polynomial(r, dx / h),
+        |_, _| false,
+    )
+}
+
+/// Contains some statistics of the integration, read with the accessors. The statistics are only
+/// updated by the solvers.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Stats {
+    pub(crate) num_eval: u32,
+    pub(crate) accepted_steps: u32,
+    pub(crate) rejected_steps: u32,
+    pub(crate) num_jac_eval: u32,
+    pub(crate) num_newton_iter: u32,
+    pub(crate) min_step: f64,
+    pub(crate) max_step: f64,
+    pub(crate) num_switches: u32,
+    pub(crate) terminal_event: Option<usize>,
+    pub(crate) order_reduced: bool,
+    pub(crate) jitter_seed: Option<u64>,
+}
+
+impl Stats {
+    pub(crate) fn new() -> Stats {
+        Stats {
+            num_eval: 0,
+            accepted_steps: 0,
+            rejected_steps: 0,
+            num_jac_eval: 0,
+            num_newton_iter: 0,
+            min_step: 0.0,
+            max_step: 0.0,
+            num_switches: 0,
+            terminal_event: None,
+            order_reduced: false,
+            jitter_seed: None,
+        }
+    }
+
+    /// Number of evaluations of the right-hand side.
+    pub fn num_eval(&self) -> u32 {
+        self.num_eval
+    }
+
+    /// Number of accepted steps.
+    pub fn accepted_steps(&self) -> u32 {
+        self.accepted_steps
+    }
+
+    /// Number of rejected steps. Only recorded by the adaptive solvers.
+    pub fn rejected_steps(&self) -> u32 {
+        self.rejected_steps
+    }
+
+    /// Number of evaluations of the Jacobian.
+    pub fn num_jac_eval(&self) -> u32 {
+        self.num_jac_eval
+    }
+
+    /// Number of Newton iterations. Only recorded by the implicit solvers.
+    pub fn num_newton_iter(&self) -> u32 {
+        self.num_newton_iter
+    }
+
+    /// Smallest accepted step size. Only recorded by the adaptive solvers.
+    pub fn min_step(&self) -> f64 {
+        self.min_step
+    }
+
+    /// Largest accepted step size. Only recorded by the adaptive solvers.
+    pub fn max_step(&self) -> f64 {
+        self.max_step
+    }
+
+    /// Number of switches between the explicit and the implicit method. Only recorded by the
+    /// `Auto` solver.
+    pub fn num_switches(&self) -> u32 {
+        self.num_switches
+    }
+
+    /// Index of the event which terminated the integration, if any, i.e. the event for which
+    /// `System::on_event` returned `EventAction::Terminate`.
+    pub fn terminal_event(&self) -> Option<usize> {
+        self.terminal_event
+    }
+
+    /// Whether the order observed from the error estimates fell well below the nominal order of
+    /// the method, see [`observed_order`]. Only checked by the adaptive solvers recording their
+    /// diagnostics.
+    pub fn order_reduced(&self) -> bool {
+        self.order_reduced
+    }
+
+    /// Seed of the random step size jitter enabled with `Dopri5::jitter` or `Dopri5::jitter_with`,
+    /// `None` if the steps were not jittered. Integrating again with the same seed and settings
+    /// reproduces the run.
+    pub fn jitter_seed(&self) -> Option<u64> {
+        self.jitter_seed
+    }
+
+    /// Updates the extreme step sizes with the size of an accepted step.
+    pub(crate) fn record_step(&mut self, h: f64) {
+        let h = h.abs();
+        if self.min_step == 0.0 || h < self.min_step {
+            self.min_step = h;
+        }
+        self.max_step = self.max_step.max(h);
+    }
+
+    /// Adds the statistics of another part of the integration, e.g. of a chunk integrated with
+    /// `integrate_to` or of a run restarted from a checkpoint. The counters are summed, the
+    /// extreme step sizes are the extremes over both parts, the terminal event and the jitter seed
+    /// are the ones of `other` if it has any and the order is reduced if it is in either part.
+    pub fn merge(&mut self, other: &Stats) {
+        self.num_eval += other.num_eval;
+        self.accepted_steps += other.accepted_steps;
+        self.rejected_steps += other.rejected_steps;
+        self.num_jac_eval += other.num_jac_eval;
+        self.num_newton_iter += other.num_newton_iter;
+        if other.min_step > 0.0 {
+            self.record_step(other.min_step);
+        }
+        if other.max_step > 0.0 {
+            self.record_step(other.max_step);
+        }
+        self.num_switches += other.num_switches;
+        if other.terminal_event.is_some() {
+            self.terminal_event = other.terminal_event;
+        }
+        self.order_reduced |= other.order_reduced;
+        if other.jitter_seed.is_some() {
+            self.jitter_seed = other.jitter_seed;
+        }
+    }
+
+    /// Prints some statistics related to the integration process.
+    #[deprecated(since = "0.2.0", note = "Use std::fmt::Display instead")]
+    pub fn print(&self) {
+        println!("{}", self);
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats::new()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "function evaluations: {}, accepted steps: {}, rejected steps: {}, \
+             Jacobian evaluations: {}, Newton iterations: {}, min step: {:e}, max step: {:e}, solver switches: {}",
+            self.num_eval,
+            self.accepted_steps,
+            self.rejected_steps,
+            self.num_jac_eval,
+            self.num_newton_iter,
+            self.min_step,
+            self.max_step,
+            self.num_switches
+        )
+    }
+}
+
+/// Writes the trajectory (`x_out`, `y_out`) to `writer` as CSV, with a header `t,y0,y1,...`
+/// followed by one row per stored point. An empty trajectory produces the header `t` only.
+pub fn write_csv<W, T, D: Dim>(
+    mut writer: W,
+    x_out: &[f64],
+    y_out: &[OVector<T, D>],
+) -> io::Result<()>
+where
+    W: Write,
+    T: Scalar + fmt::Display,
+    DefaultAllocator: Allocator<T, D>,
+{
+    write_csv_header(&mut writer, y_out.first().map_or(0, |y| y.len()))?;
+    for (x, y) in x_out.iter().zip(y_out.iter()) {
+        write_csv_row(&mut writer, *x, y)?;
+    }
+    Ok(())
+}
+
+/// Writes the CSV header `t,y0,y1,...` of a trajectory of dimension `dim`.
+pub(crate) fn write_csv_header(writer: &mut dyn Write, dim: usize) -> io::Result<()> {
+    write!(writer, "t")?;
+    for i in 0..dim {
+        write!(writer, ",y{}", i)?;
+    }
+    writeln!(writer)
+}
+
+/// Writes the CSV row of the point `(x, y)` of a trajectory.
+pub(crate) fn write_csv_row<T, D: Dim>(
+    writer: &mut dyn Write,
+    x: f64,
+    y: &OVector<T, D>,
+) -> io::Result<()>
+where
+    T: Scalar + fmt::Display,
+    DefaultAllocator: Allocator<T, D>,
+{
+    write!(writer, "{}", x)?;
+    for y_i in y.iter() {
+        write!(writer, ",{}", y_i)?;
+    }
+    writeln!(writer)
+}
+
+/// Storage receiving the stored states of a solver in order, in place of its output, e.g. to
+/// write them to disk or to store the values of each component contiguously.
+///
+/// A sink owned by the solver can be shared with the caller, who keeps access to it during and
+/// after the integration, by wrapping it in an `Rc<RefCell<_>>`:
+///
+/// ```
+/// # use ode_event_solvers::{dop_shared::ode_fn, Euler, SoaTrajectory, Vector2};
+/// # use std::{cell::RefCell, rc::Rc};
+/// # let f = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+/// #     *dy = Vector2::new(y[1], -y[0]);
+/// # });
+/// # let mut solver = Euler::new(f, 0.0, Vector2::new(1.0, 0.0), 1.0, vec![1e-3, 1e-2, 0.1]);
+/// let trajectory = Rc::new(RefCell::new(SoaTrajectory::new()));
+/// solver.store_to(trajectory.clone());
+/// solver.integrate()?;
+/// let positions = trajectory.borrow().component(0).to_vec();
+/// # assert_eq!(positions.len(), 11);
+/// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+/// ```
+pub trait TrajectorySink<V> {
+    /// Stores the state `y` at `x`.
+    fn push(&mut self, x: f64, y: &V) -> io::Result<()>;
+    /// Completes the pending writes (optional), called at the end of every call to the
+    /// integration. Defaults to doing nothing.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<V, S: TrajectorySink<V>> TrajectorySink<V> for Rc<RefCell<S>> {
+    fn push(&mut self, x: f64, y: &V) -> io::Result<()> {
+        self.borrow_mut().push(x, y)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.borrow_mut().flush()
+    }
+}
+
+/// Trajectory stored one state per entry, as in the output of the solvers.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VecTrajectory<V> {
+    /// Values of the independent variable.
+    pub x: Vec<f64>,
+    /// States at each of the values of the independent variable.
+    pub y: Vec<V>,
+}
+
+impl<V: Clone> TrajectorySink<V> for VecTrajectory<V> {
+    fn push(&mut self, x: f64, y: &V) -> io::Result<()> {
+        self.x.push(x);
+        self.y.push(y.clone());
+        Ok(())
+    }
+}
+
+/// Trajectory stored as a structure of arrays, the values of each component of the state being
+/// contiguous, e.g. for a fast access to the time series of a component.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SoaTrajectory {
+    x: Vec<f64>,
+    components: Vec<Vec<f64>>,
+}
+
+impl SoaTrajectory {
+    /// Empty trajectory, whose dimension is set by the first stored state.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of stored states.
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Returns true if no state is stored.
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Dimension of the stored states.
+    pub fn dim(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Values of the independent variable.
+    pub fn x(&self) -> &[f64] {
+        &self.x
+    }
+
+    /// Values of the component `i` of the state at each of the values of the independent
+    /// variable.
+    pub fn component(&self, i: usize) -> &[f64] {
+        &self.components[i]
+    }
+
+    /// State number `k`, gathered from the components.
+    pub fn state(&self, k: usize) -> DVector<f64> {
+        DVector::from_iterator(self.dim(), self.components.iter().map(|c| c[k]))
+    }
+}
+
+impl<T, D: Dim> TrajectorySink<OVector<T, D>> for SoaTrajectory
+where
+    f64: From<T>,
+    T: Scalar + Copy,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn push(&mut self, x: f64, y: &OVector<T, D>) -> io::Result<()> {
+        if self.x.is_empty() {
+            self.components = vec![Vec::new(); y.len()];
+        } else if y.len() != self.components.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "state of dimension {} stored in a trajectory of dimension {}",
+                    y.len(),
+                    self.components.len()
+                ),
+            ));
+        }
+        self.x.push(x);
+        for (c, y_i) in self.components.iter_mut().zip(y.iter()) {
+            c.push(f64::from(*y_i));
+        }
+        Ok(())
+    }
+}
+
+/// Sink writing the states as CSV, with a header `t,y0,y1,...` written before the first state.
+pub(crate) struct CsvSink<W> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        CsvSink {
+            writer,
+            header_written: false,
+        }
+    }
+}
+
+impl<W, T, D: Dim> TrajectorySink<OVector<T, D>> for CsvSink<W>
+where
+    W: Write,
+    T: Scalar + fmt::Display,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn push(&mut self, x: f64, y: &OVector<T, D>) -> io::Result<()> {
+        if !self.header_written {
+            write_csv_header(&mut self.writer, y.len())?;
+            self.header_written = true;
+        }
+        write_csv_row(&mut self.writer, x, y)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Converts a state vector to an `ndarray` vector.
+#[cfg(feature = "ndarray")]
+pub fn to_ndarray<T, D: Dim>(y: &OVector<T, D>) -> ndarray::Array1<f64>
+where
+    f64: From<T>,
+    T: Scalar + Copy,
+    DefaultAllocator: Allocator<T, D>,
+{
+    y.iter().map(|y_i| f64::from(*y_i)).collect()
+}
+
+/// Converts an `ndarray` vector to a state vector, e.g. to set the initial condition.
+#[cfg(feature = "ndarray")]
+pub fn from_ndarray(y: &ndarray::Array1<f64>) -> DVector<f64> {
+    DVector::from_iterator(y.len(), y.iter().copied())
+}
+
+/// Converts the dependent variables' output to an `ndarray` matrix with one row per stored point
+/// and one column per component.
+#[cfg(feature = "ndarray")]
+pub fn to_array2<T, D: Dim>(y_out: &[OVector<T, D>]) -> ndarray::Array2<f64>
+where
+    f64: From<T>,
+    T: Scalar + Copy,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let dim = y_out.first().map_or(0, |y| y.len());
+    ndarray::Array2::from_shape_fn((y_out.len(), dim), |(i, j)| f64::from(y_out[i][j]))
+}
+
+/// Integrates the system from each of the `initial_states`, using `factory` to create the solver
+/// for a given initial state, and returns the results in the same order.
+///
+/// With the `rayon` feature, the integrations are distributed over the rayon thread pool. See
+/// [`solve_ensemble_batched`] to evaluate the right-hand side of all the members at once.
+#[cfg(not(feature = "rayon"))]
+pub fn solve_ensemble<V, S, G>(
+    factory: G,
+    initial_states: Vec<V>,
+) -> Vec<Result<IntegrationResult<V>, IntegrationError>>
+where
+    V: Clone,
+    S: OdeSolver<V>,
+    G: Fn(V) -> S,
+{
+    initial_states
+        .into_iter()
+        .map(|y0| solve_one(factory(y0)))
+        .collect()
+}
+
+/// Integrates the system from each of the `initial_states`, using `factory` to create the solver
+/// for a given initial state, and returns the results in the same order.
+///
+/// With the `rayon` feature, the integrations are distributed over the rayon thread pool. See
+/// [`solve_ensemble_batched`] to evaluate the right-hand side of all the members at once.
+#[cfg(feature = "rayon")]
+pub fn solve_ensemble<V, S, G>(
+    factory: G,
+    initial_states: Vec<V>,
+) -> Vec<Result<IntegrationResult<V>, IntegrationError>>
+where
+    V: Clone + Send,
+    S: OdeSolver<V>,
+    G: Fn(V) -> S + Sync,
+{
+    use rayon::prelude::*;
+
+    initial_states
+        .into_par_iter()
+        .map(|y0| solve_one(factory(y0)))
+        .collect()
+}
+
+/// Integrates the system from the initial state `base_y` perturbed by each of the
+/// `perturbations`, i.e. from `base_y + perturbations[i]`, using `factory` to create the solver
+/// for a given initial state, and returns the results aligned with the perturbations, e.g. to
+/// compare the final states for a robustness analysis.
+///
+/// The integrations are run by [`solve_ensemble`], in parallel with the `rayon` feature.
+#[cfg(not(feature = "rayon"))]
+pub fn sweep_initial<V, S, G>(
+    base_y: &V,
+    perturbations: &[V],
+    factory: G,
+) -> Vec<Result<IntegrationResult<V>, IntegrationError>>
+where
+    V: Clone,
+    for<'a> &'a V: std::ops::Add<&'a V, Output = V>,
+    S: OdeSolver<V>,
+    G: Fn(V) -> S,
+{
+    solve_ensemble(
+        factory,
+        perturbations.iter().map(|dy| base_y + dy).collect(),
+    )
+}
+
+/// Integrates the system from the initial state `base_y` perturbed by each of the
+/// `perturbations`, i.e. from `base_y + perturbations[i]`, using `factory` to create the solver
+/// for a given initial state, and returns the results aligned with the perturbations, e.g. to
+/// compare the final states for a robustness analysis.
+///
+/// The integrations are run by [`solve_ensemble`], in parallel with the `rayon` feature.
+#[cfg(feature = "rayon")]
+pub fn sweep_initial<V, S, G>(
+    base_y: &V,
+    perturbations: &[V],
+    factory: G,
+) -> Vec<Result<IntegrationResult<V>, IntegrationError>>
+where
+    V: Clone + Send,
+    for<'a> &'a V: std::ops::Add<&'a V, Output = V>,
+    S: OdeSolver<V>,
+    G: Fn(V) -> S + Sync,
+{
+    solve_ensemble(
+        factory,
+        perturbations.iter().map(|dy| base_y + dy).collect(),
+    )
+}
+
+/// Integrates the system from each of the `initial_states` in lockstep with the classical fourth
+/// order Runge-Kutta method and the fixed step size `h`, evaluating the right-hand side of all the
+/// members at once with `System::ode_batch` at every stage, and returns the results in the same
+/// order. The last step is shortened so that the integration ends exactly at `x_end`, and
+/// `System::observer` and `System::solout` are not called. Returns
+/// `IntegrationError::InvalidStepConfig` if `h` is not finite and strictly positive.
+pub fn solve_ensemble_batched<T, D: Dim, F>(
+    f: &F,
+    x: f64,
+    x_end: f64,
+    h: f64,
+    initial_states: Vec<OVector<T, D>>,
+) -> Result<Vec<IntegrationResult<OVector<T, D>>>, IntegrationError>
+where
+    T: Scalar + ClosedAdd + ClosedMul + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    validate_step_size(h)?;
+    let mut results: Vec<_> = initial_states
+        .iter()
+        .map(|y0| IntegrationResult {
+            x: vec![x],
+            y: vec![y0.clone()],
+            stats: Stats::new(),
+        })
+        .collect();
+    let Some((rows, cols)) = initial_states.first().map(|y0| y0.shape_generic()) else {
+        return Ok(results);
+    };
+    let m = initial_states.len();
+    let direction = (x_end - x).signum();
+    let mut ys = initial_states;
+    let mut k = vec![vec![OVector::zeros_generic(rows, cols); m]; 4];
+    let mut x_i = x;
+    let num_steps = step_count((x_end - x).abs(), h);
+    for i in 0..num_steps {
+        // Shorten the last step so that the integration ends exactly at x_end
+        let last = i + 1 == num_steps;
+        let h = if last { x_end - x_i } else { direction * h };
+        f.ode_batch(x_i, &ys, &mut k[0]);
+        for (stage, c) in [(1, 0.5), (2, 0.5), (3, 1.0)] {
+            let y_stage: Vec<_> = ys
+                .iter()
+                .zip(k[stage - 1].iter())
+                .map(|(y, k_j)| y + k_j.clone() * (c * h))
+                .collect();
+            f.ode_batch(x_i + c * h, &y_stage, &mut k[stage]);
+        }
+        check_eval(f)?;
+        for (j, y) in ys.iter_mut().enumerate() {
+            let dy = k[0][j].clone() + k[1][j].clone() * 2.0 + k[2][j].clone() * 2.0 + &k[3][j];
+            *y = y.clone() + dy * (h / 6.0);
+        }
+        x_i = if last { x_end } else { x_i + h };
+        for (result, y) in results.iter_mut().zip(ys.iter()) {
+            result.x.push(x_i);
+            result.y.push(y.clone());
+            result.stats.num_eval += 4;
+            result.stats.accepted_steps += 1;
+        }
+    }
+    Ok(results)
+}
+
+/// Runs `solver` and collects its output.
+pub(crate) fn solve_one<V, S>(mut solver: S) -> Result<IntegrationResult<V>, IntegrationError>
+where
+    V: Clone,
+    S: OdeSolver<V>,
+{
+    let stats = solver.integrate()?;
+    Ok(IntegrationResult {
+        x: solver.x_out().to_vec(),
+        y: solver.y_out().to_vec(),
+        stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use nalgebra::DMatrix;
+    use std::cell::Cell;
+
+    /// Tank draining at a unit rate, whose level cannot be evaluated once it is negative.
+    struct Tank;
+
+    impl FallibleSystem<Vector1<f64>> for Tank {
+        fn try_ode(
+            &self,
+            _x: f64,
+            y: &Vector1<f64>,
+            dy: &mut Vector1<f64>,
+        ) -> Result<(), EvalError> {
+            if y[0] < 0.0 {
+                return Err(EvalError::new("negative level"));
+            }
+            dy[0] = -1.0;
+            Ok(())
+        }
+    }
+
+    fn assert_stops_when_empty(mut solver: impl OdeSolver<Vector1<f64>>) {
+        let x = match solver.integrate() {
+            Err(IntegrationError::EvalFailed { x, reason }) => {
+                assert_eq!(reason, "negative level");
+                x
+            }
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert!(x > 1.0 - 1e-9 && x <= 2.0, "{}", x);
+        // The trajectory computed before the failure is kept
+        assert!(solver.x_out().len() > 1);
+        assert!(solver.x_out().iter().all(|x_i| *x_i <= x));
+        assert!(solver.x_final() <= x);
+    }
+
+    #[test]
+    fn fallible_systems_stop_every_solver() {
+        let y0 = Vector1::new(1.0);
+        let tank = || fallible(Tank);
+        assert_stops_when_empty(Euler::new(tank(), 0.0, y0, 2.0, vec![0.1; 3]));
+        assert_stops_when_empty(RungeKutta4::new(tank(), 0.0, y0, 2.0, 0.1));
+        assert_stops_when_empty(Heun::new(tank(), 0.0, y0, 2.0, 0.1));
+        assert_stops_when_empty(Midpoint::new(tank(), 0.0, y0, 2.0, 0.1));
+        assert_stops_when_empty(Ralston2::new(tank(), 0.0, y0, 2.0, 0.1));
+        assert_stops_when_empty(SspRk3::new(tank(), 0.0, y0, 2.0, 0.1));
+        assert_stops_when_empty(AdamsBashforthMoulton::new(tank(), 0.0, y0, 2.0, 0.1, 4));
+        assert_stops_when_empty(ImplicitEuler::new(tank(), 0.0, y0, 2.0, 0.1, 1e-10, 10));
+        assert_stops_when_empty(CrankNicolson::new(tank(), 0.0, y0, 2.0, 0.1, 1e-10, 10));
+        assert_stops_when_empty(ImplicitRungeKutta::new(
+            tank(),
+            0.0,
+            y0,
+            2.0,
+            0.1,
+            ImplicitButcherTableau::radau_iia3_embedded(),
+        ));
+        assert_stops_when_empty(ExplicitRungeKutta::new(
+            tank(),
+            0.0,
+            y0,
+            2.0,
+            0.1,
+            ButcherTableau::rk4(),
+        ));
+        assert_stops_when_empty(Dopri5::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+        assert_stops_when_empty(Dop853::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+        assert_stops_when_empty(BogackiShampine23::new(
+            tank(),
+            0.0,
+            y0,
+            2.0,
+            None,
+            1e-6,
+            1e-6,
+        ));
+        assert_stops_when_empty(CashKarp45::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+        assert_stops_when_empty(BulirschStoer::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+        assert_stops_when_empty(Rosenbrock4::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+        assert_stops_when_empty(Bdf::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+        assert_stops_when_empty(Auto::new(tank(), 0.0, y0, 2.0, None, 1e-6, 1e-6));
+    }
+
+    fn assert_single_point(mut solver: impl OdeSolver<Vector1<f64>>) {
+        let stats = solver.integrate().unwrap();
+        assert_eq!(stats.num_eval, 0);
+        assert_eq!(solver.x_out(), &[1.0]);
+        assert_eq!(solver.y_out(), &[Vector1::new(1.0)]);
+        assert_eq!(solver.x_final(), 1.0);
+    }
+
+    #[test]
+    fn empty_interval_gives_the_initial_point_alone() {
+        let y0 = Vector1::new(1.0);
+        let f = || fallible(Tank);
+        assert_single_point(Euler::new(f(), 1.0, y0, 1.0, vec![0.1; 3]));
+        assert_single_point(RungeKutta4::new(f(), 1.0, y0, 1.0, 0.1));
+        assert_single_point(Heun::new(f(), 1.0, y0, 1.0, 0.1));
+        assert_single_point(Midpoint::new(f(), 1.0, y0, 1.0, 0.1));
+        assert_single_point(Ralston2::new(f(), 1.0, y0, 1.0, 0.1));
+        assert_single_point(SspRk3::new(f(), 1.0, y0, 1.0, 0.1));
+        assert_single_point(AdamsBashforthMoulton::new(f(), 1.0, y0, 1.0, 0.1, 4));
+        assert_single_point(ImplicitEuler::new(f(), 1.0, y0, 1.0, 0.1, 1e-10, 10));
+        assert_single_point(CrankNicolson::new(f(), 1.0, y0, 1.0, 0.1, 1e-10, 10));
+        assert_single_point(ImplicitRungeKutta::new(
+            f(),
+            1.0,
+            y0,
+            1.0,
+            0.1,
+            ImplicitButcherTableau::radau_iia3_embedded(),
+        ));
+        assert_single_point(ExplicitRungeKutta::new(
+            f(),
+            1.0,
+            y0,
+            1.0,
+            0.1,
+            ButcherTableau::rk4(),
+        ));
+        assert_single_point(Dopri5::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(Dop853::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(BogackiShampine23::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(CashKarp45::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(BulirschStoer::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(Rosenbrock4::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(Bdf::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+        assert_single_point(Auto::new(f(), 1.0, y0, 1.0, None, 1e-6, 1e-6));
+    }
+
+    /// Rotation `y' = w x y` of a unit vector, renormalized after each step if `normalize` is set.
+    struct Rotation {
+        normalize: bool,
+    }
+
+    impl System<Vector3<f64>> for Rotation {
+        fn ode(&self, _x: f64, y: &Vector3<f64>, dy: &mut Vector3<f64>) {
+            *dy = Vector3::new(1.0, -2.0, 0.5).cross(y);
+        }
+
+        fn project(&self, _x: f64, y: &mut Vector3<f64>) {
+            if self.normalize {
+                y.normalize_mut();
+            }
+        }
+    }
+
+    /// Largest deviation from a unit norm of the states stored by the solver.
+    fn norm_drift(mut solver: impl OdeSolver<Vector3<f64>>) -> f64 {
+        solver.integrate().unwrap();
+        solver
+            .y_out()
+            .iter()
+            .fold(0.0, |acc: f64, y| acc.max((y.norm() - 1.0).abs()))
+    }
+
+    #[test]
+    fn projection_keeps_the_state_on_the_unit_sphere() {
+        let y0 = Vector3::new(0.0, 0.6, 0.8);
+        for normalize in [false, true] {
+            let f = || Rotation { normalize };
+            let drifts = [
+                norm_drift(Euler::new(f(), 0.0, y0, 50.0, vec![0.01; 3])),
+                norm_drift(Heun::new(f(), 0.0, y0, 50.0, 0.01)),
+                norm_drift(RungeKutta4::new(f(), 0.0, y0, 50.0, 0.05)),
+                norm_drift(ImplicitEuler::new(f(), 0.0, y0, 50.0, 0.01, 1e-12, 10)),
+                norm_drift(Dopri5::new(f(), 0.0, y0, 50.0, None, 1e-6, 1e-6)),
+            ];
+            for drift in drifts {
+                if normalize {
+                    assert!(drift < 4.0 * f64::EPSILON, "{}", drift);
+                } else {
+                    assert!(drift > 1e-6, "{}", drift);
+                }
+            }
+        }
+    }
+
+    /// Forced oscillator `y0' = y1`, `y1' = sin(x) - y0`.
+    struct Forced;
+
+    impl System<Vector2<f64>> for Forced {
+        fn ode(&self, x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+            dy[0] = y[1];
+            dy[1] = x.sin() - y[0];
+        }
+    }
+
+    /// Checks that the derivatives stored by a solver are those of `Forced` at the stored states.
+    fn assert_derivatives_match(x_out: &[f64], y_out: &[Vector2<f64>], dy_out: &[Vector2<f64>]) {
+        assert!(x_out.len() > 2);
+        assert_eq!(dy_out.len(), y_out.len());
+        for ((x, y), dy) in x_out.iter().zip(y_out).zip(dy_out) {
+            let mut expected = Vector2::zeros();
+            Forced.ode(*x, y, &mut expected);
+            assert!((dy - expected).amax() < 1e-12, "{}: {} {}", x, dy, expected);
+        }
+    }
+
+    #[test]
+    fn stored_derivatives_match_the_stored_states() {
+        let y0 = Vector2::new(1.0, 0.0);
+        let mut euler = Euler::new(Forced, 0.0, y0, 5.0, vec![1e-3, 1e-2, 0.1]);
+        euler.store_derivatives(true);
+        euler.integrate().unwrap();
+        assert_derivatives_match(euler.x_out(), euler.y_out(), euler.dy_out());
+
+        let mut rk4 = RungeKutta4::new(Forced, 0.0, y0, 5.0, 0.1);
+        rk4.integrate().unwrap();
+        assert_derivatives_match(rk4.x_out(), rk4.y_out(), rk4.dy_out());
+
+        let mut adams = AdamsBashforthMoulton::new(Forced, 0.0, y0, 5.0, 0.1, 4);
+        adams.integrate().unwrap();
+        assert_derivatives_match(adams.x_out(), adams.y_out(), adams.dy_out());
+
+        let mut dopri5 = Dopri5::new(Forced, 0.0, y0, 5.0, None, 1e-8, 1e-8);
+        dopri5.store_derivatives(true);
+        dopri5.integrate().unwrap();
+        assert_derivatives_match(dopri5.x_out(), dopri5.y_out(), dopri5.dy_out());
+
+        let mut dop853 = Dop853::new(Forced, 0.0, y0, 5.0, None, 1e-8, 1e-8);
+        dop853.store_derivatives(true);
+        dop853.integrate().unwrap();
+        assert_derivatives_match(dop853.x_out(), dop853.y_out(), dop853.dy_out());
+
+        let mut rk23 = BogackiShampine23::new(Forced, 0.0, y0, 5.0, None, 1e-6, 1e-6);
+        rk23.integrate().unwrap();
+        assert_derivatives_match(rk23.x_out(), rk23.y_out(), rk23.dy_out());
+
+        let mut bs = BulirschStoer::new(Forced, 0.0, y0, 5.0, None, 1e-8, 1e-8);
+        bs.integrate().unwrap();
+        assert_derivatives_match(bs.x_out(), bs.y_out(), bs.dy_out());
+
+        let mut rosenbrock = Rosenbrock4::new(Forced, 0.0, y0, 5.0, None, 1e-6, 1e-6);
+        rosenbrock.integrate().unwrap();
+        assert_derivatives_match(rosenbrock.x_out(), rosenbrock.y_out(), rosenbrock.dy_out());
+    }
+
+    #[test]
+    fn writes_one_csv_row_per_stored_point() {
+        let oscillator = ode_fn(|_, y: &DVector<f64>, dy: &mut DVector<f64>| {
+            dy[0] = y[1];
+            dy[1] = -y[0];
+        });
+        let mut solver =
+            RungeKutta4::new(oscillator, 0.0, DVector::from_vec(vec![1.0, 0.0]), 1.0, 0.1);
+        solver.integrate().unwrap();
+        let mut csv = Vec::new();
+        write_csv(&mut csv, solver.x_out(), solver.y_out()).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("t,y0,y1"));
+        let rows: Vec<Vec<f64>> = lines
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), solver.x_out().len());
+        for ((row, x), y) in rows.iter().zip(solver.x_out()).zip(solver.y_out()) {
+            assert_eq!(row, &vec![*x, y[0], y[1]]);
+        }
+    }
+
+    #[test]
+    fn writes_the_header_alone_for_an_empty_trajectory() {
+        let mut csv = Vec::new();
+        write_csv::<_, f64, Const<2>>(&mut csv, &[], &[]).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "t\n");
+    }
+
+    /// Linear system `y' = A y` counting the evaluations of its right-hand side.
+    struct Linear {
+        a: nalgebra::Matrix3<f64>,
+        calls: Cell<usize>,
+    }
+
+    impl Linear {
+        fn new() -> Self {
+            Linear {
+                a: nalgebra::Matrix3::new(-2.0, 1.0, 0.0, 0.5, -3.0, 4.0, 0.0, 7.0, -1.0),
+                calls: Cell::new(0),
+            }
+        }
+    }
+
+    impl System<Vector3<f64>> for Linear {
+        fn ode(&self, _x: f64, y: &Vector3<f64>, dy: &mut Vector3<f64>) {
+            self.calls.set(self.calls.get() + 1);
+            *dy = self.a * y;
+        }
+    }
+
+    #[test]
+    fn finite_difference_jacobian_of_a_linear_system() {
+        let f = Linear::new();
+        let y = Vector3::new(1.0, -20.0, 3e3);
+        let mut jac = nalgebra::Matrix3::zeros();
+        finite_diff_jacobian(&f, 0.0, &y, &mut jac);
+        assert_eq!(f.calls.get(), 4);
+        assert!((jac - f.a).abs().max() < 1e-5);
+
+        // Default body of `System::jacobian`
+        let mut default = nalgebra::Matrix3::zeros();
+        f.jacobian(0.0, &y, &mut default);
+        assert_eq!(default, jac);
+
+        let mut scaled = nalgebra::Matrix3::zeros();
+        finite_diff_jacobian_with_scale(&f, 0.0, &y, &mut scaled, 1e-4);
+        assert!((scaled - f.a).abs().max() < 1e-6);
+    }
+
+    /// Heat equation `u_t = u_xx` on `(0, 1)` with homogeneous Dirichlet conditions, discretized
+    /// on `n` interior points, declaring its tridiagonal Jacobian if `banded` is set.
+    struct Heat {
+        n: usize,
+        banded: bool,
+        calls: Cell<usize>,
+    }
+
+    impl Heat {
+        fn new(n: usize, banded: bool) -> Self {
+            Heat {
+                n,
+                banded,
+                calls: Cell::new(0),
+            }
+        }
+
+        /// Initial state `sin(pi x)`, the slowest decaying mode.
+        fn initial_state(&self) -> DVector<f64> {
+            let dx = 1.0 / (self.n + 1) as f64;
+            DVector::from_fn(self.n, |i, _| {
+                (std::f64::consts::PI * (i + 1) as f64 * dx).sin()
+            })
+        }
+
+        /// Eigenvalue of the discrete Laplacian for the initial mode.
+        fn eigenvalue(&self) -> f64 {
+            let m = (self.n + 1) as f64;
+            let s = (std::f64::consts::PI / (2.0 * m)).sin();
+            -4.0 * m * m * s * s
+        }
+    }
+
+    impl System<DVector<f64>> for Heat {
+        fn ode(&self, _x: f64, y: &DVector<f64>, dy: &mut DVector<f64>) {
+            self.calls.set(self.calls.get() + 1);
+            let m2 = ((self.n + 1) * (self.n + 1)) as f64;
+            for i in 0..self.n {
+                let left = if i > 0 { y[i - 1] } else { 0.0 };
+                let right = if i + 1 < self.n { y[i + 1] } else { 0.0 };
+                dy[i] = m2 * (left - 2.0 * y[i] + right);
+            }
+        }
+
+        fn jacobian_bandwidth(&self) -> Option<(usize, usize)> {
+            self.banded.then_some((1, 1))
+        }
+    }
+
+    #[test]
+    fn banded_lu_matches_the_dense_factorization() {
+        let (n, lower, upper) = (9, 2, 1);
+        // Small diagonal entries force row interchanges
+        let m = DMatrix::from_fn(n, n, |i, j| {
+            if j + lower < i || j > i + upper {
+                0.0
+            } else if i == j {
+                1e-3 * (i + 1) as f64
+            } else {
+                1.0 + ((3 * i + 5 * j) % 7) as f64
+            }
+        });
+        let b = DVector::from_fn(n, |i, _| (i as f64).cos());
+        let banded = BandedLu::new(&m, lower, upper).solve(&b).unwrap();
+        let dense = m.clone().lu().solve(&b).unwrap();
+        assert!((&banded - dense).amax() < 1e-10);
+        assert!((&m * banded - &b).amax() < 1e-10);
+        assert!(BandedLu::new(&DMatrix::<f64>::zeros(n, n), lower, upper)
+            .solve(&b)
+            .is_none());
+    }
+
+    #[test]
+    fn banded_jacobian_perturbs_the_components_in_groups() {
+        let f = Heat::new(50, true);
+        let y = f.initial_state();
+        let mut jac = DMatrix::from_element(50, 50, 1.0);
+        f.jacobian(0.0, &y, &mut jac);
+        // One evaluation at `y` and one per group of columns, whatever the dimension
+        assert_eq!(f.calls.get(), 4);
+        let mut dense = DMatrix::zeros(50, 50);
+        finite_diff_jacobian(&Heat::new(50, false), 0.0, &y, &mut dense);
+        assert!((&jac - &dense).amax() < 1e-3 * dense.amax());
+        assert_eq!(jac[(0, 2)], 0.0);
+        assert_eq!(jac[(49, 0)], 0.0);
+    }
+
+    #[test]
+    fn implicit_solvers_factorize_the_band() {
+        // The banded and dense factorizations give the same steps
+        let y0 = Heat::new(60, true).initial_state();
+        let mut banded =
+            ImplicitEuler::new(Heat::new(60, true), 0.0, y0.clone(), 0.1, 0.01, 1e-10, 10);
+        let mut dense = ImplicitEuler::new(Heat::new(60, false), 0.0, y0, 0.1, 0.01, 1e-10, 10);
+        banded.integrate().unwrap();
+        dense.integrate().unwrap();
+        assert!((banded.y_final() - dense.y_final()).amax() < 1e-10);
+
+        // A large system integrates exactly to the decay of the mode by implicit Euler
+        let f = Heat::new(1000, true);
+        let (y0, growth) = (f.initial_state(), 1.0 / (1.0 - 0.01 * f.eigenvalue()));
+        let mut solver = ImplicitEuler::new(f, 0.0, y0.clone(), 0.1, 0.01, 1e-10, 10);
+        solver.integrate().unwrap();
+        let expected = y0 * growth.powi(10);
+        assert!((solver.y_final() - &expected).amax() < 1e-8 * expected.amax());
+    }
+
+    /// Reaction-diffusion `y_i' = y_(i-1) - 2 y_i + y_(i+1) - y_i^2` on a ring of `n` cells,
+    /// declaring the sparsity of its Jacobian.
+    struct Ring {
+        n: usize,
+        calls: Cell<usize>,
+    }
+
+    impl System<DVector<f64>> for Ring {
+        fn ode(&self, _x: f64, y: &DVector<f64>, dy: &mut DVector<f64>) {
+            self.calls.set(self.calls.get() + 1);
+            let n = self.n;
+            for i in 0..n {
+                dy[i] = y[(i + n - 1) % n] - 2.0 * y[i] + y[(i + 1) % n] - y[i] * y[i];
+            }
+        }
+
+        fn jacobian_sparsity(&self) -> Option<Vec<Vec<usize>>> {
+            let n = self.n;
+            Some(
+                (0..n)
+                    .map(|j| vec![(j + n - 1) % n, j, (j + 1) % n])
+                    .collect(),
+            )
+        }
+    }
+
+    #[test]
+    fn colors_structurally_orthogonal_columns() {
+        let tridiagonal: Vec<Vec<usize>> = (0..100)
+            .map(|j: usize| (j.saturating_sub(1)..(j + 2).min(100)).collect())
+            .collect();
+        assert_eq!(color_columns(&tridiagonal).len(), 3);
+
+        let ring = Ring {
+            n: 40,
+            calls: Cell::new(0),
+        };
+        let pattern = ring.jacobian_sparsity().unwrap();
+        let groups = color_columns(&pattern);
+        assert_eq!(groups.len(), 4);
+        for group in &groups {
+            let mut rows: Vec<usize> = group.iter().flat_map(|j| pattern[*j].clone()).collect();
+            let len = rows.len();
+            rows.sort_unstable();
+            rows.dedup();
+            assert_eq!(rows.len(), len);
+        }
+        assert_eq!(groups.iter().map(Vec::len).sum::<usize>(), 40);
+    }
+
+    #[test]
+    fn colored_jacobian_equals_the_dense_one() {
+        let ring = Ring {
+            n: 40,
+            calls: Cell::new(0),
+        };
+        let y = DVector::from_fn(40, |i, _| (0.3 * i as f64).sin());
+        let mut colored = DMatrix::zeros(40, 40);
+        ring.jacobian(0.0, &y, &mut colored);
+        // One evaluation at `y` and one per group of columns
+        assert_eq!(ring.calls.get(), 5);
+
+        let mut dense = DMatrix::zeros(40, 40);
+        finite_diff_jacobian(&ring, 0.0, &y, &mut dense);
+        assert_eq!(ring.calls.get(), 5 + 41);
+        assert_eq!(colored, dense);
+    }
+
+    /// FNV-1a hash of the bits of the points of a trajectory.
+    fn trajectory_hash<'a>(x: &'a [f64], y: impl IntoIterator<Item = &'a f64>) -> u64 {
+        x.iter().chain(y).fold(0xcbf2_9ce4_8422_2325, |hash, v| {
+            v.to_bits().to_le_bytes().iter().fold(hash, |hash, b| {
+                (hash ^ *b as u64).wrapping_mul(0x100_0000_01b3)
+            })
+        })
+    }
+
+    fn bits(y: &[f64]) -> Vec<u64> {
+        y.iter().map(|v| v.to_bits()).collect()
+    }
+
+    #[test]
+    fn trajectories_match_the_golden_references() {
+        // Only arithmetic operations and square roots, which are correctly rounded on every
+        // platform
+        let lorenz = problems::Lorenz::default();
+        let mut rk4 = RungeKutta4::new(lorenz, 0.0, lorenz.initial_state(), 10.0, 0.01);
+        rk4.integrate().unwrap();
+        let hash = trajectory_hash(rk4.x_out(), rk4.y_out().iter().flatten());
+        assert_eq!(hash, 0xf5c8_d035_84f1_5f42);
+        assert_eq!(
+            bits(rk4.y_final().as_slice()),
+            [
+                0xc013_9c7c_b5f3_89ae,
+                0xc00d_f27f_b927_2bcd,
+                0x4038_b11f_70aa_d516
+            ]
+        );
+
+        let vdp = problems::VanDerPol::default();
+        let mut euler = Euler::new(vdp, 0.0, vdp.initial_state(), 10.0, vec![1e-3, 1e-2, 1e-1]);
+        euler.integrate().unwrap();
+        let hash = trajectory_hash(euler.x_out(), euler.y_out().iter().flatten());
+        assert_eq!(hash, 0x17fa_c033_ddbe_500b);
+        assert_eq!(
+            bits(euler.y_final().as_slice()),
+            [0xc000_1547_b2e0_4dc0, 0x3f98_790f_c501_e841]
+        );
+
+        let nbody = problems::NBody::default();
+        let (q0, v0) = (nbody.initial_positions(), nbody.initial_velocities());
+        let mut verlet = VelocityVerlet::new(nbody.clone(), 0.0, q0, v0, nbody.period(), 1e-2);
+        verlet.integrate().unwrap();
+        let states = verlet.q_out().iter().chain(verlet.v_out()).flatten();
+        assert_eq!(
+            trajectory_hash(verlet.x_out(), states),
+            0x40b5_cec0_ea70_8bf4
+        );
+        assert_eq!(
+            bits(verlet.q_out().last().unwrap().as_slice()),
+            [
+                0x3f50_5def_d1a0_252a,
+                0x3ee3_4083_7d2e_6c0f,
+                0,
+                0xbfef_f778_656c_c88f,
+                0xbf82_cd00_683f_59bf,
+                0
+            ]
+        );
+    }
+
+    #[test]
+    fn adaptive_integrations_are_reproducible() {
+        // The step size controllers call `powf`, so that the trajectory is only compared between
+        // runs
+        let run = || {
+            let lorenz = problems::Lorenz::default();
+            let mut solver =
+                Dopri5::new(lorenz, 0.0, lorenz.initial_state(), 10.0, None, 1e-9, 1e-9);
+            solver.integrate().unwrap();
+            trajectory_hash(solver.x_out(), solver.y_out().iter().flatten())
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn merged_chunk_stats_equal_the_stats_of_the_whole_run() {
+        let vdp = problems::VanDerPol::new(2.0);
+        let gauss = |x0, y0, x1| {
+            let tableau = ImplicitButcherTableau::gauss_legendre2();
+            let mut solver = ImplicitRungeKutta::new(vdp, x0, y0, x1, 0.125, tableau);
+            (solver.integrate().unwrap(), *solver.y_final())
+        };
+        let (whole, y_whole) = gauss(0.0, vdp.initial_state(), 2.0);
+        let (mut merged, y1) = gauss(0.0, vdp.initial_state(), 1.0);
+        let (second, y2) = gauss(1.0, y1, 2.0);
+        merged.merge(&second);
+        assert_eq!(y2, y_whole);
+        assert_eq!(format!("{:?}", merged), format!("{:?}", whole));
+
+        // The extreme step sizes are taken over both chunks
+        let dopri = |x0, y0, x1| {
+            let mut solver = Dopri5::new(vdp, x0, y0, x1, None, 1e-8, 1e-8);
+            (solver.integrate().unwrap(), *solver.y_final())
+        };
+        let (first, y1) = dopri(0.0, vdp.initial_state(), 5.0);
+        let (second, _) = dopri(5.0, y1, 10.0);
+        let mut merged = Stats::new();
+        merged.merge(&first);
+        merged.merge(&second);
+        assert_eq!(merged.num_eval(), first.num_eval() + second.num_eval());
+        assert_eq!(
+            merged.accepted_steps(),
+            first.accepted_steps() + second.accepted_steps()
+        );
+        assert_eq!(merged.min_step, first.min_step.min(second.min_step));
+        assert_eq!(merged.max_step, first.max_step.max(second.max_step));
+        assert_ne!(first.min_step, second.min_step);
+    }
+
+    /// Typed quantities of the falling body, in SI units.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Meters(f64);
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct MetersPerSecond(f64);
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct MetersPerSecondSquared(f64);
+
+    /// Body falling under gravity, with its height and velocity as typed quantities.
+    struct Fall;
+
+    impl UnitSystem<Vector2<f64>> for Fall {
+        type State = (Meters, MetersPerSecond);
+        type Rate = (MetersPerSecond, MetersPerSecondSquared);
+
+        fn ode_units(&self, _x: f64, (_, v): &Self::State) -> Self::Rate {
+            (*v, MetersPerSecondSquared(-9.81))
+        }
+
+        fn strip(&self, (z, v): &Self::State) -> Vector2<f64> {
+            Vector2::new(z.0, v.0)
+        }
+
+        fn strip_rate(&self, (v, a): &Self::Rate, dy: &mut Vector2<f64>) {
+            dy[0] = v.0;
+            dy[1] = a.0;
+        }
+
+        fn apply(&self, y: &Vector2<f64>) -> Self::State {
+            (Meters(y[0]), MetersPerSecond(y[1]))
+        }
+    }
+
+    #[test]
+    fn integrates_a_system_in_typed_quantities() {
+        let y0 = Fall.strip(&(Meters(100.0), MetersPerSecond(0.0)));
+        let mut solver = Dopri5::new(with_units(Fall), 0.0, y0, 2.0, None, 1e-10, 1e-10);
+        solver.integrate().unwrap();
+        let (z, v): (Meters, MetersPerSecond) = Fall.apply(solver.y_final());
+        assert!((z.0 - (100.0 - 0.5 * 9.81 * 4.0)).abs() < 1e-9);
+        assert!((v.0 + 9.81 * 2.0).abs() < 1e-9);
+        assert_eq!(
+            Fall.apply(&solver.y_out()[0]),
+            (Meters(100.0), MetersPerSecond(0.0))
+        );
+    }
+
+    /// Order, implicitness, A-stability and FSAL property of a method.
+    fn method_info(method: &dyn MethodInfo) -> (u32, bool, bool, bool) {
+        (
+            method.order(),
+            method.is_implicit(),
+            method.is_a_stable(),
+            method.is_fsal(),
+        )
+    }
+
+    #[test]
+    fn solvers_report_the_properties_of_their_method() {
+        let f = ode_fn(|_, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = -y[0]);
+        let y0 = Vector1::new(1.0);
+        let euler = Euler::new(f, 0.0, y0, 1.0, vec![0.1; 3]);
+        assert_eq!(method_info(&euler), (1, false, false, false));
+        let rk4 = RungeKutta4::new(f, 0.0, y0, 1.0, 0.1);
+        assert_eq!(method_info(&rk4), (4, false, false, false));
+        let dopri5 = Dopri5::new(f, 0.0, y0, 1.0, None, 1e-6, 1e-6);
+        assert_eq!(method_info(&dopri5), (5, false, false, true));
+        let implicit_euler = ImplicitEuler::new(f, 0.0, y0, 1.0, 0.1, 1e-10, 10);
+        assert_eq!(method_info(&implicit_euler), (1, true, true, false));
+        let bdf = Bdf::new(f, 0.0, y0, 1.0, None, 1e-6, 1e-6);
+        assert_eq!(method_info(&bdf), (5, true, false, false));
+        // The order of the Adams methods is chosen at runtime
+        let adams = AdamsBashforthMoulton::new(f, 0.0, y0, 1.0, 0.1, 3);
+        assert_eq!(method_info(&adams), (3, false, false, false));
+    }
+
+    #[test]
+    fn sweep_sensitivity_matches_the_state_transition_matrix() {
+        // y' = A y with A = [[0, 1], [-2, -3]], whose eigenvalues are -1 and -2
+        let linear = || {
+            ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                *dy = Vector2::new(y[1], -2.0 * y[0] - 3.0 * y[1]);
+            })
+        };
+        let t: f64 = 1.5;
+        let (e1, e2) = ((-t).exp(), (-2.0 * t).exp());
+        let phi =
+            nalgebra::Matrix2::new(2.0 * e1 - e2, e1 - e2, -2.0 * e1 + 2.0 * e2, -e1 + 2.0 * e2);
+
+        let base_y = Vector2::new(1.0, -0.5);
+        let eps = 1e-3;
+        let perturbations = [
+            Vector2::zeros(),
+            Vector2::new(eps, 0.0),
+            Vector2::new(0.0, eps),
+        ];
+        let results = sweep_initial(&base_y, &perturbations, |y0| {
+            Dopri5::new(linear(), 0.0, y0, t, None, 1e-12, 1e-12)
+        });
+        assert_eq!(results.len(), 3);
+        let y_end: Vec<_> = results
+            .into_iter()
+            .map(|r| *r.unwrap().y.last().unwrap())
+            .collect();
+        assert!((y_end[0] - phi * base_y).amax() < 1e-10);
+        for j in 0..2 {
+            let sensitivity = (y_end[j + 1] - y_end[0]) / eps;
+            assert!((sensitivity - phi.column(j)).amax() < 1e-7, "{}", j);
+        }
+    }
+
+    const G: f64 = 9.81;
+    const RESTITUTION: f64 = 0.8;
+
+    /// Ball dropped from a height of 1, bouncing on the ground `y[0] = 0` until the `last`-th
+    /// impact, which terminates the integration.
+    struct BouncingBall {
+        direction: EventDirection,
+        last: usize,
+        bounces: Rc<RefCell<Vec<f64>>>,
+    }
+
+    impl System<Vector2<f64>> for BouncingBall {
+        fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+            dy[0] = y[1];
+            dy[1] = -G;
+        }
+
+        fn num_events(&self) -> usize {
+            1
+        }
+
+        fn event_fn(&self, _x: f64, y: &Vector2<f64>, out: &mut [f64]) {
+            out[0] = y[0];
+        }
+
+        fn event_direction(&self, _idx: usize) -> EventDirection {
+            self.direction
+        }
+
+        fn on_event(&mut self, _idx: usize, x: f64, y: &mut Vector2<f64>) -> EventAction {
+            y[1] *= -RESTITUTION;
+            let mut bounces = self.bounces.borrow_mut();
+            bounces.push(x);
+            if bounces.len() == self.last {
+                EventAction::Terminate
+            } else {
+                EventAction::Continue
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_solvers_locate_the_events_on_their_dense_output() {
+        let t1 = (2.0 / G).sqrt();
+        let t2 = t1 * (1.0 + 2.0 * RESTITUTION);
+        let x_end = t2 + 0.1;
+        for direction in [EventDirection::Falling, EventDirection::Both] {
+            for last in [2, 3] {
+                let ball = || {
+                    let bounces = Rc::new(RefCell::new(Vec::new()));
+                    let ball = BouncingBall {
+                        direction,
+                        last,
+                        bounces: bounces.clone(),
+                    };
+                    (ball, bounces)
+                };
+                let y0 = Vector2::new(1.0, 0.0);
+                let (f, bounces5) = ball();
+                let mut dopri5 = Dopri5::new(f, 0.0, y0, x_end, None, 1e-10, 1e-10);
+                let (f, bounces8) = ball();
+                let mut dop853 = Dop853::new(f, 0.0, y0, x_end, None, 1e-10, 1e-10);
+                let runs = [
+                    (dopri5.integrate().unwrap(), dopri5.x_final(), bounces5),
+                    (dop853.integrate().unwrap(), dop853.x_final(), bounces8),
+                ];
+                for (stats, x_final, bounces) in runs {
+                    let bounces = bounces.borrow();
+                    assert_eq!(bounces.len(), 2, "{:?}", direction);
+                    assert!((bounces[0] - t1).abs() < 1e-9, "{}", bounces[0]);
+                    assert!((bounces[1] - t2).abs() < 1e-9, "{}", bounces[1]);
+                    if last == 2 {
+                        // The second impact terminates the integration
+                        assert_eq!(stats.terminal_event(), Some(0));
+                        assert_eq!(x_final, bounces[1]);
+                    } else {
+                        assert_eq!(stats.terminal_event(), None);
+                        assert_eq!(x_final, x_end);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn other_solvers_reject_a_system_with_events() {
+        let ball = BouncingBall {
+            direction: EventDirection::Both,
+            last: 1,
+            bounces: Rc::new(RefCell::new(Vec::new())),
+        };
+        let mut solver = RungeKutta4::new(ball, 0.0, Vector2::new(1.0, 0.0), 1.0, 0.01);
+        assert!(matches!(
+            solver.integrate(),
+            Err(IntegrationError::InvalidStepConfig { .. })
+        ));
+    }
+
+    /// Linear system `y' = diag(0, lambda) y` whose backward Euler iteration matrix
+    /// `I - h J` is nearly singular for `h = 0.1`.
+    #[cfg(feature = "logging")]
+    struct NearlySingular;
+
+    #[cfg(feature = "logging")]
+    impl System<Vector2<f64>> for NearlySingular {
+        fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+            dy[0] = 0.0;
+            dy[1] = 10.0 * (1.0 - 1e-14) * y[1];
+        }
+
+        fn jacobian(&self, _x: f64, _y: &Vector2<f64>, jac: &mut nalgebra::Matrix2<f64>) {
+            *jac = nalgebra::Matrix2::new(0.0, 0.0, 0.0, 10.0 * (1.0 - 1e-14));
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    #[test]
+    fn solvers_log_steps_warnings_and_errors() {
+        let records = Rc::new(RefCell::new(Vec::new()));
+        let sink = records.clone();
+        let logger = move |record: &LogRecord| sink.borrow_mut().push(record.clone());
+        let count = |level: LogLevel, message: &str| {
+            let records = records.borrow();
+            let matching = records.iter().filter(|r| r.level == level);
+            matching.filter(|r| r.message.starts_with(message)).count()
+        };
+
+        let y0 = Vector2::new(1.0, 1.0);
+        let mut solver = ImplicitEuler::new(NearlySingular, 0.0, y0, 0.1, 0.1, 1e-10, 10);
+        solver.logger(logger.clone());
+        solver.integrate().unwrap();
+        assert_eq!(count(LogLevel::Warn, "nearly singular iteration matrix"), 1);
+        assert_eq!(count(LogLevel::Debug, "step accepted"), 1);
+
+        records.borrow_mut().clear();
+        let vdp = crate::problems::VanDerPol::default();
+        let mut solver = Dopri5::new(vdp, 0.0, y0, 20.0, None, 1e-6, 1e-6);
+        solver.max_step(0.1);
+        solver.max_steps(100);
+        solver.logger(logger);
+        assert!(solver.integrate().is_err());
+        assert_eq!(count(LogLevel::Debug, "step accepted"), 101);
+        assert!(count(LogLevel::Warn, "step clamped") > 0);
+        assert_eq!(count(LogLevel::Error, "Stopped at"), 1);
+        assert_eq!(records.borrow().last().unwrap().level, LogLevel::Error);
+    }
+
+    #[test]
+    fn adaptive_solvers_stop_after_the_maximum_number_of_steps() {
+        let (y0, x_end) = (Vector2::new(2.0, 0.0), 20.0);
+        let vdp = crate::problems::VanDerPol::default;
+        let mut dopri5 = Dopri5::new(vdp(), 0.0, y0, x_end, None, 1e-8, 1e-8);
+        dopri5.max_steps(10);
+        let mut dop853 = Dop853::new(vdp(), 0.0, y0, x_end, None, 1e-8, 1e-8);
+        dop853.max_steps(10);
+        let mut bs23 = BogackiShampine23::new(vdp(), 0.0, y0, x_end, None, 1e-8, 1e-8);
+        bs23.max_steps(10);
+        let mut rosenbrock = Rosenbrock4::new(vdp(), 0.0, y0, x_end, None, 1e-8, 1e-8);
+        rosenbrock.max_steps(10);
+        let mut bdf = Bdf::new(vdp(), 0.0, y0, x_end, None, 1e-8, 1e-8);
+        bdf.max_steps(10);
+        let results = [
+            dopri5.integrate(),
+            dop853.integrate(),
+            bs23.integrate(),
+            rosenbrock.integrate(),
+            bdf.integrate(),
+        ];
+        for result in results {
+            assert!(matches!(
+                result,
+                Err(IntegrationError::MaxStepsExceeded { n_step: 11, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn spaced_values_include_both_end_points() {
+        for num in [2, 3, 10, 101] {
+            let lin = linspace(-1.0, 3.0, num);
+            let log = logspace(-2.0, 3.0, num);
+            let geom = geomspace(0.5, 512.0, num);
+            for values in [&lin, &log, &geom] {
+                assert_eq!(values.len(), num);
+            }
+            assert_eq!((lin[0], lin[num - 1]), (-1.0, 3.0));
+            assert_eq!((log[0], log[num - 1]), (0.01, 1000.0));
+            assert_eq!((geom[0], geom[num - 1]), (0.5, 512.0));
+            let step = 4.0 / (num - 1) as f64;
+            assert!(lin.windows(2).all(|w| (w[1] - w[0] - step).abs() < 1e-12));
+            let ratio = 1024.0f64.powf(1.0 / (num - 1) as f64);
+            assert!(geom.windows(2).all(|w| (w[1] / w[0] - ratio).abs() < 1e-12));
+            let ratio = 10.0f64.powf(5.0 / (num - 1) as f64);
+            assert!(log
+                .windows(2)
+                .all(|w| (w[1] / w[0] / ratio - 1.0).abs() < 1e-12));
+        }
+        assert_eq!(linspace(2.0, 5.0, 1), vec![2.0]);
+        assert_eq!(logspace(2.0, 5.0, 1), vec![100.0]);
+        assert_eq!(geomspace(2.0, 5.0, 1), vec![2.0]);
+        assert!(linspace(2.0, 5.0, 0).is_empty());
+        assert!(logspace(2.0, 5.0, 0).is_empty());
+        assert!(geomspace(2.0, 5.0, 0).is_empty());
+    }
+
+    /// Decay `y' = -1` whose right-hand side is undefined below `0.5`.
+    struct Undefined;
+
+    impl System<Vector1<f64>> for Undefined {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = if y[0] > 0.5 { -1.0 } else { f64::NAN };
+        }
+    }
+
+    fn assert_diverges(mut solver: impl OdeSolver<Vector1<f64>>) {
+        assert!(matches!(
+            solver.integrate(),
+            Err(IntegrationError::NewtonDidNotConverge { .. })
+        ));
+        assert!(solver.y_final()[0].is_finite());
+    }
+
+    #[test]
+    fn newton_iterations_reject_diverged_updates() {
+        assert!(newton_converged(&[1e-11, -1e-11], 1e-10));
+        assert!(!newton_converged(&[1e-11, -1e-9], 1e-10));
+        assert!(!newton_converged(&[1e-11, f64::NAN], 1e-10));
+        assert!(!newton_converged(&[f64::NAN; 2], 1e-10));
+
+        let y0 = Vector1::new(1.0);
+        assert_diverges(ImplicitEuler::new(Undefined, 0.0, y0, 1.0, 0.1, 1e-10, 10));
+        assert_diverges(CrankNicolson::new(Undefined, 0.0, y0, 1.0, 0.1, 1e-10, 10));
+        assert_diverges(ImplicitRungeKutta::new(
+            Undefined,
+            0.0,
+            y0,
+            1.0,
+            0.1,
+            ImplicitButcherTableau::gauss_legendre2(),
+        ));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn ndarray_round_trip() {
+        let y = Vector3::new(1.0, -2.5, 3e-3);
+        let array = to_ndarray(&y);
+        assert_eq!(array, ndarray::arr1(&[1.0, -2.5, 3e-3]));
+        let back = from_ndarray(&array);
+        assert_eq!(back.as_slice(), y.as_slice());
+
+        let y_out = vec![
+            Vector2::new(1.0, 2.0),
+            Vector2::new(3.0, 4.0),
+            Vector2::new(5.0, 6.0),
+        ];
+        let table = to_array2(&y_out);
+        assert_eq!(table.dim(), (3, 2));
+        assert_eq!(table, ndarray::arr2(&[[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]));
+        for (row, y) in table.rows().into_iter().zip(&y_out) {
+            assert_eq!(from_ndarray(&row.to_owned()).as_slice(), y.as_slice());
+        }
     }
 }
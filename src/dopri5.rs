@@ -0,0 +1,1045 @@
+//! Explicit Runge-Kutta method of order 5(4) with adaptive step size control (Dormand-Prince).
+
+use crate::controller::{PiController, StepController};
+use crate::dop853::Dop853;
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, dense_polynomial, error_scale, event_values, find_segment, initial_step_size,
+    limit_step, locate_dense_event, order_reduced, ramp_initial_step, ramp_step, record_diagnostic,
+    refine_output, weighted_rms_norm, ContinuousSolution, ControlFlag, ErrorNorm, EvalError,
+    EventAction, IntegrationError, IntegrationResult, LogLevel, MethodInfo, OdeSolver, Output,
+    SolverLog, Stats, StepDiagnostic, System, Tolerance,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+#[cfg(feature = "rand")]
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+// Nodes, stage matrix, fifth order weights, error and dense output coefficients of the Dormand-Prince tableau.
+const C2: f64 = 1.0 / 5.0;
+const C3: f64 = 3.0 / 10.0;
+const C4: f64 = 4.0 / 5.0;
+const C5: f64 = 8.0 / 9.0;
+const A21: f64 = 1.0 / 5.0;
+const A31: f64 = 3.0 / 40.0;
+const A32: f64 = 9.0 / 40.0;
+const A41: f64 = 44.0 / 45.0;
+const A42: f64 = -56.0 / 15.0;
+const A43: f64 = 32.0 / 9.0;
+const A51: f64 = 19372.0 / 6561.0;
+const A52: f64 = -25360.0 / 2187.0;
+const A53: f64 = 64448.0 / 6561.0;
+const A54: f64 = -212.0 / 729.0;
+const A61: f64 = 9017.0 / 3168.0;
+const A62: f64 = -355.0 / 33.0;
+const A63: f64 = 46732.0 / 5247.0;
+const A64: f64 = 49.0 / 176.0;
+const A65: f64 = -5103.0 / 18656.0;
+const A71: f64 = 35.0 / 384.0;
+const A73: f64 = 500.0 / 1113.0;
+const A74: f64 = 125.0 / 192.0;
+const A75: f64 = -2187.0 / 6784.0;
+const A76: f64 = 11.0 / 84.0;
+const E1: f64 = 71.0 / 57600.0;
+const E3: f64 = -71.0 / 16695.0;
+const E4: f64 = 71.0 / 1920.0;
+const E5: f64 = -17253.0 / 339200.0;
+const E6: f64 = 22.0 / 525.0;
+const E7: f64 = -1.0 / 40.0;
+const D1: f64 = -12715105075.0 / 11282082432.0;
+const D3: f64 = 87487479700.0 / 32700410799.0;
+const D4: f64 = -10690763975.0 / 1880347072.0;
+const D5: f64 = 701980252875.0 / 199316789632.0;
+const D6: f64 = -1453857185.0 / 822651844.0;
+const D7: f64 = 69997945.0 / 29380423.0;
+
+/// Bound of the stability domain along the negative real axis used to detect stiffness.
+pub(crate) const STIFF_RATIO: f64 = 3.25;
+
+// Factor applied to the tolerances of the companion solve estimating the global error, and lowest
+// relative tolerance it is given
+const COMPANION_TOL_FACTOR: f64 = 1.0e-3;
+const COMPANION_MIN_RTOL: f64 = 1.0e-14;
+
+/// Structure containing the parameters for the numerical integration.
+pub struct Dopri5<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    h_accepted: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    controller: Box<dyn StepController>,
+    norm: Box<ErrorNorm<V>>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    output: Output<V>,
+    dense_samples: usize,
+    output_tol: Option<(f64, f64)>,
+    store_derivatives: bool,
+    dy_out: Vec<V>,
+    dense: Vec<[V; 5]>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    startup_ramp: usize,
+    log: SolverLog,
+    estimate_global_error: bool,
+    global_error: f64,
+    #[cfg(feature = "rand")]
+    jitter: Option<(f64, Box<dyn RngCore>)>,
+    stats: Stats,
+    started: bool,
+    pub(crate) detect_switch: bool,
+    pub(crate) switch: bool,
+}
+
+impl<T, D: Dim, F> Dopri5<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        Dopri5 {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            h_accepted: 0.0,
+            rtol: rtol.into(),
+            atol: atol.into(),
+            controller: Box::new(PiController::default()),
+            norm: Box::new(weighted_rms_norm),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            output: Output::new(),
+            output_tol: None,
+            dense_samples: 0,
+            store_derivatives: false,
+            dy_out: Vec::new(),
+            dense: Vec::new(),
+            diagnostics: None,
+            startup_ramp: 0,
+            log: SolverLog::default(),
+            estimate_global_error: false,
+            global_error: f64::NAN,
+            #[cfg(feature = "rand")]
+            jitter: None,
+            stats: Stats::new(),
+            started: false,
+            detect_switch: false,
+            switch: false,
+        }
+    }
+
+    /// Sets the step size controller, a [`PiController`] by default.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.controller = Box::new(controller);
+    }
+
+    /// Sets the norm of the local error estimate used to accept or reject a step, called with the
+    /// error estimate and the error scale `atol + rtol * |y|` of each component. A step is
+    /// accepted when the norm is at most 1. Defaults to [`weighted_rms_norm`]. For instance, the
+    /// maximum norm bounds the error of every component:
+    ///
+    /// ```
+    /// # use ode_event_solvers::{problems::Lorenz, Dopri5, Vector3};
+    /// # let (f, y0) = (Lorenz::default(), Vector3::new(1.0, 1.0, 1.0));
+    /// # let mut solver = Dopri5::new(f, 0.0, y0, 1.0, None, 1e-6, 1e-6);
+    /// solver.error_norm(|err: &Vector3<f64>, scale: &Vector3<f64>| {
+    ///     err.iter().zip(scale.iter()).fold(0.0, |acc, (e, sc)| acc.max((e / sc).abs()))
+    /// });
+    /// # solver.integrate()?;
+    /// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+    /// ```
+    pub fn error_norm(&mut self, norm: impl Fn(&OVector<T, D>, &OVector<T, D>) -> f64 + 'static) {
+        self.norm = Box::new(norm);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets the number of accepted steps at the start of the integration during which the step
+    /// size may grow by at most a factor of 2 from one step to the next (default 0), easing into
+    /// the adaptive regime on a problem with a sharp initial layer. An initial step size selected
+    /// automatically is first divided by `2^steps`, the ramp growing it back.
+    pub fn startup_ramp(&mut self, steps: usize) {
+        self.startup_ramp = steps;
+    }
+
+    /// Sets a logger receiving a [`LogRecord`] at the debug level for every accepted step, at the
+    /// warn level for every rejected step and every step clamped to the maximum step size, and at
+    /// the error level before an `IntegrationError` is returned, e.g. to forward them to the `log`
+    /// or `tracing` crates. Requires the `logging` feature, without which the solvers carry no
+    /// logger and build no records.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Perturbs each step size proposed by the step size controller by a random factor within
+    /// `[1 - amplitude, 1 + amplitude]` (off by default), e.g. to check that the solution of a
+    /// deterministic model is robust to the choice of the steps. This is a testing tool, unrelated
+    /// to the stochastic solvers. The factors are drawn from a generator seeded with `seed`, so
+    /// that the results are reproducible given the same seed and settings, and the seed is
+    /// recorded in the statistics. The integration fails with
+    /// `IntegrationError::InvalidStepConfig` unless `0 <= amplitude < 1`.
+    ///
+    /// The generator is a `StdRng`, see [`jitter_with`](Self::jitter_with) for another one.
+    #[cfg(feature = "rand")]
+    pub fn jitter(&mut self, amplitude: f64, seed: u64) {
+        self.jitter_with::<StdRng>(amplitude, seed);
+    }
+
+    /// Perturbs the step sizes as [`jitter`](Self::jitter) with the factors drawn from the
+    /// generator `R` seeded with `seed`.
+    #[cfg(feature = "rand")]
+    pub fn jitter_with<R: SeedableRng + RngCore + 'static>(&mut self, amplitude: f64, seed: u64) {
+        self.jitter = Some((amplitude, Box::new(R::seed_from_u64(seed))));
+        self.stats.jitter_seed = Some(seed);
+    }
+
+    /// Estimates the global error of the integration with a companion solve of the same problem
+    /// by [`Dop853`] with tolerances tightened by a factor 1000, roughly doubling the cost of the
+    /// integration (default false). The estimate is then given by
+    /// [`global_error_estimate`](Self::global_error_estimate).
+    pub fn estimate_global_error(&mut self, estimate: bool) {
+        self.estimate_global_error = estimate;
+    }
+
+    /// Largest difference over the components of the states stored at every step between the
+    /// integration and the companion solve enabled with
+    /// [`estimate_global_error`](Self::estimate_global_error). `NaN` if the estimation is
+    /// disabled or if either the integration or the companion solve failed. The evaluations of the
+    /// companion solve are included in `Stats::num_eval`.
+    pub fn global_error_estimate(&self) -> f64 {
+        self.global_error
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). `Stats::order_reduced` then flags an observed order well
+    /// below the nominal one. Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Sets the bound on the change of the solution between consecutive points returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out), `rtol * max(|y_a|, |y_b|) + atol` for
+    /// each component, e.g. to plot a solution made of long steps. The steps are subdivided with
+    /// the dense output where the solution changes fast, so that the linear interpolation of the
+    /// output stays within the bound of the solution, see
+    /// [`ContinuousSolution::refine`](crate::ContinuousSolution::refine). The internal stepping
+    /// is unaffected and [`t_eval`](Self::t_eval) takes precedence.
+    pub fn output_tolerance(&mut self, rtol: f64, atol: f64) {
+        self.output_tol = Some((rtol, atol));
+    }
+
+    /// Sets the number of points evenly spaced inside each accepted step at which the observer is
+    /// called in addition to the end of the step, the solution being evaluated there with the
+    /// dense output (default 0). The step sizes are unaffected.
+    pub fn dense_samples(&mut self, n: usize) {
+        self.dense_samples = n;
+    }
+
+    /// Sets whether the derivatives `f(x, y)` at the stored states are returned by
+    /// [`dy_out`](Self::dy_out) (default false). They are the first stage of the next step, so
+    /// that storing them costs no evaluation of the system.
+    pub fn store_derivatives(&mut self, store_derivatives: bool) {
+        self.store_derivatives = store_derivatives;
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        self.sample_t_eval();
+        if self.estimate_global_error && result.is_ok() {
+            self.global_error = self.companion_error();
+            return Ok(self.stats);
+        }
+        result
+    }
+
+    /// Integrates the problem again at tightened tolerances and returns the largest difference
+    /// with the states stored at every step, or `NaN` if the companion solve fails.
+    fn companion_error(&mut self) -> f64 {
+        let mut companion = Dop853::new(
+            Companion { f: &self.f },
+            self.output.x[0],
+            self.output.y[0].clone(),
+            self.x,
+            None,
+            self.rtol
+                .tightened(COMPANION_TOL_FACTOR, COMPANION_MIN_RTOL),
+            self.atol.tightened(COMPANION_TOL_FACTOR, 0.0),
+        );
+        match companion.integrate() {
+            Ok(stats) => self.stats.num_eval += stats.num_eval,
+            Err(_) => return f64::NAN,
+        }
+        self.output
+            .x
+            .iter()
+            .zip(&self.output.y)
+            .filter_map(|(x, y)| companion.at(*x).map(|yc| y - yc))
+            .map(|diff| {
+                diff.iter()
+                    .fold(0.0, |m, e| f64::max(m, f64::from(*e).abs()))
+            })
+            .fold(0.0, f64::max)
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        if let Some((rtol, atol)) = self.output_tol {
+            if !(rtol >= 0.0 && atol >= 0.0 && rtol + atol > 0.0) {
+                return Err(IntegrationError::InvalidTolerance {
+                    reason: "the output tolerances must be non-negative and not both zero"
+                        .to_string(),
+                });
+            }
+        }
+        #[cfg(feature = "rand")]
+        if let Some((amplitude, _)) = self.jitter {
+            if !(0.0..1.0).contains(&amplitude) {
+                return Err(IntegrationError::InvalidStepConfig {
+                    reason: format!("the jitter amplitude must lie in [0, 1), got {}", amplitude),
+                });
+            }
+        }
+        if self.estimate_global_error && self.f.num_events() > 0 {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "the global error cannot be estimated for a system with events".to_string(),
+            });
+        }
+        // Save initial values, unless the integration is continued by another call
+        let start = !self.started;
+        if start {
+            self.started = true;
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        }
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+
+        let (rows, cols) = self.y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); 7];
+        self.f.ode(self.x, &self.y, &mut k[0]);
+        self.stats.num_eval += 1;
+        if self.store_derivatives && start {
+            self.dy_out.push(k[0].clone());
+        }
+        if self.h == 0.0 {
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = OVector::zeros_generic(rows, cols);
+                    self.f.ode(x, y, &mut dy);
+                    dy
+                },
+                self.x,
+                &self.y,
+                &k[0],
+                self.x_end,
+                5,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.h = ramp_initial_step(self.h, self.startup_ramp);
+            self.stats.num_eval += 1;
+        }
+
+        let mut last = false;
+        let mut n_step = 0;
+        let mut n_stiff = 0;
+        let mut n_non_stiff = 0;
+        // Event indicators at the current state, and event at which the step being retried ends
+        let mut g = event_values(&self.f, self.x, &self.y);
+        let mut pending_event = None;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point
+            if pending_event.is_none() && (self.x + 1.01 * self.h - self.x_end) * posneg > 0.0 {
+                self.h = self.x_end - self.x;
+                last = true;
+            }
+            n_step += 1;
+
+            let (y_new, err) = self.step(&mut k);
+            self.stats.num_eval += 6;
+            check_eval(&self.f)?;
+
+            let err = self.norm(&err, &y_new);
+            let (accept, h_new) = self.controller.next_step(err, self.h, 4);
+            let h_new = ramp_step(h_new, self.h, self.stats.accepted_steps, self.startup_ramp);
+            record_diagnostic(&mut self.diagnostics, self.x, self.h, err, accept);
+            if accept {
+                let r = self.dense_coefficients(&k, &y_new);
+                let mut event = None;
+                if !g.is_empty() {
+                    let h = self.h;
+                    let g_new = event_values(&self.f, self.x + h, &y_new);
+                    event = match pending_event.take() {
+                        Some(idx) => Some(idx),
+                        None => match locate_dense_event(&self.f, self.x, h, &g, &g_new, &r) {
+                            // Take the step again, ending just past the crossing
+                            Some((idx, dx)) if dx != h => {
+                                self.log.log(LogLevel::Debug, self.x, h, || {
+                                    format!("step shortened to the event {}", idx)
+                                });
+                                pending_event = Some(idx);
+                                self.h = dx;
+                                last = false;
+                                continue;
+                            }
+                            located => located.map(|(idx, _)| idx),
+                        },
+                    };
+                    g = g_new;
+                }
+                self.log.accepted(self.x, self.h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(self.h);
+                self.dense.push(r);
+                // Call Observer at the interior points of the step
+                if let Some(r) = self.dense.last() {
+                    for i in 1..=self.dense_samples {
+                        let theta = i as f64 / (self.dense_samples + 1) as f64;
+                        let y = dense_polynomial(r, theta);
+                        self.f
+                            .observer_full(self.x + theta * self.h, &y, self.h, &self.stats);
+                    }
+                }
+                // Stiffness detection, used by the `Auto` solver
+                if self.detect_switch {
+                    if self.stiffness_ratio(&k, &y_new) > STIFF_RATIO {
+                        n_non_stiff = 0;
+                        n_stiff += 1;
+                    } else {
+                        n_non_stiff += 1;
+                        if n_non_stiff == 6 {
+                            n_stiff = 0;
+                        }
+                    }
+                    self.switch = n_stiff == 15;
+                }
+                // First same as last
+                k[0] = k[6].clone();
+                self.x = if last { self.x_end } else { self.x + self.h };
+                self.y = y_new.clone();
+                self.f.project(self.x, &mut self.y);
+                let action = match event {
+                    Some(idx) => {
+                        let action = self.f.on_event(idx, self.x, &mut self.y);
+                        // The state lies just past the crossing, so that the indicator is
+                        // considered to be at zero and cannot fire again until it has left it
+                        g = event_values(&self.f, self.x, &self.y);
+                        g[idx] = 0.0;
+                        if action == EventAction::Terminate {
+                            self.stats.terminal_event = Some(idx);
+                        }
+                        action
+                    }
+                    None => EventAction::Continue,
+                };
+                // Evaluate the first stage again if the projection or the event moved the state
+                if self.y != y_new {
+                    self.f.ode(self.x, &self.y, &mut k[0]);
+                    self.stats.num_eval += 1;
+                }
+                self.h_accepted = self.h;
+                self.output.push(self.x, self.y.clone());
+                if self.store_derivatives {
+                    self.dy_out.push(k[0].clone());
+                }
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop
+                    || self.switch
+                    || action == EventAction::Terminate
+                {
+                    break;
+                }
+            } else {
+                self.log.rejected(self.x, self.h, err);
+                self.stats.rejected_steps += 1;
+                pending_event = None;
+                last = false;
+            }
+            if !last {
+                #[cfg(feature = "rand")]
+                let h_new = match &mut self.jitter {
+                    Some((amplitude, rng)) => {
+                        h_new * (1.0 + *amplitude * rng.gen_range(-1.0..=1.0))
+                    }
+                    None => h_new,
+                };
+                self.h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, self.h, self.max_step);
+            }
+        }
+        check_eval(&self.f)?;
+        self.stats.order_reduced = order_reduced(&self.diagnostics, 4);
+        Ok(self.stats)
+    }
+
+    /// Computes the stages of one step and returns the fifth order solution together with the local error estimate.
+    fn step(&self, k: &mut [OVector<T, D>]) -> (OVector<T, D>, OVector<T, D>) {
+        let h = self.h;
+        let y = &self.y;
+
+        let y_tmp = y + k[0].clone() * (h * A21);
+        self.f.ode(self.x + C2 * h, &y_tmp, &mut k[1]);
+        let y_tmp = y + (k[0].clone() * A31 + k[1].clone() * A32) * h;
+        self.f.ode(self.x + C3 * h, &y_tmp, &mut k[2]);
+        let y_tmp = y + (k[0].clone() * A41 + k[1].clone() * A42 + k[2].clone() * A43) * h;
+        self.f.ode(self.x + C4 * h, &y_tmp, &mut k[3]);
+        let y_tmp = y
+            + (k[0].clone() * A51 + k[1].clone() * A52 + k[2].clone() * A53 + k[3].clone() * A54)
+                * h;
+        self.f.ode(self.x + C5 * h, &y_tmp, &mut k[4]);
+        let y_tmp = y
+            + (k[0].clone() * A61
+                + k[1].clone() * A62
+                + k[2].clone() * A63
+                + k[3].clone() * A64
+                + k[4].clone() * A65)
+                * h;
+        self.f.ode(self.x + h, &y_tmp, &mut k[5]);
+        let y_new = y
+            + (k[0].clone() * A71
+                + k[2].clone() * A73
+                + k[3].clone() * A74
+                + k[4].clone() * A75
+                + k[5].clone() * A76)
+                * h;
+        self.f.ode(self.x + h, &y_new, &mut k[6]);
+
+        let err = (k[0].clone() * E1
+            + k[2].clone() * E3
+            + k[3].clone() * E4
+            + k[4].clone() * E5
+            + k[5].clone() * E6
+            + k[6].clone() * E7)
+            * h;
+        (y_new, err)
+    }
+
+    /// Estimates `|h * lambda|` for the dominant eigenvalue `lambda` of the Jacobian from the last
+    /// two stages, which are both evaluated at the end of the step (Hairer and Wanner, Solving
+    /// Ordinary Differential Equations II, Section IV.2).
+    fn stiffness_ratio(&self, k: &[OVector<T, D>], y_new: &OVector<T, D>) -> f64 {
+        let y_stage = &self.y
+            + (k[0].clone() * A61
+                + k[1].clone() * A62
+                + k[2].clone() * A63
+                + k[3].clone() * A64
+                + k[4].clone() * A65)
+                * self.h;
+        let num = (&k[6] - &k[5])
+            .iter()
+            .fold(0.0, |acc, v| acc + f64::from(*v).powi(2));
+        let den = (y_new - y_stage)
+            .iter()
+            .fold(0.0, |acc, v| acc + f64::from(*v).powi(2));
+        if den > 0.0 {
+            self.h.abs() * (num / den).sqrt()
+        } else {
+            0.0
+        }
+    }
+
+    /// Computes the coefficients of the continuous extension of order 4 over the current step.
+    fn dense_coefficients(&self, k: &[OVector<T, D>], y_new: &OVector<T, D>) -> [OVector<T, D>; 5] {
+        let h = self.h;
+        let y_diff = y_new - &self.y;
+        let bspl = k[0].clone() * h - &y_diff;
+        [
+            self.y.clone(),
+            y_diff.clone(),
+            bspl.clone(),
+            y_diff - k[6].clone() * h - bspl,
+            (k[0].clone() * D1
+                + k[2].clone() * D3
+                + k[3].clone() * D4
+                + k[4].clone() * D5
+                + k[5].clone() * D6
+                + k[6].clone() * D7)
+                * h,
+        ]
+    }
+
+    /// Computes the norm of the local error estimate set with [`error_norm`](Self::error_norm).
+    fn norm(&self, err: &OVector<T, D>, y_new: &OVector<T, D>) -> f64 {
+        let scale = error_scale(&self.y, y_new, &self.rtol, &self.atol);
+        (self.norm)(err, &scale)
+    }
+
+    /// Getter for the last accepted step size.
+    pub fn last_step_size(&self) -> f64 {
+        self.h_accepted
+    }
+
+    /// Evaluates the solution at the output values set with [`t_eval`](Self::t_eval), or at the
+    /// points refined with the bound set with [`output_tolerance`](Self::output_tolerance).
+    fn sample_t_eval(&mut self) {
+        let samples = match self.output_tol {
+            Some((rtol, atol)) if self.output.t_eval().is_none() => {
+                let eval = |i: usize, x: f64| {
+                    if x == self.output.x[i] {
+                        return self.output.y[i].clone();
+                    }
+                    let h = self.output.x[i + 1] - self.output.x[i];
+                    dense_polynomial(&self.dense[i], (x - self.output.x[i]) / h)
+                };
+                Some(refine_output(&self.output.x, eval, rtol, atol))
+            }
+            _ => self.output.sample(|t| self.at(t)),
+        };
+        self.output.set_samples(samples);
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when neither [`t_eval`](Self::t_eval) nor
+    /// [`output_tolerance`](Self::output_tolerance) is set. Empty unless
+    /// enabled with [`store_derivatives`](Self::store_derivatives).
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Evaluates the continuous extension of the method at `x`. Returns `None` if `x` lies outside
+    /// of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        let h = self.output.x[i + 1] - self.output.x[i];
+        if h == 0.0 {
+            return Some(self.output.y[i].clone());
+        }
+        Some(dense_polynomial(&self.dense[i], (x - self.output.x[i]) / h))
+    }
+
+    /// Returns the continuous extension of the solution over the accepted steps, which can be
+    /// evaluated without the solver.
+    pub fn continuous_solution(&self) -> ContinuousSolution<OVector<T, D>> {
+        let coefficients = if self.dense.is_empty() {
+            self.output
+                .y
+                .iter()
+                .take(1)
+                .map(|y| vec![y.clone()])
+                .collect()
+        } else {
+            self.dense.iter().map(|r| r.to_vec()).collect()
+        };
+        ContinuousSolution::new(self.output.x.clone(), coefficients)
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for Dopri5<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Dopri5::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Dopri5::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Dopri5::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Dopri5::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Dopri5::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for Dopri5<V, F> {
+    fn order(&self) -> u32 {
+        5
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        true
+    }
+}
+
+/// System borrowed by the companion solve estimating the global error, which neither calls the
+/// observer nor stops the integration.
+struct Companion<'a, F> {
+    f: &'a F,
+}
+
+impl<V, F> System<V> for Companion<'_, F>
+where
+    F: System<V>,
+{
+    fn ode(&self, x: f64, y: &V, dy: &mut V) {
+        self.f.ode(x, y, dy);
+    }
+
+    fn take_eval_error(&self) -> Option<(f64, EvalError)> {
+        self.f.take_eval_error()
+    }
+
+    fn project(&self, x: f64, y: &mut V) {
+        self.f.project(x, y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::HarmonicOscillator;
+    use crate::Vector1;
+
+    #[test]
+    fn converges_with_the_fifth_order() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let exact = problem.exact_solution(x_end);
+        let mut errors = Vec::new();
+        let mut steps = Vec::new();
+        for tol in [1e-5, 1e-7, 1e-9, 1e-11] {
+            let mut solver =
+                Dopri5::new(problem, x0, problem.initial_state(), x_end, None, tol, tol);
+            let stats = solver.integrate().unwrap();
+            assert_eq!(solver.x_final(), x_end);
+            errors.push((solver.y_final() - exact).norm());
+            steps.push(stats.accepted_steps() as f64);
+        }
+        // The global error is proportional to the tolerance
+        assert!(errors.windows(2).all(|w| w[0] / w[1] > 50.0));
+        // An error per step of order h^5 gives a number of steps growing like tol^(-1/5)
+        for w in steps.windows(2) {
+            let ratio = w[1] / w[0];
+            assert!(ratio > 2.0 && ratio < 3.5, "{}", ratio);
+        }
+    }
+
+    #[test]
+    fn rejects_a_too_large_initial_step() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let mut solver = Dopri5::new(problem, x0, problem.initial_state(), x_end, 5.0, 1e-8, 1e-8);
+        let stats = solver.integrate().unwrap();
+        assert!(stats.rejected_steps() > 0);
+        // Six evaluations per attempted step, together with the first stage of the first step
+        assert_eq!(
+            stats.num_eval(),
+            6 * (stats.accepted_steps() + stats.rejected_steps()) + 1
+        );
+    }
+
+    #[test]
+    fn last_step_size_is_the_last_accepted_step() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let mut solver = Dopri5::new(
+            problem,
+            x0,
+            problem.initial_state(),
+            x_end,
+            None,
+            1e-8,
+            1e-8,
+        );
+        solver.integrate().unwrap();
+        let x = solver.x_out();
+        assert_eq!(solver.last_step_size(), x[x.len() - 1] - x[x.len() - 2]);
+    }
+
+    #[test]
+    fn dense_output_follows_the_exact_solution() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let mut solver = Dopri5::new(
+            problem,
+            x0,
+            problem.initial_state(),
+            x_end,
+            None,
+            1e-10,
+            1e-10,
+        );
+        solver.integrate().unwrap();
+        // About ten samples per step
+        let n = 10 * solver.x_out().len();
+        for i in 0..=n {
+            let x = x_end * i as f64 / n as f64;
+            let error = (solver.at(x).unwrap() - problem.exact_solution(x)).norm();
+            assert!(error < 1e-8, "x = {}: {}", x, error);
+        }
+        assert!(solver.at(x_end + 1.0).is_none());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn jitter_is_reproducible_given_the_seed() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let run = |seed| {
+            let mut solver = Dopri5::new(
+                problem,
+                x0,
+                problem.initial_state(),
+                x_end,
+                None,
+                1e-8,
+                1e-8,
+            );
+            solver.jitter_with::<rand::rngs::StdRng>(0.2, seed);
+            let stats = solver.integrate().unwrap();
+            assert_eq!(stats.jitter_seed(), Some(seed));
+            (solver.x_out().clone(), solver.y_out().clone())
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7).0, run(8).0);
+    }
+
+    #[test]
+    fn output_tolerance_bounds_the_change_between_the_points() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let (rtol, atol) = (0.0, 0.01);
+        let mut solver = Dopri5::new(
+            problem,
+            x0,
+            problem.initial_state(),
+            x_end,
+            None,
+            1e-10,
+            1e-10,
+        );
+        solver.output_tolerance(rtol, atol);
+        solver.integrate().unwrap();
+        let (x_out, y_out) = (solver.x_out(), solver.y_out());
+        // The steps are subdivided where the solution changes by more than the bound
+        assert!(x_out.len() > 2 * (solver.dense.len() + 1));
+        for i in 0..x_out.len() - 1 {
+            let (a, b) = (&y_out[i], &y_out[i + 1]);
+            for j in 0..2 {
+                let bound = rtol * a[j].abs().max(b[j].abs()) + atol;
+                assert!((b[j] - a[j]).abs() <= bound);
+                // The linear interpolation of the output stays within the band of the solution
+                for theta in [0.25, 0.5, 0.75] {
+                    let x = x_out[i] + theta * (x_out[i + 1] - x_out[i]);
+                    let linear = a[j] + theta * (b[j] - a[j]);
+                    let exact = problem.exact_solution(x)[j];
+                    assert!((linear - exact).abs() <= bound, "x = {}", x);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn startup_ramp_reduces_the_rejections_in_the_initial_layer() {
+        // The fast component of the stiff oscillator relaxes to the slow manifold at the start
+        let problem = crate::problems::VanDerPol::new(1000.0);
+        let rejections = |ramp| {
+            let y0 = problem.initial_state();
+            let mut solver = Dopri5::new(problem, 0.0, y0, 0.1, None, 1e-6, 1e-6);
+            solver.startup_ramp(ramp);
+            solver.integrate().unwrap().rejected_steps()
+        };
+        assert!(rejections(10) < rejections(0));
+    }
+
+    /// Exponential growth `y' = y`.
+    struct Growth;
+
+    impl System<Vector1<f64>> for Growth {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+    }
+
+    #[test]
+    fn global_error_estimate_matches_the_true_error() {
+        for tol in [1e-6, 1e-9] {
+            let y0 = Vector1::new(1.0);
+            let mut solver = Dopri5::new(Growth, 0.0, y0, 5.0, None, tol, tol);
+            solver.estimate_global_error(true);
+            solver.integrate().unwrap();
+            let error = solver
+                .x_out()
+                .iter()
+                .zip(solver.y_out())
+                .map(|(x, y)| (y[0] - x.exp()).abs())
+                .fold(0.0, f64::max);
+            let estimate = solver.global_error_estimate();
+            assert!(
+                0.5 * error <= estimate && estimate <= 2.0 * error,
+                "{} {}",
+                estimate,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn integrating_again_keeps_the_output() {
+        let problem = HarmonicOscillator::default();
+        let (x0, x_end) = problem.t_span();
+        let mut solver = Dopri5::new(
+            problem,
+            x0,
+            problem.initial_state(),
+            x_end,
+            None,
+            1e-6,
+            1e-6,
+        );
+        solver.store_derivatives(true);
+        solver.integrate().unwrap();
+        let (x_out, y_out) = (solver.x_out().clone(), solver.y_out().clone());
+        let num_eval = solver.integrate().unwrap().num_eval();
+        assert_eq!(solver.x_out(), &x_out);
+        assert_eq!(solver.y_out(), &y_out);
+        assert_eq!(solver.dy_out().len(), x_out.len());
+        assert_eq!(solver.into_result().stats.num_eval(), num_eval);
+    }
+}
@@ -1,32 +1,198 @@
 //! Explicit euler method with fixed step size.
 
-use crate::dop_shared::{IntegrationError, Stats, System};
+use crate::dop_shared::{
+    event_values, find_segment, hermite_interpolation, linear_interpolation, locate_event,
+    step_count, ControlFlag, CsvSink,
+    EventAction, IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Output, Stats,
+    StepSchedule, StopPredicate, System, TrajectorySink, STEP_TOL,
+};
 
 use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
 use num_traits::Zero;
-use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf};
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SupersetOf};
+use std::fmt;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// Number of steps between two checks of the elapsed time set with `Euler::max_duration`
+const DURATION_CHECK_INTERVAL: u32 = 1000;
+
+// Relative extension of a step allowed to land on a scheduled event just beyond its end, which
+// absorbs the rounding error accumulated by the independent variable over many steps
+const SCHEDULE_TOL: f64 = 1.0e-3;
 
 /// Structure containing the parameters for the numerical integration.
+///
+/// The state may use any scalar type into which an `f64` converts, e.g. `SVector<f32, 3>` or
+/// `DVector<Complex<f64>>` for the Schrödinger equation, and the arithmetic on the state is then
+/// carried out in that type. The independent variable and the step sizes are kept
+/// in `f64`, as in the `System` trait, because the independent variable is accumulated over many
+/// steps and would drift in single precision. With a constant step size, the independent
+/// variable is moreover computed from the number of steps taken, as `x + n h` from the start of
+/// the integration, instead of adding up the steps, so that it stays on the grid of the steps over
+/// millions of them.
+///
+/// The scalar type must implement `Scalar`, `ClosedAdd`, `ClosedSub`, `ClosedMul`, `ClosedNeg`,
+/// `Zero` and `SupersetOf<f64>`, through which the step sizes are converted, but not `Copy`. The
+/// integration is then differentiable end to end with a dual number or the variable of a
+/// reverse-mode tape as scalar, e.g. to compute the derivative of the final state with respect
+/// to the initial state, or to parameters carried as constant components of the state.
 pub struct Euler<V, F>
 where
     F: System<V>,
 {
     f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: Vec<f64>,
+    output: Output<V>,
+    store_derivatives: bool,
+    dy_pending: Option<usize>,
+    dy_out: Vec<V>,
+    invariant_out: Vec<f64>,
+    quadrature: bool,
+    integral: f64,
+    quad_last: f64,
+    integral_out: Vec<f64>,
+    store_all: bool,
+    store_every: usize,
+    keep_last: Option<usize>,
+    event_tol: f64,
+    event_values: Vec<f64>,
+    event_cooldown: f64,
+    event_threshold: f64,
+    last_events: Vec<Option<f64>>,
+    check_finite: bool,
+    check_dimensions: bool,
+    max_growth: Option<f64>,
+    frozen: Vec<usize>,
+    max_duration: Option<Duration>,
+    progress_every: u32,
+    x0: f64,
+    estimate_error: bool,
+    err_out: Vec<V>,
+    max_internal_step: f64,
+    store_events: bool,
+    events_out: Vec<EventPoint<V>>,
+    record_events: bool,
+    event_log: EventLog<V>,
+    scheduled: Vec<f64>,
+    next_scheduled: usize,
+    k: V,
+    y_new: V,
+    mode: usize,
+    stop_when: Option<Box<StopPredicate<V>>>,
+    step_fn: Option<Box<StepSchedule<V>>>,
+    sink: Option<Box<dyn TrajectorySink<V>>>,
+    started: bool,
+    terminated: bool,
+    stats: Stats,
+}
+
+/// State at a zero crossing of `System::event_fn`, stored when enabled with
+/// [`Euler::store_events`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventPoint<V> {
+    /// Index of the event indicator which crossed zero.
+    pub event: usize,
+    /// Position of the state in [`Euler::x_out`] and [`Euler::y_out`].
+    pub index: usize,
+    /// Value of the independent variable at the crossing.
+    pub x: f64,
+    /// State at the crossing, before `System::on_event` is applied.
+    pub y: V,
+}
+
+/// Kind of an event recorded in the [`EventLog`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventKind {
+    /// Zero crossing of `System::event_fn` after which the integration continues.
+    ZeroCrossing,
+    /// Event scheduled with [`Euler::scheduled_events`].
+    Scheduled,
+    /// Zero crossing of `System::event_fn` for which `System::on_event` terminated the
+    /// integration.
+    Terminal,
+}
+
+/// Event handled during the integration, recorded when enabled with [`Euler::record_events`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventRecord<V> {
+    /// Index of the event indicator which crossed zero, or position of the time of a scheduled
+    /// event in the list given to [`Euler::scheduled_events`].
+    pub index: usize,
+    /// Value of the independent variable at the event.
+    pub x: f64,
+    /// State at the event, before `System::on_event` or `System::scheduled_event` is applied.
+    pub y_before: V,
+    /// State after `System::on_event` or `System::scheduled_event` is applied.
+    pub y_after: V,
+    /// Kind of the event.
+    pub kind: EventKind,
+}
+
+/// Events handled during the integration, in the order they occurred.
+pub type EventLog<V> = Vec<EventRecord<V>>;
+
+/// Complete state of an [`Euler`] solver, saved with [`Euler::save_state`] and restored with
+/// [`Euler::restore`] to resume an integration, e.g. after writing it to disk. The system itself,
+/// the predicate set with [`Euler::stop_when`], the schedule set with [`Euler::step_fn`] and the
+/// writer set with [`Euler::stream_to`] are not part of the state.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SolverState<V> {
     x: f64,
     y: V,
     x_end: f64,
     step_size: Vec<f64>,
     x_out: Vec<f64>,
     y_out: Vec<V>,
+    store_derivatives: bool,
+    dy_out: Vec<V>,
+    invariant_out: Vec<f64>,
+    quadrature: bool,
+    integral: f64,
+    quad_last: f64,
+    integral_out: Vec<f64>,
+    t_eval: Option<Vec<f64>>,
+    store_all: bool,
+    store_every: usize,
+    keep_last: Option<usize>,
+    event_tol: f64,
+    event_values: Vec<f64>,
+    event_cooldown: f64,
+    event_threshold: f64,
+    last_events: Vec<Option<f64>>,
+    check_finite: bool,
+    check_dimensions: bool,
+    max_growth: Option<f64>,
+    frozen: Vec<usize>,
+    max_duration: Option<Duration>,
+    progress_every: u32,
+    x0: f64,
+    estimate_error: bool,
+    err_out: Vec<V>,
+    max_internal_step: f64,
+    store_events: bool,
+    events_out: Vec<EventPoint<V>>,
+    record_events: bool,
+    event_log: EventLog<V>,
+    scheduled: Vec<f64>,
+    next_scheduled: usize,
+    mode: usize,
+    started: bool,
+    terminated: bool,
     stats: Stats,
 }
 
 impl<T, D: Dim, F> Euler<OVector<T, D>, F>
 where
-    f64: From<T>,
-    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    T: SupersetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
     F: System<OVector<T, D>>,
-    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
     DefaultAllocator: Allocator<T, D>,
 {
     /// Default initializer for the structure
@@ -37,85 +203,2123 @@ where
     /// * `x`           - Initial value of the independent variable (usually time)
     /// * `y`           - Initial value of the dependent variable(s)
     /// * `x_end`       - Final value of the independent variable
-    /// * `step_size`   - Step size(s) used in the method
+    /// * `step_size`   - Step sizes used in the method, see below
+    ///
+    /// # Step sizes
+    ///
+    /// The integration is organised in three nested levels and `step_size` must contain exactly
+    /// three strictly positive entries with `step_size[0] <= step_size[1] <= step_size[2]`, each
+    /// of `step_size[1]` and `step_size[2]` being a whole multiple of the previous entry:
+    ///
+    /// * `step_size[0]` - Step size of the euler method used to integrate `System::ode`
+    /// * `step_size[1]` - Interval between two calls to `System::event`
+    /// * `step_size[2]` - Interval between two calls to `System::observer`
+    ///
+    /// The euler steps can also be capped with [`max_internal_step`](Self::max_internal_step),
+    /// leaving `step_size[1]` and `step_size[2]` unchanged.
+    ///
+    /// If `x_end < x`, the integration proceeds backward in time and the output is stored in
+    /// decreasing order of the independent variable.
+    ///
+    /// The configuration is checked when calling [`integrate`](Self::integrate), which returns
+    /// `IntegrationError::InvalidStepConfig` if it is not satisfied. `step_size[1]` need not be a
+    /// multiple of `step_size[0]` when the euler steps are set otherwise, with
+    /// [`step_fn`](Self::step_fn) or a smaller [`max_internal_step`](Self::max_internal_step).
+    ///
+    /// The capacity of the output is reserved for the states stored at the observer steps, so that
+    /// it is not reallocated during the integration, and adjusted at its start to the settings.
     ///
     pub fn new(f:F, x: f64, y: OVector<T, D>, x_end: f64, step_size: Vec<f64>) -> Self {
-        Euler {
+        let (rows, cols) = y.shape_generic();
+        let mut euler = Euler {
             f,
             x,
             y,
             x_end,
             step_size,
-            x_out: Vec::new(),
-            y_out: Vec::new(),
+            output: Output::new(),
+            store_derivatives: false,
+            dy_pending: None,
+            dy_out: Vec::new(),
+            invariant_out: Vec::new(),
+            quadrature: false,
+            integral: 0.0,
+            quad_last: 0.0,
+            integral_out: Vec::new(),
+            store_all: true,
+            store_every: 1,
+            keep_last: None,
+            event_tol: 1.0e-10,
+            event_values: Vec::new(),
+            event_cooldown: 0.0,
+            event_threshold: 0.0,
+            last_events: Vec::new(),
+            check_finite: true,
+            check_dimensions: cfg!(debug_assertions),
+            max_growth: None,
+            frozen: Vec::new(),
+            max_duration: None,
+            progress_every: 1000,
+            x0: x,
+            estimate_error: false,
+            err_out: Vec::new(),
+            max_internal_step: f64::INFINITY,
+            store_events: false,
+            events_out: Vec::new(),
+            record_events: false,
+            event_log: Vec::new(),
+            scheduled: Vec::new(),
+            next_scheduled: 0,
+            k: OVector::zeros_generic(rows, cols),
+            y_new: OVector::zeros_generic(rows, cols),
+            mode: 0,
+            stop_when: None,
+            step_fn: None,
+            sink: None,
+            started: false,
+            terminated: false,
             stats: Stats::new(),
+        };
+        euler.reserve_output();
+        euler
+    }
+
+    /// Saves the complete state of the solver, including the settings and the output computed so
+    /// far.
+    #[cfg(feature = "serde")]
+    pub fn save_state(&self) -> SolverState<OVector<T, D>> {
+        SolverState {
+            x: self.x,
+            y: self.y.clone(),
+            x_end: self.x_end,
+            step_size: self.step_size.clone(),
+            x_out: self.output.x.clone(),
+            y_out: self.output.y.clone(),
+            store_derivatives: self.store_derivatives,
+            dy_out: self.dy_out.clone(),
+            invariant_out: self.invariant_out.clone(),
+            quadrature: self.quadrature,
+            integral: self.integral,
+            quad_last: self.quad_last,
+            integral_out: self.integral_out.clone(),
+            t_eval: self.output.t_eval().map(<[f64]>::to_vec),
+            store_all: self.store_all,
+            store_every: self.store_every,
+            keep_last: self.keep_last,
+            event_tol: self.event_tol,
+            event_values: self.event_values.clone(),
+            event_cooldown: self.event_cooldown,
+            event_threshold: self.event_threshold,
+            last_events: self.last_events.clone(),
+            check_finite: self.check_finite,
+            check_dimensions: self.check_dimensions,
+            max_growth: self.max_growth,
+            frozen: self.frozen.clone(),
+            max_duration: self.max_duration,
+            progress_every: self.progress_every,
+            x0: self.x0,
+            estimate_error: self.estimate_error,
+            err_out: self.err_out.clone(),
+            max_internal_step: self.max_internal_step,
+            store_events: self.store_events,
+            events_out: self.events_out.clone(),
+            record_events: self.record_events,
+            event_log: self.event_log.clone(),
+            scheduled: self.scheduled.clone(),
+            next_scheduled: self.next_scheduled,
+            mode: self.mode,
+            started: self.started,
+            terminated: self.terminated,
+            stats: self.stats,
         }
     }
 
+    /// Rebuilds a solver for the system `f` from a state saved with
+    /// [`save_state`](Self::save_state). The integration is then continued with
+    /// [`integrate`](Self::integrate) or [`integrate_to`](Self::integrate_to). A stateful system
+    /// should be restored by the caller.
+    #[cfg(feature = "serde")]
+    pub fn restore(f: F, state: SolverState<OVector<T, D>>) -> Self {
+        let (rows, cols) = state.y.shape_generic();
+        let mut euler = Euler {
+            f,
+            x: state.x,
+            y: state.y,
+            x_end: state.x_end,
+            step_size: state.step_size,
+            output: Output::with_steps(state.x_out, state.y_out, state.t_eval),
+            store_derivatives: state.store_derivatives,
+            dy_pending: None,
+            dy_out: state.dy_out,
+            invariant_out: state.invariant_out,
+            quadrature: state.quadrature,
+            integral: state.integral,
+            quad_last: state.quad_last,
+            integral_out: state.integral_out,
+            store_all: state.store_all,
+            store_every: state.store_every,
+            keep_last: state.keep_last,
+            event_tol: state.event_tol,
+            event_values: state.event_values,
+            event_cooldown: state.event_cooldown,
+            event_threshold: state.event_threshold,
+            last_events: state.last_events,
+            check_finite: state.check_finite,
+            check_dimensions: state.check_dimensions,
+            max_growth: state.max_growth,
+            frozen: state.frozen,
+            max_duration: state.max_duration,
+            progress_every: state.progress_every,
+            x0: state.x0,
+            estimate_error: state.estimate_error,
+            err_out: state.err_out,
+            max_internal_step: state.max_internal_step,
+            store_events: state.store_events,
+            events_out: state.events_out,
+            record_events: state.record_events,
+            event_log: state.event_log,
+            scheduled: state.scheduled,
+            next_scheduled: state.next_scheduled,
+            k: OVector::zeros_generic(rows, cols),
+            y_new: OVector::zeros_generic(rows, cols),
+            mode: state.mode,
+            stop_when: None,
+            step_fn: None,
+            sink: None,
+            started: state.started,
+            terminated: state.terminated,
+            stats: state.stats,
+        };
+        let samples = euler.output.sample(|t| euler.at(t));
+        euler.output.set_samples(samples);
+        euler
+    }
+
+    /// Returns a [`EulerBuilder`] to configure the solver with named options.
+    pub fn builder(f: F) -> EulerBuilder<OVector<T, D>, F> {
+        EulerBuilder::new(f)
+    }
+
+    /// Sets the current mode of a hybrid system (default 0). The derivative is evaluated with
+    /// `System::mode_ode` in this mode, which is updated by `System::next_mode` at every event.
+    pub fn mode(&mut self, mode: usize) {
+        self.mode = mode;
+    }
+
+    /// Getter for the current mode of a hybrid system.
+    pub fn current_mode(&self) -> usize {
+        self.mode
+    }
+
+    /// Clears the output and the statistics and sets new initial and final values, so that the
+    /// solver can be run again, e.g. in a parameter sweep, without reallocating the output. The
+    /// settings are kept, the mode is set back to 0 and the `System` is left untouched: a stateful
+    /// system should be reset by the caller.
+    pub fn reset(&mut self, x: f64, y: OVector<T, D>, x_end: f64) {
+        self.x = x;
+        self.y = y;
+        self.x_end = x_end;
+        self.output.clear();
+        self.dy_pending = None;
+        self.dy_out.clear();
+        self.invariant_out.clear();
+        self.integral = 0.0;
+        self.integral_out.clear();
+        self.err_out.clear();
+        self.events_out.clear();
+        self.event_log.clear();
+        self.event_values.clear();
+        self.last_events.clear();
+        self.started = false;
+        self.terminated = false;
+        self.mode = 0;
+        self.stats = Stats::new();
+    }
+
+    /// Sets whether the state is stored at every observer step (default) or only at the end points.
+    pub fn store_all_steps(&mut self, store_all: bool) {
+        self.store_all = store_all;
+    }
+
+    /// Sets the number of observer steps between two stored states (default 1). The initial and
+    /// final states are always stored. `System::observer` is still called at every observer step,
+    /// so that finer output can be recorded there if needed.
+    pub fn store_every(&mut self, n: usize) {
+        self.store_every = n;
+    }
+
+    /// Sets whether the derivatives `f(x, y)` at the stored states are returned by
+    /// [`dy_out`](Self::dy_out) (default false), in which case [`at`](Self::at) interpolates the
+    /// solution with cubic Hermite polynomials instead of linearly. The derivative at a stored
+    /// state is the one of the next euler step, so that storing it costs no evaluation of the
+    /// system, unless the state is changed by `System::event` or an event before that step, and
+    /// apart from the last stored state of every call to [`integrate_to`](Self::integrate_to).
+    pub fn store_derivatives(&mut self, store_derivatives: bool) {
+        self.store_derivatives = store_derivatives;
+        self.reserve_output();
+    }
+
+    /// Keeps only the last `k` stored states, discarding the oldest ones, so that the memory used
+    /// by the output stays bounded during long integrations. [`x_out`](Self::x_out) and
+    /// [`y_out`](Self::y_out) return the retained states in chronological order, and
+    /// [`at`](Self::at) and [`t_eval`](Self::t_eval) only cover the interval they span.
+    pub fn keep_last(&mut self, k: usize) {
+        self.keep_last = Some(k);
+    }
+
+    /// Streams the stored states to `writer` as CSV during the integration, with a header
+    /// `t,y0,y1,...` followed by one row per stored state, so that the trajectory of a long run
+    /// does not have to fit in memory. Only the last stored state is then retained in the output,
+    /// unless more are kept with [`keep_last`](Self::keep_last). The integration stops with
+    /// `IntegrationError::Io` if writing fails. The writer is flushed at the end of every call to
+    /// [`integrate`](Self::integrate) or [`integrate_to`](Self::integrate_to), and should be
+    /// buffered, e.g. with `std::io::BufWriter`.
+    pub fn stream_to(&mut self, writer: impl Write + 'static)
+    where
+        T: fmt::Display,
+    {
+        self.sink = Some(Box::new(CsvSink::new(writer)));
+    }
+
+    /// Pushes the stored states to `sink` during the integration, e.g. a
+    /// [`SoaTrajectory`](crate::dop_shared::SoaTrajectory) storing the components contiguously.
+    /// As with [`stream_to`](Self::stream_to), which is the case of a CSV writer, only the last
+    /// stored state is then retained in the output, unless more are kept with
+    /// [`keep_last`](Self::keep_last), and the integration stops with `IntegrationError::Io` if
+    /// the sink fails. A sink shared in an `Rc<RefCell<_>>` remains accessible after the
+    /// integration.
+    pub fn store_to(&mut self, sink: impl TrajectorySink<OVector<T, D>> + 'static) {
+        self.sink = Some(Box::new(sink));
+    }
+
+    /// Sets whether the state at every located zero crossing of `System::event_fn` is inserted into
+    /// the output in time order (default false), e.g. to measure the period of an oscillator from
+    /// the crossings of a threshold. The crossings are listed by [`events_out`](Self::events_out),
+    /// together with their position in [`x_out`](Self::x_out) and [`y_out`](Self::y_out), which
+    /// is only valid if the oldest states are not discarded with [`keep_last`](Self::keep_last)
+    /// or [`stream_to`](Self::stream_to).
+    pub fn store_events(&mut self, store_events: bool) {
+        self.store_events = store_events;
+    }
+
+    /// Sets whether the events handled during the integration are recorded in the
+    /// [`event_log`](Self::event_log) (default false), together with the states before and after
+    /// their action, e.g. to audit the switches of a hybrid system without logging from
+    /// `System::on_event`.
+    pub fn record_events(&mut self, record_events: bool) {
+        self.record_events = record_events;
+    }
+
+    /// Sets whether the integral of `System::quad` is accumulated over the steps with the
+    /// trapezoidal rule (default false), e.g. the cost `∫ L(x, y) dx` of a trajectory, so that it
+    /// is available without post-processing the output. The running integral at the stored states
+    /// is given by [`integral_out`](Self::integral_out) and the total by
+    /// [`integral`](Self::integral). The discrete changes of the state by the events do not
+    /// contribute to the integral.
+    pub fn running_quadrature(&mut self, quadrature: bool) {
+        self.quadrature = quadrature;
+    }
+
+    /// Sets the values of the independent variable, sorted in the direction of integration, at
+    /// which `System::scheduled_event` applies a discrete change to the state, e.g. the times of
+    /// a dosing schedule. The euler steps are shortened so as to land exactly on each of them,
+    /// and the states stored at these values are the modified ones, including the initial state
+    /// for the events scheduled at the initial value.
+    pub fn scheduled_events(&mut self, times: Vec<f64>) {
+        self.scheduled = times;
+    }
+
+    /// Sets the tolerance on the independent variable used to locate the zero crossings of `System::event_fn`.
+    pub fn event_tolerance(&mut self, event_tol: f64) {
+        self.event_tol = event_tol;
+    }
+
+    /// Sets the interval of the independent variable after an event during which the same event
+    /// indicator cannot trigger again (default 0), so that an indicator hovering around zero does
+    /// not fire repeatedly on numerical noise. The crossings within the cooldown are ignored.
+    /// The cooldown is irrelevant for an event terminating the integration.
+    pub fn event_cooldown(&mut self, cooldown: f64) {
+        self.event_cooldown = cooldown;
+    }
+
+    /// Sets the magnitude that an event indicator must reach on the other side of zero to trigger
+    /// an event (default 0). The sign of reference of an indicator is only updated once its
+    /// magnitude reaches the threshold, so that small oscillations around zero are ignored
+    /// (hysteresis), and the event is then located in the step where the threshold is reached.
+    pub fn event_threshold(&mut self, threshold: f64) {
+        self.event_threshold = threshold;
+    }
+
+    /// Sets whether the integration stops with `IntegrationError::NonFinite` when the state
+    /// becomes infinite or NaN (default) or keeps propagating it.
+    pub fn check_finite(&mut self, check_finite: bool) {
+        self.check_finite = check_finite;
+    }
+
+    /// Sets whether the integration stops with `IntegrationError::DimensionMismatch` when
+    /// `System::ode` leaves a derivative with a different number of components than the state,
+    /// e.g. after replacing a `DVector` with one of another length. Enabled by default in debug
+    /// builds only, so that release builds skip the check.
+    pub fn check_dimensions(&mut self, check_dimensions: bool) {
+        self.check_dimensions = check_dimensions;
+    }
+
+    /// Sets the factor `f` by which the largest magnitude of the components of the state may grow
+    /// over one step before the integration stops with `IntegrationError::Unstable`, catching the
+    /// instability of the method with a step too large for a stiff problem before the state
+    /// overflows (disabled by default). The check is skipped for the scalars which do not convert
+    /// to `f64`, such as complex numbers, and while the state vanishes.
+    pub fn max_growth_ratio(&mut self, f: f64) {
+        self.max_growth = Some(f);
+    }
+
+    /// Holds the components `indices` of the state fixed by zeroing their derivative after each
+    /// evaluation of `System::ode`, e.g. to integrate the other components with some of them
+    /// frozen at their initial values. Events, observers and the stored output still see the full
+    /// state, and the changes applied to the frozen components by the events are kept. Returns
+    /// `IntegrationError::InvalidComponent` if an index is beyond the dimension of the state.
+    pub fn freeze(&mut self, indices: &[usize]) -> Result<(), IntegrationError> {
+        let dim = self.y.len();
+        if let Some(&index) = indices.iter().find(|&&i| i >= dim) {
+            return Err(IntegrationError::InvalidComponent { index, dim });
+        }
+        self.frozen = indices.to_vec();
+        Ok(())
+    }
+
+    /// Sets the wall-clock time allowed for each call to [`integrate`](Self::integrate) or
+    /// [`integrate_to`](Self::integrate_to) (default unbounded). The elapsed time is checked every
+    /// 1000 steps and the integration stops with `IntegrationError::Timeout` once it exceeds
+    /// `max_duration`, keeping the trajectory computed so far in the output.
+    pub fn max_duration(&mut self, max_duration: Duration) {
+        self.max_duration = Some(max_duration);
+    }
+
+    /// Sets the number of steps between two calls to `System::progress` (default 1000), which is
+    /// also called at the end of the integration. With 0, it is only called at the end.
+    pub fn progress_every(&mut self, n: u32) {
+        self.progress_every = n;
+    }
+
+    /// Sets whether a local error estimate is computed at every step by step doubling (default
+    /// false). The estimate is the difference between two steps of size `h / 2` and the step of
+    /// size `h`, and costs one more evaluation of `System::ode` per step. The estimates are
+    /// returned by [`err_out`](Self::err_out).
+    pub fn estimate_error(&mut self, estimate_error: bool) {
+        self.estimate_error = estimate_error;
+    }
+
+    /// Sets a predicate called with the state after every step, which terminates the integration
+    /// when it returns true, e.g. `|_, y| y[0] < 1.0` to integrate until a population drops below
+    /// one. `x_end` then acts as a safety cap and may be infinite (`f64::INFINITY`, or
+    /// `f64::NEG_INFINITY` backward in time) to integrate until the predicate fires, in which case
+    /// [`progress`](crate::System::progress) reports 0 until the end of the integration.
+    pub fn stop_when(&mut self, stop_when: impl FnMut(f64, &OVector<T, D>) -> bool + 'static) {
+        self.stop_when = Some(Box::new(stop_when));
+    }
+
+    /// Sets a schedule returning the size of each euler step from the state at its start, e.g. to
+    /// refine the step during a known transient, which then replaces `step_size[0]`. The schedule
+    /// is followed as given, without any error control. The steps are shortened so as to end
+    /// exactly at the calls to `System::event`, which still take place every `step_size[1]`, and
+    /// at `x_end`. A step that is not strictly positive stops the integration with
+    /// `IntegrationError::InvalidStepConfig`.
+    pub fn step_fn(&mut self, step_fn: impl FnMut(f64, &OVector<T, D>) -> f64 + 'static) {
+        self.step_fn = Some(Box::new(step_fn));
+    }
+
+    /// Caps the size of the euler steps at `h`, e.g. at a stability limit, without changing the
+    /// intervals between the calls to `System::event` and to the observer. If `h` is smaller than
+    /// `step_size[0]`, each interval `step_size[1]` is divided into the smallest number of equal
+    /// steps not longer than `h`, so that a coarse output with fine steps only requires e.g.
+    /// `step_size = vec![h_obs, h_obs, h_obs]` and `max_internal_step(h)`. The steps returned by
+    /// a schedule set with [`step_fn`](Self::step_fn) are capped at `h` as well. `h` must be
+    /// strictly positive, otherwise [`integrate`](Self::integrate) returns
+    /// `IntegrationError::InvalidStepConfig`.
+    pub fn max_internal_step(&mut self, h: f64) {
+        self.max_internal_step = h;
+    }
+
+    /// Size of the euler steps given by `step_size[0]` and capped by
+    /// [`max_internal_step`](Self::max_internal_step).
+    fn internal_step(&self) -> f64 {
+        if self.max_internal_step < self.step_size[0] {
+            self.step_size[1] / step_count(self.step_size[1], self.max_internal_step) as f64
+        } else {
+            self.step_size[0]
+        }
+    }
+
+    /// Size of the next euler step towards `target`, read from the schedule set with
+    /// [`step_fn`](Self::step_fn) or given by `step_size[0]`, together with whether the step
+    /// reaches `target`.
+    fn next_step(&mut self, target: f64) -> Result<(f64, bool), IntegrationError> {
+        let h = match self.step_fn.as_mut() {
+            Some(step_fn) => step_fn(self.x, &self.y),
+            None => self.step_size[0],
+        };
+        if h.is_nan() || h <= 0.0 {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: format!("step_fn returned the step {} at x = {}", h, self.x),
+            });
+        }
+        let h = h.min(self.max_internal_step);
+        let remaining = (target - self.x).abs();
+        let last = remaining <= h * (1.0 + STEP_TOL);
+        Ok((if last { remaining } else { h }, last))
+    }
+
+    /// Number of points stored in the output with the current settings, apart from the states
+    /// at the events stored with [`store_events`](Self::store_events): the initial point and one
+    /// point every `store_every` observer steps up to `x_end` when storing all the steps,
+    /// bounded by the states retained with [`keep_last`](Self::keep_last) or
+    /// [`stream_to`](Self::stream_to).
+    fn num_outputs(&self) -> usize {
+        if !self.store_all || self.sink.is_some() {
+            return 2;
+        }
+        let num_steps = match self.step_size.get(2) {
+            Some(h) => step_count((self.x_end - self.x).abs(), *h),
+            None => 0,
+        };
+        let num_outputs = num_steps.div_ceil(self.store_every.max(1)) + 1;
+        match self.keep_last {
+            Some(k) => num_outputs.min(2 * k),
+            None => num_outputs,
+        }
+    }
+
+    /// Reserves the capacity of the output for [`num_outputs`](Self::num_outputs) points, so that
+    /// it is not reallocated during the integration.
+    fn reserve_output(&mut self) {
+        let n = self.num_outputs();
+        self.output.x.reserve_exact(n.saturating_sub(self.output.x.len()));
+        self.output.y.reserve_exact(n.saturating_sub(self.output.y.len()));
+        if self.store_derivatives {
+            self.dy_out.reserve_exact(n.saturating_sub(self.dy_out.len()));
+        }
+        self.invariant_out.reserve_exact(n.saturating_sub(self.invariant_out.len()));
+        if self.quadrature {
+            self.integral_out.reserve_exact(n.saturating_sub(self.integral_out.len()));
+        }
+    }
+
+    /// Checks that the step sizes satisfy the requirements documented in [`new`](Self::new).
+    fn validate_step_size(&self) -> Result<(), IntegrationError> {
+        let invalid = |reason: &str| {
+            Err(IntegrationError::InvalidStepConfig {
+                reason: reason.to_string(),
+            })
+        };
+        if self.step_size.len() != 3 {
+            return invalid("expected exactly three step sizes");
+        }
+        if self.step_size.iter().any(|h| !h.is_finite() || *h <= 0.0) {
+            return invalid("step sizes must be finite and strictly positive");
+        }
+        if self.step_size[0] > self.step_size[1] || self.step_size[1] > self.step_size[2] {
+            return invalid("step sizes must satisfy step_size[0] <= step_size[1] <= step_size[2]");
+        }
+        if self.max_internal_step.is_nan() || self.max_internal_step <= 0.0 {
+            return invalid("the maximum internal step must be strictly positive");
+        }
+        // The events and the observer are called at multiples of their own step only if each
+        // interval is made of a whole number of the inner ones
+        let whole = |outer: f64, inner: f64| {
+            let n = outer / inner;
+            (n - n.round()).abs() <= STEP_TOL * n
+        };
+        let fixed_steps = self.step_fn.is_none() && self.max_internal_step >= self.step_size[0];
+        if fixed_steps && !whole(self.step_size[1], self.step_size[0]) {
+            return invalid("step_size[1] must be a whole multiple of step_size[0]");
+        }
+        if !whole(self.step_size[2], self.step_size[1]) {
+            return invalid("step_size[2] must be a whole multiple of step_size[1]");
+        }
+        Ok(())
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
     /// Core integration method.
     pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
-        // Save initial values
-        self.x_out.push(self.x);
-        self.y_out.push(self.y.clone());
-        // Call Observer 
-        self.f.observer(self.x, &self.y);
-        
-        let num_steps = ((self.x_end - self.x)/ self.step_size[2]).ceil() as usize;
-        let num_steps_per_obs = (self.step_size[2]/ self.step_size[1]).ceil() as usize;
-        let num_steps_per_event = (self.step_size[1] / self.step_size[0]).ceil() as usize;
-
-        for _ in 0..num_steps {
-          for _ in 0..num_steps_per_obs {
-            let y_new = self.e_step();
-            self.y = y_new;
-            for _ in 0..num_steps_per_event {
-              let (x_new, y_new) = self.step();
-              self.x = x_new;
-              self.y = y_new;
-              self.stats.num_eval += 1;
+        self.integrate_to(self.x_end)
+    }
+
+    /// Advances the integration from the current value of the independent variable to `t`,
+    /// appending to the output. It may be called repeatedly with targets sorted in the direction
+    /// of integration, e.g. from a simulation loop, and the integration can be completed with
+    /// [`integrate`](Self::integrate). Returns `IntegrationError::InvalidOutputTimes` if `t` lies
+    /// outside of the interval between the current value and `x_end`, and does nothing once the
+    /// integration has been terminated by an event or `System::solout`. The returned statistics
+    /// are accumulated since the start of the integration. If the integration fails, e.g. with
+    /// `IntegrationError::NonFinite` or with `IntegrationError::EvalFailed` for a system wrapped
+    /// with [`fallible`](crate::dop_shared::fallible), the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate_to(&mut self, t: f64) -> Result<Stats, IntegrationError> {
+        let result = self
+            .integrate_steps(t)
+            .and_then(|_| self.resolve_derivative().map(|()| self.stats));
+        let flushed = self.flush_sink();
+        let result = result.and_then(|stats| flushed.map(|()| stats));
+        if let Some(k) = self.keep_last {
+            self.discard_oldest(k);
+        }
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Extends the integration to the later final value `x_end`, continuing from the current
+    /// state and appending to the output, e.g. once a completed integration turns out to be too
+    /// short, without integrating again from the start. The trajectory is the one given by
+    /// setting `x_end` from the start, up to the rounding of the grid point at the previous final
+    /// value, provided this value lies on the grid of the stored observer steps. Returns
+    /// `IntegrationError::InvalidOutputTimes` if `x_end` does not lie beyond the previous final
+    /// value in the direction of integration, which is the case for any value after an
+    /// integration over an empty interval, and does nothing once the integration has been
+    /// terminated by an event or `System::solout`.
+    pub fn extend_to(&mut self, x_end: f64) -> Result<Stats, IntegrationError> {
+        let x_start = if self.started { self.x0 } else { self.x };
+        if x_end.is_nan() || (x_end - self.x_end) * (self.x_end - x_start) <= 0.0 {
+            return Err(IntegrationError::InvalidOutputTimes {
+                reason: format!(
+                    "x_end {} does not extend the interval from {} to {}",
+                    x_end, x_start, self.x_end
+                ),
+            });
+        }
+        if x_end.is_infinite() && self.stop_when.is_none() {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "an infinite x_end requires a stop_when predicate".to_string(),
+            });
+        }
+        self.x_end = x_end;
+        self.integrate_to(x_end)
+    }
+
+    /// Integration loop of [`integrate_to`](Self::integrate_to), returning early on failure.
+    fn integrate_steps(&mut self, t: f64) -> Result<Stats, IntegrationError> {
+        if !self.started {
+            self.start()?;
+        }
+        // An empty interval of integration gives the initial point alone
+        if self.terminated || self.x_end == self.x0 {
+            return Ok(self.stats);
+        }
+        let (lo, hi) = if self.x <= self.x_end {
+            (self.x, self.x_end)
+        } else {
+            (self.x_end, self.x)
+        };
+        if !(lo..=hi).contains(&t) {
+            return Err(IntegrationError::InvalidOutputTimes {
+                reason: format!("target {} lies outside of [{}, {}]", t, lo, hi),
+            });
+        }
+
+        // Integrate backward in time if t < x
+        let direction = (t - self.x).signum();
+        let num_steps = if t.is_infinite() {
+            usize::MAX
+        } else {
+            step_count((t - self.x).abs(), self.step_size[2])
+        };
+        let num_steps_per_obs = step_count(self.step_size[2], self.step_size[1]);
+        let h_internal = self.internal_step();
+        let num_steps_per_event = step_count(self.step_size[1], h_internal);
+
+        let start = Instant::now();
+        let mut terminated = false;
+        let mut timed_out = false;
+        let mut h_last = 0.0;
+        // The steps land on the grid x0 + n h_internal, computed from the number of steps taken
+        // since the start, so that successive calls continue on the same grid
+        let x_origin = self.x0;
+        let mut n_grid = ((self.x - x_origin) * direction / h_internal + STEP_TOL).floor();
+        'outer: for i in 0..num_steps {
+          // The target may be reached before the last observer step when the step counts round up
+          if i > 0 && self.x == t {
+            break;
+          }
+          for j in 0..num_steps_per_obs {
+            if self.x == t {
+              break;
+            }
+            self.e_step();
+            self.ensure_finite(self.x, &self.y)?;
+            if !self.event_values.is_empty() {
+              let g = self.event_values(self.x, &self.y);
+              self.update_event_values(g);
+            }
+            // With a step schedule, the steps are taken until the end of the event interval
+            let event_end = self.step_fn.as_ref().map(|_| {
+              let end = self.x + direction * self.step_size[1];
+              let final_event = i + 1 == num_steps && j + 1 == num_steps_per_obs;
+              if final_event || (t - end) * direction <= self.step_size[1] * STEP_TOL {
+                t
+              } else {
+                end
+              }
+            });
+            let steps_per_event = match event_end {
+              Some(_) => usize::MAX,
+              None => num_steps_per_event,
+            };
+            for l in 0..steps_per_event {
+              // Shorten the last step so that the integration ends exactly at t
+              let remaining = (t - self.x).abs();
+              if remaining == 0.0 {
+                break;
+              }
+              let (h, reached, grid) = match event_end {
+                Some(event_end) => {
+                  let (h, last) = self.next_step(event_end)?;
+                  (h, last.then_some(event_end), None)
+                }
+                None => {
+                  let final_step = i + 1 == num_steps
+                    && j + 1 == num_steps_per_obs
+                    && l + 1 == num_steps_per_event;
+                  let last = final_step || remaining <= h_internal * (1.0 + STEP_TOL);
+                  if last {
+                    (remaining, Some(t), None)
+                  } else {
+                    n_grid += 1.0;
+                    let grid = x_origin + direction * n_grid * h_internal;
+                    ((grid - self.x).abs(), None, Some(grid))
+                  }
+                }
+              };
+              let action = self.advance(direction * h)?;
               self.stats.accepted_steps += 1;
+              h_last = direction * h;
+              if let Some(max_duration) = self.max_duration {
+                if self.stats.accepted_steps.is_multiple_of(DURATION_CHECK_INTERVAL)
+                  && start.elapsed() > max_duration
+                {
+                  timed_out = true;
+                  break 'outer;
+                }
+              }
+              if self.stats.accepted_steps.is_multiple_of(self.progress_every) {
+                self.f.progress(self.progress());
+              }
+              if action == EventAction::Terminate {
+                terminated = true;
+                break 'outer;
+              }
+              if let Some(x) = reached.or(grid) {
+                self.x = x;
+              }
+              if self.f.solout(self.x, &self.y) == ControlFlag::Stop
+                || self.stop_when.as_mut().is_some_and(|stop| stop(self.x, &self.y))
+              {
+                terminated = true;
+                break 'outer;
+              }
+              if reached.is_some() {
+                break;
+              }
             }
           }
+          self.clamp_state()?;
           // Call Observer 
-          self.f.observer(self.x, &self.y);
+          self.f.observer_full(self.x, &self.y, h_last, &self.stats);
+          if self.store_all && ((i + 1) % self.store_every == 0 || i + 1 == num_steps) {
+            self.store()?;
+          }
+        }
+        self.terminated = terminated;
+        // final state, unless it was just stored at the end of the last observer step
+        let stored = self.output.x.last() == Some(&self.x) && self.output.y.last() == Some(&self.y);
+        if (terminated || timed_out || !self.store_all) && !stored {
+            self.store()?;
+        }
+        if self.stats.accepted_steps == 0
+            || !self.stats.accepted_steps.is_multiple_of(self.progress_every)
+        {
+            self.f.progress(self.progress());
+        }
+        if timed_out {
+            return Err(IntegrationError::Timeout { x: self.x });
         }
-        // final state
-        self.x_out.push(self.x);
-        self.y_out.push(self.y.clone());
         Ok(self.stats)
     }
 
-    /// Performs one step of the forward euler method.
-    fn step(&self) -> (f64, OVector<T, D>) {
-        let (rows, cols) = self.y.shape_generic();
-        let mut k = vec![OVector::zeros_generic(rows, cols); 3];
+    /// Returns an iterator advancing the integration by one euler step per call to `next`, which
+    /// yields the state at the end of the step. The iterator ends once `x_end` is reached or once
+    /// the integration has been terminated by an event, `System::solout` or the predicate set with
+    /// [`stop_when`](Self::stop_when), and after yielding an error. Events are handled as in
+    /// [`integrate`](Self::integrate), but `System::event` and the observer are not called and
+    /// the states are not stored in the output, apart from the initial one.
+    ///
+    /// ```
+    /// # use ode_event_solvers::{dop_shared::ode_fn, Euler, Vector1};
+    /// # let f = ode_fn(|_, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = -y[0]);
+    /// # let mut solver = Euler::new(f, 0.0, Vector1::new(1.0), 1.0, vec![0.1; 3]);
+    /// # let (sender, receiver) = std::sync::mpsc::channel();
+    /// for item in solver.steps().take_while(|item| item.is_ok()) {
+    ///     let (x, y) = item?;
+    ///     sender.send((x, y))?;
+    /// }
+    /// # assert_eq!(receiver.try_iter().count(), 10);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn steps(
+        &mut self,
+    ) -> impl Iterator<Item = Result<(f64, OVector<T, D>), IntegrationError>> + '_ {
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let item = match self.step_once() {
+                Ok(true) => Ok((self.x, self.y.clone())),
+                Ok(false) => return None,
+                Err(e) => Err(e),
+            };
+            done = item.is_err();
+            Some(item)
+        })
+    }
+
+    /// Performs one euler step towards `x_end` for [`steps`](Self::steps). Returns false once the
+    /// integration is complete.
+    fn step_once(&mut self) -> Result<bool, IntegrationError> {
+        if !self.started {
+            self.start()?;
+        }
+        if self.terminated || self.x == self.x_end {
+            return Ok(false);
+        }
+        // Shorten the last step so that the integration ends exactly at x_end
+        let direction = (self.x_end - self.x).signum();
+        let (h, last) = self.next_step(self.x_end)?;
+        let action = self.advance(direction * h)?;
+        self.stats.accepted_steps += 1;
+        if action == EventAction::Terminate {
+            self.terminated = true;
+        } else {
+            if last {
+                self.x = self.x_end;
+            }
+            self.terminated = self.f.solout(self.x, &self.y) == ControlFlag::Stop
+                || self.stop_when.as_mut().is_some_and(|stop| stop(self.x, &self.y));
+        }
+        Ok(true)
+    }
+
+    /// Checks the configuration, saves the initial values and evaluates the event indicators at
+    /// the start of the integration.
+    fn start(&mut self) -> Result<(), IntegrationError> {
+        self.validate_step_size()?;
+        if self.store_every == 0 {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "store_every must be at least 1".to_string(),
+            });
+        }
+        if self.keep_last == Some(0) {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "keep_last must be at least 1".to_string(),
+            });
+        }
+        if self.x_end.is_infinite() && self.stop_when.is_none() {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "an infinite x_end requires a stop_when predicate".to_string(),
+            });
+        }
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // The settings may have changed the number of stored points since the construction
+        self.reserve_output();
+        // The state may have been replaced with one of another dimension by `reset`
+        let dim = self.y.len();
+        if let Some(&index) = self.frozen.iter().find(|&&i| i >= dim) {
+            return Err(IntegrationError::InvalidComponent { index, dim });
+        }
+        let direction = (self.x_end - self.x).signum();
+        if self.scheduled.windows(2).any(|w| (w[1] - w[0]) * direction < 0.0) {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "scheduled event times must be sorted in the direction of integration"
+                    .to_string(),
+            });
+        }
+        self.next_scheduled = self
+            .scheduled
+            .partition_point(|t| (t - self.x) * direction < 0.0);
+        self.refresh_quad();
+        if self.x != self.x_end && self.scheduled.get(self.next_scheduled) == Some(&self.x) {
+            self.apply_scheduled(self.x)?;
+        }
+        // Save initial values, without the derivative over an empty interval of integration
+        if self.x == self.x_end {
+            self.output.push(self.x, self.y.clone());
+            self.invariant_out.push(self.f.invariant(self.x, &self.y));
+            if self.quadrature {
+                self.integral_out.push(self.integral);
+            }
+            self.write_sink()?;
+        } else {
+            self.store()?;
+        }
+        // Call Observer 
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        self.event_values = self.event_values(self.x, &self.y);
+        self.last_events = vec![None; self.event_values.len()];
+        self.x0 = self.x;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Fraction of the integration interval completed so far.
+    fn progress(&self) -> f64 {
+        if self.x_end == self.x0 {
+            return 1.0;
+        }
+        (self.x - self.x0) / (self.x_end - self.x0)
+    }
 
-        self.f.ode(self.x, &self.y, &mut k[0]);
-        let x_new = self.x + self.step_size[0];
-        let y_new = &self.y
-            + (k[0].clone())
-                * (self.step_size[0]);
-        (x_new, y_new)
+    /// Appends the current state and its derivative to the output. With
+    /// [`keep_last`](Self::keep_last), the oldest states are discarded once twice the number of
+    /// retained states is reached, so that the cost of discarding them is amortised.
+    fn store(&mut self) -> Result<(), IntegrationError> {
+        self.resolve_derivative()?;
+        self.output.push(self.x, self.y.clone());
+        if self.store_derivatives {
+            // The derivative is taken from the next step
+            self.dy_pending = Some(self.mode);
+        }
+        self.invariant_out.push(self.f.invariant(self.x, &self.y));
+        if self.quadrature {
+            self.integral_out.push(self.integral);
+        }
+        self.write_sink()?;
+        if let Some(k) = self.keep_last {
+            if self.output.x.len() >= 2 * k {
+                self.discard_oldest(k);
+            }
+        } else if self.sink.is_some() {
+            self.discard_oldest(1);
+        }
+        Ok(())
+    }
+
+    /// Pushes the current state to the sink set with [`stream_to`](Self::stream_to) or
+    /// [`store_to`](Self::store_to).
+    fn write_sink(&mut self) -> Result<(), IntegrationError> {
+        if let Some(sink) = &mut self.sink {
+            sink.push(self.x, &self.y).map_err(|e| IntegrationError::Io {
+                x: self.x,
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(())
     }
 
-  fn e_step(&mut self) -> OVector<T, D> {
+    /// Flushes the sink set with [`stream_to`](Self::stream_to) or [`store_to`](Self::store_to).
+    fn flush_sink(&mut self) -> Result<(), IntegrationError> {
+        if let Some(sink) = &mut self.sink {
+            sink.flush().map_err(|e| IntegrationError::Io {
+                x: self.x,
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Discards the oldest stored states, keeping the last `k` ones.
+    fn discard_oldest(&mut self, k: usize) {
+        let n = self.output.x.len().saturating_sub(k);
+        self.output.x.drain(..n);
+        self.output.y.drain(..n);
+        self.dy_out.drain(..n.min(self.dy_out.len()));
+        self.invariant_out.drain(..n);
+        if self.quadrature {
+            self.integral_out.drain(..n);
+        }
+    }
+
+    /// Adds the trapezoidal integral of `System::quad` over the step of size `h` ending at the
+    /// current state to the running integral, when enabled with
+    /// [`running_quadrature`](Self::running_quadrature).
+    fn accumulate_quad(&mut self, h: f64) {
+        if self.quadrature {
+            let q = self.f.quad(self.x, &self.y);
+            self.integral += 0.5 * (self.quad_last + q) * h;
+            self.quad_last = q;
+        }
+    }
+
+    /// Evaluates `System::quad` again after a discrete change of the state, from which the next
+    /// step of the running integral starts.
+    fn refresh_quad(&mut self) {
+        if self.quadrature {
+            self.quad_last = self.f.quad(self.x, &self.y);
+        }
+    }
+
+    /// Advances the solution by `h`, locating and handling the events crossed on the way and
+    /// stopping on the scheduled events.
+    fn advance(&mut self, h: f64) -> Result<EventAction, IntegrationError> {
+        let mut remaining = h;
+        loop {
+            // Land on the next scheduled event if it lies within the step
+            let scheduled = self.next_scheduled_within(remaining);
+            let h_step = scheduled.map_or(remaining, |t| t - self.x);
+            let x_new = self.step(h_step)?;
+            self.stats.num_eval += 1;
+            self.ensure_finite(x_new, &self.y_new)?;
+            self.ensure_stable(x_new)?;
+            let g_new = (!self.event_values.is_empty())
+                .then(|| self.event_values(x_new, &self.y_new));
+            let located = match &g_new {
+                Some(g_new) => self.locate_event(&self.y_new, h_step, g_new),
+                None => None,
+            };
+            match located {
+                None => {
+                    self.x = x_new;
+                    std::mem::swap(&mut self.y, &mut self.y_new);
+                    self.f.project(self.x, &mut self.y);
+                    self.accumulate_quad(h_step);
+                    if let Some(g_new) = g_new {
+                        self.update_event_values(g_new);
+                    }
+                    let Some(t) = scheduled else {
+                        return Ok(EventAction::Continue);
+                    };
+                    self.apply_scheduled(t)?;
+                    remaining -= h_step;
+                    if remaining * h <= 0.0 {
+                        return Ok(EventAction::Continue);
+                    }
+                }
+                Some((idx, dx)) => {
+                    self.y = self.interpolate(&self.y_new, h_step, dx);
+                    self.x += dx;
+                    self.accumulate_quad(dx);
+                    if self.store_events {
+                        self.store()?;
+                        self.events_out.push(EventPoint {
+                            event: idx,
+                            index: self.output.x.len() - 1,
+                            x: self.x,
+                            y: self.y.clone(),
+                        });
+                    }
+                    let y_before = self.record_events.then(|| self.y.clone());
+                    let action = self.f.on_event(idx, self.x, &mut self.y);
+                    if let Some(y_before) = y_before {
+                        let kind = match action {
+                            EventAction::Continue => EventKind::ZeroCrossing,
+                            EventAction::Terminate => EventKind::Terminal,
+                        };
+                        self.event_log.push(EventRecord {
+                            index: idx,
+                            x: self.x,
+                            y_before,
+                            y_after: self.y.clone(),
+                            kind,
+                        });
+                    }
+                    self.mode = self.f.next_mode(idx, self.x, &self.y, self.mode);
+                    self.ensure_finite(self.x, &self.y)?;
+                    self.refresh_quad();
+                    self.event_values = self.event_values(self.x, &self.y);
+                    // The located state lies just past the crossing, so that the indicator is
+                    // considered to be at zero and cannot fire again until it has left it, e.g.
+                    // when `on_event` reverses the velocity of a bouncing ball
+                    self.event_values[idx] = 0.0;
+                    self.last_events[idx] = Some(self.x);
+                    remaining -= dx;
+                    if action == EventAction::Terminate {
+                        self.stats.terminal_event = Some(idx);
+                    }
+                    if action == EventAction::Terminate || remaining * h <= 0.0 {
+                        return Ok(action);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Value of the next scheduled event if it lies within a step of size `h`, a value slightly
+    /// beyond the end of the step being attributed to the step.
+    fn next_scheduled_within(&self, h: f64) -> Option<f64> {
+        let t = *self.scheduled.get(self.next_scheduled)?;
+        let fraction = (t - self.x) / h;
+        (fraction > 0.0 && fraction <= 1.0 + SCHEDULE_TOL).then_some(t)
+    }
+
+    /// Moves the solution exactly onto the scheduled value `t` reached by the last step and calls
+    /// `System::scheduled_event` for each of the events scheduled at `t`.
+    fn apply_scheduled(&mut self, t: f64) -> Result<(), IntegrationError> {
+        self.x = t;
+        while self.scheduled.get(self.next_scheduled) == Some(&t) {
+            let y_before = self.record_events.then(|| self.y.clone());
+            self.f.scheduled_event(t, &mut self.y);
+            if let Some(y_before) = y_before {
+                self.event_log.push(EventRecord {
+                    index: self.next_scheduled,
+                    x: t,
+                    y_before,
+                    y_after: self.y.clone(),
+                    kind: EventKind::Scheduled,
+                });
+            }
+            self.next_scheduled += 1;
+        }
+        self.ensure_finite(self.x, &self.y)?;
+        self.refresh_quad();
+        if !self.event_values.is_empty() {
+            self.event_values = self.event_values(self.x, &self.y);
+        }
+        Ok(())
+    }
+
+    /// Applies `System::clamp` to the current state, refreshing the values of reference of the event
+    /// indicators if the state was modified.
+    fn clamp_state(&mut self) -> Result<(), IntegrationError> {
+        let y = self.y.clone();
+        self.f.clamp(self.x, &mut self.y);
+        if self.y != y {
+            self.ensure_finite(self.x, &self.y)?;
+            self.refresh_quad();
+            if !self.event_values.is_empty() {
+                self.event_values = self.event_values(self.x, &self.y);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the earliest zero crossing of the event indicators over a step of size `h` ending at `y_new`.
+    /// Returns the index of the event and the distance from the start of the step to the crossing.
+    fn locate_event(&self, y_new: &OVector<T, D>, h: f64, g_new: &[f64]) -> Option<(usize, f64)> {
+        locate_event(
+            &self.f,
+            self.x,
+            h,
+            &self.event_values,
+            g_new,
+            self.event_tol,
+            |dx| self.interpolate(y_new, h, dx),
+            |idx, dx| {
+                let x_event = self.x + dx;
+                g_new[idx].abs() < self.event_threshold
+                    || self.last_events[idx]
+                        .is_some_and(|x| (x_event - x).abs() < self.event_cooldown)
+            },
+        )
+    }
+
+    /// Linear interpolation of the state at a distance `dx` into a step of size `h` ending at `y_new`.
+    fn interpolate(&self, y_new: &OVector<T, D>, h: f64, dx: f64) -> OVector<T, D> {
+        let t: T = nalgebra::convert(dx / h);
+        &self.y + (y_new - &self.y) * t
+    }
+
+    /// Returns `IntegrationError::NonFinite` if the check is enabled and `y` contains infinite or NaN values.
+    fn ensure_finite(&self, x: f64, y: &OVector<T, D>) -> Result<(), IntegrationError> {
+        // A value is finite when its product with zero vanishes, which also holds for complex
+        // values
+        if self.check_finite && !y.iter().all(|y_i| y_i.clone() * T::zero() == T::zero()) {
+            return Err(IntegrationError::NonFinite { x });
+        }
+        Ok(())
+    }
+
+    /// Returns `IntegrationError::Unstable` if the state grew by more than the factor set with
+    /// [`max_growth_ratio`](Self::max_growth_ratio) over the step from `y` to `y_new`.
+    fn ensure_stable(&self, x: f64) -> Result<(), IntegrationError> {
+        let Some(max_growth) = self.max_growth else {
+            return Ok(());
+        };
+        let magnitude = |y: &OVector<T, D>| {
+            y.iter().try_fold(0.0, |m: f64, y_i| y_i.to_subset().map(|y_i| m.max(y_i.abs())))
+        };
+        if let (Some(m), Some(m_new)) = (magnitude(&self.y), magnitude(&self.y_new)) {
+            if m > 0.0 && m_new > max_growth * m {
+                return Err(IntegrationError::Unstable { x });
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates the event indicators of the system.
+    fn event_values(&self, x: f64, y: &OVector<T, D>) -> Vec<f64> {
+        event_values(&self.f, x, y)
+    }
+
+    /// Updates the event indicators of reference with the values `g` at the current state,
+    /// keeping the previous value of the indicators whose magnitude is below the threshold set
+    /// with [`event_threshold`](Self::event_threshold), and keeping at zero the indicators of
+    /// the events handled at the current state.
+    fn update_event_values(&mut self, g: Vec<f64>) {
+        for (idx, (g_ref, g_i)) in self.event_values.iter_mut().zip(g).enumerate() {
+            if self.last_events[idx] == Some(self.x) && *g_ref == 0.0 {
+                continue;
+            }
+            if g_i.abs() >= self.event_threshold || *g_ref == 0.0 {
+                *g_ref = g_i;
+            }
+        }
+    }
+
+    /// Evaluates the derivative at the last stored state if it has not been taken from the step
+    /// starting there, e.g. because the state has since been changed by an event.
+    fn resolve_derivative(&mut self) -> Result<(), IntegrationError> {
+        let Some(mode) = self.dy_pending.take() else {
+            return Ok(());
+        };
+        let (Some(&x), Some(y)) = (self.output.x.last(), self.output.y.last()) else {
+            return Ok(());
+        };
+        let y = std::mem::replace(&mut self.y, y.clone());
+        let current_mode = std::mem::replace(&mut self.mode, mode);
+        self.k.fill(T::zero());
+        let result = self.eval_derivative(x);
+        self.y = y;
+        self.mode = current_mode;
+        result?;
+        self.stats.num_eval += 1;
+        self.dy_out.push(self.k.clone());
+        Ok(())
+    }
+
+    /// Evaluates the right-hand side at `x` and the current state into `k`, returning
+    /// `IntegrationError::EvalFailed` if the system fails to evaluate.
+    fn eval_derivative(&mut self, x: f64) -> Result<(), IntegrationError> {
+        self.f
+            .try_mode_ode(self.mode, x, &self.y, &mut self.k)
+            .map_err(|e| IntegrationError::EvalFailed {
+                x,
+                reason: e.reason,
+            })?;
+        self.check_derivative()?;
+        self.freeze_derivative();
+        Ok(())
+    }
+
+    /// Zeroes the components of the derivative in `k` frozen with [`freeze`](Self::freeze).
+    fn freeze_derivative(&mut self) {
+        for &i in &self.frozen {
+            self.k[i] = T::zero();
+        }
+    }
+
+    /// Returns `IntegrationError::DimensionMismatch` if the derivative written into `k` does not
+    /// have as many components as the state, when enabled with
+    /// [`check_dimensions`](Self::check_dimensions).
+    fn check_derivative(&self) -> Result<(), IntegrationError> {
+        if self.check_dimensions && self.k.len() != self.y.len() {
+            return Err(IntegrationError::DimensionMismatch {
+                expected: self.y.len(),
+                got: self.k.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Performs one step of the forward euler method, writing the new state into `y_new` in place,
+    /// and returns the new value of the independent variable.
+    fn step(&mut self, h: f64) -> Result<f64, IntegrationError> {
+        let reused = self.dy_pending == Some(self.mode)
+            && self.output.x.last() == Some(&self.x)
+            && self.output.y.last() == Some(&self.y);
+        if !reused {
+            self.resolve_derivative()?;
+        }
+        self.k.fill(T::zero());
+        self.eval_derivative(self.x)?;
+        if reused {
+            self.dy_pending = None;
+            self.dy_out.push(self.k.clone());
+        }
+        let x_new = self.x + h;
+        let h_t: T = nalgebra::convert(h);
+        self.y_new.copy_from(&self.k);
+        self.y_new *= h_t;
+        self.y_new += &self.y;
+        if self.estimate_error {
+            // Two steps of size h / 2, the first one sharing the derivative of the full step
+            let half_h: T = nalgebra::convert(0.5 * h);
+            let y_mid = &self.y + self.k.clone() * half_h.clone();
+            self.k.fill(T::zero());
+            let x_mid = self.x + 0.5 * h;
+            self.f
+                .try_mode_ode(self.mode, x_mid, &y_mid, &mut self.k)
+                .map_err(|e| IntegrationError::EvalFailed {
+                    x: x_mid,
+                    reason: e.reason,
+                })?;
+            self.check_derivative()?;
+            self.freeze_derivative();
+            self.stats.num_eval += 1;
+            let y_half = y_mid + self.k.clone() * half_h;
+            self.err_out.push(y_half - &self.y_new);
+        }
+        Ok(x_new)
+    }
+
+  fn e_step(&mut self) {
         // note: does not advance time (happens instantaneously)
-        let (rows, cols) = self.y.shape_generic();
-        let mut k = vec![OVector::zeros_generic(rows, cols); 3]; //dy
-        self.f.event(self.x, &self.y, &mut k[0]);
-        let y_new = &self.y
-            + k[0].clone();
-        y_new
+        self.k.fill(T::zero()); //dy
+        self.f.event(self.x, &self.y, &mut self.k);
+        self.y += &self.k;
+        self.refresh_quad();
     }
 
-    /// Getter for the independent variable's output.
+    /// Getter for the independent variable's output. Unless [`t_eval`](Self::t_eval) is set, it
+    /// starts with the initial point, followed by the points stored according to
+    /// [`store_all_steps`](Self::store_all_steps), [`store_every`](Self::store_every) and
+    /// [`store_events`](Self::store_events), and ends with the last point reached, each of them
+    /// appearing once even when the end point coincides with a stored observer step.
     pub fn x_out(&self) -> &Vec<f64> {
-        &self.x_out
+        self.output.x_out()
     }
 
     /// Getter for the dependent variables' output.
     pub fn y_out(&self) -> &Vec<OVector<T, D>> {
-        &self.y_out
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set. Empty unless
+    /// enabled with [`store_derivatives`](Self::store_derivatives).
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Getter for the values of `System::invariant` at the stored states, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn invariant_out(&self) -> &Vec<f64> {
+        &self.invariant_out
+    }
+
+    /// Getter for the running integral of `System::quad` at the stored states, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set. Empty unless
+    /// enabled with [`running_quadrature`](Self::running_quadrature).
+    pub fn integral_out(&self) -> &Vec<f64> {
+        &self.integral_out
+    }
+
+    /// Returns the integral of `System::quad` from the initial point to
+    /// [`x_final`](Self::x_final), zero unless enabled with
+    /// [`running_quadrature`](Self::running_quadrature).
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Getter for the states at the zero crossings of `System::event_fn`. Empty unless enabled
+    /// with [`store_events`](Self::store_events).
+    pub fn events_out(&self) -> &Vec<EventPoint<OVector<T, D>>> {
+        &self.events_out
+    }
+
+    /// Getter for the events handled during the integration, in the order they occurred. Empty
+    /// unless enabled with [`record_events`](Self::record_events).
+    pub fn event_log(&self) -> &EventLog<OVector<T, D>> {
+        &self.event_log
+    }
+
+    /// Getter for the local error estimates, one per step of the euler method in the order they
+    /// were taken. Empty unless enabled with [`estimate_error`](Self::estimate_error).
+    pub fn err_out(&self) -> &Vec<OVector<T, D>> {
+        &self.err_out
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives when they are stored with
+    /// [`store_derivatives`](Self::store_derivatives), linearly between the stored states
+    /// otherwise. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        if self.dy_out.len() < i + 2 {
+            return Some(linear_interpolation(
+                self.output.x[i],
+                &self.output.y[i],
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                x,
+            ));
+        }
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for Euler<OVector<T, D>, F>
+where
+    T: SupersetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: System<OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Euler::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Euler::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Euler::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Euler::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Euler::y_final(self)
     }
 }
 
+impl<V, F: System<V>> MethodInfo for Euler<V, F> {
+    fn order(&self) -> u32 {
+        1
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+/// Summary of the configuration and progress of the solver, leaving out the system so that it does
+/// not need to implement `Debug`.
+impl<V, F> fmt::Debug for Euler<V, F>
+where
+    F: System<V>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Euler")
+            .field("x", &self.x)
+            .field("x_end", &self.x_end)
+            .field("ode_step", &self.step_size[0])
+            .field("event_step", &self.step_size[1])
+            .field("observer_step", &self.step_size[2])
+            .field("stored_points", &self.output.x.len())
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+
+/// Builder for [`Euler`] with named options.
+///
+/// The initial condition, the final value of the independent variable and the ode step are
+/// required. The event step defaults to the ode step, the observer step defaults to the event
+/// step and, unlike [`Euler::new`], only the end points are stored unless
+/// [`store_all`](Self::store_all) is set.
+///
+/// ```
+/// # use ode_event_solvers::{dop_shared::ode_fn, EulerBuilder, Vector1};
+/// # let system = ode_fn(|_, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = -y[0]);
+/// # let y0 = Vector1::new(1.0);
+/// let mut solver = EulerBuilder::new(system)
+///     .initial(0.0, y0)
+///     .end(10.0)
+///     .ode_step(1.0e-3)
+///     .observer_step(0.1)
+///     .store_all(true)
+///     .build()?;
+/// # solver.integrate()?;
+/// # assert_eq!(solver.x_out().len(), 101);
+/// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+/// ```
+pub struct EulerBuilder<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: Option<V>,
+    x_end: Option<f64>,
+    ode_step: Option<f64>,
+    event_step: Option<f64>,
+    observer_step: Option<f64>,
+    store_all: bool,
+    stop_when: Option<Box<StopPredicate<V>>>,
+}
+
+impl<T, D: Dim, F> EulerBuilder<OVector<T, D>, F>
+where
+    T: SupersetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: System<OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Creates a builder for the system `f` with no option set.
+    pub fn new(f: F) -> Self {
+        EulerBuilder {
+            f,
+            x: 0.0,
+            y: None,
+            x_end: None,
+            ode_step: None,
+            event_step: None,
+            observer_step: None,
+            store_all: false,
+            stop_when: None,
+        }
+    }
+
+    /// Sets the initial value of the independent variable and of the dependent variable(s).
+    pub fn initial(mut self, x: f64, y: OVector<T, D>) -> Self {
+        self.x = x;
+        self.y = Some(y);
+        self
+    }
+
+    /// Sets the final value of the independent variable.
+    pub fn end(mut self, x_end: f64) -> Self {
+        self.x_end = Some(x_end);
+        self
+    }
+
+    /// Sets the step size of the euler method used to integrate `System::ode`.
+    pub fn ode_step(mut self, h: f64) -> Self {
+        self.ode_step = Some(h);
+        self
+    }
+
+    /// Sets the interval between two calls to `System::event`.
+    pub fn event_step(mut self, h: f64) -> Self {
+        self.event_step = Some(h);
+        self
+    }
+
+    /// Sets the interval between two calls to `System::observer`.
+    pub fn observer_step(mut self, h: f64) -> Self {
+        self.observer_step = Some(h);
+        self
+    }
+
+    /// Sets whether the state is stored at every observer step or only at the end points (default).
+    pub fn store_all(mut self, store_all: bool) -> Self {
+        self.store_all = store_all;
+        self
+    }
+
+    /// Sets a predicate terminating the integration when it returns true, see
+    /// [`Euler::stop_when`]. The final value of the independent variable is then optional and
+    /// defaults to `f64::INFINITY`.
+    pub fn stop_when(
+        mut self,
+        stop_when: impl FnMut(f64, &OVector<T, D>) -> bool + 'static,
+    ) -> Self {
+        self.stop_when = Some(Box::new(stop_when));
+        self
+    }
+
+    /// Builds the solver, returning `IntegrationError::InvalidStepConfig` if a required option is
+    /// missing or if the step sizes do not satisfy the requirements documented in [`Euler::new`].
+    pub fn build(self) -> Result<Euler<OVector<T, D>, F>, IntegrationError> {
+        let missing = |option: &str| IntegrationError::InvalidStepConfig {
+            reason: format!("missing {}", option),
+        };
+        let y = self.y.ok_or_else(|| missing("initial condition"))?;
+        let x_end = match (self.x_end, &self.stop_when) {
+            (Some(x_end), _) => x_end,
+            (None, Some(_)) => f64::INFINITY,
+            (None, None) => return Err(missing("final value of the independent variable")),
+        };
+        let ode_step = self.ode_step.ok_or_else(|| missing("ode step"))?;
+        let event_step = self.event_step.unwrap_or(ode_step);
+        let observer_step = self.observer_step.unwrap_or(event_step);
+
+        let mut euler = Euler::new(
+            self.f,
+            self.x,
+            y,
+            x_end,
+            vec![ode_step, event_step, observer_step],
+        );
+        euler.store_all_steps(self.store_all);
+        euler.stop_when = self.stop_when;
+        euler.validate_step_size()?;
+        Ok(euler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::EventDirection;
+    use crate::{DVector, SVector, Vector1, Vector2};
+    use simba::scalar::SubsetOf;
+
+    const G: f64 = 9.81;
+    const RESTITUTION: f64 = 0.8;
+
+    /// Ball dropped from a height of 1, bouncing on the ground `y[0] = 0`.
+    struct BouncingBall {
+        direction: EventDirection,
+        bounces: Vec<f64>,
+    }
+
+    impl System<Vector2<f64>> for BouncingBall {
+        fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+            dy[0] = y[1];
+            dy[1] = -G;
+        }
+
+        fn num_events(&self) -> usize {
+            1
+        }
+
+        fn event_fn(&self, _x: f64, y: &Vector2<f64>, out: &mut [f64]) {
+            out[0] = y[0];
+        }
+
+        fn event_direction(&self, _idx: usize) -> EventDirection {
+            self.direction
+        }
+
+        fn on_event(&mut self, _idx: usize, x: f64, y: &mut Vector2<f64>) -> EventAction {
+            y[1] *= -RESTITUTION;
+            self.bounces.push(x);
+            EventAction::Continue
+        }
+    }
+
+    fn bounce(direction: EventDirection) -> (Vec<f64>, Stats) {
+        let ball = BouncingBall {
+            direction,
+            bounces: Vec::new(),
+        };
+        let mut solver = Euler::new(ball, 0.0, Vector2::new(1.0, 0.0), 1.5, vec![1e-4, 1e-3, 1e-2]);
+        solver.event_tolerance(1e-12);
+        let stats = solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 1.5);
+        (solver.f.bounces.clone(), stats)
+    }
+
+    #[test]
+    fn bouncing_ball_locates_the_impacts() {
+        let t1 = (2.0 / G).sqrt();
+        let t2 = t1 + 2.0 * RESTITUTION * G * t1 / G;
+        for direction in [EventDirection::Falling, EventDirection::Both] {
+            let (bounces, _) = bounce(direction);
+            assert_eq!(bounces.len(), 2, "{:?}", direction);
+            assert!((bounces[0] - t1).abs() < 1e-3);
+            assert!((bounces[1] - t2).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn event_does_not_fire_again_after_reversing_the_state() {
+        let (falling, falling_stats) = bounce(EventDirection::Falling);
+        let (both, both_stats) = bounce(EventDirection::Both);
+        assert_eq!(falling, both);
+        assert_eq!(falling_stats.num_eval(), both_stats.num_eval());
+    }
+
+    /// Exponential growth `y' = y`.
+    struct Growth;
+
+    impl System<Vector1<f64>> for Growth {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+    }
+
+    #[test]
+    fn integrates_backward_in_time() {
+        let e = std::f64::consts::E;
+        let steps = vec![1e-4, 1e-3, 1e-1];
+        let mut forward = Euler::new(Growth, 0.0, Vector1::new(1.0), 1.0, steps.clone());
+        forward.integrate().unwrap();
+        let mut backward = Euler::new(Growth, 1.0, Vector1::new(e), 0.0, steps);
+        backward.integrate().unwrap();
+
+        assert_eq!(backward.x_final(), 0.0);
+        assert!(backward.x_out().windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(backward.x_out().len(), forward.x_out().len());
+        let points = backward.x_out().iter().zip(backward.y_out()).rev();
+        for ((x_b, y_b), (x_f, y_f)) in points.zip(forward.x_out().iter().zip(forward.y_out())) {
+            assert!((x_b - x_f).abs() < 1e-12);
+            assert!((y_b[0] - y_f[0]).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn reserves_the_exact_output_capacity() {
+        let mut solver = Euler::new(Growth, 0.0, Vector1::new(1.0), 1.0, vec![1e-4, 1e-3, 1e-2]);
+        assert_eq!(solver.output.x.capacity(), 101);
+        assert_eq!(solver.output.y.capacity(), 101);
+        solver.integrate().unwrap();
+        // The output was not reallocated
+        assert_eq!(solver.x_out().len(), 101);
+        assert_eq!(solver.output.x.capacity(), 101);
+        assert_eq!(solver.output.y.capacity(), 101);
+    }
+
+    #[test]
+    fn time_grid_does_not_drift() {
+        let constant = crate::dop_shared::ode_fn(|_, _y: &Vector1<f64>, dy: &mut Vector1<f64>| {
+            dy[0] = 0.0;
+        });
+        let mut solver = Euler::new(constant, 0.0, Vector1::new(0.0), 1000.0, vec![0.01, 0.1, 1.0]);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 1000.0);
+        assert_eq!(solver.x_out().len(), 1001);
+        // Adding up the steps drifts by many ULPs over a hundred thousand steps
+        let mut sum = 0.0;
+        for _ in 0..100_000 {
+            sum += 0.01;
+        }
+        assert!((sum - 1000.0f64).abs() > 100.0 * f64::EPSILON * 1000.0);
+        for (k, x) in solver.x_out().iter().enumerate() {
+            let k = k as f64;
+            assert!((x - k).abs() <= f64::EPSILON * k, "{} {}", x, k);
+        }
+    }
+
+    #[test]
+    fn stores_the_end_points_exactly_once() {
+        let mut solver = Euler::new(Growth, 0.0, Vector1::new(1.0), 0.0, vec![1e-3, 1e-2, 1e-1]);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_out(), &vec![0.0]);
+        // The last observer step lands exactly on the end point
+        for (store_all, every, len) in [(true, 1, 5), (false, 1, 2), (true, 3, 3), (true, 10, 2)] {
+            let steps = vec![1e-3, 1e-2, 0.25];
+            let mut solver = Euler::new(Growth, 0.0, Vector1::new(1.0), 1.0, steps);
+            solver.store_all_steps(store_all);
+            solver.store_every(every);
+            solver.integrate().unwrap();
+            let x_out = solver.x_out();
+            assert_eq!(x_out.first(), Some(&0.0));
+            assert_eq!(x_out.last(), Some(&1.0));
+            assert!(x_out.windows(2).all(|w| w[0] < w[1]), "{:?}", x_out);
+            assert_eq!(x_out.len(), len, "{:?}", x_out);
+            assert_eq!(solver.y_out().len(), len);
+        }
+    }
+
+    #[test]
+    fn soa_sink_reconstructs_the_default_trajectory() {
+        use crate::dop_shared::{SoaTrajectory, VecTrajectory};
+        use std::{cell::RefCell, rc::Rc};
+
+        let oscillator = || crate::dop_shared::ode_fn(
+            |_, y: &Vector2<f64>, dy: &mut Vector2<f64>| *dy = Vector2::new(y[1], -y[0]),
+        );
+        let y0 = Vector2::new(1.0, 0.0);
+        let steps = vec![1e-3, 1e-2, 0.1];
+        let mut default = Euler::new(oscillator(), 0.0, y0, 2.0, steps.clone());
+        default.integrate().unwrap();
+
+        let soa = Rc::new(RefCell::new(SoaTrajectory::new()));
+        let mut solver = Euler::new(oscillator(), 0.0, y0, 2.0, steps.clone());
+        solver.store_to(soa.clone());
+        solver.integrate().unwrap();
+        let soa = soa.borrow();
+        assert_eq!(soa.len(), default.x_out().len());
+        assert_eq!(soa.dim(), 2);
+        assert_eq!(soa.x(), &default.x_out()[..]);
+        for (k, y) in default.y_out().iter().enumerate() {
+            assert_eq!(soa.state(k).as_slice(), y.as_slice());
+            assert_eq!(soa.component(1)[k], y[1]);
+        }
+
+        let vec = Rc::new(RefCell::new(VecTrajectory::default()));
+        let mut solver = Euler::new(oscillator(), 0.0, y0, 2.0, steps);
+        solver.store_to(vec.clone());
+        solver.integrate().unwrap();
+        assert_eq!(vec.borrow().x, *default.x_out());
+        assert_eq!(vec.borrow().y, *default.y_out());
+    }
+
+    /// Constant system recording the values of the independent variable at the calls to
+    /// `System::event`.
+    struct EventTimes(Vec<f64>);
+
+    impl System<Vector1<f64>> for EventTimes {
+        fn ode(&self, _x: f64, _y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = 0.0;
+        }
+
+        fn event(&mut self, x: f64, _y: &Vector1<f64>, _dy: &mut Vector1<f64>) {
+            self.0.push(x);
+        }
+    }
+
+    #[test]
+    fn rejects_steps_that_are_not_whole_multiples() {
+        for steps in [vec![0.03, 0.1, 0.25], vec![0.025, 0.1, 0.25], vec![0.03, 0.1, 0.2]] {
+            let mut solver = Euler::new(EventTimes(Vec::new()), 0.0, Vector1::new(0.0), 1.0, steps);
+            assert!(matches!(
+                solver.integrate(),
+                Err(IntegrationError::InvalidStepConfig { .. })
+            ));
+        }
+        // The events and the observer are called at multiples of their own step
+        let steps = vec![0.025, 0.05, 0.25];
+        let mut solver = Euler::new(EventTimes(Vec::new()), 0.0, Vector1::new(0.0), 1.0, steps);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_out().len(), 5);
+        for (k, x) in solver.x_out().iter().enumerate() {
+            assert!((x - 0.25 * k as f64).abs() < 1e-15);
+        }
+        assert_eq!(solver.f.0.len(), 20);
+        for (k, x) in solver.f.0.iter().enumerate() {
+            assert!((x - 0.05 * k as f64).abs() < 1e-15);
+        }
+    }
+
+    /// Decay `y' = -y` whose state is halved by `System::event` at every event step when `kick` is
+    /// set.
+    struct Kicked {
+        kick: bool,
+    }
+
+    impl System<Vector1<f64>> for Kicked {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = -y[0];
+        }
+
+        fn event(&mut self, x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            if self.kick && x > 0.0 {
+                dy[0] = -0.5 * y[0];
+            }
+        }
+    }
+
+    #[test]
+    fn derivatives_are_stored_on_request_only() {
+        let run = |kick: bool, store_derivatives: bool| {
+            let mut solver = Euler::new(Kicked { kick }, 0.0, Vector1::new(1.0), 1.0, vec![0.1; 3]);
+            solver.store_derivatives(store_derivatives);
+            let stats = solver.integrate().unwrap();
+            for ((x, y), dy) in solver.x_out().iter().zip(solver.y_out()).zip(solver.dy_out()) {
+                assert_eq!(dy[0], -y[0], "{}", x);
+            }
+            (stats.num_eval(), solver.dy_out().len())
+        };
+        assert_eq!(run(false, false), (10, 0));
+        // The derivatives are those of the next steps, apart from the last one
+        assert_eq!(run(false, true), (11, 11));
+        // The events change the stored states before the next steps
+        assert_eq!(run(true, false), (10, 0));
+        assert_eq!(run(true, true), (20, 11));
+    }
+
+    #[test]
+    fn hermite_interpolation_beats_linear_interpolation() {
+        let f = crate::dop_shared::ode_fn(|x, _y: &Vector1<f64>, dy: &mut Vector1<f64>| {
+            dy[0] = x.cos();
+        });
+        let mut solver = Euler::new(f, 0.0, Vector1::new(0.0), 3.0, vec![1e-4, 0.1, 0.3]);
+        solver.store_derivatives(true);
+        solver.integrate().unwrap();
+        let (x_out, y_out) = (solver.x_out(), solver.y_out());
+        let (mut hermite, mut linear) = (0.0f64, 0.0f64);
+        for i in 0..x_out.len() - 1 {
+            let x = 0.5 * (x_out[i] + x_out[i + 1]);
+            hermite = hermite.max((solver.at(x).unwrap()[0] - x.sin()).abs());
+            linear = linear.max((0.5 * (y_out[i][0] + y_out[i + 1][0]) - x.sin()).abs());
+        }
+        assert!(hermite < 1e-4, "{}", hermite);
+        assert!(linear > 10.0 * hermite, "{} {}", linear, hermite);
+    }
+
+    /// Decay `y_i' = -k_i y_i` of fifty components with the rates `k_i = (i + 1) / 10`, recording
+    /// the time at which the first component falls below one half.
+    struct Decays {
+        halved: Option<f64>,
+    }
+
+    impl System<DVector<f64>> for Decays {
+        fn ode(&self, _x: f64, y: &DVector<f64>, dy: &mut DVector<f64>) {
+            for i in 0..y.len() {
+                dy[i] = -0.1 * (i + 1) as f64 * y[i];
+            }
+        }
+
+        fn num_events(&self) -> usize {
+            1
+        }
+
+        fn event_fn(&self, _x: f64, y: &DVector<f64>, out: &mut [f64]) {
+            out[0] = y[0] - 0.5;
+        }
+
+        fn on_event(&mut self, _idx: usize, x: f64, _y: &mut DVector<f64>) -> EventAction {
+            self.halved = Some(x);
+            EventAction::Continue
+        }
+    }
+
+    #[test]
+    fn integrates_states_of_runtime_dimension() {
+        let exact = |x: f64| DVector::from_fn(50, |i, _| (-0.1 * (i + 1) as f64 * x).exp());
+        let decays = Decays { halved: None };
+        let mut solver = Euler::new(decays, 0.0, exact(0.0), 10.0, vec![1e-3, 1e-2, 1.0]);
+        solver.store_derivatives(true);
+        solver.event_tolerance(1e-10);
+        solver.t_eval(vec![2.5, 5.0, 7.5]);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_out(), &vec![2.5, 5.0, 7.5]);
+        for (x, y) in solver.x_out().iter().zip(solver.y_out()) {
+            assert_eq!(y.len(), 50);
+            assert!((y - exact(*x)).amax() < 2e-3);
+        }
+        assert!((solver.at(3.3).unwrap() - exact(3.3)).amax() < 2e-3);
+        assert!((solver.f.halved.unwrap() - 10.0 * 2.0f64.ln()).abs() < 2e-3);
+    }
+
+    #[test]
+    fn integrates_single_precision_states() {
+        let decay = crate::dop_shared::ode_fn(|_, y: &SVector<f32, 3>, dy: &mut SVector<f32, 3>| {
+            *dy = -y;
+        });
+        let y0 = SVector::<f32, 3>::new(1.0, 2.0, 3.0);
+        let mut solver = Euler::new(decay, 0.0, y0, 1.0, vec![1e-3, 1e-2, 0.1]);
+        solver.integrate().unwrap();
+        let y: &SVector<f32, 3> = solver.y_final();
+        assert!((y - y0 * (-1.0f32).exp()).amax() < 2e-3);
+        assert_eq!(solver.y_out().len(), 11);
+    }
+
+    /// Dual number `v + d e` with `e^2 = 0`, carrying the derivative `d` of the value `v`. It is
+    /// deliberately not `Copy`, as the variables of a reverse-mode tape.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Dual {
+        v: f64,
+        d: f64,
+    }
+
+    impl std::ops::Add for Dual {
+        type Output = Dual;
+        fn add(self, rhs: Dual) -> Dual {
+            Dual { v: self.v + rhs.v, d: self.d + rhs.d }
+        }
+    }
+
+    impl std::ops::Sub for Dual {
+        type Output = Dual;
+        fn sub(self, rhs: Dual) -> Dual {
+            Dual { v: self.v - rhs.v, d: self.d - rhs.d }
+        }
+    }
+
+    impl std::ops::Mul for Dual {
+        type Output = Dual;
+        fn mul(self, rhs: Dual) -> Dual {
+            Dual { v: self.v * rhs.v, d: self.d * rhs.v + self.v * rhs.d }
+        }
+    }
+
+    impl std::ops::Neg for Dual {
+        type Output = Dual;
+        fn neg(self) -> Dual {
+            Dual { v: -self.v, d: -self.d }
+        }
+    }
+
+    impl std::ops::AddAssign for Dual {
+        fn add_assign(&mut self, rhs: Dual) {
+            *self = self.clone() + rhs;
+        }
+    }
+
+    impl std::ops::SubAssign for Dual {
+        fn sub_assign(&mut self, rhs: Dual) {
+            *self = self.clone() - rhs;
+        }
+    }
+
+    impl std::ops::MulAssign for Dual {
+        fn mul_assign(&mut self, rhs: Dual) {
+            *self = self.clone() * rhs;
+        }
+    }
+
+    impl Zero for Dual {
+        fn zero() -> Dual {
+            Dual { v: 0.0, d: 0.0 }
+        }
+        fn is_zero(&self) -> bool {
+            self.v == 0.0 && self.d == 0.0
+        }
+    }
+
+    impl SubsetOf<Dual> for f64 {
+        fn to_superset(&self) -> Dual {
+            Dual { v: *self, d: 0.0 }
+        }
+        fn from_superset_unchecked(element: &Dual) -> f64 {
+            element.v
+        }
+        fn is_in_subset(element: &Dual) -> bool {
+            element.d == 0.0
+        }
+    }
+
+    /// Logistic decay `y' = -y^2`.
+    struct Quadratic;
+
+    impl<T: Scalar + ClosedMul + ClosedNeg> System<Vector1<T>> for Quadratic {
+        fn ode(&self, _x: f64, y: &Vector1<T>, dy: &mut Vector1<T>) {
+            dy[0] = -(y[0].clone() * y[0].clone());
+        }
+    }
+
+    #[test]
+    fn differentiates_through_the_integration_with_dual_numbers() {
+        let steps = vec![1e-3, 1e-2, 0.1];
+        let y0 = Vector1::new(Dual { v: 1.0, d: 1.0 });
+        let mut solver = Euler::new(Quadratic, 0.0, y0, 2.0, steps.clone());
+        solver.integrate().unwrap();
+        let dual = solver.y_final()[0].clone();
+
+        let final_state = |y0: f64| {
+            let mut solver = Euler::new(Quadratic, 0.0, Vector1::new(y0), 2.0, steps.clone());
+            solver.integrate().unwrap();
+            solver.y_final()[0]
+        };
+        assert_eq!(dual.v, final_state(1.0));
+        let eps = 1e-6;
+        let finite_difference = (final_state(1.0 + eps) - final_state(1.0 - eps)) / (2.0 * eps);
+        assert!((dual.d - finite_difference).abs() < 1e-8, "{} {}", dual.d, finite_difference);
+        // Derivative of the exact solution 1 / (1 + x y0)
+        assert!((dual.d - 1.0 / 9.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn complex_state_traces_the_unit_circle() {
+        use nalgebra::Complex;
+        let rotation = crate::dop_shared::ode_fn(
+            |_, y: &Vector1<Complex<f64>>, dy: &mut Vector1<Complex<f64>>| {
+                dy[0] = Complex::<f64>::i() * y[0];
+            },
+        );
+        let x_end = 2.0 * std::f64::consts::PI;
+        let y0 = Vector1::new(Complex::new(1.0, 0.0));
+        let steps = vec![x_end / 80_000.0, x_end / 800.0, x_end / 8.0];
+        let mut solver = Euler::new(rotation, 0.0, y0, x_end, steps);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_out().len(), 9);
+        for (x, y) in solver.x_out().iter().zip(solver.y_out()) {
+            assert!((y[0].norm_sqr().sqrt() - 1.0).abs() < 1e-3);
+            assert!((y[0] - Complex::new(x.cos(), x.sin())).norm_sqr() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn extending_the_interval_matches_a_single_run() {
+        let steps = vec![1e-4, 1e-3, 1e-1];
+        let mut extended = Euler::new(Growth, 0.0, Vector1::new(1.0), 1.0, steps.clone());
+        extended.integrate().unwrap();
+        extended.extend_to(2.0).unwrap();
+        let mut single = Euler::new(Growth, 0.0, Vector1::new(1.0), 2.0, steps);
+        single.integrate().unwrap();
+        assert_eq!(extended.x_final(), 2.0);
+        assert_eq!(extended.x_out().len(), single.x_out().len());
+        let points = extended.x_out().iter().zip(extended.y_out());
+        for ((x_e, y_e), (x_s, y_s)) in points.zip(single.x_out().iter().zip(single.y_out())) {
+            assert!((x_e - x_s).abs() <= 4.0 * f64::EPSILON * x_s, "{} {}", x_e, x_s);
+            assert!((y_e[0] - y_s[0]).abs() <= 1e-12 * y_s[0], "{} {}", y_e[0], y_s[0]);
+        }
+        assert!(matches!(
+            extended.extend_to(1.5),
+            Err(IntegrationError::InvalidOutputTimes { .. })
+        ));
+    }
+
+    /// Elimination `y' = -y` of a drug given as a unit bolus at every scheduled time.
+    struct Dosing;
+
+    impl System<Vector1<f64>> for Dosing {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = -y[0];
+        }
+
+        fn scheduled_event(&mut self, _x: f64, y: &mut Vector1<f64>) {
+            y[0] += 1.0;
+        }
+    }
+
+    #[test]
+    fn scheduled_doses_give_a_sawtooth_profile() {
+        let mut solver = Euler::new(Dosing, 0.0, Vector1::new(0.0), 4.0, vec![1e-4, 1e-2, 0.25]);
+        solver.scheduled_events(vec![0.0, 1.0, 2.0, 3.0]);
+        solver.integrate().unwrap();
+        for (x, y) in solver.x_out().iter().zip(solver.y_out()) {
+            // Doses given at 0, 1, ..., n, the last one at the start of the current period
+            let n = x.floor().min(3.0);
+            let peak: f64 = (0..=n as i32).map(|j| (-j as f64).exp()).sum();
+            let exact = peak * (n - x).exp();
+            assert!((y[0] - exact).abs() < 1e-3, "x = {}: {} {}", x, y[0], exact);
+        }
+    }
+
+    /// Exponential growth `y' = y` accumulating the integral of `y`.
+    struct Accumulated;
+
+    impl System<Vector1<f64>> for Accumulated {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+
+        fn quad(&self, _x: f64, y: &Vector1<f64>) -> f64 {
+            y[0]
+        }
+    }
+
+    #[test]
+    fn running_quadrature_integrates_the_solution() {
+        let steps = vec![1e-4, 1e-2, 0.1];
+        let mut solver = Euler::new(Accumulated, 0.0, Vector1::new(1.0), 1.0, steps);
+        solver.running_quadrature(true);
+        solver.integrate().unwrap();
+        let e = std::f64::consts::E;
+        assert!((solver.integral() - (e - 1.0)).abs() < 1e-3, "{}", solver.integral());
+        assert_eq!(solver.integral_out().len(), solver.x_out().len());
+        assert_eq!(solver.integral_out().last(), Some(&solver.integral()));
+        for (x, integral) in solver.x_out().iter().zip(solver.integral_out()) {
+            assert!((integral - (x.exp() - 1.0)).abs() < 1e-3);
+        }
+    }
+}
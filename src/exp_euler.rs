@@ -0,0 +1,286 @@
+//! Exponential Euler method with fixed step size for semilinear systems.
+
+use crate::dop_shared::{
+    step_count, validate_step_size, ControlFlag, IntegrationError, IntegrationResult, MethodInfo,
+    OdeSolver, SemilinearSystem, Stats,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OMatrix, OVector, RealField};
+use simba::scalar::SubsetOf;
+
+/// Degree of the Taylor polynomials approximating the exponential and `phi_1` functions of the
+/// scaled matrix, whose norm is at most 1/2.
+const TAYLOR_DEGREE: usize = 16;
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Each step `y_new = exp(h A) y + h phi_1(h A) g(x, y)`, with `phi_1(z) = (exp(z) - 1) / z`,
+/// treats the linear part exactly so that the step size is not limited by the stiffness of `A`.
+/// The method is of first order in the nonlinear remainder. The matrix functions of `h A` are
+/// computed by scaling and squaring once per step size, which costs `O(n^3)` operations for a
+/// system of dimension `n`, and each step then costs two matrix-vector products and one evaluation
+/// of `SemilinearSystem::nonlinear`.
+pub struct ExponentialEuler<V, F>
+where
+    F: SemilinearSystem<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    y_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> ExponentialEuler<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    F: SemilinearSystem<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the SemilinearSystem<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        ExponentialEuler {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.y);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, cols) = self.y.shape_generic();
+        let mut a = OMatrix::zeros_generic(rows, rows);
+        self.f.linear_operator(&mut a);
+        let mut g = OVector::zeros_generic(rows, cols);
+        let mut exp = OMatrix::identity_generic(rows, rows);
+        let mut phi1 = OMatrix::identity_generic(rows, rows);
+        let mut h_functions = f64::NAN;
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            // The matrix functions are only recomputed when the step size changes
+            if h != h_functions {
+                let h_t: T = nalgebra::convert(h);
+                (exp, phi1) = exp_and_phi1(&(&a * h_t));
+                h_functions = h;
+            }
+            self.f.nonlinear(self.x, &self.y, &mut g);
+            self.stats.num_eval += 1;
+            self.y = &exp * &self.y + &phi1 * &g * h;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.y);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        &self.y_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        IntegrationResult {
+            x: self.x_out,
+            y: self.y_out,
+            stats: self.stats,
+        }
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for ExponentialEuler<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    F: SemilinearSystem<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        ExponentialEuler::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        ExponentialEuler::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        ExponentialEuler::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        ExponentialEuler::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        ExponentialEuler::y_final(self)
+    }
+}
+
+impl<V, F: SemilinearSystem<V>> MethodInfo for ExponentialEuler<V, F> {
+    fn order(&self) -> u32 {
+        1
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        true
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+/// Computes `exp(z)` and `phi_1(z)` by scaling and squaring: both functions are approximated by
+/// their Taylor polynomials at `z / 2^s`, whose 1-norm is at most 1/2, and then brought back to
+/// `z` with `phi_1(2z) = phi_1(z) (exp(z) + I) / 2` and `exp(2z) = exp(z)^2`.
+fn exp_and_phi1<T, D: Dim>(z: &OMatrix<T, D, D>) -> (OMatrix<T, D, D>, OMatrix<T, D, D>)
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    DefaultAllocator: Allocator<T, D, D>,
+{
+    let (rows, _) = z.shape_generic();
+    let norm = z
+        .column_iter()
+        .map(|c| {
+            c.iter()
+                .fold(0.0, |acc: f64, z_ij| acc + f64::from(*z_ij).abs())
+        })
+        .fold(0.0, f64::max);
+    let s = if norm > 0.5 {
+        (norm / 0.5).log2().ceil() as i32
+    } else {
+        0
+    };
+    let scale: T = nalgebra::convert(0.5_f64.powi(s));
+    let z = z * scale;
+
+    let identity = OMatrix::identity_generic(rows, rows);
+    let mut term = identity.clone();
+    let mut exp = identity.clone();
+    let mut phi1 = identity.clone();
+    for k in 1..=TAYLOR_DEGREE {
+        term = &term * &z * nalgebra::convert::<f64, T>(1.0 / k as f64);
+        exp += &term;
+        phi1 += &term * nalgebra::convert::<f64, T>(1.0 / (k + 1) as f64);
+    }
+    let half: T = nalgebra::convert(0.5);
+    for _ in 0..s {
+        phi1 = &phi1 * (&exp + &identity) * half;
+        exp = &exp * &exp;
+    }
+    (exp, phi1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linear::MatrixExponential;
+    use nalgebra::{Matrix2, Vector2};
+
+    /// Stiff linear system `y' = A y + b` with a constant forcing, whose eigenvalues are -1000
+    /// and -1.
+    struct Forced;
+
+    const A: Matrix2<f64> = Matrix2::new(-1000.0, 999.0, 0.0, -1.0);
+    const B: Vector2<f64> = Vector2::new(1.0, 2.0);
+
+    impl SemilinearSystem<Vector2<f64>> for Forced {
+        fn linear_operator(&self, out: &mut Matrix2<f64>) {
+            *out = A;
+        }
+
+        fn nonlinear(&self, _x: f64, _y: &Vector2<f64>, out: &mut Vector2<f64>) {
+            *out = B;
+        }
+    }
+
+    #[test]
+    fn integrates_a_stiff_linear_system_exactly() {
+        let y0 = Vector2::new(1.0, -1.0);
+        // A step size far beyond the stability limit of an explicit method
+        let mut solver = ExponentialEuler::new(Forced, 0.0, y0, 5.0, 0.1);
+        solver.integrate().unwrap();
+        // y(x) = exp(A x) (y0 + A^-1 b) - A^-1 b
+        let equilibrium = A.lu().solve(&B).unwrap();
+        let exp = MatrixExponential::new(A);
+        for (x, y) in solver.x_out().iter().zip(solver.y_out()) {
+            let exact = exp.exp(*x) * (y0 + equilibrium) - equilibrium;
+            assert!(
+                (y - exact).norm() < 1e-10,
+                "x = {}: {}",
+                x,
+                (y - exact).norm()
+            );
+        }
+    }
+}
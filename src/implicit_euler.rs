@@ -0,0 +1,393 @@
+//! Implicit (backward) euler method with fixed step size.
+
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, find_segment, linear_interpolation, newton_converged, reject_events, step_count,
+    validate_step_size, ControlFlag, Factorization, IntegrationError, IntegrationResult, LogLevel,
+    MethodInfo, NonlinearSolver, OdeSolver, Output, SolverLog, Stats, System,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OMatrix, OVector, RealField};
+use simba::scalar::SubsetOf;
+
+/// Structure containing the parameters for the numerical integration.
+pub struct ImplicitEuler<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    newton_tol: f64,
+    max_newton_iter: u32,
+    nonlinear_solver: NonlinearSolver,
+    output: Output<V>,
+    stats: Stats,
+    log: SolverLog,
+}
+
+impl<T, D, F> ImplicitEuler<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`               - Structure implementing the System<V> trait
+    /// * `x`               - Initial value of the independent variable (usually time)
+    /// * `y`               - Initial value of the dependent variable(s)
+    /// * `x_end`           - Final value of the independent variable
+    /// * `step_size`       - Step size used in the method
+    /// * `newton_tol`      - Tolerance on the maximum norm of the Newton update
+    /// * `max_newton_iter` - Maximum number of Newton iterations per step
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        step_size: f64,
+        newton_tol: f64,
+        max_newton_iter: u32,
+    ) -> Self {
+        ImplicitEuler {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            newton_tol,
+            max_newton_iter,
+            nonlinear_solver: NonlinearSolver::Newton,
+            output: Output::new(),
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the strategy solving the nonlinear equation of each step (default
+    /// [`NonlinearSolver::Newton`]). The functional iteration needs no Jacobian but converges
+    /// only when `h * L < 1` for a Lipschitz constant `L` of the system.
+    pub fn nonlinear_solver(&mut self, nonlinear_solver: NonlinearSolver) {
+        self.nonlinear_solver = nonlinear_solver;
+    }
+
+    /// Sets a logger receiving a [`LogRecord`] at the debug level for every step, at the warn level
+    /// for every nearly singular iteration matrix and at the error level before an
+    /// `IntegrationError` is returned. Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.step_size, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, _) = self.y.shape_generic();
+        let mut mass = OMatrix::identity_generic(rows, rows);
+        self.f.mass_matrix(&mut mass);
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let step = self.step(h, &mass);
+            check_eval(&self.f)?;
+            let (x_new, y_new) = step?;
+            self.x = if last { self.x_end } else { x_new };
+            self.y = y_new;
+            self.f.project(self.x, &mut self.y);
+            self.log
+                .log(LogLevel::Debug, self.x, h, || "step accepted".to_string());
+            self.stats.accepted_steps += 1;
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the backward euler method by solving
+    /// `y_new - y - h * f(x_new, y_new) = 0` with a simplified Newton iteration, or with the
+    /// functional iteration, which is the same iteration with a zero Jacobian.
+    fn step(
+        &mut self,
+        h: f64,
+        mass: &OMatrix<T, D, D>,
+    ) -> Result<(f64, OVector<T, D>), IntegrationError> {
+        let (rows, cols) = self.y.shape_generic();
+        let x_new = self.x + h;
+        let h_t: T = nalgebra::convert(h);
+
+        // The Jacobian is evaluated once per step at the initial guess
+        let mut y_new = self.y.clone();
+        let mut jac = OMatrix::zeros_generic(rows, rows);
+        if self.nonlinear_solver == NonlinearSolver::Newton {
+            self.f.jacobian(x_new, &y_new, &mut jac);
+            self.stats.num_jac_eval += 1;
+        }
+        let lu = Factorization::new(mass - jac * h_t, self.f.jacobian_bandwidth());
+        self.log.factorization(self.x, h, &lu);
+
+        let mut dy = OVector::zeros_generic(rows, cols);
+        for _ in 0..self.max_newton_iter {
+            self.f.ode(x_new, &y_new, &mut dy);
+            self.stats.num_eval += 1;
+            self.stats.num_newton_iter += 1;
+            let residual = mass * (&y_new - &self.y) - dy.clone() * h;
+            let delta = match lu.solve(&residual) {
+                Some(delta) => delta,
+                None => return Err(IntegrationError::NewtonDidNotConverge { x: self.x }),
+            };
+            y_new -= &delta;
+            if newton_converged(delta.as_slice(), self.newton_tol) {
+                return Ok((x_new, y_new));
+            }
+        }
+        Err(IntegrationError::NewtonDidNotConverge { x: self.x })
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` using the stored output. Returns `None` if `x` lies outside
+    /// of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(linear_interpolation(
+            self.output.x[i],
+            &self.output.y[i],
+            self.output.x[i + 1],
+            &self.output.y[i + 1],
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D, F> OdeSolver<OVector<T, D>> for ImplicitEuler<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        ImplicitEuler::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        ImplicitEuler::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        ImplicitEuler::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        ImplicitEuler::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        ImplicitEuler::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for ImplicitEuler<V, F> {
+    fn order(&self) -> u32 {
+        1
+    }
+
+    fn is_implicit(&self) -> bool {
+        true
+    }
+
+    fn is_a_stable(&self) -> bool {
+        true
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Vector1, Vector2};
+    use nalgebra::Matrix2;
+
+    /// Exponential growth `y' = y`.
+    struct Growth;
+
+    impl System<Vector1<f64>> for Growth {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+    }
+
+    #[test]
+    fn integrates_backward_in_time() {
+        let e = std::f64::consts::E;
+        let mut solver = ImplicitEuler::new(Growth, 1.0, Vector1::new(e), 0.0, 1e-3, 1e-12, 10);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 0.0);
+        assert!(solver.x_out().windows(2).all(|w| w[1] < w[0]));
+        assert!((solver.y_final()[0] - 1.0).abs() < 1e-3);
+    }
+
+    /// Decay `M y' = -y` with the mass matrix `M = diag(1, 4)`.
+    struct HeavyDecay;
+
+    impl System<Vector2<f64>> for HeavyDecay {
+        fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+            *dy = -y;
+        }
+
+        fn mass_matrix(&self, out: &mut Matrix2<f64>) {
+            out[(1, 1)] = 4.0;
+        }
+    }
+
+    #[test]
+    fn applies_the_mass_matrix() {
+        let y0 = Vector2::new(1.0, 1.0);
+        let exact = Vector2::new((-2.0f64).exp(), (-0.5f64).exp());
+        let mut solver = ImplicitEuler::new(HeavyDecay, 0.0, y0, 2.0, 1e-3, 1e-12, 10);
+        solver.integrate().unwrap();
+        assert!((solver.y_final() - exact).amax() < 1e-3);
+
+        let mut solver = crate::CrankNicolson::new(HeavyDecay, 0.0, y0, 2.0, 1e-2, 1e-12, 10);
+        solver.integrate().unwrap();
+        assert!((solver.y_final() - exact).amax() < 1e-5);
+
+        let mut solver = crate::Bdf::new(HeavyDecay, 0.0, y0, 2.0, None, 1e-8, 1e-8);
+        solver.integrate().unwrap();
+        assert!((solver.y_final() - exact).amax() < 1e-5);
+    }
+
+    /// Decay `y' = -lambda y`, with a Lipschitz constant `lambda`.
+    struct Decay {
+        lambda: f64,
+    }
+
+    impl System<Vector1<f64>> for Decay {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = -self.lambda * y[0];
+        }
+    }
+
+    #[test]
+    fn functional_iteration_converges_on_a_non_stiff_problem() {
+        let y0 = Vector1::new(1.0);
+        let solve = |lambda: f64, nonlinear_solver: NonlinearSolver| {
+            let mut solver = ImplicitEuler::new(Decay { lambda }, 0.0, y0, 1.0, 0.01, 1e-12, 50);
+            solver.nonlinear_solver(nonlinear_solver);
+            let result = solver.integrate();
+            (result, solver.y_final()[0])
+        };
+        // h * L = 0.01: the iteration contracts
+        let (newton, y_newton) = solve(1.0, NonlinearSolver::Newton);
+        let (functional, y_functional) = solve(1.0, NonlinearSolver::FunctionalIteration);
+        assert!(newton.unwrap().num_jac_eval() > 0);
+        assert_eq!(functional.unwrap().num_jac_eval(), 0);
+        assert!((y_functional - y_newton).abs() < 1e-10);
+        assert!((y_functional - (-1.0f64).exp()).abs() < 1e-2);
+
+        let mut solver =
+            crate::CrankNicolson::new(Decay { lambda: 1.0 }, 0.0, y0, 1.0, 0.01, 1e-12, 50);
+        solver.nonlinear_solver(NonlinearSolver::FunctionalIteration);
+        assert_eq!(solver.integrate().unwrap().num_jac_eval(), 0);
+        assert!((solver.y_final()[0] - (-1.0f64).exp()).abs() < 1e-5);
+
+        let tableau = crate::ImplicitButcherTableau::gauss_legendre2();
+        let mut solver =
+            crate::ImplicitRungeKutta::new(Decay { lambda: 1.0 }, 0.0, y0, 1.0, 0.01, tableau);
+        solver.nonlinear_solver(NonlinearSolver::FunctionalIteration);
+        assert_eq!(solver.integrate().unwrap().num_jac_eval(), 0);
+        assert!((solver.y_final()[0] - (-1.0f64).exp()).abs() < 1e-9);
+
+        // h * L = 10: only the Newton iteration converges
+        assert!(solve(1000.0, NonlinearSolver::Newton).0.is_ok());
+        assert!(matches!(
+            solve(1000.0, NonlinearSolver::FunctionalIteration).0,
+            Err(IntegrationError::NewtonDidNotConverge { .. })
+        ));
+    }
+}
@@ -0,0 +1,762 @@
+//! Generic implicit Runge-Kutta driver with fixed or adaptive step size.
+
+use crate::controller::{PiController, StepController};
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, error_scale, find_segment, limit_step, linear_interpolation, newton_converged,
+    order_reduced, ramp_step, record_diagnostic, reject_events, step_count, validate_step_size,
+    weighted_rms_norm, ControlFlag, Factorization, IntegrationError, IntegrationResult, MethodInfo,
+    NonlinearSolver, OdeSolver, Output, SolverLog, Stats, StepDiagnostic, System, Tolerance,
+};
+
+use nalgebra::{
+    allocator::Allocator, DMatrix, DVector, DefaultAllocator, Dim, DimMin, Dynamic, OMatrix,
+    OVector, RealField,
+};
+use simba::scalar::SubsetOf;
+
+/// Coefficients of a fully implicit Runge-Kutta method.
+#[derive(Clone, Debug)]
+pub struct ImplicitButcherTableau {
+    /// Stage matrix. Row `i` holds the coefficients `a_ij` of all the stages `j`. It must be
+    /// invertible.
+    pub a: Vec<Vec<f64>>,
+    /// Weights of the solution.
+    pub b: Vec<f64>,
+    /// Nodes.
+    pub c: Vec<f64>,
+    /// Weights of the embedded solution used for error estimation (optional). The error is
+    /// estimated from the solved stages, at the cost of no further evaluation of `System::ode`.
+    pub b_hat: Option<Vec<f64>>,
+    /// Order of the solution.
+    pub order: u32,
+    /// Order of the embedded solution. Ignored when `b_hat` is `None`.
+    pub embedded_order: u32,
+}
+
+impl ImplicitButcherTableau {
+    /// Number of stages of the method.
+    pub fn stages(&self) -> usize {
+        self.b.len()
+    }
+
+    /// Two stage Gauss-Legendre method, of order 4, A-stable and symplectic. Quadratic invariants
+    /// such as the energy of linear oscillators are conserved, but the stiff components are not
+    /// damped.
+    pub fn gauss_legendre2() -> Self {
+        let s = 3.0_f64.sqrt() / 6.0;
+        ImplicitButcherTableau {
+            a: vec![vec![0.25, 0.25 - s], vec![0.25 + s, 0.25]],
+            b: vec![0.5, 0.5],
+            c: vec![0.5 - s, 0.5 + s],
+            b_hat: None,
+            order: 4,
+            embedded_order: 0,
+        }
+    }
+
+    /// Two stage Gauss-Legendre method with an embedded solution of order 1, whose error
+    /// estimate `h (f_2 - f_1) / 2` follows the variation of the derivative over the step. The
+    /// estimate is pessimistic for the propagated solution of order 4, so that looser tolerances
+    /// may be used than with the other adaptive solvers.
+    pub fn gauss_legendre2_embedded() -> Self {
+        ImplicitButcherTableau {
+            b_hat: Some(vec![1.0, 0.0]),
+            embedded_order: 1,
+            ..Self::gauss_legendre2()
+        }
+    }
+
+    /// Three stage Radau IIA method, of order 5 and L-stable, which damps the stiff components.
+    pub fn radau_iia3() -> Self {
+        let s = 6.0_f64.sqrt();
+        let a = vec![
+            vec![
+                (88.0 - 7.0 * s) / 360.0,
+                (296.0 - 169.0 * s) / 1800.0,
+                (-2.0 + 3.0 * s) / 225.0,
+            ],
+            vec![
+                (296.0 + 169.0 * s) / 1800.0,
+                (88.0 + 7.0 * s) / 360.0,
+                (-2.0 - 3.0 * s) / 225.0,
+            ],
+            vec![(16.0 - s) / 36.0, (16.0 + s) / 36.0, 1.0 / 9.0],
+        ];
+        ImplicitButcherTableau {
+            b: a[2].clone(),
+            a,
+            c: vec![(4.0 - s) / 10.0, (4.0 + s) / 10.0, 1.0],
+            b_hat: None,
+            order: 5,
+            embedded_order: 0,
+        }
+    }
+
+    /// Three stage Radau IIA method with an embedded solution of order 2, given by the
+    /// quadrature on the first two nodes, which is exact for linear functions. As the stage
+    /// order of the method is 3, it is the order of the embedded solution.
+    pub fn radau_iia3_embedded() -> Self {
+        let tableau = Self::radau_iia3();
+        let (c1, c2) = (tableau.c[0], tableau.c[1]);
+        ImplicitButcherTableau {
+            b_hat: Some(vec![(c2 - 0.5) / (c2 - c1), (0.5 - c1) / (c2 - c1), 0.0]),
+            embedded_order: 2,
+            ..tableau
+        }
+    }
+}
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The stage equations of an `s` stage method are solved together with a simplified Newton
+/// iteration using the Jacobian at the start of the step. The linear system has `s * n` unknowns
+/// for a state of dimension `n`, so that every step costs one LU decomposition of a `(s * n)`
+/// square matrix, i.e. `O((s * n)^3)` operations, and every Newton iteration `s` evaluations of
+/// `System::ode`. The method is therefore best suited to small stiff systems or to problems
+/// requiring the stability or the conservation properties of the implicit methods.
+///
+/// If the tableau provides embedded weights, the step size is adapted with the error estimated
+/// from the solved stages `z_j` as `sum_j e_j z_j`, with `e = A^-T (b - b_hat)`, and a step whose
+/// Newton iteration fails is retried with half the step size.
+pub struct ImplicitRungeKutta<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    controller: Box<dyn StepController>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    tableau: ImplicitButcherTableau,
+    newton_tol: f64,
+    max_newton_iter: u32,
+    nonlinear_solver: NonlinearSolver,
+    output: Output<V>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    startup_ramp: usize,
+    stats: Stats,
+    log: SolverLog,
+}
+
+impl<T, D, F> ImplicitRungeKutta<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// If the tableau provides embedded weights, the step size is adapted using the
+    /// tolerances set with [`tolerances`](Self::tolerances) and `step_size` is the initial guess.
+    /// Otherwise the integration is performed with the fixed step size `step_size`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size (initial guess for adaptive tableaux)
+    /// * `tableau`     - Coefficients of the method
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        step_size: f64,
+        tableau: ImplicitButcherTableau,
+    ) -> Self {
+        ImplicitRungeKutta {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            rtol: Tolerance::Scalar(1.0e-6),
+            atol: Tolerance::Scalar(1.0e-6),
+            controller: Box::new(PiController::default()),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            tableau,
+            newton_tol: 1.0e-10,
+            max_newton_iter: 10,
+            nonlinear_solver: NonlinearSolver::Newton,
+            output: Output::new(),
+            diagnostics: None,
+            startup_ramp: 0,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the tolerance on the maximum norm of the Newton update (default 1e-10).
+    pub fn newton_tol(&mut self, newton_tol: f64) {
+        self.newton_tol = newton_tol;
+    }
+
+    /// Sets the maximum number of Newton iterations per step (default 10).
+    pub fn max_newton_iter(&mut self, max_newton_iter: u32) {
+        self.max_newton_iter = max_newton_iter;
+    }
+
+    /// Sets the strategy solving the stage equations of each step (default
+    /// [`NonlinearSolver::Newton`]). The functional iteration needs no Jacobian but converges
+    /// only when `h * max_i sum_j |a_ij| * L < 1` for a Lipschitz constant `L` of the system,
+    /// the step size being halved otherwise if the tableau is adaptive.
+    pub fn nonlinear_solver(&mut self, nonlinear_solver: NonlinearSolver) {
+        self.nonlinear_solver = nonlinear_solver;
+    }
+
+    /// Sets the relative and absolute tolerances used by adaptive tableaux.
+    pub fn tolerances(&mut self, rtol: impl Into<Tolerance>, atol: impl Into<Tolerance>) {
+        self.rtol = rtol.into();
+        self.atol = atol.into();
+    }
+
+    /// Sets the step size controller, a [`PiController`] by default.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.controller = Box::new(controller);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets the number of accepted steps at the start of the integration during which the step
+    /// size may grow by at most a factor of 2 from one step to the next (default 0), easing into
+    /// the adaptive regime on a problem with a sharp initial layer.
+    pub fn startup_ramp(&mut self, steps: usize) {
+        self.startup_ramp = steps;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). `Stats::order_reduced` then flags an observed order well
+    /// below the nominal one. Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger), together with a warning for every nearly singular
+    /// iteration matrix. Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.step_size, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        let (d, e) = self.solution_weights()?;
+        if e.is_none() {
+            validate_step_size(self.step_size)?;
+        }
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, _) = self.y.shape_generic();
+        let mut mass = OMatrix::identity_generic(rows, rows);
+        self.f.mass_matrix(&mut mass);
+
+        match e {
+            Some(e) => self.integrate_adaptive(&mass, &d, &e)?,
+            None => self.integrate_fixed(&mass, &d)?,
+        }
+        self.stats.order_reduced = order_reduced(
+            &self.diagnostics,
+            self.tableau.order.min(self.tableau.embedded_order),
+        );
+        Ok(self.stats)
+    }
+
+    fn integrate_fixed(
+        &mut self,
+        mass: &OMatrix<T, D, D>,
+        d: &[f64],
+    ) -> Result<(), IntegrationError> {
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let z = self.step(h, mass);
+            check_eval(&self.f)?;
+            let z = z?;
+            let y_new = &self.y + combine(d, &z);
+            self.x = if last { self.x_end } else { self.x + h };
+            self.y = y_new;
+            self.f.project(self.x, &mut self.y);
+            self.stats.accepted_steps += 1;
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn integrate_adaptive(
+        &mut self,
+        mass: &OMatrix<T, D, D>,
+        d: &[f64],
+        e: &[f64],
+    ) -> Result<(), IntegrationError> {
+        let posneg = (self.x_end - self.x).signum();
+        let mut h = self.step_size.abs().min(self.max_step) * posneg;
+
+        let mut last = false;
+        let mut n_step = 0;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point
+            if (self.x + 1.01 * h - self.x_end) * posneg > 0.0 {
+                h = self.x_end - self.x;
+                last = true;
+            }
+            n_step += 1;
+            let z = self.step(h, mass);
+            check_eval(&self.f)?;
+            let z = match z {
+                Ok(z) => z,
+                Err(IntegrationError::NewtonDidNotConverge { .. }) => {
+                    // Retry with half the step size
+                    record_diagnostic(&mut self.diagnostics, self.x, h, f64::INFINITY, false);
+                    self.log.rejected(self.x, h, f64::INFINITY);
+                    self.stats.rejected_steps += 1;
+                    h = limit_step(0.5 * h, self.min_step, self.max_step, self.x)?;
+                    last = false;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let y_new = &self.y + combine(d, &z);
+            let scale = error_scale(&self.y, &y_new, &self.rtol, &self.atol);
+            let err = weighted_rms_norm(&combine(e, &z), &scale);
+            let (accept, h_new) = self.controller.next_step(
+                err,
+                h,
+                self.tableau.order.min(self.tableau.embedded_order),
+            );
+            let h_new = ramp_step(h_new, h, self.stats.accepted_steps, self.startup_ramp);
+            record_diagnostic(&mut self.diagnostics, self.x, h, err, accept);
+            if accept {
+                self.log.accepted(self.x, h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(h);
+                self.x = if last { self.x_end } else { self.x + h };
+                self.y = y_new;
+                self.f.project(self.x, &mut self.y);
+                self.output.push(self.x, self.y.clone());
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                    break;
+                }
+            } else {
+                self.log.rejected(self.x, h, err);
+                self.stats.rejected_steps += 1;
+                last = false;
+            }
+            if !last {
+                h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, h, self.max_step);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the tableau and computes the weights `d = A^-T b` giving the solution
+    /// `y_new = y + sum_j d_j z_j` from the stage increments `z_j`, which avoids evaluating
+    /// `System::ode` at the converged stages, together with the weights `e = A^-T (b - b_hat)`
+    /// of the error estimate if the tableau provides embedded weights.
+    #[allow(clippy::type_complexity)]
+    fn solution_weights(&self) -> Result<(Vec<f64>, Option<Vec<f64>>), IntegrationError> {
+        let s = self.tableau.stages();
+        let invalid = |reason: &str| {
+            Err(IntegrationError::InvalidStepConfig {
+                reason: reason.to_string(),
+            })
+        };
+        if s == 0
+            || self.tableau.c.len() != s
+            || self.tableau.a.len() != s
+            || self.tableau.a.iter().any(|row| row.len() != s)
+        {
+            return invalid("the tableau must have s nodes, s weights and an s x s stage matrix");
+        }
+        if self
+            .tableau
+            .b_hat
+            .as_ref()
+            .is_some_and(|b_hat| b_hat.len() != s)
+        {
+            return invalid("the tableau must have s embedded weights");
+        }
+        let lu = DMatrix::from_fn(s, s, |i, j| self.tableau.a[j][i]).lu();
+        let Some(d) = lu.solve(&DVector::from_column_slice(&self.tableau.b)) else {
+            return invalid("the stage matrix must be invertible");
+        };
+        let e = self.tableau.b_hat.as_ref().map(|b_hat| {
+            let b_err = DVector::from_fn(s, |i, _| self.tableau.b[i] - b_hat[i]);
+            // The stage matrix is invertible, as checked above
+            lu.solve(&b_err).unwrap_or(b_err).iter().copied().collect()
+        });
+        Ok((d.iter().copied().collect(), e))
+    }
+
+    /// Performs one step of the method by solving the stage equations
+    /// `M z_i - h * sum_j a_ij f(x + c_j h, y + z_j) = 0` with a simplified Newton iteration, or
+    /// with the functional iteration, which is the same iteration with a zero Jacobian.
+    /// Returns the stage increments `z_j`.
+    fn step(
+        &mut self,
+        h: f64,
+        mass: &OMatrix<T, D, D>,
+    ) -> Result<Vec<OVector<T, D>>, IntegrationError> {
+        let (rows, cols) = self.y.shape_generic();
+        let n = self.y.len();
+        let s = self.tableau.stages();
+
+        let mut jac = OMatrix::zeros_generic(rows, rows);
+        if self.nonlinear_solver == NonlinearSolver::Newton {
+            self.f.jacobian(self.x, &self.y, &mut jac);
+            self.stats.num_jac_eval += 1;
+        }
+        // Block (i, j) of the matrix of the iteration is `delta_ij M - h a_ij J`
+        let a = &self.tableau.a;
+        let lu = DMatrix::from_fn(s * n, s * n, |r, c| {
+            let (i, j) = (r / n, c / n);
+            let m = if i == j {
+                mass[(r % n, c % n)]
+            } else {
+                T::zero()
+            };
+            let h_a: T = nalgebra::convert(h * a[i][j]);
+            m - jac[(r % n, c % n)] * h_a
+        });
+        let lu = Factorization::<T, Dynamic>::new(lu, None);
+        self.log.factorization::<T, Dynamic>(self.x, h, &lu);
+
+        let mut z = vec![OVector::zeros_generic(rows, cols); s];
+        let mut k = vec![OVector::zeros_generic(rows, cols); s];
+        for _ in 0..self.max_newton_iter {
+            for (j, k_j) in k.iter_mut().enumerate() {
+                self.f
+                    .ode(self.x + self.tableau.c[j] * h, &(&self.y + &z[j]), k_j);
+            }
+            self.stats.num_eval += s as u32;
+            self.stats.num_newton_iter += 1;
+            let residual = DVector::from_iterator(
+                s * n,
+                (0..s).flat_map(|i| {
+                    let r_i = k.iter().enumerate().fold(mass * &z[i], |acc, (j, k_j)| {
+                        acc - k_j.clone() * (h * a[i][j])
+                    });
+                    r_i.iter().copied().collect::<Vec<_>>()
+                }),
+            );
+            let delta = match lu.solve(&residual) {
+                Some(delta) => delta,
+                None => return Err(IntegrationError::NewtonDidNotConverge { x: self.x }),
+            };
+            for (i, z_i) in z.iter_mut().enumerate() {
+                for p in 0..n {
+                    z_i[p] -= delta[i * n + p];
+                }
+            }
+            if newton_converged(delta.as_slice(), self.newton_tol) {
+                return Ok(z);
+            }
+        }
+        Err(IntegrationError::NewtonDidNotConverge { x: self.x })
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` using the stored output. Returns `None` if `x` lies outside
+    /// of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(linear_interpolation(
+            self.output.x[i],
+            &self.output.y[i],
+            self.output.x[i + 1],
+            &self.output.y[i + 1],
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+/// Computes `sum_j w_j z_j` from the stage increments `z_j`.
+fn combine<T, D: Dim>(weights: &[f64], z: &[OVector<T, D>]) -> OVector<T, D>
+where
+    T: Copy + RealField,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    let (rows, cols) = z[0].shape_generic();
+    z.iter()
+        .zip(weights.iter())
+        .fold(OVector::zeros_generic(rows, cols), |acc, (z_j, w_j)| {
+            acc + z_j.clone() * *w_j
+        })
+}
+
+impl<T, D, F> OdeSolver<OVector<T, D>> for ImplicitRungeKutta<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        ImplicitRungeKutta::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        ImplicitRungeKutta::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        ImplicitRungeKutta::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        ImplicitRungeKutta::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        ImplicitRungeKutta::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for ImplicitRungeKutta<V, F> {
+    fn order(&self) -> u32 {
+        self.tableau.order
+    }
+
+    fn is_implicit(&self) -> bool {
+        true
+    }
+
+    /// The Gauss-Legendre and Radau IIA tableaux provided are A-stable, which is assumed of any
+    /// other tableau.
+    fn is_a_stable(&self) -> bool {
+        true
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::ode_fn;
+    use crate::{Dopri5, Vector1, Vector2};
+
+    /// Exponential growth `y' = y`.
+    struct Growth;
+
+    impl System<Vector1<f64>> for Growth {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+    }
+
+    #[test]
+    fn integrates_backward_in_time() {
+        let e = std::f64::consts::E;
+        let mut solver = ImplicitRungeKutta::new(
+            Growth,
+            1.0,
+            Vector1::new(e),
+            0.0,
+            0.01,
+            ImplicitButcherTableau::gauss_legendre2(),
+        );
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 0.0);
+        assert!(solver.x_out().windows(2).all(|w| w[1] < w[0]));
+        assert!((solver.y_final()[0] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gauss_legendre_integrates_polynomials_exactly() {
+        // y' = 3 x^2 - 2 x, the stage equations being solved exactly for a linear system
+        let f = ode_fn(|x, _y: &Vector1<f64>, dy: &mut Vector1<f64>| {
+            dy[0] = 3.0 * x * x - 2.0 * x;
+        });
+        let tableau = ImplicitButcherTableau::gauss_legendre2();
+        let mut solver = ImplicitRungeKutta::new(f, 0.0, Vector1::new(0.0), 3.0, 0.5, tableau);
+        solver.integrate().unwrap();
+        for (x, y) in solver.x_out().iter().zip(solver.y_out()) {
+            assert!((y[0] - (x * x * x - x * x)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn gauss_legendre_preserves_the_oscillator_energy() {
+        let oscillator = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+            dy[0] = y[1];
+            dy[1] = -y[0];
+        });
+        let tableau = ImplicitButcherTableau::gauss_legendre2();
+        let x_end = 200.0 * std::f64::consts::PI;
+        let mut solver =
+            ImplicitRungeKutta::new(oscillator, 0.0, Vector2::new(1.0, 0.0), x_end, 0.1, tableau);
+        solver.integrate().unwrap();
+        assert!(solver
+            .y_out()
+            .iter()
+            .all(|y| (y.norm_squared() - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn radau_converges_with_the_fifth_order() {
+        let error = |h: f64| {
+            let tableau = ImplicitButcherTableau::radau_iia3();
+            let mut solver =
+                ImplicitRungeKutta::new(Growth, 0.0, Vector1::new(1.0), 1.0, h, tableau);
+            solver.integrate().unwrap();
+            (solver.y_final()[0] - std::f64::consts::E).abs()
+        };
+        let order = (error(0.1) / error(0.05)).log2();
+        assert!((order - 5.0).abs() < 0.2, "{}", order);
+    }
+
+    /// Stiff relaxation `y' = -1000 (y - cos(x))` towards a slow solution close to `cos(x)`.
+    struct Relaxation;
+
+    impl System<Vector1<f64>> for Relaxation {
+        fn ode(&self, x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = -1000.0 * (y[0] - x.cos());
+        }
+    }
+
+    #[test]
+    fn embedded_radau_adapts_the_step_to_the_transient() {
+        let tableau = ImplicitButcherTableau::radau_iia3_embedded();
+        let mut solver =
+            ImplicitRungeKutta::new(Relaxation, 0.0, Vector1::new(0.0), 10.0, 1e-4, tableau);
+        solver.tolerances(1e-6, 1e-6);
+        let stats = solver.integrate().unwrap();
+        // The transient has decayed at x = 10
+        let exact = (1e6 * 10.0f64.cos() + 1e3 * 10.0f64.sin()) / (1e6 + 1.0);
+        assert!((solver.y_final()[0] - exact).abs() < 1e-5);
+        // Small steps resolve the initial transient, large ones follow the slow solution
+        let steps: Vec<f64> = solver.x_out().windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(steps[0] < 1e-2 * steps[steps.len() / 2]);
+
+        // The step size of the explicit method is limited by its stability instead
+        let mut explicit = Dopri5::new(Relaxation, 0.0, Vector1::new(0.0), 10.0, None, 1e-6, 1e-6);
+        let explicit = explicit.integrate().unwrap();
+        assert!(10 * stats.accepted_steps() < explicit.accepted_steps());
+    }
+}
@@ -0,0 +1,201 @@
+//! Leapfrog (kick-drift-kick) method with fixed step size for second order systems.
+
+use crate::dop_shared::{
+    step_count, validate_step_size, ControlFlag, IntegrationError, SecondOrderSystem, Stats,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The positions are advanced at the integer steps and the velocities at the half steps, the
+/// method being started with a half kick of the velocities. The stored output is synchronized,
+/// the velocities at the integer steps being recovered with another half kick, while the
+/// staggered velocities are given by [`v_half`](Self::v_half). The method is symplectic,
+/// time-reversible and of second order when the acceleration does not depend on the velocity, in
+/// which case it is evaluated with the staggered velocities.
+pub struct Leapfrog<V, F>
+where
+    F: SecondOrderSystem<V>,
+{
+    f: F,
+    x: f64,
+    q: V,
+    v: V,
+    v_half: V,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    q_out: Vec<V>,
+    v_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> Leapfrog<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: SecondOrderSystem<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the SecondOrderSystem<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `q`           - Initial value of the position(s)
+    /// * `v`           - Initial value of the velocity(ies)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        q: OVector<T, D>,
+        v: OVector<T, D>,
+        x_end: f64,
+        step_size: f64,
+    ) -> Self {
+        Leapfrog {
+            f,
+            x,
+            q,
+            v_half: v.clone(),
+            v,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            q_out: Vec::new(),
+            v_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.q_out.push(self.q.clone());
+        self.v_out.push(self.v.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.q, &self.v);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, cols) = self.q.shape_generic();
+        let mut a = OVector::zeros_generic(rows, cols);
+        self.f.acceleration(self.x, &self.q, &self.v, &mut a);
+        self.stats.num_eval += 1;
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        let (x_end, step_size) = (self.x_end, self.step_size);
+        let step = |x: f64, i: usize| {
+            // Shorten the last step so that the integration ends exactly at x_end
+            if i + 1 == num_steps {
+                x_end - x
+            } else {
+                direction * step_size
+            }
+        };
+        // Half kick staggering the velocities
+        let mut h = step(self.x, 0);
+        self.v_half = &self.v + a.clone() * (0.5 * h);
+        for i in 0..num_steps {
+            let last = i + 1 == num_steps;
+            let x_new = if last { self.x_end } else { self.x + h };
+
+            // Drift
+            self.q = &self.q + self.v_half.clone() * h;
+            self.f.acceleration(x_new, &self.q, &self.v_half, &mut a);
+            self.x = x_new;
+            self.stats.num_eval += 1;
+            self.stats.accepted_steps += 1;
+            // Velocities synchronized with the positions
+            self.v = &self.v_half + a.clone() * (0.5 * h);
+            // Kick over the two halves of consecutive steps, whose sizes differ before the last
+            // step
+            if !last {
+                let h_next = step(self.x, i + 1);
+                self.v_half = &self.v_half + a.clone() * (0.5 * (h + h_next));
+                h = h_next;
+            } else {
+                self.v_half = self.v.clone();
+            }
+
+            self.x_out.push(self.x);
+            self.q_out.push(self.q.clone());
+            self.v_out.push(self.v.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.q, &self.v);
+            if self.f.solout(self.x, &self.q, &self.v) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Returns the current value of the independent variable together with the positions and
+    /// the velocities synchronized at this value.
+    pub fn synchronized(&self) -> (f64, &OVector<T, D>, &OVector<T, D>) {
+        (self.x, &self.q, &self.v)
+    }
+
+    /// Staggered velocities, half a step ahead of the positions during the integration, equal to
+    /// the synchronized velocities once it has reached `x_end`.
+    pub fn v_half(&self) -> &OVector<T, D> {
+        &self.v_half
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the positions' output.
+    pub fn q_out(&self) -> &Vec<OVector<T, D>> {
+        &self.q_out
+    }
+
+    /// Getter for the synchronized velocities' output.
+    pub fn v_out(&self) -> &Vec<OVector<T, D>> {
+        &self.v_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector1;
+
+    /// Pendulum `q'' = -sin(q)`.
+    struct Pendulum;
+
+    impl SecondOrderSystem<Vector1<f64>> for Pendulum {
+        fn acceleration(&self, _x: f64, q: &Vector1<f64>, _v: &Vector1<f64>, a: &mut Vector1<f64>) {
+            a[0] = -q[0].sin();
+        }
+    }
+
+    #[test]
+    fn integrating_back_recovers_the_initial_state() {
+        let (q0, v0) = (Vector1::new(1.0), Vector1::new(0.5));
+        let mut forward = Leapfrog::new(Pendulum, 0.0, q0, v0, 20.0, 0.125);
+        forward.integrate().unwrap();
+        let (x, q, v) = forward.synchronized();
+        let mut backward = Leapfrog::new(Pendulum, x, *q, *v, 0.0, 0.125);
+        backward.integrate().unwrap();
+        let (x, q, v) = backward.synchronized();
+        assert_eq!(x, 0.0);
+        assert!((q - q0).norm() < 1e-12, "{}", (q - q0).norm());
+        assert!((v - v0).norm() < 1e-12, "{}", (v - v0).norm());
+    }
+}
@@ -1,5 +1,30 @@
 //! # ODEs Solvers
 //! `ode-solvers` is a collection of numerical methods to solve ordinary differential equations (ODEs).
+//!
+//! # Reproducibility
+//!
+//! The solvers are deterministic: integrating the same system from the same initial state with
+//! the same settings gives the same trajectory to the last bit from one run to the next. Every
+//! reduction, e.g. the combination of the stages of a step, the error norms or the products
+//! with the Jacobian, is a sequential sum in increasing index order, and no solver uses threads,
+//! hashing or the clock internally. With the `rayon` feature, [`dop_shared::solve_ensemble`]
+//! integrates each trajectory on a single thread and returns them in the order of the initial
+//! states, so that the results do not depend on the scheduling. The Wiener increments of
+//! `EulerMaruyama` are drawn from the generator passed by the caller, which must be seeded, and
+//! the step size jitter of `Dopri5::jitter` and `Dopri5::jitter_with` from a generator seeded
+//! with the given seed.
+//!
+//! The remaining sources of differences are outside of the solvers:
+//!
+//! * `Euler::max_duration` stops the integration after a wall-clock time, at a step that varies
+//!   between runs.
+//! * The elementary functions, e.g. the `powf` of the step size controllers or the `exp` and `sin`
+//!   of a right-hand side, come from the platform's math library, whose results are not
+//!   correctly rounded and may differ in the last bit between platforms, so that an adaptive
+//!   solver may then choose different steps. The arithmetic operations themselves follow IEEE
+//!   754, the compiler not contracting them into fused multiply-adds.
+//! * The matrix operations of `nalgebra`, e.g. the LU factorization of the implicit solvers, may
+//!   change between its versions, which are pinned by the `Cargo.lock` of the application.
 
 // Re-export from external crate
 pub use crate::na::{
@@ -8,7 +33,82 @@ pub use crate::na::{
 use nalgebra as na;
 
 // Declare modules
+pub mod adams;
+pub mod adjoint;
+pub mod auto;
+pub mod bdf;
+pub mod bench;
+pub mod bulirsch_stoer;
+pub mod cash_karp;
+pub mod controller;
+pub mod crank_nicolson;
+pub mod dde;
+pub mod dop853;
 pub mod dop_shared;
+pub mod dopri5;
 pub mod euler;
-pub use euler::Euler;
-pub use dop_shared::System;
+pub mod exp_euler;
+pub mod heun;
+pub mod implicit_euler;
+pub mod implicit_rk;
+pub mod leapfrog;
+pub mod lie;
+pub mod linear;
+pub mod midpoint;
+pub mod parareal;
+pub mod problems;
+pub mod ralston;
+pub mod rk23;
+pub mod rk4;
+pub mod rosenbrock;
+#[cfg(feature = "rand")]
+pub mod sde;
+pub mod sensitivity;
+pub mod shooting;
+pub mod ssprk3;
+pub mod symplectic_euler;
+pub mod tableau;
+pub mod verlet;
+pub use adams::AdamsBashforthMoulton;
+pub use adjoint::AdjointSensitivity;
+pub use auto::Auto;
+pub use bdf::Bdf;
+pub use bench::{WorkPrecision, WorkPrecisionPoint};
+pub use bulirsch_stoer::BulirschStoer;
+pub use cash_karp::CashKarp45;
+pub use controller::{GustafssonController, IController, PiController, StepController};
+pub use crank_nicolson::CrankNicolson;
+pub use dde::DelayRungeKutta4;
+pub use dop853::Dop853;
+pub use dopri5::Dopri5;
+pub use euler::{Euler, EulerBuilder, EventKind, EventLog, EventPoint, EventRecord};
+#[cfg(feature = "serde")]
+pub use euler::SolverState;
+pub use exp_euler::ExponentialEuler;
+pub use heun::Heun;
+pub use implicit_euler::ImplicitEuler;
+pub use implicit_rk::{ImplicitButcherTableau, ImplicitRungeKutta};
+pub use leapfrog::Leapfrog;
+pub use lie::{LieEuler, LieGroup, LieRungeKutta4, LieSystem};
+pub use linear::{LinearSystem, MatrixExponential};
+pub use midpoint::Midpoint;
+pub use parareal::Parareal;
+pub use ralston::Ralston2;
+pub use rk23::BogackiShampine23;
+pub use rk4::RungeKutta4;
+pub use rosenbrock::Rosenbrock4;
+#[cfg(feature = "rand")]
+pub use sde::EulerMaruyama;
+pub use sensitivity::Sensitivity;
+pub use shooting::Shooting;
+pub use ssprk3::SspRk3;
+pub use symplectic_euler::SymplecticEuler;
+pub use tableau::{ButcherTableau, ExplicitRungeKutta};
+pub use verlet::VelocityVerlet;
+pub use dop_shared::{
+    ContinuousSolution, Control, ControlFlag, DelaySystem, EvalError, EventAction,
+    EventDirection, FallibleSystem, ForcedSystem, HamiltonianSystem, Hold, IntegrationResult,
+    LogLevel, LogRecord, MethodInfo, NonlinearSolver, OdeSolver, SecondOrderSystem,
+    SemilinearSystem, SoaTrajectory, StepDiagnostic, System, System1d, Tolerance,
+    TrajectorySink, UnitSystem, VecTrajectory,
+};
@@ -0,0 +1,375 @@
+//! Lie group methods with fixed step size, integrating states which live in a Lie group such as
+//! the rotations SO(3) or the rigid motions SE(3) directly on the group.
+
+use crate::dop_shared::{step_count, validate_step_size, ControlFlag, IntegrationError, Stats};
+
+use nalgebra::{Isometry3, Matrix3, Rotation3, Translation3, UnitQuaternion, Vector3, Vector6};
+use std::ops::{Add, Mul};
+
+/// Trait implemented by the Lie groups in which the state of a [`LieSystem`] lives.
+pub trait LieGroup: Clone {
+    /// Element of the Lie algebra of the group, e.g. an angular velocity.
+    type Algebra: Clone + Add<Output = Self::Algebra> + Mul<f64, Output = Self::Algebra>;
+    /// Group product `self * other`.
+    fn compose(&self, other: &Self) -> Self;
+    /// Exponential map of the algebra element `xi` onto the group.
+    fn exp(xi: &Self::Algebra) -> Self;
+    /// Lie bracket `[a, b]` of two algebra elements.
+    fn bracket(a: &Self::Algebra, b: &Self::Algebra) -> Self::Algebra;
+}
+
+/// Rotations, with the angular velocity as algebra element.
+impl LieGroup for UnitQuaternion<f64> {
+    type Algebra = Vector3<f64>;
+
+    fn compose(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn exp(xi: &Vector3<f64>) -> Self {
+        UnitQuaternion::from_scaled_axis(*xi)
+    }
+
+    fn bracket(a: &Vector3<f64>, b: &Vector3<f64>) -> Vector3<f64> {
+        a.cross(b)
+    }
+}
+
+/// Rotations, with the angular velocity as algebra element.
+impl LieGroup for Rotation3<f64> {
+    type Algebra = Vector3<f64>;
+
+    fn compose(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn exp(xi: &Vector3<f64>) -> Self {
+        Rotation3::from_scaled_axis(*xi)
+    }
+
+    fn bracket(a: &Vector3<f64>, b: &Vector3<f64>) -> Vector3<f64> {
+        a.cross(b)
+    }
+}
+
+/// Rigid motions, with the twist `(omega, v)` of the angular and the linear velocity as algebra
+/// element.
+impl LieGroup for Isometry3<f64> {
+    type Algebra = Vector6<f64>;
+
+    fn compose(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn exp(xi: &Vector6<f64>) -> Self {
+        let omega = xi.fixed_rows::<3>(0).into_owned();
+        let v = xi.fixed_rows::<3>(3).into_owned();
+        let theta = omega.norm();
+        let w = omega.cross_matrix();
+        // Left Jacobian of SO(3), with its series expansion for small angles
+        let (b, c) = if theta < 1.0e-4 {
+            (
+                0.5 - theta * theta / 24.0,
+                1.0 / 6.0 - theta * theta / 120.0,
+            )
+        } else {
+            (
+                (1.0 - theta.cos()) / (theta * theta),
+                (theta - theta.sin()) / (theta * theta * theta),
+            )
+        };
+        let jac = Matrix3::identity() + w * b + w * w * c;
+        Isometry3::from_parts(
+            Translation3::from(jac * v),
+            UnitQuaternion::from_scaled_axis(omega),
+        )
+    }
+
+    fn bracket(a: &Vector6<f64>, b: &Vector6<f64>) -> Vector6<f64> {
+        let (omega_a, v_a) = (a.fixed_rows::<3>(0), a.fixed_rows::<3>(3));
+        let (omega_b, v_b) = (b.fixed_rows::<3>(0), b.fixed_rows::<3>(3));
+        let omega = omega_a.cross(&omega_b);
+        let v = omega_a.cross(&v_b) - omega_b.cross(&v_a);
+        Vector6::new(omega[0], omega[1], omega[2], v[0], v[1], v[2])
+    }
+}
+
+/// Trait needed to be implemented by the user for systems `g' = g xi(x, g)` whose state `g` lives
+/// in a Lie group, `xi` being the velocity in the body frame.
+pub trait LieSystem<G: LieGroup> {
+    /// Velocity `xi` of the system in the body frame, an element of the Lie algebra.
+    fn velocity(&self, x: f64, g: &G) -> G::Algebra;
+    /// Observer (optional)
+    fn observer(&self, _x: f64, _g: &G) {}
+    /// Stop function called at every integration step (optional). The integration is stopped
+    /// when this function returns `ControlFlag::Stop`.
+    fn solout(&mut self, _x: f64, _g: &G) -> ControlFlag {
+        ControlFlag::Continue
+    }
+}
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The Lie-Euler method of first order updates the state with the exponential map,
+/// `g_new = g exp(h xi(x, g))`, so that it remains exactly on the group. In particular, a constant
+/// velocity is integrated exactly whatever the step size.
+pub struct LieEuler<G, F>
+where
+    G: LieGroup,
+    F: LieSystem<G>,
+{
+    f: F,
+    x: f64,
+    y: G,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    y_out: Vec<G>,
+    stats: Stats,
+}
+
+impl<G, F> LieEuler<G, F>
+where
+    G: LieGroup,
+    F: LieSystem<G>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the LieSystem<G> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the state, an element of the group
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: G, x_end: f64, step_size: f64) -> Self {
+        LieEuler {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.y);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let xi = self.f.velocity(self.x, &self.y);
+            self.y = self.y.compose(&G::exp(&(xi * h)));
+            self.x = if last { self.x_end } else { self.x + h };
+            self.stats.num_eval += 1;
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.y);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the states' output.
+    pub fn y_out(&self) -> &Vec<G> {
+        &self.y_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current state, i.e. the state at [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &G {
+        &self.y
+    }
+}
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The Runge-Kutta-Munthe-Kaas method of fourth order applies the classical Runge-Kutta method to
+/// the algebra element `u` of the state `g exp(u)` over each step, the inverse of the derivative
+/// of the exponential map being truncated after the first commutator. The state remains exactly
+/// on the group, and a constant velocity is integrated exactly whatever the step size.
+pub struct LieRungeKutta4<G, F>
+where
+    G: LieGroup,
+    F: LieSystem<G>,
+{
+    f: F,
+    x: f64,
+    y: G,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    y_out: Vec<G>,
+    stats: Stats,
+}
+
+impl<G, F> LieRungeKutta4<G, F>
+where
+    G: LieGroup,
+    F: LieSystem<G>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the LieSystem<G> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the state, an element of the group
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: G, x_end: f64, step_size: f64) -> Self {
+        LieRungeKutta4 {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.y);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            self.y = self.step(h);
+            self.x = if last { self.x_end } else { self.x + h };
+            self.stats.num_eval += 4;
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.y);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the method and returns the new state.
+    fn step(&self, h: f64) -> G {
+        let stage = |u: &G::Algebra| self.y.compose(&G::exp(u));
+        let k1 = self.f.velocity(self.x, &self.y) * h;
+        let k2 = self
+            .f
+            .velocity(self.x + 0.5 * h, &stage(&(k1.clone() * 0.5)))
+            * h;
+        let u3 = k2.clone() * 0.5 + G::bracket(&k1, &k2) * 0.125;
+        let k3 = self.f.velocity(self.x + 0.5 * h, &stage(&u3)) * h;
+        let k4 = self.f.velocity(self.x + h, &stage(&k3)) * h;
+        let bracket = G::bracket(&k1, &k4) * (1.0 / 12.0);
+        let u = (k1 + k2 * 2.0 + k3 * 2.0 + k4) * (1.0 / 6.0) + bracket;
+        stage(&u)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the states' output.
+    pub fn y_out(&self) -> &Vec<G> {
+        &self.y_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current state, i.e. the state at [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &G {
+        &self.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rotation about a fixed axis with the angular speed `1 + cos(x) / 2`, whose angle grows by
+    /// `2 pi` over `[0, 2 pi]`.
+    struct Spin;
+
+    impl LieSystem<Rotation3<f64>> for Spin {
+        fn velocity(&self, x: f64, _g: &Rotation3<f64>) -> Vector3<f64> {
+            Vector3::new(1.0, 2.0, 2.0) / 3.0 * (1.0 + 0.5 * x.cos())
+        }
+    }
+
+    #[test]
+    fn full_rotation_returns_to_the_identity() {
+        let x_end = 2.0 * std::f64::consts::PI;
+        let mut solver = LieRungeKutta4::new(Spin, 0.0, Rotation3::identity(), x_end, 0.01);
+        solver.integrate().unwrap();
+        for g in solver.y_out() {
+            let m = g.matrix();
+            assert!((m.transpose() * m - Matrix3::identity()).norm() < 1e-12);
+        }
+        let error = (solver.y_final().matrix() - Matrix3::identity()).norm();
+        assert!(error < 1e-9, "{}", error);
+    }
+}
@@ -0,0 +1,133 @@
+//! Exact solution of linear systems with constant coefficients by the matrix exponential.
+
+use crate::dop_shared::State;
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OMatrix, OVector, RealField};
+use simba::scalar::SubsetOf;
+
+/// Degree of the diagonal Pade approximant of the exponential of the scaled matrix, whose 1-norm
+/// is at most 1/2, which is then accurate to the double precision.
+const PADE_DEGREE: usize = 6;
+
+/// Trait needed to be implemented by the user for linear systems `y' = A y` with a constant
+/// matrix `A`, solved exactly by [`MatrixExponential`].
+pub trait LinearSystem<V> {
+    /// Matrix `A` of the system. `out` is zero on entry.
+    fn matrix(&self, out: &mut V::Matrix)
+    where
+        V: State;
+}
+
+/// Exponential of a constant matrix `A`, giving the solution `y(x) = exp(A (x - x0)) y0` of the
+/// linear system `y' = A y` at any value of the independent variable, without the error and the
+/// step size restrictions of a stepping method.
+///
+/// The exponential of `A dt` is computed by scaling and squaring: the diagonal Pade approximant
+/// of degree 6 is evaluated at `A dt / 2^s`, whose 1-norm is at most 1/2, and squared `s` times,
+/// which costs `O(n^3)` operations for a system of dimension `n`.
+pub struct MatrixExponential<T, D>
+where
+    T: RealField,
+    D: Dim,
+    DefaultAllocator: Allocator<T, D, D>,
+{
+    a: OMatrix<T, D, D>,
+}
+
+impl<T, D> MatrixExponential<T, D>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Exponential of the matrix `a`.
+    pub fn new(a: OMatrix<T, D, D>) -> Self {
+        MatrixExponential { a }
+    }
+
+    /// Exponential of the matrix of the linear system `f`, whose dimension is given by the state
+    /// `y`.
+    pub fn from_system<F>(f: &F, y: &OVector<T, D>) -> Self
+    where
+        F: LinearSystem<OVector<T, D>>,
+        OVector<T, D>: State<Matrix = OMatrix<T, D, D>>,
+    {
+        let (rows, _) = y.shape_generic();
+        let mut a = OMatrix::zeros_generic(rows, rows);
+        f.matrix(&mut a);
+        MatrixExponential { a }
+    }
+
+    /// Computes `exp(A dt)`.
+    pub fn exp(&self, dt: f64) -> OMatrix<T, D, D> {
+        let dt_t: T = nalgebra::convert(dt);
+        let z = &self.a * dt_t;
+        let (rows, _) = z.shape_generic();
+        let norm = z
+            .column_iter()
+            .map(|c| {
+                c.iter()
+                    .fold(0.0, |acc: f64, z_ij| acc + f64::from(*z_ij).abs())
+            })
+            .fold(0.0, f64::max);
+        let s = if norm > 0.5 {
+            (norm / 0.5).log2().ceil() as i32
+        } else {
+            0
+        };
+        let scale: T = nalgebra::convert(0.5_f64.powi(s));
+        let z = z * scale;
+
+        // Numerator N(z) and denominator N(-z) of the Pade approximant
+        let identity = OMatrix::identity_generic(rows, rows);
+        let mut term = identity.clone();
+        let mut numerator = identity.clone();
+        let mut denominator = identity;
+        let mut c = 1.0;
+        for k in 1..=PADE_DEGREE {
+            c *= (PADE_DEGREE - k + 1) as f64 / (k * (2 * PADE_DEGREE - k + 1)) as f64;
+            term = &term * &z;
+            let c_term = &term * nalgebra::convert::<f64, T>(c);
+            numerator += &c_term;
+            if k % 2 == 0 {
+                denominator += c_term;
+            } else {
+                denominator -= c_term;
+            }
+        }
+        // The denominator is well conditioned for a scaled matrix of norm at most 1/2
+        let mut exp = denominator
+            .lu()
+            .solve(&numerator)
+            .expect("the denominator of the Pade approximant is invertible");
+        for _ in 0..s {
+            exp = &exp * &exp;
+        }
+        exp
+    }
+
+    /// Propagates the state `y` at `x` to each of the values `x_out` of the independent
+    /// variable, in any order, returning the states `exp(A (x_out[i] - x)) y`.
+    pub fn propagate(&self, x: f64, y: &OVector<T, D>, x_out: &[f64]) -> Vec<OVector<T, D>> {
+        x_out.iter().map(|t| self.exp(t - x) * y).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Matrix2;
+
+    #[test]
+    fn exponential_of_a_rotation_generator() {
+        let omega = 3.0_f64;
+        let exp = MatrixExponential::new(Matrix2::new(0.0, -omega, omega, 0.0));
+        for dt in [0.0, 0.1, 1.0, 10.0, -25.0] {
+            let (sin, cos) = (omega * dt).sin_cos();
+            let exact = Matrix2::new(cos, -sin, sin, cos);
+            let error = (exp.exp(dt) - exact).norm();
+            assert!(error < 1e-12, "dt = {}: {}", dt, error);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Parareal method, integrating long intervals in parallel across time slices.
+
+use crate::dop_shared::{
+    linspace, solve_one, IntegrationError, IntegrationResult, OdeSolver, Stats,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use simba::scalar::{ClosedAdd, ClosedSub};
+
+/// Structure containing the parameters of the Parareal method.
+///
+/// The interval of integration is divided into slices of equal length. A cheap coarse solver
+/// first propagates the initial state across the slices one after the other. Every iteration then
+/// integrates all the slices independently with an accurate fine solver from the current states at
+/// their start, and corrects these states with a sweep of the coarse solver,
+/// `U[n + 1] = coarse(U[n]) + fine(U_old[n]) - coarse(U_old[n])`. The iterations stop once the
+/// states at the boundaries of the slices change by less than the tolerance, in maximum norm. After
+/// `k` iterations, the first `k` slices coincide with the serial fine solution, so that at most as
+/// many iterations as slices are performed.
+///
+/// The solvers are created by the factories for the initial value of the independent variable,
+/// the initial state and the final value of the independent variable of a slice. With the `rayon`
+/// feature, the fine integrations of an iteration are distributed over the rayon thread pool.
+pub struct Parareal<C, G> {
+    coarse: C,
+    fine: G,
+    slices: usize,
+    tol: f64,
+    max_iter: u32,
+    iterations: u32,
+    stats: Stats,
+}
+
+impl<C, G> Parareal<C, G> {
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `coarse`      - Creates the coarse solver over a slice
+    /// * `fine`        - Creates the fine solver over a slice
+    /// * `slices`      - Number of slices of the interval of integration
+    ///
+    pub fn new(coarse: C, fine: G, slices: usize) -> Self {
+        Parareal {
+            coarse,
+            fine,
+            slices,
+            tol: 1.0e-8,
+            max_iter: 10,
+            iterations: 0,
+            stats: Stats::new(),
+        }
+    }
+
+    /// Sets the tolerance on the maximum norm of the change of the states at the boundaries of
+    /// the slices between two iterations (default 1e-8).
+    pub fn tolerance(&mut self, tol: f64) {
+        self.tol = tol;
+    }
+
+    /// Sets the maximum number of iterations (default 10).
+    pub fn max_iterations(&mut self, max_iter: u32) {
+        self.max_iter = max_iter;
+    }
+
+    /// Integrates from `x` to `x_end` starting from the state `y`, and returns the trajectory of
+    /// the fine solver over the slices during the last iteration. The statistics of the result add
+    /// up all the integrations performed, coarse and fine.
+    #[cfg(not(feature = "rayon"))]
+    pub fn solve<T, D, Sc, Sf>(
+        &mut self,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+    ) -> Result<IntegrationResult<OVector<T, D>>, IntegrationError>
+    where
+        f64: From<T>,
+        T: Copy + Scalar + ClosedAdd + ClosedSub,
+        D: Dim,
+        C: Fn(f64, OVector<T, D>, f64) -> Sc,
+        G: Fn(f64, OVector<T, D>, f64) -> Sf,
+        Sc: OdeSolver<OVector<T, D>>,
+        Sf: OdeSolver<OVector<T, D>>,
+        DefaultAllocator: Allocator<T, D>,
+    {
+        self.iterate(x, y, x_end, |fine, tasks| {
+            tasks
+                .into_iter()
+                .map(|(a, y, b)| solve_one(fine(a, y, b)))
+                .collect()
+        })
+    }
+
+    /// Integrates from `x` to `x_end` starting from the state `y`, and returns the trajectory of
+    /// the fine solver over the slices during the last iteration. The statistics of the result add
+    /// up all the integrations performed, coarse and fine.
+    #[cfg(feature = "rayon")]
+    pub fn solve<T, D, Sc, Sf>(
+        &mut self,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+    ) -> Result<IntegrationResult<OVector<T, D>>, IntegrationError>
+    where
+        f64: From<T>,
+        T: Copy + Scalar + ClosedAdd + ClosedSub,
+        D: Dim,
+        C: Fn(f64, OVector<T, D>, f64) -> Sc,
+        G: Fn(f64, OVector<T, D>, f64) -> Sf + Sync,
+        Sc: OdeSolver<OVector<T, D>>,
+        Sf: OdeSolver<OVector<T, D>>,
+        OVector<T, D>: Send,
+        DefaultAllocator: Allocator<T, D>,
+    {
+        use rayon::prelude::*;
+
+        self.iterate(x, y, x_end, |fine, tasks| {
+            tasks
+                .into_par_iter()
+                .map(|(a, y, b)| solve_one(fine(a, y, b)))
+                .collect()
+        })
+    }
+
+    /// Iterations of the method, `sweep` performing the fine integrations over the slices.
+    fn iterate<T, D, Sc>(
+        &mut self,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        sweep: impl Fn(&G, Vec<(f64, OVector<T, D>, f64)>) -> Vec<SliceResult<T, D>>,
+    ) -> Result<IntegrationResult<OVector<T, D>>, IntegrationError>
+    where
+        f64: From<T>,
+        T: Copy + Scalar + ClosedAdd + ClosedSub,
+        D: Dim,
+        C: Fn(f64, OVector<T, D>, f64) -> Sc,
+        Sc: OdeSolver<OVector<T, D>>,
+        DefaultAllocator: Allocator<T, D>,
+    {
+        if self.slices == 0 {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: "the number of slices must be strictly positive".to_string(),
+            });
+        }
+        self.iterations = 0;
+        self.stats = Stats::new();
+        let n = self.slices;
+        let t = linspace(x, x_end, n + 1);
+
+        // Initial coarse sweep
+        let mut u = vec![y];
+        let mut g_old = Vec::with_capacity(n);
+        for i in 0..n {
+            let g = self.coarse_step(t[i], u[i].clone(), t[i + 1])?;
+            u.push(g.clone());
+            g_old.push(g);
+        }
+
+        loop {
+            let tasks = (0..n).map(|i| (t[i], u[i].clone(), t[i + 1])).collect();
+            let fine = sweep(&self.fine, tasks)
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?;
+            for result in &fine {
+                self.stats.merge(&result.stats);
+            }
+            self.iterations += 1;
+
+            // Correction sweep
+            let mut change = 0.0_f64;
+            for i in 0..n {
+                let g = self.coarse_step(t[i], u[i].clone(), t[i + 1])?;
+                let f_end = fine[i].y.last().unwrap_or(&u[i]);
+                let u_new = &g + f_end - &g_old[i];
+                change = (&u_new - &u[i + 1])
+                    .iter()
+                    .fold(change, |m, v| m.max(f64::from(*v).abs()));
+                u[i + 1] = u_new;
+                g_old[i] = g;
+            }
+
+            if change <= self.tol || self.iterations as usize >= n {
+                let mut result = IntegrationResult {
+                    x: Vec::new(),
+                    y: Vec::new(),
+                    stats: self.stats,
+                };
+                for (i, slice) in fine.into_iter().enumerate() {
+                    // The start of a slice repeats the end of the previous one
+                    let skip = usize::from(i > 0);
+                    result.x.extend(slice.x.into_iter().skip(skip));
+                    result.y.extend(slice.y.into_iter().skip(skip));
+                }
+                return Ok(result);
+            }
+            if self.iterations >= self.max_iter {
+                return Err(IntegrationError::PararealDidNotConverge {
+                    iterations: self.iterations,
+                    change,
+                });
+            }
+        }
+    }
+
+    /// Integrates a slice with the coarse solver and returns the state at its end.
+    fn coarse_step<T, D, Sc>(
+        &mut self,
+        a: f64,
+        y: OVector<T, D>,
+        b: f64,
+    ) -> Result<OVector<T, D>, IntegrationError>
+    where
+        T: Scalar,
+        D: Dim,
+        C: Fn(f64, OVector<T, D>, f64) -> Sc,
+        Sc: OdeSolver<OVector<T, D>>,
+        DefaultAllocator: Allocator<T, D>,
+    {
+        let mut solver = (self.coarse)(a, y, b);
+        self.stats.merge(&solver.integrate()?);
+        Ok(solver.y_final().clone())
+    }
+
+    /// Number of iterations performed by the last call to `solve`.
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+}
+
+/// Result of the fine integration of a slice.
+type SliceResult<T, D> = Result<IntegrationResult<OVector<T, D>>, IntegrationError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::HarmonicOscillator;
+    use crate::{Euler, RungeKutta4};
+
+    #[test]
+    fn converges_to_the_serial_fine_solution() {
+        let problem = HarmonicOscillator::default();
+        let y0 = problem.initial_state();
+        let coarse = |a, y, b| Euler::new(problem, a, y, b, vec![0.01, 0.01, 0.01]);
+        let fine = |a, y, b| RungeKutta4::new(problem, a, y, b, 0.01);
+        let mut parareal = Parareal::new(coarse, fine, 10);
+        parareal.tolerance(1e-6);
+        let result = parareal.solve(0.0, y0, 10.0).unwrap();
+        assert!(parareal.iterations() <= 5, "{}", parareal.iterations());
+
+        let mut serial = RungeKutta4::new(problem, 0.0, y0, 10.0, 0.01);
+        serial.integrate().unwrap();
+        assert_eq!(result.x.len(), serial.x_out().len());
+        for (y, y_serial) in result.y.iter().zip(serial.y_out()) {
+            assert!((y - y_serial).norm() < 1e-6, "{}", (y - y_serial).norm());
+        }
+    }
+}
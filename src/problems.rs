@@ -0,0 +1,450 @@
+//! Classic test problems implementing the `System` trait, to try the solvers against.
+//!
+//! Each problem provides its usual initial state and interval of integration, and its invariant
+//! or exact solution when known.
+
+use crate::dop_shared::{SecondOrderSystem, System};
+
+use nalgebra::{DVector, Matrix2, Matrix3, Vector2, Vector3};
+use std::f64::consts::PI;
+
+/// Van der Pol oscillator `x'' = mu (1 - x^2) x' - x`, written as the first order system
+/// `y0' = y1`, `y1' = mu (1 - y0^2) y1 - y0`. The problem is stiff for large values of `mu`,
+/// the solution then alternating between slow phases and fast transitions. The Jacobian is
+/// computed analytically.
+#[derive(Clone, Copy, Debug)]
+pub struct VanDerPol {
+    /// Stiffness parameter.
+    pub mu: f64,
+}
+
+impl VanDerPol {
+    /// Creates the oscillator with the stiffness parameter `mu`.
+    pub fn new(mu: f64) -> Self {
+        VanDerPol { mu }
+    }
+
+    /// Initial state `(2, 0)`, close to the limit cycle.
+    pub fn initial_state(&self) -> Vector2<f64> {
+        Vector2::new(2.0, 0.0)
+    }
+
+    /// Interval covering about two periods of the limit cycle, whose period is close to `2 pi`
+    /// for small values of `mu` and to `(3 - 2 ln 2) mu` for large ones.
+    pub fn t_span(&self) -> (f64, f64) {
+        let period = (2.0 * PI).max((3.0 - 2.0 * 2.0_f64.ln()) * self.mu);
+        (0.0, 2.0 * period)
+    }
+}
+
+impl Default for VanDerPol {
+    fn default() -> Self {
+        VanDerPol::new(1.0)
+    }
+}
+
+impl System<Vector2<f64>> for VanDerPol {
+    fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+        dy[0] = y[1];
+        dy[1] = self.mu * (1.0 - y[0] * y[0]) * y[1] - y[0];
+    }
+
+    fn jacobian(&self, _x: f64, y: &Vector2<f64>, out: &mut Matrix2<f64>) {
+        *out = Matrix2::new(
+            0.0,
+            1.0,
+            -2.0 * self.mu * y[0] * y[1] - 1.0,
+            self.mu * (1.0 - y[0] * y[0]),
+        );
+    }
+}
+
+/// Lotka-Volterra predator-prey model `y0' = alpha y0 - beta y0 y1`, `y1' = delta y0 y1 -
+/// gamma y1`, `y0` being the prey and `y1` the predators. The solutions are periodic and
+/// conserve the invariant `delta y0 - gamma ln(y0) + beta y1 - alpha ln(y1)`.
+#[derive(Clone, Copy, Debug)]
+pub struct LotkaVolterra {
+    /// Growth rate of the prey.
+    pub alpha: f64,
+    /// Predation rate.
+    pub beta: f64,
+    /// Death rate of the predators.
+    pub gamma: f64,
+    /// Growth rate of the predators per prey eaten.
+    pub delta: f64,
+}
+
+impl LotkaVolterra {
+    /// Creates the model with the given rates.
+    pub fn new(alpha: f64, beta: f64, gamma: f64, delta: f64) -> Self {
+        LotkaVolterra {
+            alpha,
+            beta,
+            gamma,
+            delta,
+        }
+    }
+
+    /// Initial state `(10, 5)`.
+    pub fn initial_state(&self) -> Vector2<f64> {
+        Vector2::new(10.0, 5.0)
+    }
+
+    /// Interval `[0, 15]`, covering a few periods with the default rates.
+    pub fn t_span(&self) -> (f64, f64) {
+        (0.0, 15.0)
+    }
+}
+
+impl Default for LotkaVolterra {
+    fn default() -> Self {
+        LotkaVolterra::new(1.5, 1.0, 3.0, 1.0)
+    }
+}
+
+impl System<Vector2<f64>> for LotkaVolterra {
+    fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+        dy[0] = self.alpha * y[0] - self.beta * y[0] * y[1];
+        dy[1] = self.delta * y[0] * y[1] - self.gamma * y[1];
+    }
+
+    fn jacobian(&self, _x: f64, y: &Vector2<f64>, out: &mut Matrix2<f64>) {
+        *out = Matrix2::new(
+            self.alpha - self.beta * y[1],
+            -self.beta * y[0],
+            self.delta * y[1],
+            self.delta * y[0] - self.gamma,
+        );
+    }
+
+    fn invariant(&self, _x: f64, y: &Vector2<f64>) -> f64 {
+        self.delta * y[0] - self.gamma * y[0].ln() + self.beta * y[1] - self.alpha * y[1].ln()
+    }
+}
+
+/// Lorenz system `y0' = sigma (y1 - y0)`, `y1' = y0 (rho - y2) - y1`, `y2' = y0 y1 - beta y2`,
+/// whose solutions are chaotic with the classic parameters, so that only short time accuracy
+/// can be compared between solvers.
+#[derive(Clone, Copy, Debug)]
+pub struct Lorenz {
+    /// Prandtl number.
+    pub sigma: f64,
+    /// Rayleigh number.
+    pub rho: f64,
+    /// Geometric factor.
+    pub beta: f64,
+}
+
+impl Lorenz {
+    /// Creates the system with the given parameters.
+    pub fn new(sigma: f64, rho: f64, beta: f64) -> Self {
+        Lorenz { sigma, rho, beta }
+    }
+
+    /// Initial state `(1, 1, 1)`.
+    pub fn initial_state(&self) -> Vector3<f64> {
+        Vector3::new(1.0, 1.0, 1.0)
+    }
+
+    /// Interval `[0, 20]`, over which the trajectory settles on the attractor.
+    pub fn t_span(&self) -> (f64, f64) {
+        (0.0, 20.0)
+    }
+}
+
+impl Default for Lorenz {
+    fn default() -> Self {
+        Lorenz::new(10.0, 28.0, 8.0 / 3.0)
+    }
+}
+
+impl System<Vector3<f64>> for Lorenz {
+    fn ode(&self, _x: f64, y: &Vector3<f64>, dy: &mut Vector3<f64>) {
+        dy[0] = self.sigma * (y[1] - y[0]);
+        dy[1] = y[0] * (self.rho - y[2]) - y[1];
+        dy[2] = y[0] * y[1] - self.beta * y[2];
+    }
+
+    fn jacobian(&self, _x: f64, y: &Vector3<f64>, out: &mut Matrix3<f64>) {
+        *out = Matrix3::new(
+            -self.sigma,
+            self.sigma,
+            0.0,
+            self.rho - y[2],
+            -1.0,
+            -y[0],
+            y[1],
+            y[0],
+            -self.beta,
+        );
+    }
+}
+
+/// Harmonic oscillator `x'' = -omega^2 x`, written as the first order system `y0' = y1`,
+/// `y1' = -omega^2 y0`. The energy `(y1^2 + omega^2 y0^2) / 2` is conserved and the exact
+/// solution is given by [`exact_solution`](Self::exact_solution).
+#[derive(Clone, Copy, Debug)]
+pub struct HarmonicOscillator {
+    /// Angular frequency.
+    pub omega: f64,
+}
+
+impl HarmonicOscillator {
+    /// Creates the oscillator with the angular frequency `omega`.
+    pub fn new(omega: f64) -> Self {
+        HarmonicOscillator { omega }
+    }
+
+    /// Initial state `(1, 0)`.
+    pub fn initial_state(&self) -> Vector2<f64> {
+        Vector2::new(1.0, 0.0)
+    }
+
+    /// Interval covering ten periods.
+    pub fn t_span(&self) -> (f64, f64) {
+        (0.0, 20.0 * PI / self.omega)
+    }
+
+    /// Exact solution at `x` starting from [`initial_state`](Self::initial_state) at 0.
+    pub fn exact_solution(&self, x: f64) -> Vector2<f64> {
+        let (sin, cos) = (self.omega * x).sin_cos();
+        Vector2::new(cos, -self.omega * sin)
+    }
+}
+
+impl Default for HarmonicOscillator {
+    fn default() -> Self {
+        HarmonicOscillator::new(1.0)
+    }
+}
+
+impl System<Vector2<f64>> for HarmonicOscillator {
+    fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+        dy[0] = y[1];
+        dy[1] = -self.omega * self.omega * y[0];
+    }
+
+    fn jacobian(&self, _x: f64, _y: &Vector2<f64>, out: &mut Matrix2<f64>) {
+        *out = Matrix2::new(0.0, 1.0, -self.omega * self.omega, 0.0);
+    }
+
+    fn invariant(&self, _x: f64, y: &Vector2<f64>) -> f64 {
+        0.5 * (y[1] * y[1] + self.omega * self.omega * y[0] * y[0])
+    }
+}
+
+/// Gravitational N-body problem in three dimensions, in units where the gravitational constant
+/// equals one. The positions and velocities of the bodies are stored one after the other,
+/// `(x, y, z)` for each body. The system implements `SecondOrderSystem` on the positions, e.g. for
+/// [`VelocityVerlet`](crate::VelocityVerlet), and `System` on the first order state made of the
+/// positions followed by the velocities, whose invariant is the total energy given by
+/// [`energy`](Self::energy). The initial state is a planetary system of at least two bodies.
+///
+/// The interaction is softened with the length `softening`, the potential of each pair of bodies
+/// at distance `r` being `-m_i m_j / sqrt(r^2 + softening^2)`, so that the accelerations remain
+/// bounded during close encounters. A softening of 0 gives the exact Newtonian interaction.
+#[derive(Clone, Debug)]
+pub struct NBody {
+    /// Masses of the bodies.
+    pub masses: Vec<f64>,
+    /// Softening length.
+    pub softening: f64,
+}
+
+impl NBody {
+    /// Creates the problem for bodies of the given masses with the softening length `softening`.
+    pub fn new(masses: Vec<f64>, softening: f64) -> Self {
+        NBody { masses, softening }
+    }
+
+    /// Number of bodies.
+    pub fn num_bodies(&self) -> usize {
+        self.masses.len()
+    }
+
+    /// Initial positions of a planetary system: the first body lies at the origin and the body `i`
+    /// on the circle of radius `i` of the `(x, y)` plane, at the angle `2 pi i / n` for `n` bodies,
+    /// before moving to the frame of the centre of mass.
+    pub fn initial_positions(&self) -> DVector<f64> {
+        let (q, _) = self.planetary_system();
+        q
+    }
+
+    /// Initial velocities of the planetary system of
+    /// [`initial_positions`](Self::initial_positions): each body other than the first one moves
+    /// with the velocity of a circular orbit around the first body, ignoring the other bodies,
+    /// before moving to the frame of the centre of mass. The orbits are circular for two bodies
+    /// when the second one is much lighter than the first one, and remain bound otherwise.
+    pub fn initial_velocities(&self) -> DVector<f64> {
+        let (_, v) = self.planetary_system();
+        v
+    }
+
+    /// Initial first order state, made of the [`initial_positions`](Self::initial_positions)
+    /// followed by the [`initial_velocities`](Self::initial_velocities).
+    pub fn initial_state(&self) -> DVector<f64> {
+        let (q, v) = self.planetary_system();
+        DVector::from_iterator(q.len() + v.len(), q.iter().chain(v.iter()).copied())
+    }
+
+    /// Period `2 pi / sqrt(m_0 + m_1)` of a circular orbit of radius 1 of the second body around
+    /// the first one, i.e. the period of the second body in the initial state when it is much
+    /// lighter than the first one.
+    pub fn period(&self) -> f64 {
+        2.0 * PI / (self.masses[0] + self.masses[1]).sqrt()
+    }
+
+    /// Interval covering ten periods of the orbit of the second body, see
+    /// [`period`](Self::period).
+    pub fn t_span(&self) -> (f64, f64) {
+        (0.0, 10.0 * self.period())
+    }
+
+    /// Positions and velocities of the planetary system of the initial state.
+    fn planetary_system(&self) -> (DVector<f64>, DVector<f64>) {
+        let n = self.num_bodies();
+        let mut q = DVector::zeros(3 * n);
+        let mut v = DVector::zeros(3 * n);
+        for i in 1..n {
+            let r = i as f64;
+            let (sin, cos) = (2.0 * PI * r / n as f64).sin_cos();
+            let speed = (self.masses[0] / r).sqrt();
+            q[3 * i] = r * cos;
+            q[3 * i + 1] = r * sin;
+            v[3 * i] = -speed * sin;
+            v[3 * i + 1] = speed * cos;
+        }
+        // Frame of the centre of mass
+        let total: f64 = self.masses.iter().sum();
+        for y in [&mut q, &mut v] {
+            let mut centre = Vector3::zeros();
+            for (i, m) in self.masses.iter().enumerate() {
+                centre += y.fixed_rows::<3>(3 * i) * (*m / total);
+            }
+            for i in 0..n {
+                let mut y_i = y.fixed_rows_mut::<3>(3 * i);
+                y_i -= centre;
+            }
+        }
+        (q, v)
+    }
+
+    /// Total energy of the bodies at the positions `q` with the velocities `v`, conserved by the
+    /// exact solution.
+    pub fn energy(&self, q: &DVector<f64>, v: &DVector<f64>) -> f64 {
+        let n = self.num_bodies();
+        let mut energy = 0.0;
+        for i in 0..n {
+            energy += 0.5 * self.masses[i] * v.rows(3 * i, 3).norm_squared();
+            for j in i + 1..n {
+                let r2 = (q.rows(3 * j, 3) - q.rows(3 * i, 3)).norm_squared();
+                energy -=
+                    self.masses[i] * self.masses[j] / (r2 + self.softening * self.softening).sqrt();
+            }
+        }
+        energy
+    }
+
+    /// Computes the gravitational accelerations of the bodies at the positions `q` in `out`,
+    /// looping once over each pair of bodies.
+    fn accelerations(&self, q: &DVector<f64>, out: &mut DVector<f64>) {
+        out.fill(0.0);
+        let n = self.num_bodies();
+        for i in 0..n {
+            for j in i + 1..n {
+                let r = q.rows(3 * j, 3) - q.rows(3 * i, 3);
+                let r2 = r.norm_squared() + self.softening * self.softening;
+                let f = r / (r2 * r2.sqrt());
+                let mut a_i = out.rows_mut(3 * i, 3);
+                a_i += &f * self.masses[j];
+                let mut a_j = out.rows_mut(3 * j, 3);
+                a_j -= &f * self.masses[i];
+            }
+        }
+    }
+}
+
+impl Default for NBody {
+    /// Sun and a planet a thousand times lighter on a nearly circular orbit, without softening.
+    fn default() -> Self {
+        NBody::new(vec![1.0, 1.0e-3], 0.0)
+    }
+}
+
+impl SecondOrderSystem<DVector<f64>> for NBody {
+    fn acceleration(
+        &self,
+        _x: f64,
+        q: &DVector<f64>,
+        _qdot: &DVector<f64>,
+        out: &mut DVector<f64>,
+    ) {
+        self.accelerations(q, out);
+    }
+}
+
+impl System<DVector<f64>> for NBody {
+    fn ode(&self, _x: f64, y: &DVector<f64>, dy: &mut DVector<f64>) {
+        let n = 3 * self.num_bodies();
+        dy.rows_mut(0, n).copy_from(&y.rows(n, n));
+        let mut a = DVector::zeros(n);
+        self.accelerations(&y.rows(0, n).into_owned(), &mut a);
+        dy.rows_mut(n, n).copy_from(&a);
+    }
+
+    fn invariant(&self, _x: f64, y: &DVector<f64>) -> f64 {
+        let n = 3 * self.num_bodies();
+        self.energy(&y.rows(0, n).into_owned(), &y.rows(n, n).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RungeKutta4, VelocityVerlet};
+
+    /// Largest value of `drift`, and of its first tenth.
+    fn max_drift(drift: &[f64]) -> (f64, f64) {
+        let max = |d: &[f64]| d.iter().cloned().fold(0.0, f64::max);
+        (max(drift), max(&drift[..drift.len() / 10]))
+    }
+
+    #[test]
+    fn n_body_energy_drift_of_verlet_and_rk4() {
+        let problem = NBody::new(vec![1.0, 1e-3, 1e-3], 0.0);
+        let (q0, v0) = (problem.initial_positions(), problem.initial_velocities());
+        let e0 = problem.energy(&q0, &v0);
+        // A hundred orbits of the inner planet, with the same number of evaluations per unit of
+        // time for both solvers
+        let (x_end, h) = (100.0 * problem.period(), problem.period() / 100.0);
+
+        let mut verlet = VelocityVerlet::new(problem.clone(), 0.0, q0, v0, x_end, h);
+        verlet.integrate().unwrap();
+        let verlet_drift: Vec<f64> = verlet
+            .q_out()
+            .iter()
+            .zip(verlet.v_out())
+            .map(|(q, v)| (problem.energy(q, v) - e0).abs())
+            .collect();
+
+        let x0 = problem.initial_state();
+        let mut rk4 = RungeKutta4::new(problem.clone(), 0.0, x0, x_end, 4.0 * h);
+        rk4.integrate().unwrap();
+        let n = 3 * problem.num_bodies();
+        let rk4_drift: Vec<f64> = rk4
+            .y_out()
+            .iter()
+            .map(|y| {
+                let (q, v) = (y.rows(0, n).into_owned(), y.rows(n, n).into_owned());
+                (problem.energy(&q, &v) - e0).abs()
+            })
+            .collect();
+
+        // The energy error of Verlet stays bounded while that of RK4 grows with the orbits
+        let (verlet_max, verlet_first) = max_drift(&verlet_drift);
+        let (rk4_max, rk4_first) = max_drift(&rk4_drift);
+        assert!(verlet_max < 1.1 * verlet_first);
+        assert!(rk4_max > 5.0 * rk4_first);
+        assert!(rk4_max > 100.0 * verlet_max);
+    }
+}
@@ -0,0 +1,196 @@
+//! Ralston's second order Runge-Kutta method with fixed step size.
+
+use crate::dop_shared::{
+    IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Stats, System,
+};
+use crate::tableau::{ButcherTableau, ExplicitRungeKutta};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The solver runs the generic [`ExplicitRungeKutta`] driver with the
+/// [`ralston`](ButcherTableau::ralston) tableau. The derivative is evaluated at the start of the
+/// step and at the point `2/3` of the way predicted by an euler step, and the solution is
+/// advanced with the weights `1/4` and `3/4`. Among the explicit two stage methods of second
+/// order, which cost two evaluations of `System::ode` per step, this choice minimises the bound
+/// on the local truncation error.
+pub struct Ralston2<V, F>
+where
+    F: System<V>,
+{
+    solver: ExplicitRungeKutta<V, F>,
+}
+
+impl<T, D: Dim, F> Ralston2<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        let solver = ExplicitRungeKutta::new(f, x, y, x_end, step_size, ButcherTableau::ralston());
+        Ralston2 { solver }
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.solver.t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        self.solver.integrate()
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.solver.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.solver.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        self.solver.dy_out()
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.solver.x_final()
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        self.solver.y_final()
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        self.solver.at(x)
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.solver.into_result()
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for Ralston2<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Ralston2::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Ralston2::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Ralston2::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Ralston2::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Ralston2::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for Ralston2<V, F> {
+    fn order(&self) -> u32 {
+        2
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Heun, Vector1};
+
+    /// Linear equation `y' = cos(x) y` with the solution `exp(sin(x))`.
+    struct Modulated;
+
+    impl System<Vector1<f64>> for Modulated {
+        fn ode(&self, x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = x.cos() * y[0];
+        }
+    }
+
+    fn error(mut solver: impl OdeSolver<Vector1<f64>>) -> f64 {
+        solver.integrate().unwrap();
+        (solver.y_final()[0] - 3.0f64.sin().exp()).abs()
+    }
+
+    #[test]
+    fn converges_with_a_lower_error_constant_than_heun() {
+        let ralston = |h| error(Ralston2::new(Modulated, 0.0, Vector1::new(1.0), 3.0, h));
+        let heun = |h| error(Heun::new(Modulated, 0.0, Vector1::new(1.0), 3.0, h));
+        let order = (ralston(0.02) / ralston(0.01)).log2();
+        assert!((order - 2.0).abs() < 0.1, "{}", order);
+        for h in [0.02, 0.01] {
+            assert!(ralston(h) < 0.8 * heun(h));
+        }
+    }
+}
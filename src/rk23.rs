@@ -0,0 +1,477 @@
+//! Explicit Runge-Kutta method of order 3(2) with adaptive step size control (Bogacki-Shampine).
+
+use crate::controller::{PiController, StepController};
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, error_scale, find_segment, hermite_interpolation, initial_step_size, limit_step,
+    order_reduced, ramp_initial_step, ramp_step, record_diagnostic, reject_events,
+    weighted_rms_norm, ControlFlag, ErrorNorm, IntegrationError, IntegrationResult, MethodInfo,
+    OdeSolver, Output, SolverLog, Stats, StepDiagnostic, System, Tolerance,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+// Nodes, stage matrix and error coefficients of the Bogacki-Shampine tableau. The third order
+// weights are the last row of the stage matrix, so that the last stage is the derivative at the
+// new point (first same as last).
+const C2: f64 = 1.0 / 2.0;
+const C3: f64 = 3.0 / 4.0;
+const A21: f64 = 1.0 / 2.0;
+const A32: f64 = 3.0 / 4.0;
+const A41: f64 = 2.0 / 9.0;
+const A42: f64 = 1.0 / 3.0;
+const A43: f64 = 4.0 / 9.0;
+const E1: f64 = -5.0 / 72.0;
+const E2: f64 = 1.0 / 12.0;
+const E3: f64 = 1.0 / 9.0;
+const E4: f64 = -1.0 / 8.0;
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Each step costs three evaluations of `System::ode`: the last stage of an accepted step is
+/// reused as the first stage of the next one.
+pub struct BogackiShampine23<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    controller: Box<dyn StepController>,
+    norm: Box<ErrorNorm<V>>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    startup_ramp: usize,
+    stats: Stats,
+    log: SolverLog,
+}
+
+impl<T, D: Dim, F> BogackiShampine23<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        BogackiShampine23 {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            rtol: rtol.into(),
+            atol: atol.into(),
+            controller: Box::new(PiController::default()),
+            norm: Box::new(weighted_rms_norm),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            diagnostics: None,
+            startup_ramp: 0,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the step size controller, a [`PiController`] by default.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.controller = Box::new(controller);
+    }
+
+    /// Sets the norm of the local error estimate used to accept or reject a step, called with the
+    /// error estimate and the error scale `atol + rtol * |y|` of each component. A step is
+    /// accepted when the norm is at most 1. Defaults to [`weighted_rms_norm`]. For instance, the
+    /// maximum norm bounds the error of every component:
+    ///
+    /// ```
+    /// # use ode_event_solvers::{problems::Lorenz, BogackiShampine23, Vector3};
+    /// # let (f, y0) = (Lorenz::default(), Vector3::new(1.0, 1.0, 1.0));
+    /// # let mut solver = BogackiShampine23::new(f, 0.0, y0, 1.0, None, 1e-6, 1e-6);
+    /// solver.error_norm(|err: &Vector3<f64>, scale: &Vector3<f64>| {
+    ///     err.iter().zip(scale.iter()).fold(0.0, |acc, (e, sc)| acc.max((e / sc).abs()))
+    /// });
+    /// # solver.integrate()?;
+    /// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+    /// ```
+    pub fn error_norm(&mut self, norm: impl Fn(&OVector<T, D>, &OVector<T, D>) -> f64 + 'static) {
+        self.norm = Box::new(norm);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets the number of accepted steps at the start of the integration during which the step
+    /// size may grow by at most a factor of 2 from one step to the next (default 0), easing into
+    /// the adaptive regime on a problem with a sharp initial layer. An initial step size selected
+    /// automatically is first divided by `2^steps`, the ramp growing it back.
+    pub fn startup_ramp(&mut self, steps: usize) {
+        self.startup_ramp = steps;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). `Stats::order_reduced` then flags an observed order well
+    /// below the nominal one. Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger). Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+
+        let (rows, cols) = self.y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); 4];
+        self.f.ode(self.x, &self.y, &mut k[0]);
+        self.stats.num_eval += 1;
+        self.dy_out.push(k[0].clone());
+        if self.h == 0.0 {
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = OVector::zeros_generic(rows, cols);
+                    self.f.ode(x, y, &mut dy);
+                    dy
+                },
+                self.x,
+                &self.y,
+                &k[0],
+                self.x_end,
+                3,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.h = ramp_initial_step(self.h, self.startup_ramp);
+            self.stats.num_eval += 1;
+        }
+
+        let mut last = false;
+        let mut n_step = 0;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point
+            if (self.x + 1.01 * self.h - self.x_end) * posneg > 0.0 {
+                self.h = self.x_end - self.x;
+                last = true;
+            }
+            n_step += 1;
+
+            let (y_new, err) = self.step(&mut k);
+            self.stats.num_eval += 3;
+            check_eval(&self.f)?;
+
+            let err = self.norm(&err, &y_new);
+            let (accept, h_new) = self.controller.next_step(err, self.h, 2);
+            let h_new = ramp_step(h_new, self.h, self.stats.accepted_steps, self.startup_ramp);
+            record_diagnostic(&mut self.diagnostics, self.x, self.h, err, accept);
+            if accept {
+                self.log.accepted(self.x, self.h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(self.h);
+                // First same as last
+                k[0] = k[3].clone();
+                self.x = if last { self.x_end } else { self.x + self.h };
+                self.y = y_new.clone();
+                self.f.project(self.x, &mut self.y);
+                // Evaluate the first stage again if the projection moved the state
+                if self.y != y_new {
+                    self.f.ode(self.x, &self.y, &mut k[0]);
+                    self.stats.num_eval += 1;
+                }
+                self.dy_out.push(k[0].clone());
+                self.output.push(self.x, self.y.clone());
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                    break;
+                }
+            } else {
+                self.log.rejected(self.x, self.h, err);
+                self.stats.rejected_steps += 1;
+                last = false;
+            }
+            if !last {
+                self.h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, self.h, self.max_step);
+            }
+        }
+        check_eval(&self.f)?;
+        self.stats.order_reduced = order_reduced(&self.diagnostics, 2);
+        Ok(self.stats)
+    }
+
+    /// Computes the stages of one step and returns the third order solution together with the local error estimate.
+    fn step(&self, k: &mut [OVector<T, D>]) -> (OVector<T, D>, OVector<T, D>) {
+        let h = self.h;
+        let y = &self.y;
+
+        let y_tmp = y + k[0].clone() * (h * A21);
+        self.f.ode(self.x + C2 * h, &y_tmp, &mut k[1]);
+        let y_tmp = y + k[1].clone() * (h * A32);
+        self.f.ode(self.x + C3 * h, &y_tmp, &mut k[2]);
+        let y_new = y + (k[0].clone() * A41 + k[1].clone() * A42 + k[2].clone() * A43) * h;
+        self.f.ode(self.x + h, &y_new, &mut k[3]);
+
+        let err =
+            (k[0].clone() * E1 + k[1].clone() * E2 + k[2].clone() * E3 + k[3].clone() * E4) * h;
+        (y_new, err)
+    }
+
+    /// Computes the norm of the local error estimate set with [`error_norm`](Self::error_norm).
+    fn norm(&self, err: &OVector<T, D>, y_new: &OVector<T, D>) -> f64 {
+        let scale = error_scale(&self.y, y_new, &self.rtol, &self.atol);
+        (self.norm)(err, &scale)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for BogackiShampine23<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        BogackiShampine23::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        BogackiShampine23::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        BogackiShampine23::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        BogackiShampine23::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        BogackiShampine23::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for BogackiShampine23<V, F> {
+    fn order(&self) -> u32 {
+        3
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector2;
+    use std::cell::Cell;
+
+    /// Harmonic oscillator counting the evaluations of its right-hand side.
+    struct Oscillator {
+        calls: Cell<u32>,
+    }
+
+    impl System<Vector2<f64>> for Oscillator {
+        fn ode(&self, _x: f64, y: &Vector2<f64>, dy: &mut Vector2<f64>) {
+            self.calls.set(self.calls.get() + 1);
+            dy[0] = y[1];
+            dy[1] = -y[0];
+        }
+    }
+
+    #[test]
+    fn reuses_the_last_stage_as_the_next_first_stage() {
+        let f = Oscillator {
+            calls: Cell::new(0),
+        };
+        let mut solver =
+            BogackiShampine23::new(f, 0.0, Vector2::new(1.0, 0.0), 10.0, 0.1, 1e-6, 1e-6);
+        let stats = solver.integrate().unwrap();
+        let attempts = stats.accepted_steps() + stats.rejected_steps();
+        assert!(stats.rejected_steps() > 0);
+        assert_eq!(solver.f.calls.get(), stats.num_eval());
+        // Three new stages per attempt instead of the four of the tableau
+        assert_eq!(stats.num_eval(), 1 + 3 * attempts);
+        assert!((solver.y_final()[0] - 10.0f64.cos()).abs() < 1e-4);
+    }
+}
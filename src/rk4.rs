@@ -0,0 +1,319 @@
+//! Classical fourth order Runge-Kutta method with fixed step size.
+
+use crate::dop_shared::{
+    check_eval, find_segment, hermite_interpolation, reject_events, step_count, validate_step_size,
+    ControlFlag, IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Output, Stats, System,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Structure containing the parameters for the numerical integration.
+pub struct RungeKutta4<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> RungeKutta4<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        RungeKutta4 {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let (x_new, y_new, dy) = self.step(h);
+            check_eval(&self.f)?;
+            self.dy_out.push(dy);
+            self.x = if last { self.x_end } else { x_new };
+            self.y = y_new;
+            self.f.project(self.x, &mut self.y);
+            self.stats.num_eval += 4;
+            self.stats.accepted_steps += 1;
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        // Derivative at the final state, used for dense output
+        let mut dy = self.y.clone();
+        self.f.ode(self.x, &self.y, &mut dy);
+        self.stats.num_eval += 1;
+        check_eval(&self.f)?;
+        self.dy_out.push(dy);
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the classical Runge-Kutta method.
+    /// Returns the new point together with the derivative at the start of the step.
+    fn step(&self, h: f64) -> (f64, OVector<T, D>, OVector<T, D>) {
+        let (rows, cols) = self.y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); 4];
+
+        self.f.ode(self.x, &self.y, &mut k[0]);
+        self.f.ode(
+            self.x + 0.5 * h,
+            &(&self.y + k[0].clone() * (0.5 * h)),
+            &mut k[1],
+        );
+        self.f.ode(
+            self.x + 0.5 * h,
+            &(&self.y + k[1].clone() * (0.5 * h)),
+            &mut k[2],
+        );
+        self.f
+            .ode(self.x + h, &(&self.y + k[2].clone() * h), &mut k[3]);
+
+        let x_new = self.x + h;
+        let y_new = &self.y
+            + (k[0].clone() * (1.0 / 6.0)
+                + k[1].clone() * (1.0 / 3.0)
+                + k[2].clone() * (1.0 / 3.0)
+                + k[3].clone() * (1.0 / 6.0))
+                * h;
+        (x_new, y_new, k.swap_remove(0))
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for RungeKutta4<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        RungeKutta4::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        RungeKutta4::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        RungeKutta4::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        RungeKutta4::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        RungeKutta4::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for RungeKutta4<V, F> {
+    fn order(&self) -> u32 {
+        4
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector1;
+
+    /// Exponential growth `y' = y`.
+    struct Growth;
+
+    impl System<Vector1<f64>> for Growth {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+    }
+
+    #[test]
+    fn growth_matches_the_exponential() {
+        let mut solver = RungeKutta4::new(Growth, 0.0, Vector1::new(1.0), 1.0, 0.05);
+        let stats = solver.integrate().unwrap();
+        assert_eq!(solver.x_out().len(), 21);
+        assert_eq!(solver.x_final(), 1.0);
+        assert!((solver.y_final()[0] - std::f64::consts::E).abs() < 1e-6);
+        // Four stages per step and the derivative at the final state for the dense output
+        assert_eq!(stats.accepted_steps(), 20);
+        assert_eq!(stats.num_eval(), 81);
+    }
+
+    #[test]
+    fn integrates_backward_in_time() {
+        let e = std::f64::consts::E;
+        let mut solver = RungeKutta4::new(Growth, 1.0, Vector1::new(e), 0.0, 0.01);
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 0.0);
+        assert!(solver.x_out().windows(2).all(|w| w[1] < w[0]));
+        assert!((solver.y_final()[0] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_invalid_step_sizes() {
+        for h in [0.0, -0.1, f64::NAN, f64::INFINITY] {
+            let mut solver = RungeKutta4::new(Growth, 0.0, Vector1::new(1.0), 1.0, h);
+            assert!(
+                matches!(
+                    solver.integrate(),
+                    Err(IntegrationError::InvalidStepConfig { .. })
+                ),
+                "{}",
+                h
+            );
+        }
+    }
+}
@@ -0,0 +1,509 @@
+//! Linearly implicit Rosenbrock method of order 4(3) with adaptive step size control.
+//!
+//! The coefficients are those of Shampine (Implementation of Rosenbrock methods, ACM
+//! Transactions on Mathematical Software, 1982), in the formulation of Kaps and Rentrop where the
+//! stages are solved for directly.
+
+use crate::controller::{PiController, StepController};
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, error_scale, find_segment, hermite_interpolation, initial_step_size, limit_step,
+    order_reduced, ramp_initial_step, ramp_step, record_diagnostic, reject_events,
+    weighted_rms_norm, ControlFlag, ErrorNorm, Factorization, IntegrationError, IntegrationResult,
+    LogLevel, MethodInfo, OdeSolver, Output, SolverLog, Stats, StepDiagnostic, System, Tolerance,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OMatrix, OVector, RealField};
+use simba::scalar::SubsetOf;
+
+const GAMMA: f64 = 0.5;
+const A21: f64 = 2.0;
+const A31: f64 = 48.0 / 25.0;
+const A32: f64 = 6.0 / 25.0;
+const C21: f64 = -8.0;
+const C31: f64 = 372.0 / 25.0;
+const C32: f64 = 12.0 / 5.0;
+const C41: f64 = -112.0 / 125.0;
+const C42: f64 = -54.0 / 125.0;
+const C43: f64 = -2.0 / 5.0;
+const B1: f64 = 19.0 / 9.0;
+const B2: f64 = 1.0 / 2.0;
+const B3: f64 = 25.0 / 108.0;
+const B4: f64 = 125.0 / 108.0;
+const E1: f64 = 17.0 / 54.0;
+const E2: f64 = 7.0 / 36.0;
+const E4: f64 = 125.0 / 108.0;
+const C1X: f64 = 1.0 / 2.0;
+const C2X: f64 = -3.0 / 2.0;
+const C3X: f64 = 121.0 / 50.0;
+const C4X: f64 = 29.0 / 250.0;
+const A2X: f64 = 1.0;
+const A3X: f64 = 3.0 / 5.0;
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Each step solves four linear systems with the matrix `I / (gamma * h) - J`, which is
+/// factorized once per step, instead of the nonlinear equations of the implicit methods. The
+/// Jacobian `J` is given by `System::jacobian` and is evaluated once per step, and kept when the
+/// step is rejected. The derivative of the right-hand side with respect to the independent
+/// variable is approximated by finite differences. The method is A-stable and suited to
+/// moderately stiff problems at moderate tolerances. The solution between the steps is
+/// interpolated with cubic Hermite polynomials. `System::mass_matrix` is not supported.
+pub struct Rosenbrock4<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    h_accepted: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    norm: Box<ErrorNorm<V>>,
+    controller: Box<dyn StepController>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    startup_ramp: usize,
+    stats: Stats,
+    log: SolverLog,
+}
+
+impl<T, D, F> Rosenbrock4<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Initial guess for the step size, or `None` to let the solver choose it
+    /// * `rtol`        - Relative tolerance used in the computation of the adaptive step size
+    /// * `atol`        - Absolute tolerance used in the computation of the adaptive step size
+    ///
+    /// The tolerances are either scalars or vectors with one entry per component of the state.
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        rtol: impl Into<Tolerance>,
+        atol: impl Into<Tolerance>,
+    ) -> Self {
+        Rosenbrock4 {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            h_accepted: 0.0,
+            rtol: rtol.into(),
+            atol: atol.into(),
+            norm: Box::new(weighted_rms_norm),
+            controller: Box::new(PiController::default()),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            diagnostics: None,
+            startup_ramp: 0,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the norm of the local error estimate, see [`Dopri5::error_norm`](crate::Dopri5::error_norm).
+    pub fn error_norm(&mut self, norm: impl Fn(&OVector<T, D>, &OVector<T, D>) -> f64 + 'static) {
+        self.norm = Box::new(norm);
+    }
+
+    /// Sets the step size controller, a [`PiController`] by default.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.controller = Box::new(controller);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets the number of accepted steps at the start of the integration during which the step
+    /// size may grow by at most a factor of 2 from one step to the next (default 0), easing into
+    /// the adaptive regime on a problem with a sharp initial layer. An initial step size selected
+    /// automatically is first divided by `2^steps`, the ramp growing it back.
+    pub fn startup_ramp(&mut self, steps: usize) {
+        self.startup_ramp = steps;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). `Stats::order_reduced` then flags an observed order well
+    /// below the nominal one. Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger), together with a warning for every nearly singular
+    /// iteration matrix. Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+
+        let (rows, _) = self.y.shape_generic();
+        let mut dy = self.y.clone();
+        self.f.ode(self.x, &self.y, &mut dy);
+        self.stats.num_eval += 1;
+        self.dy_out.push(dy.clone());
+        if self.h == 0.0 {
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = y.clone();
+                    self.f.ode(x, y, &mut dy);
+                    dy
+                },
+                self.x,
+                &self.y,
+                &dy,
+                self.x_end,
+                4,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.h = ramp_initial_step(self.h, self.startup_ramp);
+            self.stats.num_eval += 1;
+        }
+        let mut jac = OMatrix::zeros_generic(rows, rows);
+        let mut current_jac = false;
+
+        let mut last = false;
+        let mut n_step = 0;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point
+            if (self.x + 1.01 * self.h - self.x_end) * posneg > 0.0 {
+                self.h = self.x_end - self.x;
+                last = true;
+            }
+            n_step += 1;
+
+            if !current_jac {
+                self.f.jacobian(self.x, &self.y, &mut jac);
+                self.stats.num_jac_eval += 1;
+                current_jac = true;
+            }
+            let step = self.step(&dy, &jac);
+            check_eval(&self.f)?;
+            let (y_new, err) = match step {
+                Some(step) => step,
+                None => {
+                    // Singular iteration matrix, retry with a smaller step
+                    self.log.log(LogLevel::Warn, self.x, self.h, || {
+                        "singular iteration matrix, step rejected".to_string()
+                    });
+                    record_diagnostic(&mut self.diagnostics, self.x, self.h, f64::INFINITY, false);
+                    self.stats.rejected_steps += 1;
+                    self.h = limit_step(0.5 * self.h, self.min_step, self.max_step, self.x)?;
+                    last = false;
+                    continue;
+                }
+            };
+            let scale = error_scale(&self.y, &y_new, &self.rtol, &self.atol);
+            let err = (self.norm)(&err, &scale);
+            let (accept, h_new) = self.controller.next_step(err, self.h, 3);
+            let h_new = ramp_step(h_new, self.h, self.stats.accepted_steps, self.startup_ramp);
+            record_diagnostic(&mut self.diagnostics, self.x, self.h, err, accept);
+            if accept {
+                self.log.accepted(self.x, self.h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(self.h);
+                self.x = if last { self.x_end } else { self.x + self.h };
+                self.y = y_new;
+                self.f.project(self.x, &mut self.y);
+                self.h_accepted = self.h;
+                current_jac = false;
+                self.f.ode(self.x, &self.y, &mut dy);
+                self.stats.num_eval += 1;
+                self.dy_out.push(dy.clone());
+                self.output.push(self.x, self.y.clone());
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                    break;
+                }
+            } else {
+                self.log.rejected(self.x, self.h, err);
+                self.stats.rejected_steps += 1;
+                last = false;
+            }
+            if !last {
+                self.h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, self.h, self.max_step);
+            }
+        }
+        check_eval(&self.f)?;
+        self.stats.order_reduced = order_reduced(&self.diagnostics, 3);
+        Ok(self.stats)
+    }
+
+    /// Computes one step of size `h` from the current point, `dy` and `jac` being the derivative
+    /// and the Jacobian at the current point. Returns the solution at the end of the step and the
+    /// local error estimate, or `None` if the iteration matrix is singular.
+    fn step(
+        &mut self,
+        dy: &OVector<T, D>,
+        jac: &OMatrix<T, D, D>,
+    ) -> Option<(OVector<T, D>, OVector<T, D>)> {
+        let (rows, cols) = self.y.shape_generic();
+        let h = self.h;
+        let inv_gamma_h: T = nalgebra::convert(1.0 / (GAMMA * h));
+        let lu = Factorization::new(
+            OMatrix::identity_generic(rows, rows) * inv_gamma_h - jac,
+            self.f.jacobian_bandwidth(),
+        );
+        self.log.factorization(self.x, h, &lu);
+
+        // Derivative with respect to the independent variable by forward differences
+        let delta = f64::EPSILON.sqrt() * self.x.abs().max(1.0e-5);
+        let mut dfdx = OVector::zeros_generic(rows, cols);
+        self.f.ode(self.x + delta, &self.y, &mut dfdx);
+        let dfdx = (dfdx - dy) * (1.0 / delta);
+
+        let mut k = OVector::zeros_generic(rows, cols);
+        let g1 = lu.solve(&(dy + dfdx.clone() * (h * C1X)))?;
+
+        let y2 = &self.y + g1.clone() * A21;
+        self.f.ode(self.x + A2X * h, &y2, &mut k);
+        let g2 = lu.solve(&(&k + dfdx.clone() * (h * C2X) + g1.clone() * (C21 / h)))?;
+
+        let y3 = &self.y + g1.clone() * A31 + g2.clone() * A32;
+        self.f.ode(self.x + A3X * h, &y3, &mut k);
+        let g3 = lu.solve(
+            &(&k + dfdx.clone() * (h * C3X) + (g1.clone() * C31 + g2.clone() * C32) * (1.0 / h)),
+        )?;
+        let g4 = lu.solve(
+            &(&k + dfdx * (h * C4X)
+                + (g1.clone() * C41 + g2.clone() * C42 + g3.clone() * C43) * (1.0 / h)),
+        )?;
+        self.stats.num_eval += 4;
+
+        let y_new = &self.y + g1.clone() * B1 + g2.clone() * B2 + g3 * B3 + g4.clone() * B4;
+        let err = g1 * E1 + g2 * E2 + g4 * E4;
+        Some((y_new, err))
+    }
+
+    /// Getter for the last accepted step size.
+    pub fn last_step_size(&self) -> f64 {
+        self.h_accepted
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D, F> OdeSolver<OVector<T, D>> for Rosenbrock4<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + RealField,
+    D: Dim + DimMin<D, Output = D>,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Rosenbrock4::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Rosenbrock4::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Rosenbrock4::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Rosenbrock4::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Rosenbrock4::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for Rosenbrock4<V, F> {
+    fn order(&self) -> u32 {
+        4
+    }
+
+    fn is_implicit(&self) -> bool {
+        true
+    }
+
+    fn is_a_stable(&self) -> bool {
+        true
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::VanDerPol;
+    use crate::ImplicitEuler;
+
+    #[test]
+    fn takes_fewer_steps_than_implicit_euler_on_a_stiff_problem() {
+        // Slow phase of the stiff Van der Pol oscillator, before its first fast transition
+        let problem = VanDerPol::new(1000.0);
+        let (y0, x_end) = (problem.initial_state(), 700.0);
+        let mut reference = Rosenbrock4::new(problem, 0.0, y0, x_end, None, 1e-11, 1e-11);
+        reference.integrate().unwrap();
+
+        let mut solver = Rosenbrock4::new(problem, 0.0, y0, x_end, None, 1e-6, 1e-6);
+        let stats = solver.integrate().unwrap();
+        let error = (solver.y_final() - reference.y_final()).norm();
+        let attempts = stats.accepted_steps() + stats.rejected_steps();
+        assert!(attempts < 100, "{}", attempts);
+        // One Jacobian per step at most and no Newton iteration
+        assert!(stats.num_jac_eval() <= attempts);
+        assert_eq!(stats.num_newton_iter(), 0);
+
+        // A hundred times as many implicit Euler steps are still less accurate
+        let h = x_end / (100 * attempts) as f64;
+        let mut implicit_euler = ImplicitEuler::new(problem, 0.0, y0, x_end, h, 1e-10, 10);
+        implicit_euler.integrate().unwrap();
+        assert!((implicit_euler.y_final() - reference.y_final()).norm() > error);
+    }
+}
@@ -0,0 +1,269 @@
+//! Euler-Maruyama method with fixed step size for stochastic differential equations.
+
+use crate::dop_shared::{
+    check_eval, reject_events, step_count, validate_step_size, ControlFlag, IntegrationError,
+    IntegrationResult, OdeSolver, Stats, System,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use rand::rngs::StdRng;
+use rand::Rng;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Integrates `dy = f(x, y) dx + g(x, y) dW`, where `f` is given by `System::ode`, `g` by
+/// `System::diffusion` and `W` is a vector of independent Wiener processes. The method is of
+/// strong order 1/2 and weak order 1. Each step costs one evaluation of `System::ode`, counted in
+/// the statistics, and one of `System::diffusion`. The random number generator is supplied by the
+/// caller, e.g. a seeded `StdRng`, so that the paths are reproducible.
+pub struct EulerMaruyama<V, F, R = StdRng>
+where
+    F: System<V>,
+    R: Rng,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    rng: R,
+    x_out: Vec<f64>,
+    y_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F, R> EulerMaruyama<OVector<T, D>, F, R>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    R: Rng,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    /// * `rng`         - Random number generator used to draw the Wiener increments
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64, rng: R) -> Self {
+        EulerMaruyama {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            rng,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let y_new = self.step(h);
+            check_eval(&self.f)?;
+            self.y = y_new;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.f.project(self.x, &mut self.y);
+            self.stats.num_eval += 1;
+            self.stats.accepted_steps += 1;
+            if !self.y.iter().all(|y_i| f64::from(*y_i).is_finite()) {
+                return Err(IntegrationError::NonFinite { x: self.x });
+            }
+
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the Euler-Maruyama method.
+    fn step(&mut self, h: f64) -> OVector<T, D> {
+        let (rows, cols) = self.y.shape_generic();
+        let mut drift = OVector::zeros_generic(rows, cols);
+        let mut diffusion = OVector::zeros_generic(rows, cols);
+        self.f.ode(self.x, &self.y, &mut drift);
+        self.f.diffusion(self.x, &self.y, &mut diffusion);
+
+        let sqrt_h = h.abs().sqrt();
+        let rng = &mut self.rng;
+        let dw = OVector::from_fn_generic(rows, cols, |_, _| {
+            nalgebra::convert::<f64, T>(sqrt_h * standard_normal(rng))
+        });
+        &self.y + drift * h + diffusion.component_mul(&dw)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        &self.y_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        IntegrationResult {
+            x: self.x_out,
+            y: self.y_out,
+            stats: self.stats,
+        }
+    }
+}
+
+impl<T, D: Dim, F, R> OdeSolver<OVector<T, D>> for EulerMaruyama<OVector<T, D>, F, R>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    R: Rng,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        EulerMaruyama::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        EulerMaruyama::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        EulerMaruyama::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        EulerMaruyama::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        EulerMaruyama::y_final(self)
+    }
+}
+
+/// Draws a sample of the standard normal distribution with the Box-Muller transform.
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    // 1 - u lies in (0, 1], keeping the logarithm finite
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector1;
+    use rand::SeedableRng;
+
+    /// Geometric Brownian motion `dy = mu y dx + sigma y dW`.
+    struct Gbm {
+        mu: f64,
+        sigma: f64,
+    }
+
+    impl System<Vector1<f64>> for Gbm {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = self.mu * y[0];
+        }
+
+        fn diffusion(&self, _x: f64, y: &Vector1<f64>, out: &mut Vector1<f64>) {
+            out[0] = self.sigma * y[0];
+        }
+    }
+
+    fn final_value(seed: u64) -> f64 {
+        let gbm = Gbm {
+            mu: 0.5,
+            sigma: 0.3,
+        };
+        let rng = StdRng::seed_from_u64(seed);
+        let mut solver = EulerMaruyama::new(gbm, 0.0, Vector1::new(1.0), 1.0, 0.01, rng);
+        solver.integrate().unwrap();
+        solver.y_final()[0]
+    }
+
+    #[test]
+    fn sample_mean_of_geometric_brownian_motion() {
+        let n = 2000;
+        let samples: Vec<f64> = (0..n).map(final_value).collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = samples.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        let standard_error = (variance / n as f64).sqrt();
+        // Analytic mean exp(mu x) at x = 1
+        assert!((mean - 0.5f64.exp()).abs() < 4.0 * standard_error);
+    }
+
+    #[test]
+    fn seeded_paths_are_reproducible() {
+        assert_eq!(final_value(7), final_value(7));
+        assert_ne!(final_value(7), final_value(8));
+    }
+}
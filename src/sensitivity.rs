@@ -0,0 +1,312 @@
+//! Forward sensitivity analysis with the classical fourth order Runge-Kutta method.
+
+use crate::dop_shared::{
+    check_eval, reject_events, step_count, validate_step_size, ControlFlag, IntegrationError,
+    IntegrationResult, OdeSolver, Stats, System,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OMatrix, OVector, Scalar};
+use num_traits::{One, Zero};
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The sensitivities `s_j = dy/dp_j` of the solution with respect to the `System::num_params`
+/// parameters of the system satisfy `s_j' = (df/dy) s_j + df/dp_j`, where `df/dy` is given by
+/// `System::jacobian` and `df/dp_j` by `System::df_dp`. They are integrated alongside the state
+/// with a fixed step size, each stage requiring one evaluation of the right-hand side, of the
+/// Jacobian and of the parameter derivatives.
+pub struct Sensitivity<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    s: Vec<V>,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    y_out: Vec<V>,
+    s_out: Vec<Vec<V>>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> Sensitivity<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero + One,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    /// The initial sensitivities are zero, i.e. the initial condition does not depend on the
+    /// parameters, unless set with [`initial_sensitivity`](Self::initial_sensitivity).
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        let (rows, cols) = y.shape_generic();
+        let s = vec![OVector::zeros_generic(rows, cols); f.num_params()];
+        Sensitivity {
+            f,
+            x,
+            y,
+            s,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            y_out: Vec::new(),
+            s_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Sets the initial sensitivities, one vector per parameter.
+    pub fn initial_sensitivity(&mut self, s: Vec<OVector<T, D>>) {
+        self.s = s;
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        if self.s.len() != self.f.num_params() {
+            return Err(IntegrationError::InvalidStepConfig {
+                reason: format!(
+                    "expected {} initial sensitivities, got {}",
+                    self.f.num_params(),
+                    self.s.len()
+                ),
+            });
+        }
+        // Save initial values
+        self.x_out.push(self.x);
+        self.y_out.push(self.y.clone());
+        self.s_out.push(self.s.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            self.step(h)?;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.stats.num_eval += 4;
+            self.stats.num_jac_eval += 4;
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.y_out.push(self.y.clone());
+            self.s_out.push(self.s.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the classical Runge-Kutta method on the state and the sensitivities,
+    /// which are left unchanged if an evaluation of the system fails.
+    fn step(&mut self, h: f64) -> Result<(), IntegrationError> {
+        let (k0, l0) = self.derivatives(self.x, &self.y, &self.s);
+        let (y1, s1) = self.stage(&k0, &l0, 0.5 * h);
+        let (k1, l1) = self.derivatives(self.x + 0.5 * h, &y1, &s1);
+        let (y2, s2) = self.stage(&k1, &l1, 0.5 * h);
+        let (k2, l2) = self.derivatives(self.x + 0.5 * h, &y2, &s2);
+        let (y3, s3) = self.stage(&k2, &l2, h);
+        let (k3, l3) = self.derivatives(self.x + h, &y3, &s3);
+
+        let combine =
+            |a: &OVector<T, D>, b: &OVector<T, D>, c: &OVector<T, D>, d: &OVector<T, D>| {
+                (a.clone() * (1.0 / 6.0)
+                    + b.clone() * (1.0 / 3.0)
+                    + c.clone() * (1.0 / 3.0)
+                    + d.clone() * (1.0 / 6.0))
+                    * h
+            };
+        check_eval(&self.f)?;
+        self.y = &self.y + combine(&k0, &k1, &k2, &k3);
+        for j in 0..self.s.len() {
+            self.s[j] = &self.s[j] + combine(&l0[j], &l1[j], &l2[j], &l3[j]);
+        }
+        Ok(())
+    }
+
+    /// Returns the state and the sensitivities advanced by `h` with the derivatives `k` and `l`.
+    fn stage(
+        &self,
+        k: &OVector<T, D>,
+        l: &[OVector<T, D>],
+        h: f64,
+    ) -> (OVector<T, D>, Vec<OVector<T, D>>) {
+        let y = &self.y + k.clone() * h;
+        let s = self
+            .s
+            .iter()
+            .zip(l)
+            .map(|(s_j, l_j)| s_j + l_j.clone() * h)
+            .collect();
+        (y, s)
+    }
+
+    /// Evaluates the derivatives of the state and of the sensitivities.
+    fn derivatives(
+        &self,
+        x: f64,
+        y: &OVector<T, D>,
+        s: &[OVector<T, D>],
+    ) -> (OVector<T, D>, Vec<OVector<T, D>>) {
+        let (rows, cols) = y.shape_generic();
+        let mut dy = OVector::zeros_generic(rows, cols);
+        self.f.ode(x, y, &mut dy);
+        let mut jac = OMatrix::zeros_generic(rows, rows);
+        self.f.jacobian(x, y, &mut jac);
+        let mut ds = vec![OVector::zeros_generic(rows, cols); s.len()];
+        self.f.df_dp(x, y, &mut ds);
+        for (ds_j, s_j) in ds.iter_mut().zip(s) {
+            *ds_j += &jac * s_j;
+        }
+        (dy, ds)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        &self.y_out
+    }
+
+    /// Getter for the sensitivities' output. Each entry holds the sensitivities `dy/dp_j` at the
+    /// corresponding point of [`x_out`](Self::x_out), one vector per parameter.
+    pub fn s_out(&self) -> &Vec<Vec<OVector<T, D>>> {
+        &self.s_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        IntegrationResult {
+            x: self.x_out,
+            y: self.y_out,
+            stats: self.stats,
+        }
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for Sensitivity<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero + One,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D> + Allocator<T, D, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        Sensitivity::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        Sensitivity::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        Sensitivity::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        Sensitivity::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        Sensitivity::y_final(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RungeKutta4, Vector1};
+    use nalgebra::Matrix1;
+
+    /// Logistic growth `y' = r y (1 - y / k)` with the parameters `(r, k)`.
+    struct Logistic {
+        r: f64,
+        k: f64,
+    }
+
+    impl System<Vector1<f64>> for Logistic {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = self.r * y[0] * (1.0 - y[0] / self.k);
+        }
+
+        fn jacobian(&self, _x: f64, y: &Vector1<f64>, out: &mut Matrix1<f64>) {
+            out[0] = self.r * (1.0 - 2.0 * y[0] / self.k);
+        }
+
+        fn num_params(&self) -> usize {
+            2
+        }
+
+        fn df_dp(&self, _x: f64, y: &Vector1<f64>, out: &mut [Vector1<f64>]) {
+            out[0][0] = y[0] * (1.0 - y[0] / self.k);
+            out[1][0] = self.r * y[0] * y[0] / (self.k * self.k);
+        }
+    }
+
+    fn final_state(r: f64, k: f64) -> f64 {
+        let mut solver = RungeKutta4::new(Logistic { r, k }, 0.0, Vector1::new(0.1), 5.0, 0.01);
+        solver.integrate().unwrap();
+        solver.y_final()[0]
+    }
+
+    #[test]
+    fn sensitivities_match_finite_differences() {
+        let (r, k) = (1.5, 2.0);
+        let mut solver = Sensitivity::new(Logistic { r, k }, 0.0, Vector1::new(0.1), 5.0, 0.01);
+        solver.integrate().unwrap();
+        let s = &solver.s_out()[solver.s_out().len() - 1];
+        assert_eq!(solver.s_out().len(), solver.x_out().len());
+        let eps = 1e-6;
+        let dr = (final_state(r + eps, k) - final_state(r - eps, k)) / (2.0 * eps);
+        let dk = (final_state(r, k + eps) - final_state(r, k - eps)) / (2.0 * eps);
+        assert!((s[0][0] - dr).abs() < 1e-6, "{} {}", s[0][0], dr);
+        assert!((s[1][0] - dk).abs() < 1e-6, "{} {}", s[1][0], dk);
+    }
+}
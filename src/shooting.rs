@@ -0,0 +1,175 @@
+//! Two-point boundary value problems solved by the shooting method on top of an initial value
+//! problem solver.
+
+use crate::dop_shared::{IntegrationError, IntegrationResult, OdeSolver, Stats};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, DimMin, OMatrix, OVector};
+use simba::scalar::{RealField, SubsetOf};
+
+/// Structure containing the parameters of the shooting method.
+///
+/// The boundary value problem `y' = f(x, y)` on `[a, b]` with the boundary conditions
+/// `bc(y(a), y(b)) = 0`, as many equations as there are components in the state, is solved by
+/// looking for the initial state `y(a)` whose solution satisfies the boundary conditions. The
+/// initial value problems are integrated by the solvers that `factory` creates for a given initial
+/// state, and the initial state is corrected with Newton's method on the residual
+/// `bc(y(a), y(b))`, its Jacobian being approximated by forward differences. Every iteration thus
+/// costs one integration per component of the state in addition to the one at the current guess.
+pub struct Shooting<G, B> {
+    factory: G,
+    bc: B,
+    tol: f64,
+    max_iter: u32,
+    iterations: u32,
+    stats: Stats,
+}
+
+impl<G, B> Shooting<G, B> {
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `factory`     - Creates the solver integrating from `a` to `b` from an initial state
+    /// * `bc`          - Residual of the boundary conditions given the states at both ends
+    ///
+    pub fn new(factory: G, bc: B) -> Self {
+        Shooting {
+            factory,
+            bc,
+            tol: 1.0e-8,
+            max_iter: 20,
+            iterations: 0,
+            stats: Stats::new(),
+        }
+    }
+
+    /// Sets the tolerance on the maximum norm of the residual of the boundary conditions
+    /// (default 1e-8).
+    pub fn tolerance(&mut self, tol: f64) {
+        self.tol = tol;
+    }
+
+    /// Sets the maximum number of Newton iterations (default 20).
+    pub fn max_iterations(&mut self, max_iter: u32) {
+        self.max_iter = max_iter;
+    }
+
+    /// Solves the boundary value problem starting from the initial state `guess` and returns
+    /// the trajectory of the initial value problem from the initial state found. The statistics of
+    /// the result add up all the integrations performed.
+    pub fn solve<T, D, S>(
+        &mut self,
+        guess: OVector<T, D>,
+    ) -> Result<IntegrationResult<OVector<T, D>>, IntegrationError>
+    where
+        f64: From<T>,
+        T: Copy + SubsetOf<f64> + RealField,
+        D: Dim + DimMin<D, Output = D>,
+        G: Fn(OVector<T, D>) -> S,
+        S: OdeSolver<OVector<T, D>>,
+        B: Fn(&OVector<T, D>, &OVector<T, D>) -> OVector<T, D>,
+        DefaultAllocator: Allocator<T, D> + Allocator<T, D, D> + Allocator<(usize, usize), D>,
+    {
+        self.iterations = 0;
+        self.stats = Stats::new();
+        let (rows, _) = guess.shape_generic();
+        let mut y0 = guess;
+        loop {
+            let mut solver = (self.factory)(y0.clone());
+            self.stats.merge(&solver.integrate()?);
+            let residual = (self.bc)(&y0, solver.y_final());
+            // A NaN component makes the norm infinite instead of being ignored by the maximum
+            let norm = residual.iter().fold(0.0_f64, |m, r| {
+                let r = f64::from(*r).abs();
+                m.max(if r.is_nan() { f64::INFINITY } else { r })
+            });
+            if norm <= self.tol {
+                return Ok(IntegrationResult {
+                    x: solver.x_out().to_vec(),
+                    y: solver.y_out().to_vec(),
+                    stats: self.stats,
+                });
+            }
+            if self.iterations == self.max_iter || !norm.is_finite() {
+                return Err(IntegrationError::ShootingDidNotConverge {
+                    iterations: self.iterations,
+                    residual: norm,
+                });
+            }
+            self.iterations += 1;
+
+            // Jacobian of the residual with respect to the initial state, by forward differences
+            let mut jac = OMatrix::<T, D, D>::zeros_generic(rows, rows);
+            for j in 0..y0.len() {
+                let dy = f64::EPSILON.sqrt() * f64::from(y0[j]).abs().max(1.0);
+                let mut y0_shifted = y0.clone();
+                y0_shifted[j] += T::from_subset(&dy);
+                let mut solver = (self.factory)(y0_shifted.clone());
+                self.stats.merge(&solver.integrate()?);
+                let column = ((self.bc)(&y0_shifted, solver.y_final()) - &residual)
+                    * T::from_subset(&(1.0 / dy));
+                jac.set_column(j, &column);
+            }
+            match jac.lu().solve(&residual) {
+                Some(delta) => y0 -= delta,
+                None => {
+                    return Err(IntegrationError::ShootingDidNotConverge {
+                        iterations: self.iterations,
+                        residual: norm,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Number of Newton iterations performed by the last call to [`solve`](Self::solve).
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    /// Statistics of all the integrations performed by the last call to [`solve`](Self::solve).
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::ode_fn;
+    use crate::{Dopri5, Vector2};
+
+    /// Solver of `y'' = y` on `[0, 1]`, written as a first order system.
+    fn hyperbolic(y0: Vector2<f64>) -> impl OdeSolver<Vector2<f64>> {
+        let f = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+            dy[0] = y[1];
+            dy[1] = y[0];
+        });
+        Dopri5::new(f, 0.0, y0, 1.0, None, 1e-12, 1e-12)
+    }
+
+    #[test]
+    fn solves_a_linear_boundary_value_problem() {
+        // y(0) = 0 and y(1) = 1, whose solution is sinh(x) / sinh(1)
+        let bc = |ya: &Vector2<f64>, yb: &Vector2<f64>| Vector2::new(ya[0], yb[0] - 1.0);
+        let mut shooting = Shooting::new(hyperbolic, bc);
+        let result = shooting.solve(Vector2::new(0.5, 0.0)).unwrap();
+        // Newton's method converges at once on a linear problem, up to the finite differences
+        assert!(shooting.iterations() <= 2, "{}", shooting.iterations());
+        assert!((result.y[0][1] - 1.0 / 1.0f64.sinh()).abs() < 1e-7);
+        for (x, y) in result.x.iter().zip(&result.y) {
+            assert!((y[0] - x.sinh() / 1.0f64.sinh()).abs() < 1e-7);
+        }
+        assert_eq!(result.stats.num_eval(), shooting.stats().num_eval());
+    }
+
+    #[test]
+    fn fails_on_a_nan_residual() {
+        let bc = |ya: &Vector2<f64>, yb: &Vector2<f64>| Vector2::new(ya[0], (yb[0] - 10.0).sqrt());
+        let mut shooting = Shooting::new(hyperbolic, bc);
+        assert!(matches!(
+            shooting.solve(Vector2::new(0.0, 1.0)),
+            Err(IntegrationError::ShootingDidNotConverge { .. })
+        ));
+    }
+}
@@ -0,0 +1,318 @@
+//! Strong stability preserving Runge-Kutta method of order 3 (Shu-Osher) with fixed step size.
+
+use crate::dop_shared::{
+    check_eval, find_segment, hermite_interpolation, reject_events, step_count, validate_step_size,
+    ControlFlag, IntegrationError, IntegrationResult, MethodInfo, OdeSolver, Output, Stats, System,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::{One, Zero};
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// Each step is a convex combination of three forward euler steps, so that any bound on the
+/// total variation or the maximum of the solution satisfied by the forward euler method, e.g. for
+/// the method of lines discretisation of a hyperbolic equation, is preserved under the same
+/// time step restriction. The method costs three evaluations of `System::ode` per step and the
+/// stages are computed in place, without allocating, for large states such as `DVector`.
+pub struct SspRk3<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    step_size: f64,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    k: V,
+    u: V,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> SspRk3<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero
+        + One,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(f: F, x: f64, y: OVector<T, D>, x_end: f64, step_size: f64) -> Self {
+        let (rows, cols) = y.shape_generic();
+        SspRk3 {
+            f,
+            x,
+            y,
+            x_end,
+            step_size,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            k: OVector::zeros_generic(rows, cols),
+            u: OVector::zeros_generic(rows, cols),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        validate_step_size(self.step_size)?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            self.step(h)?;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.f.project(self.x, &mut self.y);
+            self.stats.num_eval += 3;
+            self.stats.accepted_steps += 1;
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        // Derivative at the final state, used for dense output
+        let mut dy = self.y.clone();
+        self.f.ode(self.x, &self.y, &mut dy);
+        self.stats.num_eval += 1;
+        check_eval(&self.f)?;
+        self.dy_out.push(dy);
+        Ok(self.stats)
+    }
+
+    /// Performs one step of the SSPRK3 method in place, storing the derivative at the start of the
+    /// step for dense output. The state is left unchanged if an evaluation of the system fails.
+    fn step(&mut self, h: f64) -> Result<(), IntegrationError> {
+        let c = |c: f64| -> T { nalgebra::convert(c) };
+
+        // u = y + h f(x, y)
+        self.k.fill(T::zero());
+        self.f.ode(self.x, &self.y, &mut self.k);
+        self.dy_out.push(self.k.clone());
+        self.u.copy_from(&self.y);
+        self.u.axpy(c(h), &self.k, c(1.0));
+
+        // u = 3/4 y + 1/4 (u + h f(x + h, u))
+        self.k.fill(T::zero());
+        self.f.ode(self.x + h, &self.u, &mut self.k);
+        self.u.axpy(c(h), &self.k, c(1.0));
+        self.u.axpy(c(0.75), &self.y, c(0.25));
+
+        // y = 1/3 y + 2/3 (u + h f(x + h / 2, u))
+        self.k.fill(T::zero());
+        self.f.ode(self.x + 0.5 * h, &self.u, &mut self.k);
+        self.u.axpy(c(h), &self.k, c(1.0));
+        check_eval(&self.f)?;
+        self.y.axpy(c(2.0 / 3.0), &self.u, c(1.0 / 3.0));
+        Ok(())
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for SspRk3<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero
+        + One,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        SspRk3::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        SspRk3::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        SspRk3::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        SspRk3::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        SspRk3::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for SspRk3<V, F> {
+    fn order(&self) -> u32 {
+        3
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DVector;
+
+    /// Upwind semi-discretisation of the advection equation `u_t + u_x = 0` on a periodic grid of
+    /// spacing `dx`.
+    struct Advection {
+        dx: f64,
+    }
+
+    impl System<DVector<f64>> for Advection {
+        fn ode(&self, _x: f64, y: &DVector<f64>, dy: &mut DVector<f64>) {
+            let n = y.len();
+            for i in 0..n {
+                dy[i] = -(y[i] - y[(i + n - 1) % n]) / self.dx;
+            }
+        }
+    }
+
+    fn total_variation(y: &DVector<f64>) -> f64 {
+        let n = y.len();
+        (0..n).map(|i| (y[(i + 1) % n] - y[i]).abs()).sum()
+    }
+
+    #[test]
+    fn total_variation_does_not_increase() {
+        let n = 200;
+        let dx = 1.0 / n as f64;
+        // Square wave
+        let y0 = DVector::from_fn(n, |i, _| if (50..100).contains(&i) { 1.0 } else { 0.0 });
+        // Courant number 0.9, within the forward euler limit of 1
+        let mut solver = SspRk3::new(Advection { dx }, 0.0, y0.clone(), 0.5, 0.9 * dx);
+        solver.integrate().unwrap();
+        let tv: Vec<f64> = solver.y_out().iter().map(total_variation).collect();
+        assert_eq!(tv[0], 2.0);
+        assert!(tv.windows(2).all(|w| w[1] <= w[0] + 1e-12));
+        assert!(solver
+            .y_out()
+            .iter()
+            .all(|y| y.min() >= -1e-12 && y.max() <= 1.0 + 1e-12));
+    }
+}
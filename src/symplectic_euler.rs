@@ -0,0 +1,184 @@
+//! Symplectic euler method with fixed step size for separable Hamiltonian systems.
+
+use crate::dop_shared::{
+    step_count, validate_step_size, ControlFlag, HamiltonianSystem, IntegrationError, Stats,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The momenta are updated first with the positions at the start of the step and the positions
+/// are then updated with the new momenta. The method is of first order but, being symplectic, it
+/// preserves the phase-space volume and the energy oscillates within a bounded band instead of
+/// drifting.
+pub struct SymplecticEuler<V, F>
+where
+    F: HamiltonianSystem<V>,
+{
+    f: F,
+    x: f64,
+    q: V,
+    p: V,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    q_out: Vec<V>,
+    p_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> SymplecticEuler<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: HamiltonianSystem<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the HamiltonianSystem<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `q`           - Initial value of the position(s)
+    /// * `p`           - Initial value of the momentum(a)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        q: OVector<T, D>,
+        p: OVector<T, D>,
+        x_end: f64,
+        step_size: f64,
+    ) -> Self {
+        SymplecticEuler {
+            f,
+            x,
+            q,
+            p,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            q_out: Vec::new(),
+            p_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.q_out.push(self.q.clone());
+        self.p_out.push(self.p.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.q, &self.p);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, cols) = self.q.shape_generic();
+        let mut dq = OVector::zeros_generic(rows, cols);
+        let mut dp = OVector::zeros_generic(rows, cols);
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+
+            self.f.momentum_derivative(self.x, &self.q, &mut dp);
+            self.p = &self.p + dp.clone() * h;
+            self.f.position_derivative(self.x, &self.p, &mut dq);
+            self.q = &self.q + dq.clone() * h;
+            self.x = if last { self.x_end } else { self.x + h };
+            self.stats.num_eval += 2;
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.q_out.push(self.q.clone());
+            self.p_out.push(self.p.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.q, &self.p);
+            if self.f.solout(self.x, &self.q, &self.p) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the positions' output.
+    pub fn q_out(&self) -> &Vec<OVector<T, D>> {
+        &self.q_out
+    }
+
+    /// Getter for the momenta's output.
+    pub fn p_out(&self) -> &Vec<OVector<T, D>> {
+        &self.p_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector1;
+
+    /// Harmonic oscillator of Hamiltonian `H = (p^2 + q^2) / 2`.
+    struct Oscillator;
+
+    impl HamiltonianSystem<Vector1<f64>> for Oscillator {
+        fn position_derivative(&self, _x: f64, p: &Vector1<f64>, dq: &mut Vector1<f64>) {
+            dq[0] = p[0];
+        }
+
+        fn momentum_derivative(&self, _x: f64, q: &Vector1<f64>, dp: &mut Vector1<f64>) {
+            dp[0] = -q[0];
+        }
+    }
+
+    #[test]
+    fn energy_oscillates_within_a_fixed_band() {
+        let h = 0.1;
+        // A hundred periods
+        let x_end = 200.0 * std::f64::consts::PI;
+        let mut solver = SymplecticEuler::new(
+            Oscillator,
+            0.0,
+            Vector1::new(1.0),
+            Vector1::new(0.0),
+            x_end,
+            h,
+        );
+        solver.integrate().unwrap();
+        let drift: Vec<f64> = solver
+            .q_out()
+            .iter()
+            .zip(solver.p_out())
+            .map(|(q, p)| (0.5 * (p[0] * p[0] + q[0] * q[0]) - 0.5).abs())
+            .collect();
+        let max = |drift: &[f64]| drift.iter().cloned().fold(0.0, f64::max);
+        // The band of the first period is not exceeded later on
+        let first_period = drift.len() / 100;
+        assert!(max(&drift) < 1.01 * max(&drift[..first_period]));
+        assert!(max(&drift) < h);
+    }
+}
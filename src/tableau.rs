@@ -0,0 +1,925 @@
+//! Butcher tableaux and a generic explicit Runge-Kutta driver.
+
+use crate::controller::{PiController, StepController};
+#[cfg(feature = "logging")]
+use crate::dop_shared::LogRecord;
+use crate::dop_shared::{
+    check_eval, error_scale, find_segment, hermite_interpolation, initial_step_size, limit_step,
+    order_reduced, ramp_initial_step, ramp_step, record_diagnostic, reject_events, step_count,
+    validate_step_size, weighted_rms_norm, ControlFlag, ErrorNorm, IntegrationError,
+    IntegrationResult, MethodInfo, OdeSolver, Output, SolverLog, Stats, StepDiagnostic, System,
+    Tolerance,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf, SupersetOf};
+
+/// Coefficients of an explicit Runge-Kutta method.
+#[derive(Clone, Debug)]
+pub struct ButcherTableau {
+    /// Stage matrix, strictly lower triangular. Row `i` holds the coefficients `a_ij` for `j < i`.
+    pub a: Vec<Vec<f64>>,
+    /// Weights of the solution that is propagated.
+    pub b: Vec<f64>,
+    /// Nodes.
+    pub c: Vec<f64>,
+    /// Weights of the embedded solution used for error estimation (optional).
+    pub b_hat: Option<Vec<f64>>,
+    /// Order of the propagated solution.
+    pub order: u32,
+    /// Order of the embedded solution. Ignored when `b_hat` is `None`.
+    pub embedded_order: u32,
+    /// Whether the method is first same as last, i.e. the last row of `a` equals `b` and the last
+    /// node is 1, so that the last stage of a step is the first stage of the next one.
+    pub fsal: bool,
+}
+
+impl ButcherTableau {
+    /// Number of stages of the method.
+    pub fn stages(&self) -> usize {
+        self.b.len()
+    }
+
+    /// Classical fourth order Runge-Kutta method.
+    pub fn rk4() -> Self {
+        ButcherTableau {
+            a: vec![vec![], vec![0.5], vec![0.0, 0.5], vec![0.0, 0.0, 1.0]],
+            b: vec![1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+            c: vec![0.0, 0.5, 0.5, 1.0],
+            b_hat: None,
+            order: 4,
+            embedded_order: 0,
+            fsal: false,
+        }
+    }
+
+    /// Heun's method (explicit trapezoidal rule).
+    pub fn heun() -> Self {
+        ButcherTableau {
+            a: vec![vec![], vec![1.0]],
+            b: vec![0.5, 0.5],
+            c: vec![0.0, 1.0],
+            b_hat: None,
+            order: 2,
+            embedded_order: 0,
+            fsal: false,
+        }
+    }
+
+    /// Ralston's second order method, minimising the bound on the local truncation error.
+    pub fn ralston() -> Self {
+        ButcherTableau {
+            a: vec![vec![], vec![2.0 / 3.0]],
+            b: vec![0.25, 0.75],
+            c: vec![0.0, 2.0 / 3.0],
+            b_hat: None,
+            order: 2,
+            embedded_order: 0,
+            fsal: false,
+        }
+    }
+
+    /// Explicit midpoint method.
+    pub fn midpoint() -> Self {
+        ButcherTableau {
+            a: vec![vec![], vec![0.5]],
+            b: vec![0.0, 1.0],
+            c: vec![0.0, 0.5],
+            b_hat: None,
+            order: 2,
+            embedded_order: 0,
+            fsal: false,
+        }
+    }
+
+    /// Runge-Kutta-Fehlberg method of order 4 with an embedded fifth order solution.
+    pub fn fehlberg45() -> Self {
+        ButcherTableau {
+            a: vec![
+                vec![],
+                vec![1.0 / 4.0],
+                vec![3.0 / 32.0, 9.0 / 32.0],
+                vec![1932.0 / 2197.0, -7200.0 / 2197.0, 7296.0 / 2197.0],
+                vec![439.0 / 216.0, -8.0, 3680.0 / 513.0, -845.0 / 4104.0],
+                vec![
+                    -8.0 / 27.0,
+                    2.0,
+                    -3544.0 / 2565.0,
+                    1859.0 / 4104.0,
+                    -11.0 / 40.0,
+                ],
+            ],
+            b: vec![
+                25.0 / 216.0,
+                0.0,
+                1408.0 / 2565.0,
+                2197.0 / 4104.0,
+                -1.0 / 5.0,
+                0.0,
+            ],
+            c: vec![0.0, 1.0 / 4.0, 3.0 / 8.0, 12.0 / 13.0, 1.0, 1.0 / 2.0],
+            b_hat: Some(vec![
+                16.0 / 135.0,
+                0.0,
+                6656.0 / 12825.0,
+                28561.0 / 56430.0,
+                -9.0 / 50.0,
+                2.0 / 55.0,
+            ]),
+            order: 4,
+            embedded_order: 5,
+            fsal: false,
+        }
+    }
+
+    /// Cash-Karp method of order 5 with an embedded fourth order solution.
+    pub fn cash_karp45() -> Self {
+        ButcherTableau {
+            a: vec![
+                vec![],
+                vec![1.0 / 5.0],
+                vec![3.0 / 40.0, 9.0 / 40.0],
+                vec![3.0 / 10.0, -9.0 / 10.0, 6.0 / 5.0],
+                vec![-11.0 / 54.0, 5.0 / 2.0, -70.0 / 27.0, 35.0 / 27.0],
+                vec![
+                    1631.0 / 55296.0,
+                    175.0 / 512.0,
+                    575.0 / 13824.0,
+                    44275.0 / 110592.0,
+                    253.0 / 4096.0,
+                ],
+            ],
+            b: vec![
+                37.0 / 378.0,
+                0.0,
+                250.0 / 621.0,
+                125.0 / 594.0,
+                0.0,
+                512.0 / 1771.0,
+            ],
+            c: vec![0.0, 1.0 / 5.0, 3.0 / 10.0, 3.0 / 5.0, 1.0, 7.0 / 8.0],
+            b_hat: Some(vec![
+                2825.0 / 27648.0,
+                0.0,
+                18575.0 / 48384.0,
+                13525.0 / 55296.0,
+                277.0 / 14336.0,
+                1.0 / 4.0,
+            ]),
+            order: 5,
+            embedded_order: 4,
+            fsal: false,
+        }
+    }
+
+    /// Bogacki-Shampine method of order 3 with an embedded second order solution.
+    pub fn bogacki_shampine23() -> Self {
+        ButcherTableau {
+            a: vec![
+                vec![],
+                vec![1.0 / 2.0],
+                vec![0.0, 3.0 / 4.0],
+                vec![2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0],
+            ],
+            b: vec![2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
+            c: vec![0.0, 1.0 / 2.0, 3.0 / 4.0, 1.0],
+            b_hat: Some(vec![7.0 / 24.0, 1.0 / 4.0, 1.0 / 3.0, 1.0 / 8.0]),
+            order: 3,
+            embedded_order: 2,
+            fsal: true,
+        }
+    }
+
+    /// Dormand-Prince method of order 5 with an embedded fourth order solution.
+    pub fn dormand_prince45() -> Self {
+        ButcherTableau {
+            a: vec![
+                vec![],
+                vec![1.0 / 5.0],
+                vec![3.0 / 40.0, 9.0 / 40.0],
+                vec![44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0],
+                vec![
+                    19372.0 / 6561.0,
+                    -25360.0 / 2187.0,
+                    64448.0 / 6561.0,
+                    -212.0 / 729.0,
+                ],
+                vec![
+                    9017.0 / 3168.0,
+                    -355.0 / 33.0,
+                    46732.0 / 5247.0,
+                    49.0 / 176.0,
+                    -5103.0 / 18656.0,
+                ],
+                vec![
+                    35.0 / 384.0,
+                    0.0,
+                    500.0 / 1113.0,
+                    125.0 / 192.0,
+                    -2187.0 / 6784.0,
+                    11.0 / 84.0,
+                ],
+            ],
+            b: vec![
+                35.0 / 384.0,
+                0.0,
+                500.0 / 1113.0,
+                125.0 / 192.0,
+                -2187.0 / 6784.0,
+                11.0 / 84.0,
+                0.0,
+            ],
+            c: vec![0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0],
+            b_hat: Some(vec![
+                5179.0 / 57600.0,
+                0.0,
+                7571.0 / 16695.0,
+                393.0 / 640.0,
+                -92097.0 / 339200.0,
+                187.0 / 2100.0,
+                1.0 / 40.0,
+            ]),
+            order: 5,
+            embedded_order: 4,
+            fsal: true,
+        }
+    }
+}
+
+/// Structure containing the parameters for the numerical integration.
+pub struct ExplicitRungeKutta<V, F>
+where
+    F: System<V>,
+{
+    f: F,
+    x: f64,
+    y: V,
+    x_end: f64,
+    h: f64,
+    rtol: Tolerance,
+    atol: Tolerance,
+    controller: Box<dyn StepController>,
+    norm: Box<ErrorNorm<V>>,
+    n_max: u32,
+    min_step: f64,
+    max_step: f64,
+    tableau: ButcherTableau,
+    output: Output<V>,
+    dy_out: Vec<V>,
+    diagnostics: Option<Vec<StepDiagnostic>>,
+    startup_ramp: usize,
+    stats: Stats,
+    log: SolverLog,
+}
+
+impl<T, D: Dim, F> ExplicitRungeKutta<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// If the tableau provides embedded weights, the step size is adapted using the
+    /// tolerances set with [`tolerances`](Self::tolerances) and `h` is the initial guess.
+    /// Otherwise the integration is performed with the fixed step size `h`.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the System<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `y`           - Initial value of the dependent variable(s)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `h`           - Step size, or initial guess for adaptive tableaux (`None` to let them choose it)
+    /// * `tableau`     - Coefficients of the method
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        y: OVector<T, D>,
+        x_end: f64,
+        h: impl Into<Option<f64>>,
+        tableau: ButcherTableau,
+    ) -> Self {
+        ExplicitRungeKutta {
+            f,
+            x,
+            y,
+            x_end,
+            h: h.into().unwrap_or(0.0),
+            rtol: Tolerance::Scalar(1.0e-6),
+            atol: Tolerance::Scalar(1.0e-6),
+            controller: Box::new(PiController::default()),
+            norm: Box::new(weighted_rms_norm),
+            n_max: 100000,
+            min_step: 0.0,
+            max_step: f64::INFINITY,
+            tableau,
+            output: Output::new(),
+            dy_out: Vec::new(),
+            diagnostics: None,
+            startup_ramp: 0,
+            stats: Stats::new(),
+            log: SolverLog::default(),
+        }
+    }
+
+    /// Sets the relative and absolute tolerances used by adaptive tableaux.
+    pub fn tolerances(&mut self, rtol: impl Into<Tolerance>, atol: impl Into<Tolerance>) {
+        self.rtol = rtol.into();
+        self.atol = atol.into();
+    }
+
+    /// Sets the step size controller, a [`PiController`] by default.
+    pub fn step_controller(&mut self, controller: impl StepController + 'static) {
+        self.controller = Box::new(controller);
+    }
+
+    /// Sets the norm of the local error estimate used to accept or reject a step, called with the
+    /// error estimate and the error scale `atol + rtol * |y|` of each component. A step is
+    /// accepted when the norm is at most 1. Defaults to [`weighted_rms_norm`]. For instance, the
+    /// maximum norm bounds the error of every component:
+    ///
+    /// ```
+    /// # use ode_event_solvers::{problems::Lorenz, ButcherTableau, ExplicitRungeKutta, Vector3};
+    /// # let (f, y0) = (Lorenz::default(), Vector3::new(1.0, 1.0, 1.0));
+    /// # let tableau = ButcherTableau::dormand_prince45();
+    /// # let mut solver = ExplicitRungeKutta::new(f, 0.0, y0, 1.0, None, tableau);
+    /// solver.error_norm(|err: &Vector3<f64>, scale: &Vector3<f64>| {
+    ///     err.iter().zip(scale.iter()).fold(0.0, |acc, (e, sc)| acc.max((e / sc).abs()))
+    /// });
+    /// # solver.integrate()?;
+    /// # Ok::<(), ode_event_solvers::dop_shared::IntegrationError>(())
+    /// ```
+    pub fn error_norm(&mut self, norm: impl Fn(&OVector<T, D>, &OVector<T, D>) -> f64 + 'static) {
+        self.norm = Box::new(norm);
+    }
+
+    /// Sets the smallest step size allowed (default 0). The integration stops with
+    /// `IntegrationError::StepSizeTooSmall` if a smaller step is needed, apart from the last step
+    /// which may be shortened to end exactly at `x_end`.
+    pub fn min_step(&mut self, min_step: f64) {
+        self.min_step = min_step;
+    }
+
+    /// Sets the largest step size allowed (default unbounded).
+    pub fn max_step(&mut self, max_step: f64) {
+        self.max_step = max_step;
+    }
+
+    /// Sets the maximum number of steps (default 100000) after which the integration stops with
+    /// `IntegrationError::MaxStepsExceeded`.
+    pub fn max_steps(&mut self, max_steps: u32) {
+        self.n_max = max_steps;
+    }
+
+    /// Sets the number of accepted steps at the start of the integration during which the step
+    /// size may grow by at most a factor of 2 from one step to the next (default 0), easing into
+    /// the adaptive regime on a problem with a sharp initial layer. An initial step size selected
+    /// automatically is first divided by `2^steps`, the ramp growing it back.
+    pub fn startup_ramp(&mut self, steps: usize) {
+        self.startup_ramp = steps;
+    }
+
+    /// Records a [`StepDiagnostic`] for every attempted step, with the step size, the norm of the
+    /// error estimate and whether the step was accepted, retrieved after the integration with
+    /// [`diagnostics`](Self::diagnostics). `Stats::order_reduced` then flags an observed order well
+    /// below the nominal one. Disabled by default.
+    pub fn record_diagnostics(&mut self, record: bool) {
+        self.diagnostics = record.then(Vec::new);
+    }
+
+    /// Diagnostics of the attempted steps, empty unless enabled with
+    /// [`record_diagnostics`](Self::record_diagnostics).
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        self.diagnostics.as_deref().unwrap_or(&[])
+    }
+
+    /// Sets a logger receiving the [`LogRecord`]s of the integration, as described for
+    /// [`Dopri5::logger`](crate::Dopri5::logger). Requires the `logging` feature.
+    #[cfg(feature = "logging")]
+    pub fn logger(&mut self, logger: impl Fn(&LogRecord) + 'static) {
+        self.log.set(logger);
+    }
+
+    /// Sets the values of the independent variable at which the solution is returned by
+    /// [`x_out`](Self::x_out) and [`y_out`](Self::y_out). The solution is interpolated at these
+    /// values while the internal stepping is unaffected. The values must be sorted in the direction
+    /// of integration and lie within `[x, x_end]`.
+    pub fn t_eval(&mut self, t_eval: Vec<f64>) {
+        self.output.set_t_eval(t_eval);
+    }
+
+    /// Core integration method. If the integration fails, the output computed until the failure
+    /// remains available from [`x_out`](Self::x_out) and [`y_out`](Self::y_out).
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        let result = self.integrate_steps();
+        let result = self.log.result(self.x, self.h, result);
+        let samples = self.output.sample(|t| self.at(t));
+        self.output.set_samples(samples);
+        result
+    }
+
+    /// Integration loop, returning early on failure.
+    fn integrate_steps(&mut self) -> Result<Stats, IntegrationError> {
+        reject_events(&self.f)?;
+        if self.tableau.b_hat.is_none() {
+            validate_step_size(self.h)?;
+        }
+        self.rtol.validate(self.y.len())?;
+        self.atol.validate(self.y.len())?;
+        self.output.validate_t_eval(self.x, self.x_end)?;
+        // Save initial values
+        self.output.push(self.x, self.y.clone());
+        // Call Observer
+        self.f.observer_full(self.x, &self.y, 0.0, &self.stats);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, cols) = self.y.shape_generic();
+        let mut k = vec![OVector::zeros_generic(rows, cols); self.tableau.stages()];
+        if self.tableau.b_hat.is_some() {
+            self.integrate_adaptive(&mut k)?;
+        } else {
+            self.integrate_fixed(&mut k)?;
+        }
+        // Derivative at the final state, used for dense output
+        if !self.tableau.fsal || self.stats.accepted_steps == 0 {
+            self.f.ode(self.x, &self.y, &mut k[0]);
+            self.stats.num_eval += 1;
+        }
+        check_eval(&self.f)?;
+        self.dy_out.push(k[0].clone());
+        self.stats.order_reduced = order_reduced(
+            &self.diagnostics,
+            self.tableau.order.min(self.tableau.embedded_order),
+        );
+        Ok(self.stats)
+    }
+
+    fn integrate_fixed(&mut self, k: &mut [OVector<T, D>]) -> Result<(), IntegrationError> {
+        let step_size = self.h;
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            self.h = if last {
+                self.x_end - self.x
+            } else {
+                direction * step_size
+            };
+            self.compute_stages(k, i > 0);
+            check_eval(&self.f)?;
+            let y_new = self.combine(&self.tableau.b, k);
+            self.x = if last { self.x_end } else { self.x + self.h };
+            self.y = y_new.clone();
+            self.f.project(self.x, &mut self.y);
+            self.stats.accepted_steps += 1;
+            self.dy_out.push(k[0].clone());
+            self.carry_last_stage(k, &y_new);
+            self.output.push(self.x, self.y.clone());
+            // Call Observer
+            self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+            if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                break;
+            }
+        }
+        self.h = step_size;
+        Ok(())
+    }
+
+    fn integrate_adaptive(&mut self, k: &mut [OVector<T, D>]) -> Result<(), IntegrationError> {
+        let b_hat = self.tableau.b_hat.clone().unwrap_or_default();
+        let err_weights: Vec<f64> = self
+            .tableau
+            .b
+            .iter()
+            .zip(b_hat.iter())
+            .map(|(b, b_hat)| b - b_hat)
+            .collect();
+
+        if self.h == 0.0 {
+            let (rows, cols) = self.y.shape_generic();
+            let mut f0 = OVector::zeros_generic(rows, cols);
+            self.f.ode(self.x, &self.y, &mut f0);
+            self.h = initial_step_size(
+                |x, y| {
+                    let mut dy = OVector::zeros_generic(rows, cols);
+                    self.f.ode(x, y, &mut dy);
+                    dy
+                },
+                self.x,
+                &self.y,
+                &f0,
+                self.x_end,
+                self.tableau.order.min(self.tableau.embedded_order) + 1,
+                &self.rtol,
+                &self.atol,
+                self.max_step,
+            );
+            self.h = ramp_initial_step(self.h, self.startup_ramp);
+            self.stats.num_eval += 2;
+        }
+        let posneg = (self.x_end - self.x).signum();
+        self.h = self.h.abs().min(self.max_step) * posneg;
+
+        let mut last = false;
+        let mut n_step = 0;
+        while !last {
+            if n_step > self.n_max {
+                return Err(IntegrationError::MaxStepsExceeded { x: self.x, n_step });
+            }
+            if 0.1 * self.h.abs() <= f64::EPSILON * self.x.abs() {
+                return Err(IntegrationError::StepSizeUnderflow { x: self.x });
+            }
+            // Do not step past the end point
+            if (self.x + 1.01 * self.h - self.x_end) * posneg > 0.0 {
+                self.h = self.x_end - self.x;
+                last = true;
+            }
+            // The first stage is known after the first attempt, even if it was rejected
+            self.compute_stages(k, n_step > 0);
+            check_eval(&self.f)?;
+            n_step += 1;
+
+            let y_new = self.combine(&self.tableau.b, k);
+            let err = self.combine_increment(&err_weights, k);
+
+            let err = self.norm(&err, &y_new);
+            let (accept, h_new) = self.controller.next_step(
+                err,
+                self.h,
+                self.tableau.order.min(self.tableau.embedded_order),
+            );
+            let h_new = ramp_step(h_new, self.h, self.stats.accepted_steps, self.startup_ramp);
+            record_diagnostic(&mut self.diagnostics, self.x, self.h, err, accept);
+            if accept {
+                self.log.accepted(self.x, self.h, err);
+                self.stats.accepted_steps += 1;
+                self.stats.record_step(self.h);
+                self.x = if last { self.x_end } else { self.x + self.h };
+                self.y = y_new.clone();
+                self.f.project(self.x, &mut self.y);
+                self.dy_out.push(k[0].clone());
+                self.carry_last_stage(k, &y_new);
+                self.output.push(self.x, self.y.clone());
+                // Call Observer
+                self.f.observer_full(self.x, &self.y, self.h, &self.stats);
+                if self.f.solout(self.x, &self.y) == ControlFlag::Stop {
+                    break;
+                }
+            } else {
+                self.log.rejected(self.x, self.h, err);
+                self.stats.rejected_steps += 1;
+                last = false;
+            }
+            if !last {
+                self.h = limit_step(h_new, self.min_step, self.max_step, self.x)?;
+                self.log.clamped(self.x, h_new, self.h, self.max_step);
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluates the stages `k_i` of one step of size `h` from the current point. The first stage
+    /// is not evaluated again if `first_known` is set and the tableau is first same as last.
+    fn compute_stages(&mut self, k: &mut [OVector<T, D>], first_known: bool) {
+        if !(first_known && self.tableau.fsal) {
+            self.f.ode(self.x, &self.y, &mut k[0]);
+            self.stats.num_eval += 1;
+        }
+        self.stats.num_eval += self.tableau.stages() as u32 - 1;
+        for i in 1..self.tableau.stages() {
+            let y_tmp = self.combine(&self.tableau.a[i], &k[..i]);
+            self.f
+                .ode(self.x + self.tableau.c[i] * self.h, &y_tmp, &mut k[i]);
+        }
+    }
+
+    /// Reuses the last stage of an accepted step, ending at `y_new`, as the first stage of the next
+    /// one if the tableau is first same as last. The first stage is evaluated again instead if
+    /// `System::project` moved the state away from `y_new`.
+    fn carry_last_stage(&mut self, k: &mut [OVector<T, D>], y_new: &OVector<T, D>) {
+        if self.tableau.fsal {
+            if self.y == *y_new {
+                k[0] = k[self.tableau.stages() - 1].clone();
+            } else {
+                self.f.ode(self.x, &self.y, &mut k[0]);
+                self.stats.num_eval += 1;
+            }
+        }
+    }
+
+    /// Computes `y + h * sum_j w_j * k_j`.
+    fn combine(&self, weights: &[f64], k: &[OVector<T, D>]) -> OVector<T, D> {
+        &self.y + self.combine_increment(weights, k)
+    }
+
+    /// Computes `h * sum_j w_j * k_j`, skipping zero weights.
+    fn combine_increment(&self, weights: &[f64], k: &[OVector<T, D>]) -> OVector<T, D> {
+        let (rows, cols) = self.y.shape_generic();
+        let mut acc: Option<OVector<T, D>> = None;
+        for (w, k_j) in weights.iter().zip(k.iter()) {
+            if *w == 0.0 {
+                continue;
+            }
+            let term = k_j.clone() * *w;
+            acc = Some(match acc {
+                Some(acc) => acc + term,
+                None => term,
+            });
+        }
+        acc.unwrap_or_else(|| OVector::zeros_generic(rows, cols)) * self.h
+    }
+
+    /// Computes the norm of the local error estimate set with [`error_norm`](Self::error_norm).
+    fn norm(&self, err: &OVector<T, D>, y_new: &OVector<T, D>) -> f64 {
+        let scale = error_scale(&self.y, y_new, &self.rtol, &self.atol);
+        (self.norm)(err, &scale)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        self.output.x_out()
+    }
+
+    /// Getter for the dependent variables' output.
+    pub fn y_out(&self) -> &Vec<OVector<T, D>> {
+        self.output.y_out()
+    }
+
+    /// Getter for the derivatives `f(x, y)` at the states stored at every step, i.e. at the points
+    /// returned by [`x_out`](Self::x_out) when [`t_eval`](Self::t_eval) is not set.
+    pub fn dy_out(&self) -> &Vec<OVector<T, D>> {
+        &self.dy_out
+    }
+
+    /// Returns the current value of the independent variable, i.e. the end point of the
+    /// integration once it has completed, independently of the stored output.
+    pub fn x_final(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the current value of the dependent variable(s), i.e. the state at
+    /// [`x_final`](Self::x_final).
+    pub fn y_final(&self) -> &OVector<T, D> {
+        &self.y
+    }
+
+    /// Interpolates the solution at `x` with cubic Hermite polynomials built from the stored
+    /// states and derivatives. Returns `None` if `x` lies outside of the integration interval.
+    pub fn at(&self, x: f64) -> Option<OVector<T, D>> {
+        if self.output.x.len() == 1 && self.output.x[0] == x {
+            return Some(self.output.y[0].clone());
+        }
+        let i = find_segment(&self.output.x, x)?;
+        Some(hermite_interpolation(
+            (self.output.x[i], &self.output.y[i], self.dy_out.get(i)?),
+            (
+                self.output.x[i + 1],
+                &self.output.y[i + 1],
+                self.dy_out.get(i + 1)?,
+            ),
+            x,
+        ))
+    }
+
+    /// Consumes the solver and returns the computed trajectory together with the statistics.
+    pub fn into_result(self) -> IntegrationResult<OVector<T, D>> {
+        self.output.into_result(self.stats)
+    }
+}
+
+impl<T, D: Dim, F> OdeSolver<OVector<T, D>> for ExplicitRungeKutta<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy
+        + SubsetOf<f64>
+        + SupersetOf<f64>
+        + Scalar
+        + ClosedAdd
+        + ClosedMul
+        + ClosedSub
+        + ClosedNeg
+        + Zero,
+    F: System<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        ExplicitRungeKutta::integrate(self)
+    }
+
+    fn x_out(&self) -> &[f64] {
+        ExplicitRungeKutta::x_out(self)
+    }
+
+    fn y_out(&self) -> &[OVector<T, D>] {
+        ExplicitRungeKutta::y_out(self)
+    }
+
+    fn x_final(&self) -> f64 {
+        ExplicitRungeKutta::x_final(self)
+    }
+
+    fn y_final(&self) -> &OVector<T, D> {
+        ExplicitRungeKutta::y_final(self)
+    }
+}
+
+impl<V, F: System<V>> MethodInfo for ExplicitRungeKutta<V, F> {
+    fn order(&self) -> u32 {
+        self.tableau.order
+    }
+
+    fn is_implicit(&self) -> bool {
+        false
+    }
+
+    fn is_a_stable(&self) -> bool {
+        false
+    }
+
+    fn is_fsal(&self) -> bool {
+        self.tableau.fsal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::ode_fn;
+    use crate::{Vector1, Vector2};
+
+    /// Exponential growth `y' = y`.
+    struct Growth;
+
+    impl System<Vector1<f64>> for Growth {
+        fn ode(&self, _x: f64, y: &Vector1<f64>, dy: &mut Vector1<f64>) {
+            dy[0] = y[0];
+        }
+    }
+
+    #[test]
+    fn integrates_backward_in_time() {
+        let e = std::f64::consts::E;
+        let mut solver = ExplicitRungeKutta::new(
+            Growth,
+            1.0,
+            Vector1::new(e),
+            0.0,
+            0.01,
+            ButcherTableau::rk4(),
+        );
+        solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 0.0);
+        assert!(solver.x_out().windows(2).all(|w| w[1] < w[0]));
+        assert!((solver.y_final()[0] - 1.0).abs() < 1e-9);
+    }
+
+    /// Whether the first step from the initial step size chosen by the solver is accepted.
+    fn first_step_accepted(
+        f: impl System<Vector2<f64>>,
+        y: Vector2<f64>,
+        tableau: &ButcherTableau,
+        tol: f64,
+    ) -> bool {
+        let mut solver = ExplicitRungeKutta::new(f, 0.0, y, 10.0, None, tableau.clone());
+        solver.tolerances(tol, tol);
+        solver.record_diagnostics(true);
+        solver.integrate().unwrap();
+        solver.diagnostics()[0].accepted
+    }
+
+    #[test]
+    fn chooses_an_initial_step_accepted_at_the_first_attempt() {
+        let tableaux = [
+            ButcherTableau::bogacki_shampine23(),
+            ButcherTableau::cash_karp45(),
+            ButcherTableau::dormand_prince45(),
+        ];
+        for tableau in &tableaux {
+            for tol in [1e-3, 1e-6, 1e-9] {
+                let decay = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                    dy[0] = -y[0];
+                    dy[1] = -10.0 * y[1];
+                });
+                assert!(first_step_accepted(
+                    decay,
+                    Vector2::new(1.0, 1.0),
+                    tableau,
+                    tol
+                ));
+                let oscillator = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                    dy[0] = y[1];
+                    dy[1] = -100.0 * y[0];
+                });
+                assert!(first_step_accepted(
+                    oscillator,
+                    Vector2::new(1.0, 0.0),
+                    tableau,
+                    tol
+                ));
+                let van_der_pol = ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                    dy[0] = y[1];
+                    dy[1] = 5.0 * (1.0 - y[0] * y[0]) * y[1] - y[0];
+                });
+                assert!(first_step_accepted(
+                    van_der_pol,
+                    Vector2::new(2.0, 0.0),
+                    tableau,
+                    tol
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn custom_rk4_tableau_matches_the_dedicated_solver() {
+        let tableau = ButcherTableau {
+            a: vec![vec![], vec![0.5], vec![0.0, 0.5], vec![0.0, 0.0, 1.0]],
+            b: vec![1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+            c: vec![0.0, 0.5, 0.5, 1.0],
+            b_hat: None,
+            order: 4,
+            embedded_order: 0,
+            fsal: false,
+        };
+        let oscillator = || {
+            ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                dy[0] = y[1];
+                dy[1] = -y[0];
+            })
+        };
+        let y0 = Vector2::new(1.0, 0.0);
+        let mut generic = ExplicitRungeKutta::new(oscillator(), 0.0, y0, 10.0, 0.1, tableau);
+        let mut dedicated = crate::RungeKutta4::new(oscillator(), 0.0, y0, 10.0, 0.1);
+        generic.integrate().unwrap();
+        dedicated.integrate().unwrap();
+        assert_eq!(generic.x_out(), dedicated.x_out());
+        assert_eq!(generic.y_out(), dedicated.y_out());
+    }
+
+    #[test]
+    fn embedded_tableau_adapts_the_step_size() {
+        let decay = ode_fn(|_, y: &Vector1<f64>, dy: &mut Vector1<f64>| dy[0] = -y[0]);
+        let mut solver = ExplicitRungeKutta::new(
+            decay,
+            0.0,
+            Vector1::new(1.0),
+            5.0,
+            None,
+            ButcherTableau::fehlberg45(),
+        );
+        solver.tolerances(1e-8, 1e-8);
+        let stats = solver.integrate().unwrap();
+        assert_eq!(solver.x_final(), 5.0);
+        assert!((solver.y_final()[0] - (-5.0f64).exp()).abs() < 1e-7);
+        let steps: Vec<f64> = solver.x_out().windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(steps.iter().any(|h| (h - steps[0]).abs() > 1e-3));
+        assert!(stats.accepted_steps() < 100);
+    }
+
+    #[test]
+    fn first_same_as_last_saves_one_evaluation_per_step() {
+        let van_der_pol = || {
+            ode_fn(|_, y: &Vector2<f64>, dy: &mut Vector2<f64>| {
+                dy[0] = y[1];
+                dy[1] = 5.0 * (1.0 - y[0] * y[0]) * y[1] - y[0];
+            })
+        };
+        let run = |fsal| {
+            let tableau = ButcherTableau {
+                fsal,
+                ..ButcherTableau::dormand_prince45()
+            };
+            let y0 = Vector2::new(2.0, 0.0);
+            let mut solver = ExplicitRungeKutta::new(van_der_pol(), 0.0, y0, 20.0, 0.01, tableau);
+            solver.tolerances(1e-6, 1e-6);
+            let stats = solver.integrate().unwrap();
+            (stats, solver.into_result())
+        };
+        let (with, with_result) = run(true);
+        let (without, without_result) = run(false);
+        assert!(with.rejected_steps() > 0);
+        assert_eq!(with_result.x, without_result.x);
+        assert_eq!(with_result.y, without_result.y);
+        let attempts = with.accepted_steps() + with.rejected_steps();
+        assert_eq!(without.num_eval() - with.num_eval(), attempts);
+    }
+}
@@ -0,0 +1,208 @@
+//! Velocity Verlet method with fixed step size for second order systems.
+
+use crate::dop_shared::{
+    step_count, validate_step_size, ControlFlag, IntegrationError, SecondOrderSystem, Stats,
+};
+
+use nalgebra::{allocator::Allocator, DefaultAllocator, Dim, OVector, Scalar};
+use num_traits::Zero;
+use simba::scalar::{ClosedAdd, ClosedMul, ClosedNeg, ClosedSub, SubsetOf};
+
+/// Structure containing the parameters for the numerical integration.
+///
+/// The method is symplectic and of second order when the acceleration does not depend on the
+/// velocity, so that the energy of conservative mechanical systems remains bounded over long
+/// integration times. If it does, the acceleration at the end of a step is evaluated with the
+/// velocity at the middle of the step and the method loses these properties.
+pub struct VelocityVerlet<V, F>
+where
+    F: SecondOrderSystem<V>,
+{
+    f: F,
+    x: f64,
+    q: V,
+    v: V,
+    x_end: f64,
+    step_size: f64,
+    x_out: Vec<f64>,
+    q_out: Vec<V>,
+    v_out: Vec<V>,
+    stats: Stats,
+}
+
+impl<T, D: Dim, F> VelocityVerlet<OVector<T, D>, F>
+where
+    f64: From<T>,
+    T: Copy + SubsetOf<f64> + Scalar + ClosedAdd + ClosedMul + ClosedSub + ClosedNeg + Zero,
+    F: SecondOrderSystem<OVector<T, D>>,
+    OVector<T, D>: std::ops::Mul<f64, Output = OVector<T, D>>,
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Default initializer for the structure
+    ///
+    /// # Arguments
+    ///
+    /// * `f`           - Structure implementing the SecondOrderSystem<V> trait
+    /// * `x`           - Initial value of the independent variable (usually time)
+    /// * `q`           - Initial value of the position(s)
+    /// * `v`           - Initial value of the velocity(ies)
+    /// * `x_end`       - Final value of the independent variable
+    /// * `step_size`   - Step size used in the method
+    ///
+    pub fn new(
+        f: F,
+        x: f64,
+        q: OVector<T, D>,
+        v: OVector<T, D>,
+        x_end: f64,
+        step_size: f64,
+    ) -> Self {
+        VelocityVerlet {
+            f,
+            x,
+            q,
+            v,
+            x_end,
+            step_size,
+            x_out: Vec::new(),
+            q_out: Vec::new(),
+            v_out: Vec::new(),
+            stats: Stats::new(),
+        }
+    }
+
+    /// Core integration method.
+    pub fn integrate(&mut self) -> Result<Stats, IntegrationError> {
+        validate_step_size(self.step_size)?;
+        // Save initial values
+        self.x_out.push(self.x);
+        self.q_out.push(self.q.clone());
+        self.v_out.push(self.v.clone());
+        // Call Observer
+        self.f.observer(self.x, &self.q, &self.v);
+        // An empty interval of integration gives the initial point alone
+        if self.x == self.x_end {
+            return Ok(self.stats);
+        }
+
+        let (rows, cols) = self.q.shape_generic();
+        let mut a = OVector::zeros_generic(rows, cols);
+        self.f.acceleration(self.x, &self.q, &self.v, &mut a);
+        self.stats.num_eval += 1;
+
+        // Integrate backward in time if x_end < x
+        let direction = (self.x_end - self.x).signum();
+        let num_steps = step_count((self.x_end - self.x).abs(), self.step_size);
+        for i in 0..num_steps {
+            // Shorten the last step so that the integration ends exactly at x_end
+            let last = i + 1 == num_steps;
+            let h = if last {
+                self.x_end - self.x
+            } else {
+                direction * self.step_size
+            };
+            let x_new = if last { self.x_end } else { self.x + h };
+
+            let v_half = &self.v + a.clone() * (0.5 * h);
+            self.q = &self.q + v_half.clone() * h;
+            self.f.acceleration(x_new, &self.q, &v_half, &mut a);
+            self.v = v_half + a.clone() * (0.5 * h);
+            self.x = x_new;
+            self.stats.num_eval += 1;
+            self.stats.accepted_steps += 1;
+
+            self.x_out.push(self.x);
+            self.q_out.push(self.q.clone());
+            self.v_out.push(self.v.clone());
+            // Call Observer
+            self.f.observer(self.x, &self.q, &self.v);
+            if self.f.solout(self.x, &self.q, &self.v) == ControlFlag::Stop {
+                break;
+            }
+        }
+        Ok(self.stats)
+    }
+
+    /// Getter for the independent variable's output.
+    pub fn x_out(&self) -> &Vec<f64> {
+        &self.x_out
+    }
+
+    /// Getter for the positions' output.
+    pub fn q_out(&self) -> &Vec<OVector<T, D>> {
+        &self.q_out
+    }
+
+    /// Getter for the velocities' output.
+    pub fn v_out(&self) -> &Vec<OVector<T, D>> {
+        &self.v_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dop_shared::{first_order, stack_second_order};
+    use crate::{Euler, Vector2, Vector4};
+
+    /// Kepler problem `q'' = -q / |q|^3`.
+    struct Kepler;
+
+    impl SecondOrderSystem<Vector2<f64>> for Kepler {
+        fn acceleration(
+            &self,
+            _x: f64,
+            q: &Vector2<f64>,
+            _qdot: &Vector2<f64>,
+            out: &mut Vector2<f64>,
+        ) {
+            *out = -q / q.norm().powi(3);
+        }
+    }
+
+    fn energy(q: &Vector2<f64>, v: &Vector2<f64>) -> f64 {
+        0.5 * v.norm_squared() - 1.0 / q.norm()
+    }
+
+    #[test]
+    fn energy_drift_stays_bounded() {
+        // Eccentric orbit of period 2 pi / (2 - 1.2^2)^1.5, about 14.9
+        let (q0, v0) = (Vector2::new(1.0, 0.0), Vector2::new(0.0, 1.2));
+        let (x_end, h) = (150.0, 1e-3);
+        let e0 = energy(&q0, &v0);
+
+        let mut verlet = VelocityVerlet::new(Kepler, 0.0, q0, v0, x_end, h);
+        verlet.integrate().unwrap();
+        let verlet_drift: Vec<f64> = verlet
+            .q_out()
+            .iter()
+            .zip(verlet.v_out())
+            .map(|(q, v)| (energy(q, v) - e0).abs())
+            .collect();
+
+        let mut euler = Euler::new(
+            first_order(Kepler),
+            0.0,
+            stack_second_order(&q0, &v0),
+            x_end,
+            vec![h, h, h],
+        );
+        euler.integrate().unwrap();
+        let euler_drift: Vec<f64> = euler
+            .y_out()
+            .iter()
+            .map(|y: &Vector4<f64>| {
+                let (q, v) = (Vector2::new(y[0], y[1]), Vector2::new(y[2], y[3]));
+                (energy(&q, &v) - e0).abs()
+            })
+            .collect();
+
+        let max = |drift: &[f64]| drift.iter().cloned().fold(0.0, f64::max);
+        let n = verlet_drift.len();
+        // The drift of Verlet does not grow after the first orbit, that of Euler keeps growing
+        assert!(max(&verlet_drift) < 2.0 * max(&verlet_drift[..n / 10]));
+        assert!(max(&verlet_drift) < 1e-6);
+        assert!(euler_drift[n - 1] > 5.0 * max(&euler_drift[..n / 10]));
+        assert!(euler_drift[n - 1] > 100.0 * max(&verlet_drift));
+    }
+}